@@ -0,0 +1,111 @@
+// timings of violation checks and candidate search on large boards.
+// run with `cargo bench --bench check`, and with `--features parallel`
+// to compare against the threaded checks.
+
+use std::time::{Duration, Instant};
+
+use fabric_tools::solver::{
+    Board, DomainKind, DomainRelation, Entity, GreedySolver, IDRelation, IDRelationKind, Pending,
+    Priority, PropertyRelation, PropertyRelationKind, Resource, Solver,
+};
+
+// a board with the given number of entities spread over one resource per
+// 20 entities, with a pair relation per 10 entities and a few property
+// and domain relations.
+fn board(entities: usize) -> Board {
+    let mut b = Board::new();
+    let resources = entities / 20;
+    for i in 0..resources {
+        let mut r = Resource::new(format!("node{}", i));
+        r.add_property(format!("zone=z{}", i % 5));
+        if i % 2 == 0 {
+            r.add_property(String::from("ssd"));
+        }
+        r.fault_domain = Some(format!("/dc/rack{}", i % 10));
+        r.capacities.insert(String::from("cpu"), 100);
+        b.add_resource(r).expect("added");
+    }
+    for i in 0..entities {
+        let mut e = Entity::new(format!("app{}", i));
+        e.add_property(format!("svc=s{}", i % 50));
+        if i % 3 == 0 {
+            e.add_property(String::from("fast"));
+        }
+        e.metrics.insert(String::from("cpu"), 1 + (i % 4) as i64);
+        b.add_entity(format!("node{}", i % resources), e)
+            .expect("added");
+    }
+    for i in (0..entities).step_by(10) {
+        b.add_id_relation(IDRelation {
+            id: format!("pair{}", i),
+            kind: IDRelationKind::EEAntiAffinity,
+            id1: format!("app{}", i),
+            id2: format!("app{}", i + 1),
+            priority: Priority::Hard,
+        })
+        .unwrap();
+    }
+    b.add_property_relation(PropertyRelation {
+        id: String::from("fast-ssd"),
+        kind: PropertyRelationKind::Affinity,
+        entity_property: String::from("fast"),
+        resource_property: String::from("ssd"),
+        priority: Priority::Soft(1),
+    })
+    .unwrap();
+    for s in 0..5 {
+        b.add_domain_relation(DomainRelation {
+            id: format!("spread-s{}", s),
+            domain: DomainKind::Fault,
+            entity_property: format!("svc=s{}", s),
+            min_domains: 3,
+            priority: Priority::Hard,
+        })
+        .unwrap();
+    }
+    b
+}
+
+fn time<T>(runs: u32, mut f: impl FnMut() -> T) -> Duration {
+    let start = Instant::now();
+    for _ in 0..runs {
+        std::hint::black_box(f());
+    }
+    start.elapsed() / runs
+}
+
+// ten new entities of the given services.
+fn pending() -> Pending {
+    let mut p = Pending::new();
+    for i in 0..10 {
+        let mut e = Entity::new(format!("new{}", i));
+        e.add_property(format!("svc=s{}", i % 5));
+        e.metrics.insert(String::from("cpu"), 1);
+        p.add_entity(e);
+    }
+    p
+}
+
+fn main() {
+    let mode = if cfg!(feature = "parallel") {
+        "parallel"
+    } else {
+        "serial"
+    };
+    println!("{} checks", mode);
+    for n in [1_000, 10_000, 20_000] {
+        let b = board(n);
+        let check = time(5, || b.check_all());
+        let candidates = time(20, || b.find_candidate_resources("app0"));
+        let p = pending();
+        let solve = time(3, || GreedySolver.place(&b, &p).expect("places"));
+        println!(
+            "{:>6} entities: check_all {:>10.2?} ({:.2?}/entity), find_candidate_resources {:>10.2?}, solve {:>10.2?}",
+            n,
+            check,
+            check / n as u32,
+            candidates,
+            solve
+        );
+    }
+}
@@ -0,0 +1,63 @@
+// timings of check, solve and rebalance on generated boards, to compare
+// strategies and catch regressions. run with `cargo bench --bench solver`.
+// rebalance re-checks the board per trial move, so it only runs on the
+// smaller boards.
+
+use std::time::{Duration, Instant};
+
+use fabric_tools::solver::{
+    BacktrackingSolver, Board, GeneratorConfig, GreedySolver, Objective, Solver, SolverConfig,
+};
+
+fn time<T>(runs: u32, mut f: impl FnMut() -> T) -> Duration {
+    let start = Instant::now();
+    for _ in 0..runs {
+        std::hint::black_box(f());
+    }
+    start.elapsed() / runs
+}
+
+fn main() {
+    for (resources, entities) in [(10, 100), (25, 500), (200, 4_000)] {
+        let config = GeneratorConfig {
+            resources,
+            entities,
+            seed: 1,
+            ..Default::default()
+        };
+        let b = Board::generate(&config);
+        let pending = config.pending(20);
+        println!(
+            "{} resources, {} entities, {} violations",
+            resources,
+            entities,
+            b.check_all().len()
+        );
+
+        let check = time(5, || b.check_violation());
+        println!("  check_violation      {:>10.2?}", check);
+        let strategies: [(&str, &dyn Solver); 2] = [
+            ("greedy", &GreedySolver),
+            ("backtracking", &BacktrackingSolver),
+        ];
+        for (name, solver) in strategies {
+            let solve = time(3, || solver.place(&b, &pending));
+            println!("  solve {:<14} {:>10.2?}", name, solve);
+        }
+        if entities > 500 {
+            continue;
+        }
+        let rebalance = time(1, || b.rebalance());
+        println!("  rebalance            {:>10.2?}", rebalance);
+        let defrag = SolverConfig {
+            objective: Objective::Defragment {
+                target_empty_resources: resources / 10,
+            },
+            ..Default::default()
+        };
+        let defragment = time(1, || b.rebalance_with(&defrag));
+        println!("  defragment           {:>10.2?}", defragment);
+        let anneal = time(1, || b.anneal(&SolverConfig::default()));
+        println!("  anneal               {:>10.2?}", anneal);
+    }
+}
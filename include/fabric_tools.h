@@ -0,0 +1,48 @@
+/*
+ * c abi of fabric-tools, built with the ffi feature:
+ *
+ *   cargo rustc --lib --release --features ffi --crate-type cdylib
+ *
+ * Kept by hand in sync with src/ffi.rs, which documents the functions.
+ * Objects and results are json strings in the format of the board files.
+ * Functions returning int give 0 on success and -1 on failure, those
+ * returning pointers give NULL on failure; fabric_last_error then holds
+ * the message. Returned strings are released with fabric_string_free.
+ * Panics inside the library are caught and reported as failures.
+ */
+
+#ifndef FABRIC_TOOLS_H
+#define FABRIC_TOOLS_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct FabricBoard FabricBoard;
+
+const char *fabric_last_error(void);
+
+FabricBoard *fabric_board_new(void);
+FabricBoard *fabric_board_from_json(const char *json);
+void fabric_board_free(FabricBoard *board);
+char *fabric_board_to_json(FabricBoard *board);
+
+int fabric_board_add_resource(FabricBoard *board, const char *resource);
+int fabric_board_add_entity(FabricBoard *board, const char *resource_id, const char *entity);
+int fabric_board_add_id_relation(FabricBoard *board, const char *relation);
+int fabric_board_add_property_relation(FabricBoard *board, const char *relation);
+int fabric_board_add_id_property_relation(FabricBoard *board, const char *relation);
+int fabric_board_add_domain_relation(FabricBoard *board, const char *relation);
+int fabric_board_add_chain(FabricBoard *board, const char *chain);
+
+char *fabric_board_check(FabricBoard *board);
+char *fabric_board_solve(FabricBoard *board, const char *pending);
+char *fabric_board_rebalance(FabricBoard *board);
+
+void fabric_string_free(char *s);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,156 @@
+// the json requests of the solver services, see server and wasm. A
+// request carries the whole board, the answer is a status and a json body
+// like for http: {"error": message} with 400 for malformed requests and
+// 422 when the solver finds no placement.
+//
+// Solves and rebalances search within the limits of the config passed to
+// route and say whether they finished:
+//
+//   /solve      {"placement": placement, "complete": bool}
+//   /rebalance  move plan with "complete": bool
+
+use std::collections::BTreeMap;
+
+use crate::json::{field, FromJson, ToJson, Value};
+use crate::solver::{Board, Objective, Pending, SolverConfig};
+
+// the status and json body answering a request. Solves and rebalances
+// start from config, a rebalance's query parameters adjust it.
+pub(crate) fn route(method: &str, target: &str, body: &str, config: &SolverConfig) -> (u16, Value) {
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    if !matches!(path, "/check" | "/solve" | "/rebalance") {
+        return (404, error_body(&format!("no such endpoint: {}", path)));
+    }
+    if method != "POST" {
+        return (405, error_body("use POST"));
+    }
+    let request = match Value::parse(body) {
+        Ok(v) => v,
+        Err(e) => return (400, error_body(&e.to_string())),
+    };
+    let result = match path {
+        "/check" => board(&request).map(|b| (200, b.check_all().to_value())),
+        "/solve" => solve(&request, config),
+        _ => rebalance(&request, query, config),
+    };
+    result.unwrap_or_else(|e| (400, error_body(&e)))
+}
+
+fn board(v: &Value) -> Result<Board, String> {
+    Board::from_value(v).map_err(|e| e.to_string())
+}
+
+fn solve(request: &Value, config: &SolverConfig) -> Result<(u16, Value), String> {
+    let mut b = board(field(request, "board", "$").map_err(|e| e.to_string())?)?;
+    let pending = field(request, "pending", "$")
+        .and_then(Pending::from_value)
+        .map_err(|e| e.to_string())?;
+    Ok(match b.solve_bounded(pending, config) {
+        Ok(placement) => {
+            let mut v = BTreeMap::new();
+            v.insert(String::from("placement"), placement.value.to_value());
+            v.insert(String::from("complete"), Value::Bool(placement.complete));
+            (200, Value::Object(v))
+        }
+        Err(e) => (422, error_body(&e.to_string())),
+    })
+}
+
+fn rebalance(request: &Value, query: &str, config: &SolverConfig) -> Result<(u16, Value), String> {
+    let b = board(request)?;
+    let mut config = config.clone();
+    for pair in query.split('&').filter(|p| !p.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        match key {
+            "budget" => {
+                let budget = value
+                    .parse()
+                    .map_err(|_| format!("invalid budget: {}", value))?;
+                config.move_cost_budget = Some(budget);
+            }
+            "defragment" => {
+                let n = value
+                    .parse()
+                    .map_err(|_| format!("invalid count: {}", value))?;
+                config.objective = Objective::Defragment {
+                    target_empty_resources: n,
+                };
+            }
+            _ => return Err(format!("unknown parameter: {}", key)),
+        }
+    }
+    let plan = b.rebalance_bounded(&config);
+    let mut v = plan.value.to_value();
+    if let Value::Object(fields) = &mut v {
+        fields.insert(String::from("complete"), Value::Bool(plan.complete));
+    }
+    Ok((200, v))
+}
+
+pub(crate) fn error_body(message: &str) -> Value {
+    let mut v = BTreeMap::new();
+    v.insert(String::from("error"), Value::String(message.to_string()));
+    Value::Object(v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::route;
+    use crate::json::Value;
+    use crate::solver::SolverConfig;
+
+    const BOARD: &str = r#"{
+      "resources": [{"id": "node1"}, {"id": "node2"}],
+      "entities": [{"id": "a", "move_cost": 1}, {"id": "b", "move_cost": 2}],
+      "assignment": {"a": "node1", "b": "node1"},
+      "id_relations": [{"id": "apart", "kind": "EEAntiAffinity", "id1": "a", "id2": "b"}]
+    }"#;
+
+    #[test]
+    fn route_test() {
+        let route = |method, target, body| route(method, target, body, &SolverConfig::default());
+        let (status, report) = route("POST", "/check", BOARD);
+        assert_eq!(status, 200);
+        assert_eq!(report.as_array().map(Vec::len), Some(2));
+
+        let (status, plan) = route("POST", "/rebalance?budget=5", BOARD);
+        assert_eq!(status, 200);
+        let moves = plan.get("moves").unwrap().as_array().unwrap();
+        assert_eq!(moves[0].get("entity_id").unwrap().as_str(), Some("a"));
+        assert_eq!(plan.get("complete").and_then(Value::as_bool), Some(true));
+
+        let solve = format!(
+            r#"{{"board": {}, "pending": {{"entities": [{{"id": "c"}}],
+                "id_relations": [{{"id": "c-on-2", "kind": "ERAffinity", "id1": "c",
+                                   "id2": "node2"}}]}}}}"#,
+            BOARD
+        );
+        let (status, answer) = route("POST", "/solve", &solve);
+        assert_eq!(status, 200);
+        let placement = answer.get("placement").unwrap();
+        assert_eq!(placement.get("c").unwrap().as_str(), Some("node2"));
+        assert_eq!(answer.get("complete").and_then(Value::as_bool), Some(true));
+
+        let unplaceable = solve.replace(
+            "\"id\": \"c\"}",
+            "\"id\": \"c\", \"constraint\": \"zone==z9\"}",
+        );
+        assert_eq!(route("POST", "/solve", &unplaceable).0, 422);
+        assert_eq!(route("POST", "/check", "{").0, 400);
+        assert_eq!(route("POST", "/rebalance?budget=x", BOARD).0, 400);
+        assert_eq!(route("GET", "/check", "").0, 405);
+        assert_eq!(route("POST", "/nope", "").0, 404);
+    }
+
+    #[test]
+    fn route_limits_test() {
+        // a search out of iterations answers what it found, marked incomplete.
+        let config = SolverConfig {
+            max_iterations: Some(0),
+            ..Default::default()
+        };
+        let (status, plan) = route("POST", "/rebalance", BOARD, &config);
+        assert_eq!(status, 200);
+        assert_eq!(plan.get("complete").and_then(Value::as_bool), Some(false));
+    }
+}
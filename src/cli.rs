@@ -0,0 +1,531 @@
+// command line front end, see USAGE. Kept in the library so the commands
+// can be tested without spawning the binary.
+
+use std::io::Write;
+use std::time::Duration;
+
+#[cfg(feature = "fabric")]
+use crate::fabric::FabricClient;
+use crate::json::{ToJson, Value};
+#[cfg(feature = "k8s")]
+use crate::k8s::KubeClient;
+use crate::solver::{
+    Board, BoardDiff, LoadError, MovePlan, Objective, Pending, Placement, SolveError, SolverConfig,
+    ViolationReport,
+};
+use crate::{fabric, k8s};
+
+pub const USAGE: &str = "usage:
+  fabric-tools check <board> [--json]
+  fabric-tools solve <board> <pending> [--exact] [--json]
+  fabric-tools rebalance <board> [--budget <cost>] [--defragment <count>] [--json]
+  fabric-tools dot <board>
+  fabric-tools diff <before> <after> [--json]
+  fabric-tools import-fabric <snapshot.json | http://gateway>
+  fabric-tools import-k8s <snapshot.json | http://proxy> [--pending <file>]
+  fabric-tools serve <address>
+
+board and pending files are .toml or .json. check exits with 1 when the
+board has violations. solve --exact places the batch with the lowest
+soft penalty instead of the first that fits, and exits with 1 when it
+stops at its time or state bound before proving that placement optimal. dot prints the board as a
+graphviz graph. diff lists what changed between two boards and exits
+with 1 when they differ.
+import-fabric prints the board of a service fabric cluster snapshot, or of
+a live cluster when built with the fabric feature. import-k8s does the same
+for kubernetes with the k8s feature, writing pods not scheduled yet to the
+--pending file. serve answers solver requests over http, see the server
+feature.";
+
+// how long solve --exact searches.
+const EXACT_DURATION: Duration = Duration::from_secs(60);
+
+struct Options {
+    json: bool,
+    // solve with the exact solver.
+    exact: bool,
+    budget: Option<i64>,
+    // resources to empty instead of balancing.
+    defragment: Option<usize>,
+    // where import-k8s writes the unscheduled pods.
+    pending: Option<String>,
+    positional: Vec<String>,
+}
+
+fn parse_options(args: &[String]) -> Result<Options, String> {
+    let mut opts = Options {
+        json: false,
+        exact: false,
+        budget: None,
+        defragment: None,
+        pending: None,
+        positional: Vec::new(),
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => opts.json = true,
+            "--exact" => opts.exact = true,
+            "--budget" => {
+                let v = args.next().ok_or("--budget needs a value")?;
+                opts.budget = Some(v.parse().map_err(|_| format!("invalid budget: {}", v))?);
+            }
+            "--defragment" => {
+                let v = args.next().ok_or("--defragment needs a value")?;
+                opts.defragment = Some(v.parse().map_err(|_| format!("invalid count: {}", v))?);
+            }
+            "--pending" => {
+                opts.pending = Some(args.next().ok_or("--pending needs a value")?.clone());
+            }
+            s if s.starts_with("--") => return Err(format!("unknown option: {}", s)),
+            s => opts.positional.push(s.to_string()),
+        }
+    }
+    Ok(opts)
+}
+
+fn load_errors(path: &str, errors: Vec<LoadError>) -> String {
+    let msgs: Vec<String> = errors.iter().map(|e| format!("{}: {}", path, e)).collect();
+    msgs.join("\n")
+}
+
+fn load_board(path: &str) -> Result<Board, String> {
+    Board::from_file(path).map_err(|e| load_errors(path, e))
+}
+
+fn import_fabric(source: &str) -> Result<Board, String> {
+    if source.starts_with("http://") {
+        #[cfg(feature = "fabric")]
+        return FabricClient::new(source).board().map_err(|e| e.to_string());
+        #[cfg(not(feature = "fabric"))]
+        return Err(String::from(
+            "live import needs the fabric feature, pass a snapshot file",
+        ));
+    }
+    let snapshot = read_snapshot(source)?;
+    fabric::board_from_snapshot(&snapshot).map_err(|e| format!("{}: {}", source, e))
+}
+
+fn import_k8s(source: &str) -> Result<(Board, Pending), String> {
+    if source.starts_with("http://") {
+        #[cfg(feature = "k8s")]
+        return KubeClient::new(source).board().map_err(|e| e.to_string());
+        #[cfg(not(feature = "k8s"))]
+        return Err(String::from(
+            "live import needs the k8s feature, pass a snapshot file",
+        ));
+    }
+    let snapshot = read_snapshot(source)?;
+    k8s::board_from_snapshot(&snapshot).map_err(|e| format!("{}: {}", source, e))
+}
+
+fn read_snapshot(path: &str) -> Result<Value, String> {
+    let content = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    Value::parse(&content).map_err(|e| format!("{}: {}", path, e))
+}
+
+// runs the command line and returns the exit code. Usage problems and
+// load or solve failures are returned as errors.
+pub fn run(args: &[String], out: &mut dyn Write) -> Result<i32, String> {
+    let (command, rest) = args.split_first().ok_or(USAGE)?;
+    let opts = parse_options(rest)?;
+    let expect_args = |n: usize| {
+        if opts.positional.len() == n {
+            Ok(())
+        } else {
+            Err(USAGE.to_string())
+        }
+    };
+    let io = |e: std::io::Error| e.to_string();
+
+    match command.as_str() {
+        "check" => {
+            expect_args(1)?;
+            let b = load_board(&opts.positional[0])?;
+            let report = b.check_all();
+            if opts.json {
+                writeln!(out, "{}", report.to_value().to_string_pretty()).map_err(io)?;
+            } else {
+                write_report(out, &report).map_err(io)?;
+            }
+            let shortfalls = b.check_groups();
+            if !opts.json {
+                for s in &shortfalls {
+                    writeln!(out, "{}", s).map_err(io)?;
+                }
+            }
+            Ok(if report.is_empty() && shortfalls.is_empty() {
+                0
+            } else {
+                1
+            })
+        }
+        "solve" => {
+            expect_args(2)?;
+            let mut b = load_board(&opts.positional[0])?;
+            let path = &opts.positional[1];
+            let pending = Pending::from_file(path).map_err(|e| load_errors(path, e))?;
+            let (placement, complete) = if opts.exact {
+                let config = SolverConfig {
+                    max_duration: Some(EXACT_DURATION),
+                    ..Default::default()
+                };
+                let bounded = b
+                    .place_exact(&pending, &config)
+                    .map_err(|e| e.to_string())?;
+                b.apply_pending(pending, &bounded.value)
+                    .map_err(|e| SolveError::Invalid(e).to_string())?;
+                (bounded.value, bounded.complete)
+            } else {
+                (b.solve(pending).map_err(|e| e.to_string())?, true)
+            };
+            if opts.json {
+                writeln!(out, "{}", placement.to_value().to_string_pretty()).map_err(io)?;
+            } else {
+                write_placement(out, &placement).map_err(io)?;
+                if !complete {
+                    writeln!(out, "stopped early, not proven optimal").map_err(io)?;
+                }
+            }
+            Ok(if complete { 0 } else { 1 })
+        }
+        "rebalance" => {
+            expect_args(1)?;
+            let b = load_board(&opts.positional[0])?;
+            let config = SolverConfig {
+                move_cost_budget: opts.budget,
+                objective: opts
+                    .defragment
+                    .map_or(Objective::Balance, |n| Objective::Defragment {
+                        target_empty_resources: n,
+                    }),
+                ..Default::default()
+            };
+            let plan = b.rebalance_with(&config);
+            if opts.json {
+                writeln!(out, "{}", plan.to_value().to_string_pretty()).map_err(io)?;
+            } else {
+                write_plan(out, &plan).map_err(io)?;
+            }
+            Ok(0)
+        }
+        "dot" => {
+            expect_args(1)?;
+            let b = load_board(&opts.positional[0])?;
+            write!(out, "{}", b.to_dot()).map_err(io)?;
+            Ok(0)
+        }
+        "diff" => {
+            expect_args(2)?;
+            let before = load_board(&opts.positional[0])?;
+            let after = load_board(&opts.positional[1])?;
+            let diff = before.diff(&after);
+            if opts.json {
+                writeln!(out, "{}", diff.to_value().to_string_pretty()).map_err(io)?;
+            } else {
+                write_diff(out, &diff).map_err(io)?;
+            }
+            Ok(if diff.is_empty() { 0 } else { 1 })
+        }
+        "import-fabric" => {
+            expect_args(1)?;
+            let b = import_fabric(&opts.positional[0])?;
+            writeln!(out, "{}", b.to_json()).map_err(io)?;
+            Ok(0)
+        }
+        "import-k8s" => {
+            expect_args(1)?;
+            let (b, pending) = import_k8s(&opts.positional[0])?;
+            if let Some(path) = &opts.pending {
+                std::fs::write(path, pending.to_value().to_string_pretty())
+                    .map_err(|e| format!("{}: {}", path, e))?;
+            }
+            writeln!(out, "{}", b.to_json()).map_err(io)?;
+            Ok(0)
+        }
+        "serve" => {
+            expect_args(1)?;
+            #[cfg(feature = "server")]
+            return crate::server::serve(opts.positional[0].as_str())
+                .map(|_| 0)
+                .map_err(io);
+            #[cfg(not(feature = "server"))]
+            return Err(String::from("serve needs the server feature"));
+        }
+        "help" | "--help" | "-h" => {
+            writeln!(out, "{}", USAGE).map_err(io)?;
+            Ok(0)
+        }
+        _ => Err(format!("unknown command: {}\n{}", command, USAGE)),
+    }
+}
+
+fn write_report(out: &mut dyn Write, report: &ViolationReport) -> std::io::Result<()> {
+    if report.is_empty() {
+        return writeln!(out, "no violations");
+    }
+    for v in report.iter() {
+        let kind = v.kind;
+        match (&v.relation_id, &v.entity_id, &v.metric, v.overage) {
+            (Some(rel), Some(e), _, _) if !v.is_hard() => writeln!(
+                out,
+                "{} {}: {} on {} (soft, weight {})",
+                kind,
+                rel,
+                e,
+                v.resource_id,
+                v.penalty()
+            )?,
+            (Some(rel), Some(e), _, _) => {
+                writeln!(out, "{} {}: {} on {}", kind, rel, e, v.resource_id)?
+            }
+            (None, Some(e), _, _) => writeln!(out, "{}: {} on {}", kind, e, v.resource_id)?,
+            (_, _, Some(metric), Some(over)) => writeln!(
+                out,
+                "{}: {} {} over by {}",
+                kind, v.resource_id, metric, over
+            )?,
+            _ => writeln!(out, "{}: {}", kind, v.resource_id)?,
+        }
+    }
+    writeln!(out, "{} violation(s)", report.len())
+}
+
+fn write_placement(out: &mut dyn Write, placement: &Placement) -> std::io::Result<()> {
+    let mut ids: Vec<&String> = placement.assignment.keys().collect();
+    ids.sort();
+    for id in ids {
+        writeln!(out, "{} -> {}", id, placement.assignment[id])?;
+    }
+    Ok(())
+}
+
+fn write_plan(out: &mut dyn Write, plan: &MovePlan) -> std::io::Result<()> {
+    if plan.is_empty() {
+        return writeln!(out, "no moves");
+    }
+    for m in &plan.moves {
+        writeln!(
+            out,
+            "move {}: {} -> {} (cost {})",
+            m.entity_id, m.from, m.to, m.cost
+        )?;
+    }
+    writeln!(
+        out,
+        "{} move(s), total cost {}",
+        plan.len(),
+        plan.total_cost()
+    )
+}
+
+fn write_diff(out: &mut dyn Write, diff: &BoardDiff) -> std::io::Result<()> {
+    if diff.is_empty() {
+        return writeln!(out, "no changes");
+    }
+    for (kind, changes) in diff.kinds() {
+        for id in &changes.added {
+            writeln!(out, "+ {} {}", kind, id)?;
+        }
+        for id in &changes.removed {
+            writeln!(out, "- {} {}", kind, id)?;
+        }
+        for id in &changes.changed {
+            writeln!(out, "~ {} {}", kind, id)?;
+        }
+    }
+    for m in &diff.moves {
+        writeln!(out, "move {}: {} -> {}", m.entity_id, m.from, m.to)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::run;
+    use crate::json::Value;
+
+    fn parse_output(out: &[u8]) -> Value {
+        Value::parse(std::str::from_utf8(out).expect("utf-8")).expect("json")
+    }
+
+    fn write_board(name: &str, content: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("fabric-tools-cli-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        std::fs::write(&path, content).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    const BOARD: &str = r#"
+[[resources]]
+id = "node1"
+
+[[resources]]
+id = "node2"
+
+[[entities]]
+id = "a"
+resource = "node1"
+move_cost = 3
+
+[[entities]]
+id = "c"
+resource = "node1"
+move_cost = 1
+
+[[id_relations]]
+id = "apart"
+kind = "EEAntiAffinity"
+id1 = "a"
+id2 = "c"
+"#;
+
+    fn run_args(args: &[&str]) -> (Result<i32, String>, String) {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        let mut out = Vec::new();
+        let code = run(&args, &mut out);
+        (code, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn cli_check_and_rebalance_test() {
+        let board = write_board("board.toml", BOARD);
+        let (code, out) = run_args(&["check", &board]);
+        assert_eq!(code, Ok(1));
+        assert_eq!(
+            out,
+            "EEAntiAffinity apart: a on node1\nEEAntiAffinity apart: c on node1\n2 violation(s)\n"
+        );
+
+        let (code, out) = run_args(&["rebalance", &board]);
+        assert_eq!(code, Ok(0));
+        assert_eq!(
+            out,
+            "move c: node1 -> node2 (cost 1)\n1 move(s), total cost 1\n"
+        );
+
+        let (code, out) = run_args(&["rebalance", &board, "--json", "--budget", "0"]);
+        assert_eq!(code, Ok(0));
+        let v = parse_output(out.as_bytes());
+        assert_eq!(v.get("moves").unwrap().as_array().unwrap().len(), 0);
+
+        // the pair can't share a node, so nothing consolidates.
+        let (code, out) = run_args(&["rebalance", &board, "--defragment", "1"]);
+        assert_eq!(code, Ok(0));
+        assert_eq!(
+            out,
+            "move c: node1 -> node2 (cost 1)\n1 move(s), total cost 1\n"
+        );
+        let (code, out) = run_args(&["dot", &board]);
+        assert_eq!(code, Ok(0));
+        assert!(out.contains("\"e:a\" -> \"r:node1\" [style=bold, color=red, penwidth=2];"));
+        let (code, _) = run_args(&["rebalance", &board, "--defragment", "x"]);
+        assert_eq!(code, Err(String::from("invalid count: x")));
+    }
+
+    #[test]
+    fn cli_diff_test() {
+        let before = write_board("diff-before.toml", BOARD);
+        let moved = BOARD
+            .replace(
+                "id = \"c\"\nresource = \"node1\"",
+                "id = \"c\"\nresource = \"node2\"",
+            )
+            .replace("id = \"apart\"", "id = \"apart2\"");
+        let after = write_board("diff-after.toml", &moved);
+        let (code, out) = run_args(&["diff", &before, &after]);
+        assert_eq!(code, Ok(1));
+        assert_eq!(
+            out,
+            "+ id relation apart2\n- id relation apart\nmove c: node1 -> node2\n"
+        );
+        let (code, out) = run_args(&["diff", &before, &before, "--json"]);
+        assert_eq!(code, Ok(0));
+        assert_eq!(
+            parse_output(out.as_bytes())
+                .get("moves")
+                .and_then(|m| m.as_array())
+                .map(|m| m.len()),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn cli_solve_test() {
+        let board = write_board("solve-board.toml", BOARD);
+        let pending = write_board(
+            "pending.toml",
+            "[[entities]]\nid = \"b\"\n\n[[id_relations]]\nid = \"b-on-2\"\nkind = \"ERAffinity\"\nid1 = \"b\"\nid2 = \"node2\"\n",
+        );
+        for exact in [false, true] {
+            let mut args = vec!["solve", &board, &pending, "--json"];
+            if exact {
+                args.push("--exact");
+            }
+            let (code, out) = run_args(&args);
+            assert_eq!(code, Ok(0));
+            assert_eq!(
+                parse_output(out.as_bytes()).get("b").unwrap().as_str(),
+                Some("node2")
+            );
+        }
+
+        let (code, _) = run_args(&["solve", &board]);
+        assert!(code.unwrap_err().starts_with("usage:"));
+        let (code, _) = run_args(&["check", "missing.yaml"]);
+        assert!(code.is_err());
+    }
+
+    #[test]
+    fn cli_constraint_test() {
+        let board = write_board(
+            "constraint-board.toml",
+            "[[resources]]\nid = \"node1\"\nproperties = [\"zone=z1\"]\n\n[[entities]]\nid = \"a\"\nresource = \"node1\"\nconstraint = \"zone != z1\"\n",
+        );
+        let (code, out) = run_args(&["check", &board]);
+        assert_eq!(code, Ok(1));
+        assert_eq!(out, "Constraint: a on node1\n1 violation(s)\n");
+
+        let board = write_board(
+            "bad-constraint-board.toml",
+            "[[resources]]\nid = \"node1\"\n\n[[entities]]\nid = \"a\"\nresource = \"node1\"\nconstraint = \"zone ==\"\n",
+        );
+        let (code, _) = run_args(&["check", &board]);
+        assert!(code.unwrap_err().contains("expected value"));
+    }
+
+    #[test]
+    fn cli_import_fabric_test() {
+        let snapshot = write_board(
+            "snapshot.json",
+            r#"{"nodes": [{"Name": "n0", "Type": "Front", "NodeStatus": "Up"}],
+                "services": [{"description": {"ServiceName": "fabric:/app/web"},
+                  "partitions": [{"partition": {"PartitionInformation": {"Id": "p"}},
+                    "replicas": [{"InstanceId": "1", "NodeName": "n0"}]}]}]}"#,
+        );
+        let (code, out) = run_args(&["import-fabric", &snapshot]);
+        assert_eq!(code, Ok(0));
+        let b = crate::solver::Board::from_json(&out).expect("board json");
+        assert_eq!(b.assignment()["app/web/p/1"], "n0");
+    }
+
+    #[test]
+    fn cli_import_k8s_test() {
+        let snapshot = write_board(
+            "k8s-snapshot.json",
+            r#"{"nodes": {"items": [{"metadata": {"name": "n1"},
+                  "status": {"conditions": [{"type": "Ready", "status": "True"}]}}]},
+                "pods": {"items": [
+                  {"metadata": {"name": "a"}, "spec": {"nodeName": "n1", "containers": []}},
+                  {"metadata": {"name": "b"}, "spec": {"containers": []}}]}}"#,
+        );
+        let pending = write_board("k8s-pending.json", "");
+        let (code, out) = run_args(&["import-k8s", &snapshot, "--pending", &pending]);
+        assert_eq!(code, Ok(0));
+        let b = crate::solver::Board::from_json(&out).expect("board json");
+        assert_eq!(b.assignment()["default/a"], "n1");
+        let p = crate::solver::Pending::from_file(&pending).expect("pending json");
+        assert!(p.entities.contains_key("default/b"));
+    }
+}
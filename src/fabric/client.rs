@@ -0,0 +1,122 @@
+// rest client fetching a snapshot from a live cluster, see board_from_snapshot.
+
+use std::collections::BTreeMap;
+
+use crate::http;
+use crate::import::ImportError;
+use crate::json::{array_field, field, str_field, Value};
+use crate::solver::Board;
+
+use super::board_from_snapshot;
+
+const API_VERSION: &str = "6.0";
+
+// talks to the cluster's http gateway, like "http://localhost:19080".
+pub struct FabricClient {
+    endpoint: String,
+}
+
+impl FabricClient {
+    pub fn new(endpoint: impl Into<String>) -> FabricClient {
+        let endpoint: String = endpoint.into();
+        FabricClient {
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+        }
+    }
+
+    // the board of the cluster's current state.
+    pub fn board(&self) -> Result<Board, ImportError> {
+        board_from_snapshot(&self.fetch_snapshot()?)
+    }
+
+    // the nodes, their loads and every service of the user applications,
+    // in the layout board_from_snapshot reads.
+    pub fn fetch_snapshot(&self) -> Result<Value, ImportError> {
+        let nodes = self.get_all("/Nodes")?;
+        let mut node_loads = Vec::new();
+        for node in &nodes {
+            let name = str_field(node, "Name", "$.nodes")?;
+            node_loads.push(self.get(&format!(
+                "/Nodes/{}/$/GetLoadInformation",
+                http::encode(&name)
+            ))?);
+        }
+
+        let mut services = Vec::new();
+        for app in self.get_all("/Applications")? {
+            let app_id = str_field(&app, "Id", "$.applications")?;
+            for service in self.get_all(&format!(
+                "/Applications/{}/$/GetServices",
+                http::encode(&app_id)
+            ))? {
+                let service_id = http::encode(&str_field(&service, "Id", "$.services")?);
+                let description =
+                    self.get(&format!("/Services/{}/$/GetDescription", service_id))?;
+                let mut partitions = Vec::new();
+                for partition in
+                    self.get_all(&format!("/Services/{}/$/GetPartitions", service_id))?
+                {
+                    let info = field(&partition, "PartitionInformation", "$.partitions")?;
+                    let pid = http::encode(&str_field(info, "Id", "$.partitions")?);
+                    let replicas = self.get_all(&format!("/Partitions/{}/$/GetReplicas", pid))?;
+                    let load = self.get(&format!("/Partitions/{}/$/GetLoadInformation", pid))?;
+                    partitions.push(object([
+                        ("partition", partition),
+                        ("replicas", Value::Array(replicas)),
+                        ("load", load),
+                    ]));
+                }
+                services.push(object([
+                    ("description", description),
+                    ("partitions", Value::Array(partitions)),
+                ]));
+            }
+        }
+
+        Ok(object([
+            ("nodes", Value::Array(nodes)),
+            ("node_loads", Value::Array(node_loads)),
+            ("services", Value::Array(services)),
+        ]))
+    }
+
+    fn get(&self, path: &str) -> Result<Value, ImportError> {
+        self.get_page(path, "")
+    }
+
+    fn get_page(&self, path: &str, continuation: &str) -> Result<Value, ImportError> {
+        let mut url = format!("{}{}?api-version={}", self.endpoint, path, API_VERSION);
+        if !continuation.is_empty() {
+            url.push_str("&ContinuationToken=");
+            url.push_str(&http::encode(continuation));
+        }
+        Ok(Value::parse(&http::get(&url)?)?)
+    }
+
+    // the items of a paged list, following continuation tokens.
+    fn get_all(&self, path: &str) -> Result<Vec<Value>, ImportError> {
+        let mut items = Vec::new();
+        let mut token = String::new();
+        loop {
+            let page = self.get_page(path, &token)?;
+            items.extend(array_field(&page, "Items", path)?.iter().cloned());
+            token = page
+                .get("ContinuationToken")
+                .and_then(Value::as_str)
+                .unwrap_or("")
+                .to_string();
+            if token.is_empty() {
+                return Ok(items);
+            }
+        }
+    }
+}
+
+fn object<const N: usize>(fields: [(&str, Value); N]) -> Value {
+    Value::Object(
+        fields
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect::<BTreeMap<_, _>>(),
+    )
+}
@@ -0,0 +1,361 @@
+// service fabric cluster state as a board. The conversion works on a
+// snapshot of the cluster's rest responses, so a saved snapshot can be
+// solved offline; FabricClient (feature "fabric") fetches one from a live
+// cluster.
+//
+// snapshot layout, each object as returned by the rest api:
+//
+//   {"nodes": [NodeInfo], "node_loads": [NodeLoadInfo],
+//    "services": [{"description": ServiceDescription,
+//                  "partitions": [{"partition": ServicePartitionInfo,
+//                                  "replicas": [ReplicaInfo],
+//                                  "load": PartitionLoadInformation}]}]}
+//
+// nodes become resources with NodeType and NodeName properties, their
+// domains and the node capacities. Replicas and instances become entities
+// "<service>/<partition id>/<replica id>" with their reported loads, the
+// service's placement constraint and properties service, partition and
+// role. Replicas of a partition are kept on distinct nodes, fault domains
+// and upgrade domains, and services with an affinity correlation prefer
+// their primaries on the parent's primary.
+
+#[cfg(feature = "fabric")]
+mod client;
+
+#[cfg(feature = "fabric")]
+pub use client::FabricClient;
+
+use std::collections::HashMap;
+
+use crate::import::{number, ImportError};
+use crate::json::{array_field, field, shape_error, str_field, Value};
+use crate::solver::{
+    Board, BoardBuilder, DomainKind, DomainRelation, EntityBuilder, IDRelation, IDRelationKind,
+    PlacementConstraint, Priority, PropertyRelation, PropertyRelationKind, ResourceBuilder,
+    ResourceState,
+};
+
+// builds the board from a snapshot, see the module comment.
+pub fn board_from_snapshot(snapshot: &Value) -> Result<Board, ImportError> {
+    let mut builder = BoardBuilder::new();
+
+    let mut capacities: HashMap<String, Vec<(String, i64)>> = HashMap::new();
+    for (i, load) in array_field(snapshot, "node_loads", "$")?.iter().enumerate() {
+        let path = format!("$.node_loads[{}]", i);
+        let node = str_field(load, "NodeName", &path)?;
+        for (j, m) in array_field(load, "NodeLoadMetricInformation", &path)?
+            .iter()
+            .enumerate()
+        {
+            let path = format!("{}.NodeLoadMetricInformation[{}]", path, j);
+            let name = str_field(m, "Name", &path)?;
+            // 0 means the metric has no capacity on the node.
+            let cap = number_field(m, "NodeCapacity", &path)?;
+            if cap > 0 {
+                capacities
+                    .entry(node.clone())
+                    .or_default()
+                    .push((name, cap));
+            }
+        }
+    }
+
+    for (i, node) in array_field(snapshot, "nodes", "$")?.iter().enumerate() {
+        let path = format!("$.nodes[{}]", i);
+        let name = str_field(node, "Name", &path)?;
+        let mut r = ResourceBuilder::new(name.clone())
+            .typed_property("NodeName", name.as_str())
+            .typed_property("NodeType", str_field(node, "Type", &path)?.as_str());
+        if let Some(fd) = node.get("FaultDomain").and_then(Value::as_str) {
+            r = r.fault_domain(fd.trim_start_matches("fd:").trim_start_matches('/'));
+        }
+        if let Some(ud) = node.get("UpgradeDomain").and_then(Value::as_str) {
+            r = r.upgrade_domain(ud);
+        }
+        for (metric, cap) in capacities.remove(&name).unwrap_or_default() {
+            r = r.capacity(metric, cap);
+        }
+        let mut r = r.build();
+        r.state = match node.get("NodeStatus").and_then(Value::as_str) {
+            Some("Up") => ResourceState::Active,
+            Some("Disabling") => ResourceState::Draining,
+            // down, disabled or unknown nodes keep what they report.
+            _ => ResourceState::Paused,
+        };
+        builder = builder.resource(r);
+    }
+
+    // service name -> entity of the first partition's primary, for
+    // correlations. Stateless services use their first instance.
+    let mut primaries: HashMap<String, String> = HashMap::new();
+    // (child service name, parent service name, child primaries)
+    let mut correlated: Vec<(String, String, Vec<String>)> = Vec::new();
+    for (i, service) in array_field(snapshot, "services", "$")?.iter().enumerate() {
+        let path = format!("$.services[{}]", i);
+        let desc = field(service, "description", &path)?;
+        let desc_path = format!("{}.description", path);
+        let service_name = str_field(desc, "ServiceName", &desc_path)?;
+        let short_name = service_name
+            .strip_prefix("fabric:/")
+            .unwrap_or(&service_name)
+            .to_string();
+        let constraint = match desc.get("PlacementConstraints").and_then(Value::as_str) {
+            Some(s) if !s.trim().is_empty() => {
+                Some(PlacementConstraint::parse(s).map_err(|e| {
+                    shape_error(
+                        &format!("{}.PlacementConstraints", desc_path),
+                        &e.to_string(),
+                    )
+                })?)
+            }
+            _ => None,
+        };
+        let move_cost = match desc.get("DefaultMoveCost").and_then(Value::as_str) {
+            Some("Low") => 1,
+            Some("Medium") => 5,
+            Some("High") => 10,
+            Some("VeryHigh") => 20,
+            _ => 0,
+        };
+
+        let mut service_primaries = Vec::new();
+        for (j, p) in array_field(service, "partitions", &path)?
+            .iter()
+            .enumerate()
+        {
+            let path = format!("{}.partitions[{}]", path, j);
+            let info = field(field(p, "partition", &path)?, "PartitionInformation", &path)?;
+            let pid = str_field(
+                info,
+                "Id",
+                &format!("{}.partition.PartitionInformation", path),
+            )?;
+            let load = p.get("load").unwrap_or(&Value::Null);
+            let primary_load = metric_reports(load, "PrimaryLoadMetricReports", &path)?;
+            let secondary_load = metric_reports(load, "SecondaryLoadMetricReports", &path)?;
+
+            let mut placed = 0;
+            for (k, replica) in array_field(p, "replicas", &path)?.iter().enumerate() {
+                let path = format!("{}.replicas[{}]", path, k);
+                if replica.get("ReplicaStatus").and_then(Value::as_str) == Some("Dropped") {
+                    continue;
+                }
+                let Some(node) = replica.get("NodeName").and_then(Value::as_str) else {
+                    continue;
+                };
+                let replica_id = match replica.get("ReplicaId").or(replica.get("InstanceId")) {
+                    Some(Value::String(s)) => s.clone(),
+                    Some(Value::Int(n)) => n.to_string(),
+                    _ => return Err(shape_error(&path, "missing replica or instance id").into()),
+                };
+                let role = replica
+                    .get("ReplicaRole")
+                    .and_then(Value::as_str)
+                    .unwrap_or("Instance");
+                let id = format!("{}/{}/{}", short_name, pid, replica_id);
+                let mut e = EntityBuilder::new(id.clone())
+                    .typed_property("service", short_name.as_str())
+                    .typed_property("partition", pid.as_str())
+                    .typed_property("role", role)
+                    .move_cost(move_cost);
+                // stateless instance loads are reported as secondary load.
+                let metrics = if role == "Primary" {
+                    &primary_load
+                } else {
+                    &secondary_load
+                };
+                for (metric, v) in metrics {
+                    e = e.metric(metric.clone(), *v);
+                }
+                if let Some(c) = &constraint {
+                    e = e.constraint(c.clone());
+                }
+                if matches!(role, "Primary" | "Instance") && j == 0 {
+                    service_primaries.push(id.clone());
+                }
+                builder = builder.entity(node, e);
+                placed += 1;
+            }
+
+            if placed > 1 {
+                let spec = format!("partition={}", pid);
+                builder = builder.property_relation(PropertyRelation {
+                    id: format!("partition:{}", pid),
+                    kind: PropertyRelationKind::EEAntiAffinity,
+                    entity_property: spec.clone(),
+                    resource_property: String::new(),
+                    priority: Priority::Hard,
+                });
+                for (domain, name) in [(DomainKind::Fault, "fd"), (DomainKind::Upgrade, "ud")] {
+                    builder = builder.domain_relation(DomainRelation {
+                        id: format!("partition:{}:{}", pid, name),
+                        domain,
+                        entity_property: spec.clone(),
+                        min_domains: placed,
+                        priority: Priority::Hard,
+                    });
+                }
+            }
+        }
+        if let Some(first) = service_primaries.first() {
+            primaries.insert(service_name.clone(), first.clone());
+        }
+
+        for (j, c) in array_field(desc, "CorrelationScheme", &desc_path)?
+            .iter()
+            .enumerate()
+        {
+            let path = format!("{}.CorrelationScheme[{}]", desc_path, j);
+            if str_field(c, "Scheme", &path)?.ends_with("Affinity") {
+                correlated.push((
+                    service_name.clone(),
+                    str_field(c, "ServiceName", &path)?,
+                    service_primaries.clone(),
+                ));
+            }
+        }
+    }
+
+    // correlations to services outside the snapshot are dropped.
+    for (_, parent, children) in &correlated {
+        let Some(parent_primary) = primaries.get(parent) else {
+            continue;
+        };
+        for child in children {
+            builder = builder.id_relation(IDRelation {
+                id: format!("correlation:{}", child),
+                kind: IDRelationKind::EEAffinity,
+                id1: child.clone(),
+                id2: parent_primary.clone(),
+                priority: Priority::Soft(1),
+            });
+        }
+    }
+
+    Ok(builder.build()?)
+}
+
+// reads a number that may be sent as a string.
+fn number_field(v: &Value, key: &str, path: &str) -> Result<i64, ImportError> {
+    number(field(v, key, path)?)
+        .ok_or_else(|| shape_error(&format!("{}.{}", path, key), "expected number").into())
+}
+
+// (metric, value) from a list of LoadMetricReport.
+fn metric_reports(load: &Value, key: &str, path: &str) -> Result<Vec<(String, i64)>, ImportError> {
+    let mut reports = Vec::new();
+    for (i, m) in array_field(load, key, path)?.iter().enumerate() {
+        let path = format!("{}.load.{}[{}]", path, key, i);
+        let name = str_field(m, "Name", &path)?;
+        // newer clusters send CurrentValue next to the older Value.
+        let value = match m.get("CurrentValue") {
+            Some(_) => number_field(m, "CurrentValue", &path)?,
+            None => number_field(m, "Value", &path)?,
+        };
+        reports.push((name, value));
+    }
+    Ok(reports)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::board_from_snapshot;
+    use crate::import::ImportError;
+    use crate::json::Value;
+    use crate::solver::{IDRelationKind, PropertyValue, ResourceState};
+
+    const SNAPSHOT: &str = r#"{
+      "nodes": [
+        {"Name": "_Node_0", "Type": "Front", "NodeStatus": "Up",
+         "FaultDomain": "fd:/0", "UpgradeDomain": "0"},
+        {"Name": "_Node_1", "Type": "Back", "NodeStatus": "Up",
+         "FaultDomain": "fd:/1", "UpgradeDomain": "1"},
+        {"Name": "_Node_2", "Type": "Back", "NodeStatus": "Disabling",
+         "FaultDomain": "fd:/2", "UpgradeDomain": "2"}
+      ],
+      "node_loads": [
+        {"NodeName": "_Node_0", "NodeLoadMetricInformation": [
+          {"Name": "cpu", "NodeCapacity": "8", "NodeLoad": "3"},
+          {"Name": "Count", "NodeCapacity": "0", "NodeLoad": "2"}]},
+        {"NodeName": "_Node_1", "NodeLoadMetricInformation": [
+          {"Name": "cpu", "NodeCapacity": "8", "NodeLoad": "2"}]}
+      ],
+      "services": [
+        {"description": {"ServiceKind": "Stateful", "ServiceName": "fabric:/app/db",
+                         "PlacementConstraints": "NodeType==Back",
+                         "DefaultMoveCost": "Medium"},
+         "partitions": [
+           {"partition": {"PartitionInformation": {"Id": "p1"}},
+            "replicas": [
+              {"ReplicaId": "11", "ReplicaRole": "Primary", "ReplicaStatus": "Ready",
+               "NodeName": "_Node_1"},
+              {"ReplicaId": "12", "ReplicaRole": "ActiveSecondary",
+               "ReplicaStatus": "Ready", "NodeName": "_Node_2"},
+              {"ReplicaId": "13", "ReplicaRole": "None", "ReplicaStatus": "Dropped",
+               "NodeName": "_Node_0"}],
+            "load": {"PrimaryLoadMetricReports": [{"Name": "cpu", "Value": "2"}],
+                     "SecondaryLoadMetricReports": [{"Name": "cpu", "Value": "1"}]}}]},
+        {"description": {"ServiceKind": "Stateless", "ServiceName": "fabric:/app/web",
+                         "CorrelationScheme": [
+                           {"ServiceName": "fabric:/app/db", "Scheme": "Affinity"}]},
+         "partitions": [
+           {"partition": {"PartitionInformation": {"Id": "p2"}},
+            "replicas": [{"InstanceId": 21, "ReplicaStatus": "Ready",
+                          "NodeName": "_Node_0"}],
+            "load": {"SecondaryLoadMetricReports": [{"Name": "cpu", "CurrentValue": 3}]}}]}
+      ]
+    }"#;
+
+    #[test]
+    fn fabric_snapshot_test() {
+        let b = board_from_snapshot(&Value::parse(SNAPSHOT).unwrap()).expect("converts");
+        let node = &b.resources["_Node_0"];
+        assert_eq!(
+            node.properties.get("NodeType"),
+            Some(&PropertyValue::from("Front"))
+        );
+        assert_eq!(node.fault_domain.as_deref(), Some("0"));
+        assert_eq!(node.capacities.len(), 1);
+        assert_eq!(node.capacities["cpu"], 8);
+        assert_eq!(b.resources["_Node_2"].state, ResourceState::Draining);
+
+        let mut ids: Vec<&String> = b.entities.keys().collect();
+        ids.sort();
+        assert_eq!(ids, vec!["app/db/p1/11", "app/db/p1/12", "app/web/p2/21"]);
+        let primary = &b.entities["app/db/p1/11"];
+        assert_eq!(primary.metrics["cpu"], 2);
+        assert_eq!(primary.move_cost, 5);
+        assert_eq!(b.entities["app/db/p1/12"].metrics["cpu"], 1);
+        assert_eq!(b.entities["app/web/p2/21"].metrics["cpu"], 3);
+        assert_eq!(b.assignment()["app/web/p2/21"], "_Node_0");
+
+        assert!(b.property_relations.contains_key("partition:p1"));
+        assert_eq!(b.domain_relations.len(), 2);
+        let correlation = &b.id_relations["correlation:app/web/p2/21"];
+        assert_eq!(correlation.kind, IDRelationKind::EEAffinity);
+        assert_eq!(correlation.id2, "app/db/p1/11");
+
+        // the web instance breaks its soft affinity to the db primary,
+        // reported for both.
+        let report = b.check_all();
+        assert_eq!(report.len(), 2);
+        assert!(report.entries.iter().all(|v| !v.is_hard()));
+    }
+
+    #[test]
+    fn fabric_snapshot_error_test() {
+        let bad = SNAPSHOT.replace("NodeType==Back", "NodeType==");
+        let Err(ImportError::Json(e)) = board_from_snapshot(&Value::parse(&bad).unwrap()) else {
+            panic!("expected a json error");
+        };
+        assert!(e
+            .0
+            .starts_with("$.services[0].description.PlacementConstraints"));
+
+        let bad = SNAPSHOT.replace("\"_Node_2\"}", "\"_Node_9\"}");
+        assert!(matches!(
+            board_from_snapshot(&Value::parse(&bad).unwrap()),
+            Err(ImportError::Board(_))
+        ));
+    }
+}
@@ -0,0 +1,427 @@
+// c abi of the solver for embedding it in c and c++ tools, with the ffi
+// feature. The declarations are in include/fabric_tools.h. A board is an
+// opaque handle; objects go in and results come out as json strings in
+// the codec's format. Functions returning int give 0 on success and -1 on
+// failure, those returning pointers give NULL on failure; the message of
+// the last failure on the thread is then in fabric_last_error. Returned
+// strings are released with fabric_string_free. A panic inside a call is
+// caught and reported as a failure; the board may then be left partly
+// modified.
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use crate::json::{FromJson, ToJson, Value};
+use crate::solver::{
+    AffinityChain, Board, DomainRelation, Entity, IDPropertyRelation, IDRelation, Pending,
+    PropertyRelation, Resource,
+};
+
+// the board behind a handle.
+pub struct FabricBoard(Board);
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_error(message: String) {
+    let message = CString::new(message.replace('\0', " ")).expect("no nul");
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(message));
+}
+
+fn string(s: String) -> *mut c_char {
+    CString::new(s.replace('\0', " "))
+        .expect("no nul")
+        .into_raw()
+}
+
+unsafe fn str_arg<'a>(s: *const c_char, name: &str) -> Result<&'a str, String> {
+    if s.is_null() {
+        return Err(format!("{} is null", name));
+    }
+    CStr::from_ptr(s)
+        .to_str()
+        .map_err(|_| format!("{} is not utf-8", name))
+}
+
+unsafe fn json_arg<T: FromJson>(s: *const c_char, name: &str) -> Result<T, String> {
+    let v = Value::parse(str_arg(s, name)?).map_err(|e| e.to_string())?;
+    T::from_value(&v).map_err(|e| e.to_string())
+}
+
+unsafe fn board_arg<'a>(board: *mut FabricBoard) -> Result<&'a mut Board, String> {
+    board
+        .as_mut()
+        .map(|b| &mut b.0)
+        .ok_or_else(|| String::from("board is null"))
+}
+
+fn panic_message(panic: Box<dyn Any + Send>) -> String {
+    let message = panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown cause");
+    format!("panicked: {}", message)
+}
+
+// runs the body of a call, a panic must not unwind into c.
+fn guard<T>(f: impl FnOnce() -> Result<T, String>) -> Result<T, String> {
+    catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|panic| Err(panic_message(panic)))
+}
+
+fn status(f: impl FnOnce() -> Result<(), String>) -> c_int {
+    match guard(f) {
+        Ok(()) => 0,
+        Err(e) => {
+            set_error(e);
+            -1
+        }
+    }
+}
+
+fn pointer<T>(f: impl FnOnce() -> Result<*mut T, String>) -> *mut T {
+    guard(f).unwrap_or_else(|e| {
+        set_error(e);
+        std::ptr::null_mut()
+    })
+}
+
+fn json_result(f: impl FnOnce() -> Result<Value, String>) -> *mut c_char {
+    pointer(|| f().map(|v| string(v.to_string())))
+}
+
+/// The message of the last failure on this thread, NULL if none. Valid
+/// until the next failing call on the thread.
+#[no_mangle]
+pub extern "C" fn fabric_last_error() -> *const c_char {
+    guard(|| Ok(LAST_ERROR.with(|e| e.borrow().as_ref().map_or(std::ptr::null(), |s| s.as_ptr()))))
+        .unwrap_or(std::ptr::null())
+}
+
+/// An empty board, released with fabric_board_free.
+#[no_mangle]
+pub extern "C" fn fabric_board_new() -> *mut FabricBoard {
+    pointer(|| Ok(Box::into_raw(Box::new(FabricBoard(Board::new())))))
+}
+
+/// The board of a json document, NULL if it does not parse.
+///
+/// # Safety
+///
+/// json must be a nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn fabric_board_from_json(json: *const c_char) -> *mut FabricBoard {
+    pointer(|| {
+        let b = Board::from_json(str_arg(json, "json")?).map_err(|e| e.to_string())?;
+        Ok(Box::into_raw(Box::new(FabricBoard(b))))
+    })
+}
+
+/// Releases a board, NULL is ignored.
+///
+/// # Safety
+///
+/// board must come from fabric_board_new or fabric_board_from_json and
+/// not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn fabric_board_free(board: *mut FabricBoard) {
+    status(|| {
+        if !board.is_null() {
+            drop(Box::from_raw(board));
+        }
+        Ok(())
+    });
+}
+
+/// The board as json.
+///
+/// # Safety
+///
+/// board must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn fabric_board_to_json(board: *mut FabricBoard) -> *mut c_char {
+    json_result(|| board_arg(board).map(|b| b.to_value()))
+}
+
+/// Adds a resource given as json.
+///
+/// # Safety
+///
+/// board must be a live handle and resource a nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn fabric_board_add_resource(
+    board: *mut FabricBoard,
+    resource: *const c_char,
+) -> c_int {
+    status(|| {
+        let b = board_arg(board)?;
+        let r: Resource = json_arg(resource, "resource")?;
+        b.add_resource(r).map_err(|e| e.to_string())
+    })
+}
+
+/// Adds an entity given as json on the resource.
+///
+/// # Safety
+///
+/// board must be a live handle, resource_id and entity nul terminated
+/// strings.
+#[no_mangle]
+pub unsafe extern "C" fn fabric_board_add_entity(
+    board: *mut FabricBoard,
+    resource_id: *const c_char,
+    entity: *const c_char,
+) -> c_int {
+    status(|| {
+        let b = board_arg(board)?;
+        let r_id = str_arg(resource_id, "resource_id")?;
+        let e: Entity = json_arg(entity, "entity")?;
+        b.add_entity(r_id.to_string(), e).map_err(|e| e.to_string())
+    })
+}
+
+/// Adds an id relation given as json.
+///
+/// # Safety
+///
+/// board must be a live handle and relation a nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn fabric_board_add_id_relation(
+    board: *mut FabricBoard,
+    relation: *const c_char,
+) -> c_int {
+    status(|| {
+        let b = board_arg(board)?;
+        let rel: IDRelation = json_arg(relation, "relation")?;
+        b.add_id_relation(rel).map_err(|e| e.to_string())
+    })
+}
+
+/// Adds a property relation given as json.
+///
+/// # Safety
+///
+/// board must be a live handle and relation a nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn fabric_board_add_property_relation(
+    board: *mut FabricBoard,
+    relation: *const c_char,
+) -> c_int {
+    status(|| {
+        let b = board_arg(board)?;
+        let rel: PropertyRelation = json_arg(relation, "relation")?;
+        b.add_property_relation(rel).map_err(|e| e.to_string())
+    })
+}
+
+/// Adds an id property relation given as json.
+///
+/// # Safety
+///
+/// board must be a live handle and relation a nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn fabric_board_add_id_property_relation(
+    board: *mut FabricBoard,
+    relation: *const c_char,
+) -> c_int {
+    status(|| {
+        let b = board_arg(board)?;
+        let rel: IDPropertyRelation = json_arg(relation, "relation")?;
+        b.add_id_property_relation(rel).map_err(|e| e.to_string())
+    })
+}
+
+/// Adds a domain relation given as json.
+///
+/// # Safety
+///
+/// board must be a live handle and relation a nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn fabric_board_add_domain_relation(
+    board: *mut FabricBoard,
+    relation: *const c_char,
+) -> c_int {
+    status(|| {
+        let b = board_arg(board)?;
+        let rel: DomainRelation = json_arg(relation, "relation")?;
+        b.add_domain_relation(rel).map_err(|e| e.to_string())
+    })
+}
+
+/// Adds an affinity chain given as json.
+///
+/// # Safety
+///
+/// board must be a live handle and chain a nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn fabric_board_add_chain(
+    board: *mut FabricBoard,
+    chain: *const c_char,
+) -> c_int {
+    status(|| {
+        let b = board_arg(board)?;
+        let chain: AffinityChain = json_arg(chain, "chain")?;
+        b.add_chain(chain).map_err(|e| e.to_string())
+    })
+}
+
+/// The violation report of the board as json.
+///
+/// # Safety
+///
+/// board must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn fabric_board_check(board: *mut FabricBoard) -> *mut c_char {
+    json_result(|| board_arg(board).map(|b| b.check_all().to_value()))
+}
+
+/// Places a pending batch given as json and adds it to the board,
+/// returning the placement as json. The board is unchanged on failure.
+///
+/// # Safety
+///
+/// board must be a live handle and pending a nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn fabric_board_solve(
+    board: *mut FabricBoard,
+    pending: *const c_char,
+) -> *mut c_char {
+    json_result(|| {
+        let b = board_arg(board)?;
+        let p: Pending = json_arg(pending, "pending")?;
+        b.solve(p).map(|p| p.to_value()).map_err(|e| e.to_string())
+    })
+}
+
+/// The rebalance move plan of the board as json. The board is not
+/// modified.
+///
+/// # Safety
+///
+/// board must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn fabric_board_rebalance(board: *mut FabricBoard) -> *mut c_char {
+    json_result(|| board_arg(board).map(|b| b.rebalance().to_value()))
+}
+
+/// Releases a string returned by the library, NULL is ignored.
+///
+/// # Safety
+///
+/// s must come from this library and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn fabric_string_free(s: *mut c_char) {
+    status(|| {
+        if !s.is_null() {
+            drop(CString::from_raw(s));
+        }
+        Ok(())
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::{CStr, CString};
+
+    use super::*;
+    use crate::json::Value;
+
+    fn c(s: &str) -> CString {
+        CString::new(s).unwrap()
+    }
+
+    unsafe fn take(s: *mut c_char) -> Value {
+        assert!(!s.is_null());
+        let v = Value::parse(CStr::from_ptr(s).to_str().unwrap()).expect("json");
+        fabric_string_free(s);
+        v
+    }
+
+    #[test]
+    fn ffi_test() {
+        unsafe {
+            let b = fabric_board_new();
+            for id in ["node1", "node2"] {
+                let r = c(&format!(
+                    r#"{{"id": "{}", "capacities": {{"cpu": 4}}}}"#,
+                    id
+                ));
+                assert_eq!(fabric_board_add_resource(b, r.as_ptr()), 0);
+            }
+            let e = c(r#"{"id": "a", "metrics": {"cpu": 3}}"#);
+            assert_eq!(
+                fabric_board_add_entity(b, c("node1").as_ptr(), e.as_ptr()),
+                0
+            );
+            let z = c(r#"{"id": "z"}"#);
+            assert_eq!(
+                fabric_board_add_entity(b, c("node9").as_ptr(), z.as_ptr()),
+                -1
+            );
+            let err = CStr::from_ptr(fabric_last_error()).to_str().unwrap();
+            assert_eq!(err, "resource does not exist: node9");
+
+            let rel = c(
+                r#"{"id": "no-gpu", "entity_id": "a", "kind": "AntiAffinity", "resource_property": "gpu"}"#,
+            );
+            assert_eq!(fabric_board_add_id_property_relation(b, rel.as_ptr()), 0);
+            assert_eq!(fabric_board_add_id_property_relation(b, rel.as_ptr()), -1);
+            let rel = c(
+                r#"{"id": "spread", "domain": "Fault", "entity_property": "tier", "min_domains": 1}"#,
+            );
+            assert_eq!(fabric_board_add_domain_relation(b, rel.as_ptr()), 0);
+            let chain = c(r#"{"id": "tiers", "entity_property": "tier"}"#);
+            assert_eq!(fabric_board_add_chain(b, chain.as_ptr()), 0);
+
+            let pending = c(r#"{"entities": [{"id": "b", "metrics": {"cpu": 3}}]}"#);
+            let placement = take(fabric_board_solve(b, pending.as_ptr()));
+            assert_eq!(placement.get("b").and_then(|r| r.as_str()), Some("node2"));
+            let report = take(fabric_board_check(b));
+            assert_eq!(report.as_array().map(Vec::len), Some(0));
+
+            let json = fabric_board_to_json(b);
+            let copy = fabric_board_from_json(json);
+            fabric_string_free(json);
+            assert!(!copy.is_null());
+            assert!(take(fabric_board_rebalance(copy)).get("moves").is_some());
+            fabric_board_free(copy);
+            fabric_board_free(b);
+
+            assert!(fabric_board_from_json(c("{").as_ptr()).is_null());
+            assert!(fabric_board_check(std::ptr::null_mut()).is_null());
+            let err = CStr::from_ptr(fabric_last_error()).to_str().unwrap();
+            assert_eq!(err, "board is null");
+        }
+    }
+
+    #[test]
+    fn ffi_panic_test() {
+        assert_eq!(status(|| panic!("boom")), -1);
+        let err = unsafe { CStr::from_ptr(fabric_last_error()) };
+        assert_eq!(err.to_str(), Ok("panicked: boom"));
+        let p: *mut FabricBoard = pointer(|| panic!("{}", String::from("bang")));
+        assert!(p.is_null());
+        let err = unsafe { CStr::from_ptr(fabric_last_error()) };
+        assert_eq!(err.to_str(), Ok("panicked: bang"));
+    }
+
+    // the header declares every exported function.
+    #[test]
+    fn header_test() {
+        let header = include_str!("../include/fabric_tools.h");
+        let source = include_str!("ffi.rs");
+        let exported: Vec<&str> = source
+            .split("extern \"C\" fn ")
+            .skip(1)
+            .map(|s| s.split('(').next().unwrap())
+            .filter(|name| name.starts_with("fabric_"))
+            .collect();
+        assert_eq!(exported.len(), 16);
+        for name in exported {
+            assert!(header.contains(&format!("{}(", name)), "{}", name);
+        }
+    }
+}
@@ -0,0 +1,61 @@
+// pieces shared by the cluster importers, see the fabric and k8s modules.
+
+use std::fmt;
+
+use crate::json::{JsonError, Value};
+use crate::solver::SolverError;
+
+#[derive(Debug)]
+pub enum ImportError {
+    // the cluster could not be reached or answered with an error.
+    Io(std::io::Error),
+    // a response is not json or not shaped as expected.
+    Json(JsonError),
+    // the cluster state does not make a valid board.
+    Board(Vec<SolverError>),
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportError::Io(e) => write!(f, "cluster request failed: {}", e),
+            ImportError::Json(e) => write!(f, "unexpected cluster response: {}", e),
+            ImportError::Board(errors) => {
+                let msgs: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+                write!(f, "invalid cluster state: {}", msgs.join(", "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+impl From<std::io::Error> for ImportError {
+    fn from(e: std::io::Error) -> Self {
+        ImportError::Io(e)
+    }
+}
+
+impl From<JsonError> for ImportError {
+    fn from(e: JsonError) -> Self {
+        ImportError::Json(e)
+    }
+}
+
+impl From<Vec<SolverError>> for ImportError {
+    fn from(errors: Vec<SolverError>) -> Self {
+        ImportError::Board(errors)
+    }
+}
+
+// cluster apis often send numbers as strings, like "NodeCapacity": "100".
+pub(crate) fn number(v: &Value) -> Option<i64> {
+    match v {
+        Value::String(s) => s
+            .parse::<i64>()
+            .ok()
+            .or_else(|| s.parse::<f64>().ok().map(|f| f.round() as i64)),
+        Value::Float(f) => Some(f.round() as i64),
+        _ => v.as_i64(),
+    }
+}
@@ -0,0 +1,69 @@
+// fetches a snapshot from the api server, see board_from_snapshot.
+
+use std::collections::BTreeMap;
+
+use crate::http;
+use crate::import::ImportError;
+use crate::json::{array_field, Value};
+use crate::solver::{Board, Pending};
+
+use super::board_from_snapshot;
+
+// items per list request.
+const PAGE: usize = 500;
+
+// talks plain http to the api server, normally through `kubectl proxy`
+// at "http://127.0.0.1:8001" which handles authentication.
+pub struct KubeClient {
+    endpoint: String,
+}
+
+impl KubeClient {
+    pub fn new(endpoint: impl Into<String>) -> KubeClient {
+        let endpoint: String = endpoint.into();
+        KubeClient {
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+        }
+    }
+
+    // the board and pending pods of the cluster's current state.
+    pub fn board(&self) -> Result<(Board, Pending), ImportError> {
+        board_from_snapshot(&self.fetch_snapshot()?)
+    }
+
+    // the nodes and the pods of every namespace, in the layout
+    // board_from_snapshot reads.
+    pub fn fetch_snapshot(&self) -> Result<Value, ImportError> {
+        let mut snapshot = BTreeMap::new();
+        for (key, path) in [("nodes", "/api/v1/nodes"), ("pods", "/api/v1/pods")] {
+            let mut list = BTreeMap::new();
+            list.insert(String::from("items"), Value::Array(self.list(path)?));
+            snapshot.insert(key.to_string(), Value::Object(list));
+        }
+        Ok(Value::Object(snapshot))
+    }
+
+    // the items of a list, following continue tokens.
+    fn list(&self, path: &str) -> Result<Vec<Value>, ImportError> {
+        let mut items = Vec::new();
+        let mut token = String::new();
+        loop {
+            let mut url = format!("{}{}?limit={}", self.endpoint, path, PAGE);
+            if !token.is_empty() {
+                url.push_str("&continue=");
+                url.push_str(&http::encode(&token));
+            }
+            let page = Value::parse(&http::get(&url)?)?;
+            items.extend(array_field(&page, "items", path)?.iter().cloned());
+            token = page
+                .get("metadata")
+                .and_then(|m| m.get("continue"))
+                .and_then(Value::as_str)
+                .unwrap_or("")
+                .to_string();
+            if token.is_empty() {
+                return Ok(items);
+            }
+        }
+    }
+}
@@ -0,0 +1,452 @@
+// kubernetes cluster state as a board, for analyzing the scheduler's
+// decisions offline. Like the fabric module the conversion works on a
+// snapshot, here the node and pod lists as printed by
+// `kubectl get nodes -o json` and `kubectl get pods -A -o json`:
+//
+//   {"nodes": NodeList, "pods": PodList}
+//
+// KubeClient (feature "k8s") fetches one through `kubectl proxy`.
+//
+// nodes become resources with their labels as properties and allocatable
+// resources as capacities; cordoned or not ready nodes are paused. Running
+// pods become entities "<namespace>/<name>" with their labels and a
+// namespace property, the summed container requests as metrics and one
+// "pods" each; daemonset pods are pinned. nodeSelector and required node
+// affinity become the placement constraint. Pod affinity and anti-affinity
+// on the hostname topology with a single matchLabels label become EE
+// property relations on that label, hard when required and soft with the
+// term's weight when preferred. Other affinity terms and taints are not
+// modeled.
+//
+// quantities are in base units, except cpu which is in millicores.
+
+#[cfg(feature = "k8s")]
+mod client;
+
+#[cfg(feature = "k8s")]
+pub use client::KubeClient;
+
+use std::collections::BTreeMap;
+
+use crate::import::{number, ImportError};
+use crate::json::{array_field, field, shape_error, str_field, Value};
+use crate::solver::{
+    Board, BoardBuilder, Entity, EntityBuilder, Pending, PlacementConstraint, Priority,
+    PropertyRelation, PropertyRelationKind, ResourceBuilder, ResourceState,
+};
+
+const HOSTNAME: &str = "kubernetes.io/hostname";
+
+// builds the board of the scheduled pods, and the pending pods that are
+// not scheduled yet. See the module comment.
+pub fn board_from_snapshot(snapshot: &Value) -> Result<(Board, Pending), ImportError> {
+    let mut builder = BoardBuilder::new();
+    let nodes = field(snapshot, "nodes", "$")?;
+    for (i, node) in array_field(nodes, "items", "$.nodes")?.iter().enumerate() {
+        let path = format!("$.nodes.items[{}]", i);
+        let meta = field(node, "metadata", &path)?;
+        let mut r = ResourceBuilder::new(str_field(meta, "name", &format!("{}.metadata", path))?);
+        for (key, value) in labels(meta) {
+            r = r.property(format!("{}={}", key, value));
+        }
+        let status = node.get("status").unwrap_or(&Value::Null);
+        if let Some(Value::Object(allocatable)) = status.get("allocatable") {
+            for (name, q) in allocatable {
+                let path = format!("{}.status.allocatable.{}", path, name);
+                r = r.capacity(name.clone(), quantity(name, q, &path)?);
+            }
+        }
+        let mut r = r.build();
+        let cordoned = node
+            .get("spec")
+            .and_then(|s| s.get("unschedulable"))
+            .and_then(Value::as_bool)
+            == Some(true);
+        let ready = array_field(status, "conditions", &path)?.iter().any(|c| {
+            c.get("type").and_then(Value::as_str) == Some("Ready")
+                && c.get("status").and_then(Value::as_str) == Some("True")
+        });
+        if cordoned || !ready {
+            r.state = ResourceState::Paused;
+        }
+        builder = builder.resource(r);
+    }
+
+    let mut pending = Pending::new();
+    // by id, so replicas sharing a term share the relation.
+    let mut relations: BTreeMap<String, PropertyRelation> = BTreeMap::new();
+    let pods = field(snapshot, "pods", "$")?;
+    for (i, pod) in array_field(pods, "items", "$.pods")?.iter().enumerate() {
+        let path = format!("$.pods.items[{}]", i);
+        let phase = pod
+            .get("status")
+            .and_then(|s| s.get("phase"))
+            .and_then(Value::as_str);
+        if matches!(phase, Some("Succeeded" | "Failed")) {
+            continue;
+        }
+        let (entity, node) = pod_entity(pod, &path, &mut relations)?;
+        match node {
+            Some(node) => builder = builder.entity(node, entity),
+            None => pending.add_entity(entity),
+        }
+    }
+
+    let board = builder
+        .property_relations(relations.into_values())
+        .build()?;
+    Ok((board, pending))
+}
+
+// the entity of a pod and the node it is scheduled on. Collects the
+// relations of its affinity terms.
+fn pod_entity(
+    pod: &Value,
+    path: &str,
+    relations: &mut BTreeMap<String, PropertyRelation>,
+) -> Result<(Entity, Option<String>), ImportError> {
+    let meta = field(pod, "metadata", path)?;
+    let meta_path = format!("{}.metadata", path);
+    let namespace = meta
+        .get("namespace")
+        .and_then(Value::as_str)
+        .unwrap_or("default");
+    let name = str_field(meta, "name", &meta_path)?;
+    let mut e = EntityBuilder::new(format!("{}/{}", namespace, name))
+        .typed_property("namespace", namespace)
+        .metric("pods", 1);
+    for (key, value) in labels(meta) {
+        e = e.property(format!("{}={}", key, value));
+    }
+    let daemon = array_field(meta, "ownerReferences", &meta_path)?
+        .iter()
+        .any(|o| o.get("kind").and_then(Value::as_str) == Some("DaemonSet"));
+    e = e.pinned(daemon);
+
+    let spec = field(pod, "spec", path)?;
+    let spec_path = format!("{}.spec", path);
+    let mut requests: BTreeMap<String, i64> = BTreeMap::new();
+    for (i, c) in array_field(spec, "containers", &spec_path)?
+        .iter()
+        .enumerate()
+    {
+        let path = format!("{}.containers[{}].resources.requests", spec_path, i);
+        if let Some(Value::Object(r)) = c.get("resources").and_then(|r| r.get("requests")) {
+            for (name, q) in r {
+                *requests.entry(name.clone()).or_insert(0) +=
+                    quantity(name, q, &format!("{}.{}", path, name))?;
+            }
+        }
+    }
+    for (name, v) in requests {
+        e = e.metric(name, v);
+    }
+
+    let mut terms = Vec::new();
+    if let Some(Value::Object(selector)) = spec.get("nodeSelector") {
+        for (key, value) in selector {
+            terms.push(format!("{}=={}", key, word(value.as_str().unwrap_or(""))));
+        }
+    }
+    let affinity = spec.get("affinity").unwrap_or(&Value::Null);
+    if let Some(required) = affinity
+        .get("nodeAffinity")
+        .and_then(|a| a.get("requiredDuringSchedulingIgnoredDuringExecution"))
+    {
+        let path = format!("{}.affinity.nodeAffinity", spec_path);
+        let mut any = Vec::new();
+        for (i, term) in array_field(required, "nodeSelectorTerms", &path)?
+            .iter()
+            .enumerate()
+        {
+            let path = format!("{}.nodeSelectorTerms[{}]", path, i);
+            let mut all = Vec::new();
+            for (j, m) in array_field(term, "matchExpressions", &path)?
+                .iter()
+                .enumerate()
+            {
+                all.push(node_requirement(
+                    m,
+                    &format!("{}.matchExpressions[{}]", path, j),
+                )?);
+            }
+            // an empty term matches no node.
+            if !all.is_empty() {
+                any.push(all.join(" && "));
+            }
+        }
+        // && binds tighter than ||, the terms need no parentheses.
+        match any.len() {
+            0 => {}
+            1 => terms.append(&mut any),
+            _ => terms.push(format!("({})", any.join(" || "))),
+        }
+    }
+    if !terms.is_empty() {
+        let source = terms.join(" && ");
+        let c = PlacementConstraint::parse(&source)
+            .map_err(|err| shape_error(&spec_path, &format!("{}: {}", source, err)))?;
+        e = e.constraint(c);
+    }
+
+    for (kind, key) in [
+        (PropertyRelationKind::EEAffinity, "podAffinity"),
+        (PropertyRelationKind::EEAntiAffinity, "podAntiAffinity"),
+    ] {
+        let Some(a) = affinity.get(key) else {
+            continue;
+        };
+        let path = format!("{}.affinity.{}", spec_path, key);
+        let required = array_field(a, "requiredDuringSchedulingIgnoredDuringExecution", &path)?;
+        let preferred = array_field(a, "preferredDuringSchedulingIgnoredDuringExecution", &path)?;
+        let terms =
+            required
+                .iter()
+                .map(|t| (t, Priority::Hard))
+                .chain(preferred.iter().filter_map(|t| {
+                    let weight = t.get("weight").and_then(number).unwrap_or(1);
+                    Some((t.get("podAffinityTerm")?, Priority::Soft(weight)))
+                }));
+        for (term, priority) in terms {
+            if term.get("topologyKey").and_then(Value::as_str) != Some(HOSTNAME) {
+                continue;
+            }
+            let Some(Value::Object(selector)) =
+                term.get("labelSelector").and_then(|s| s.get("matchLabels"))
+            else {
+                continue;
+            };
+            let [(label, value)] = selector.iter().collect::<Vec<_>>()[..] else {
+                continue;
+            };
+            let spec = format!("{}={}", label, value.as_str().unwrap_or(""));
+            let prefix = match (kind, priority) {
+                (PropertyRelationKind::EEAffinity, Priority::Hard) => "affinity",
+                (PropertyRelationKind::EEAffinity, _) => "preferred-affinity",
+                (_, Priority::Hard) => "anti-affinity",
+                _ => "preferred-anti-affinity",
+            };
+            let id = format!("{}:{}", prefix, spec);
+            let relation = relations.entry(id.clone()).or_insert(PropertyRelation {
+                id,
+                kind,
+                entity_property: spec,
+                resource_property: String::new(),
+                priority,
+            });
+            // replicas may weigh the same term differently, keep the highest.
+            if let (Priority::Soft(a), Priority::Soft(b)) = (relation.priority, priority) {
+                relation.priority = Priority::Soft(a.max(b));
+            }
+        }
+    }
+
+    let node = spec
+        .get("nodeName")
+        .and_then(Value::as_str)
+        .map(String::from);
+    Ok((e.build(), node))
+}
+
+// a nodeSelectorRequirement as a constraint expression. Exists is a bare
+// key, so it misses labels set to "false".
+fn node_requirement(m: &Value, path: &str) -> Result<String, ImportError> {
+    let key = str_field(m, "key", path)?;
+    let values: Vec<String> = array_field(m, "values", path)?
+        .iter()
+        .map(|v| word(v.as_str().unwrap_or("")))
+        .collect();
+    let any_of = || {
+        if let [v] = &values[..] {
+            return format!("{}=={}", key, v);
+        }
+        let alternatives: Vec<String> = values.iter().map(|v| format!("{}=={}", key, v)).collect();
+        format!("({})", alternatives.join(" || "))
+    };
+    let first = || {
+        values
+            .first()
+            .cloned()
+            .ok_or_else(|| shape_error(&format!("{}.values", path), "expected a value"))
+    };
+    Ok(match str_field(m, "operator", path)?.as_str() {
+        "In" if !values.is_empty() => any_of(),
+        "NotIn" if !values.is_empty() => format!("!{}", any_of()),
+        "Exists" => key,
+        "DoesNotExist" => format!("!{}", key),
+        "Gt" => format!("{}>{}", key, first()?),
+        "Lt" => format!("{}<{}", key, first()?),
+        op => {
+            return Err(shape_error(
+                &format!("{}.operator", path),
+                &format!("unsupported operator {}", op),
+            )
+            .into())
+        }
+    })
+}
+
+// a label value as a constraint value, empty values need quotes.
+fn word(value: &str) -> String {
+    if value.is_empty() {
+        String::from("''")
+    } else {
+        value.to_string()
+    }
+}
+
+fn labels(meta: &Value) -> Vec<(&String, &str)> {
+    match meta.get("labels") {
+        Some(Value::Object(labels)) => labels
+            .iter()
+            .map(|(k, v)| (k, v.as_str().unwrap_or("")))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+// a resource quantity like "500m", "2", "1.5Gi" or "128974848", in
+// millicores for cpu and base units otherwise.
+fn quantity(name: &str, q: &Value, path: &str) -> Result<i64, ImportError> {
+    let invalid = || shape_error(path, "invalid quantity");
+    let s = match q {
+        Value::String(s) => s.trim().to_string(),
+        Value::Int(i) => i.to_string(),
+        Value::Float(f) => f.to_string(),
+        _ => return Err(invalid().into()),
+    };
+    // an e followed by digits is an exponent, a trailing E is exa.
+    let bytes = s.as_bytes();
+    let split = (0..bytes.len())
+        .find(|&i| {
+            bytes[i].is_ascii_alphabetic()
+                && !(matches!(bytes[i], b'e' | b'E')
+                    && bytes
+                        .get(i + 1)
+                        .is_some_and(|c| c.is_ascii_digit() || *c == b'-'))
+        })
+        .unwrap_or(s.len());
+    let (digits, suffix) = s.split_at(split);
+    let n: f64 = digits.parse().map_err(|_| invalid())?;
+    let scale = match suffix {
+        "" => 1.0,
+        "m" => 1e-3,
+        "k" => 1e3,
+        "M" => 1e6,
+        "G" => 1e9,
+        "T" => 1e12,
+        "P" => 1e15,
+        "E" => 1e18,
+        "Ki" => 1024.0,
+        "Mi" => 1024f64.powi(2),
+        "Gi" => 1024f64.powi(3),
+        "Ti" => 1024f64.powi(4),
+        "Pi" => 1024f64.powi(5),
+        "Ei" => 1024f64.powi(6),
+        _ => return Err(invalid().into()),
+    };
+    let unit = if name == "cpu" { 1000.0 } else { 1.0 };
+    Ok((n * scale * unit).round() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{board_from_snapshot, quantity};
+    use crate::json::Value;
+    use crate::solver::{PropertyRelationKind, PropertyValue, ResourceState};
+
+    const SNAPSHOT: &str = r#"{
+      "nodes": {"kind": "NodeList", "items": [
+        {"metadata": {"name": "n1", "labels": {"kubernetes.io/hostname": "n1", "disk": "ssd"}},
+         "status": {"allocatable": {"cpu": "4", "memory": "8Gi", "pods": "110"},
+                    "conditions": [{"type": "Ready", "status": "True"}]}},
+        {"metadata": {"name": "n2", "labels": {"disk": "hdd"}},
+         "status": {"allocatable": {"cpu": "3500m", "memory": "4Gi", "pods": "110"},
+                    "conditions": [{"type": "Ready", "status": "True"}]}},
+        {"metadata": {"name": "n3", "labels": {"disk": "ssd"}},
+         "spec": {"unschedulable": true},
+         "status": {"allocatable": {"cpu": "4"},
+                    "conditions": [{"type": "Ready", "status": "True"}]}}
+      ]},
+      "pods": {"kind": "PodList", "items": [
+        {"metadata": {"name": "web-1", "namespace": "shop", "labels": {"app": "web"}},
+         "spec": {"nodeName": "n1",
+                  "containers": [
+                    {"resources": {"requests": {"cpu": "250m", "memory": "128Mi"}}},
+                    {"resources": {"requests": {"cpu": "0.25"}}}],
+                  "nodeSelector": {"disk": "ssd"},
+                  "affinity": {"podAntiAffinity": {
+                    "requiredDuringSchedulingIgnoredDuringExecution": [
+                      {"topologyKey": "kubernetes.io/hostname",
+                       "labelSelector": {"matchLabels": {"app": "web"}}}]}}},
+         "status": {"phase": "Running"}},
+        {"metadata": {"name": "web-2", "namespace": "shop", "labels": {"app": "web"}},
+         "spec": {"containers": [{"resources": {"requests": {"cpu": "250m"}}}],
+                  "affinity": {
+                    "nodeAffinity": {"requiredDuringSchedulingIgnoredDuringExecution": {
+                      "nodeSelectorTerms": [{"matchExpressions": [
+                        {"key": "disk", "operator": "In", "values": ["ssd", "nvme"]}]}]}},
+                    "podAntiAffinity": {
+                      "requiredDuringSchedulingIgnoredDuringExecution": [
+                        {"topologyKey": "kubernetes.io/hostname",
+                         "labelSelector": {"matchLabels": {"app": "web"}}}]}}},
+         "status": {"phase": "Pending"}},
+        {"metadata": {"name": "agent-x", "namespace": "kube-system",
+                      "ownerReferences": [{"kind": "DaemonSet", "name": "agent"}]},
+         "spec": {"nodeName": "n2", "containers": [{}]},
+         "status": {"phase": "Running"}},
+        {"metadata": {"name": "job-1"},
+         "spec": {"nodeName": "n2", "containers": [{}]},
+         "status": {"phase": "Succeeded"}}
+      ]}
+    }"#;
+
+    #[test]
+    fn k8s_snapshot_test() {
+        let (mut b, pending) =
+            board_from_snapshot(&Value::parse(SNAPSHOT).unwrap()).expect("converts");
+        let n1 = &b.resources["n1"];
+        assert_eq!(n1.properties.get("disk"), Some(&PropertyValue::from("ssd")));
+        assert_eq!(n1.capacities["cpu"], 4000);
+        assert_eq!(n1.capacities["memory"], 8 << 30);
+        assert_eq!(b.resources["n2"].capacities["cpu"], 3500);
+        assert_eq!(b.resources["n3"].state, ResourceState::Paused);
+
+        let web = &b.entities["shop/web-1"];
+        assert_eq!(web.metrics["cpu"], 500);
+        assert_eq!(web.metrics["memory"], 128 << 20);
+        assert_eq!(web.metrics["pods"], 1);
+        assert_eq!(web.constraint.as_ref().unwrap().source(), "disk==ssd");
+        assert!(b.entities["kube-system/agent-x"].pinned);
+        assert!(!b.entities.contains_key("default/job-1"));
+        assert_eq!(b.property_relations.len(), 1);
+        assert_eq!(
+            b.property_relations["anti-affinity:app=web"].kind,
+            PropertyRelationKind::EEAntiAffinity
+        );
+        assert!(b.check_all().is_empty());
+
+        // the pending pod avoids web-1's node and the cordoned n3.
+        let web2 = &pending.entities["shop/web-2"];
+        assert_eq!(
+            web2.constraint.as_ref().unwrap().source(),
+            "(disk==ssd || disk==nvme)"
+        );
+        let Err(e) = b.solve(pending) else {
+            panic!("no ssd node left");
+        };
+        assert!(e.to_string().contains("n3: resource is Paused"));
+    }
+
+    #[test]
+    fn quantity_test() {
+        let q = |name: &str, s: &str| quantity(name, &Value::String(s.to_string()), "$");
+        assert_eq!(q("cpu", "100m").unwrap(), 100);
+        assert_eq!(q("cpu", "1.5").unwrap(), 1500);
+        assert_eq!(q("memory", "1Ki").unwrap(), 1024);
+        assert_eq!(q("memory", "2G").unwrap(), 2_000_000_000);
+        assert_eq!(q("memory", "1e3").unwrap(), 1000);
+        assert_eq!(q("memory", "1E").unwrap(), 1_000_000_000_000_000_000);
+        assert!(q("memory", "12Zi").is_err());
+    }
+}
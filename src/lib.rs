@@ -1 +1,17 @@
+#[cfg(any(feature = "server", feature = "wasm"))]
+mod api;
+pub mod cli;
+pub mod fabric;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(any(feature = "fabric", feature = "k8s"))]
+mod http;
+pub mod import;
+pub mod json;
+pub mod k8s;
+#[cfg(feature = "server")]
+pub mod server;
 pub mod solver;
+pub mod toml;
+#[cfg(feature = "wasm")]
+pub mod wasm;
@@ -0,0 +1,13 @@
+use std::process::exit;
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mut out = std::io::stdout().lock();
+    match fabric_tools::cli::run(&args, &mut out) {
+        Ok(code) => exit(code),
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(2)
+        }
+    }
+}
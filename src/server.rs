@@ -0,0 +1,322 @@
+// http service exposing the solver, for running it as a sidecar. Every
+// request carries the whole board, the service keeps no state:
+//
+//   POST /check      board json          -> violation report
+//   POST /solve      {"board", "pending"} -> placement
+//   POST /rebalance  board json          -> move plan
+//
+// rebalance takes the cli's options as query parameters, like
+// /rebalance?budget=10&defragment=2. Errors are {"error": message} with
+// 400 for malformed requests and 422 when the solver finds no placement.
+// Solves and rebalances stop at the max_duration of the ServerConfig and
+// say whether they finished, see api.
+
+use std::io::{BufRead, BufReader, Error, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::mpsc::{sync_channel, Receiver};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::api::{error_body, route};
+use crate::solver::SolverConfig;
+
+// request lines and headers longer than this in total are refused.
+const MAX_HEAD: u64 = 16 << 10;
+// connections served at once; more wait to be accepted.
+const WORKERS: usize = 16;
+
+// limits of the service.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerConfig {
+    // how long a solve or rebalance searches, unlimited if None.
+    pub max_duration: Option<Duration>,
+    // how long a client has to send its request, and again to read the
+    // answer, however it spreads them over reads and writes.
+    pub io_deadline: Duration,
+    // requests with larger bodies are refused.
+    pub max_body: usize,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            max_duration: Some(Duration::from_secs(10)),
+            io_deadline: Duration::from_secs(30),
+            max_body: 8 << 20,
+        }
+    }
+}
+
+// serve_with the default limits.
+pub fn serve(addr: impl ToSocketAddrs) -> std::io::Result<()> {
+    serve_with(addr, &ServerConfig::default())
+}
+
+// serves requests on a fixed pool of worker threads. Fails only when the
+// address can not be bound; a failed accept is logged and skipped.
+pub fn serve_with(addr: impl ToSocketAddrs, config: &ServerConfig) -> std::io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    let (queue, streams) = sync_channel::<TcpStream>(WORKERS);
+    let streams = Arc::new(Mutex::new(streams));
+    for _ in 0..WORKERS {
+        let streams = Arc::clone(&streams);
+        let config = config.clone();
+        std::thread::spawn(move || work(&streams, &config));
+    }
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("accept: {}", e);
+                // out of descriptors, say; give the workers time to free some.
+                std::thread::sleep(Duration::from_millis(10));
+                continue;
+            }
+        };
+        if queue.send(stream).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn work(streams: &Mutex<Receiver<TcpStream>>, config: &ServerConfig) {
+    loop {
+        let stream = match streams.lock().expect("queue lock").recv() {
+            Ok(stream) => stream,
+            Err(_) => return,
+        };
+        // the client went away, nothing to answer. A panic only loses
+        // its connection, the worker serves the next one.
+        let _ = catch_unwind(AssertUnwindSafe(|| handle_connection(stream, config)));
+    }
+}
+
+// a stream whose reads and writes fail once the deadline has passed,
+// rather than only when a single one takes too long.
+struct Deadline {
+    stream: TcpStream,
+    deadline: Instant,
+}
+
+impl Deadline {
+    fn new(stream: TcpStream, within: Duration) -> Deadline {
+        Deadline {
+            stream,
+            deadline: Instant::now() + within,
+        }
+    }
+
+    // the time left, an error once there is none.
+    fn left(&self) -> std::io::Result<Duration> {
+        match self.deadline.checked_duration_since(Instant::now()) {
+            Some(left) if !left.is_zero() => Ok(left),
+            _ => Err(Error::new(
+                ErrorKind::TimedOut,
+                "connection deadline passed",
+            )),
+        }
+    }
+}
+
+impl Read for Deadline {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.stream.set_read_timeout(Some(self.left()?))?;
+        self.stream.read(buf)
+    }
+}
+
+impl Write for Deadline {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.stream.set_write_timeout(Some(self.left()?))?;
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+fn handle_connection(stream: TcpStream, config: &ServerConfig) -> std::io::Result<()> {
+    let mut reader = BufReader::new(Deadline::new(stream.try_clone()?, config.io_deadline));
+    let solver = SolverConfig {
+        max_duration: config.max_duration,
+        ..Default::default()
+    };
+    let (status, body) = match read_request(&mut reader, config.max_body) {
+        Ok((method, target, body)) => route(&method, &target, &body, &solver),
+        Err(e) => (400, error_body(&e.to_string())),
+    };
+    let body = body.to_string_pretty();
+    let mut stream = Deadline::new(stream, config.io_deadline);
+    let response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason(status),
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes())
+}
+
+// (method, target, body) of one request.
+fn read_request(
+    reader: &mut impl BufRead,
+    max_body: usize,
+) -> std::io::Result<(String, String, String)> {
+    let invalid = |msg: &str| Error::new(ErrorKind::InvalidData, msg.to_string());
+    let mut head = reader.take(MAX_HEAD);
+    let mut line = String::new();
+    let mut next_line = |line: &mut String| {
+        line.clear();
+        head.read_line(line)?;
+        if !line.ends_with('\n') {
+            return Err(invalid("request head too large or truncated"));
+        }
+        Ok(())
+    };
+    next_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err(invalid("malformed request line"));
+    };
+    let (method, target) = (method.to_string(), target.to_string());
+
+    let mut length = 0;
+    loop {
+        next_line(&mut line)?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value
+                    .trim()
+                    .parse()
+                    .map_err(|_| invalid("invalid content-length"))?;
+            } else if name.eq_ignore_ascii_case("transfer-encoding") {
+                return Err(invalid("chunked requests are not supported"));
+            }
+        }
+    }
+    if length > max_body {
+        return Err(invalid("request body too large"));
+    }
+    // read as it arrives, the content-length is only the client's word.
+    let mut body = Vec::new();
+    reader.take(length as u64).read_to_end(&mut body)?;
+    if body.len() < length {
+        return Err(invalid("request body truncated"));
+    }
+    let body = String::from_utf8(body).map_err(|_| invalid("request body is not utf-8"))?;
+    Ok((method, target, body))
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Unprocessable Entity",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::time::{Duration, Instant};
+
+    use super::{handle_connection, read_request, ServerConfig};
+    use crate::json::Value;
+
+    const BOARD: &str = r#"{
+      "resources": [{"id": "node1"}, {"id": "node2"}],
+      "entities": [{"id": "a", "move_cost": 1}, {"id": "b", "move_cost": 2}],
+      "assignment": {"a": "node1", "b": "node1"},
+      "id_relations": [{"id": "apart", "kind": "EEAntiAffinity", "id1": "a", "id2": "b"}]
+    }"#;
+
+    #[test]
+    fn serve_connection_test() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            handle_connection(stream, &ServerConfig::default()).unwrap();
+        });
+        let mut client = TcpStream::connect(addr).unwrap();
+        write!(
+            client,
+            "POST /check HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}",
+            BOARD.len(),
+            BOARD
+        )
+        .unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        server.join().unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        let report = Value::parse(body).expect("json body");
+        assert_eq!(report.as_array().map(Vec::len), Some(2));
+    }
+
+    #[test]
+    fn read_request_test() {
+        let max_body = ServerConfig::default().max_body;
+        let read =
+            |req: String| read_request(&mut req.as_bytes(), max_body).map_err(|e| e.to_string());
+        let (method, target, body) = read(String::from(
+            "POST /solve HTTP/1.1\r\nContent-Length: 2\r\n\r\n{}",
+        ))
+        .expect("reads");
+        assert_eq!(
+            (method.as_str(), target.as_str(), body.as_str()),
+            ("POST", "/solve", "{}")
+        );
+
+        let huge = format!(
+            "POST /check HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            max_body + 1
+        );
+        assert_eq!(read(huge), Err(String::from("request body too large")));
+        let short = String::from("POST /check HTTP/1.1\r\nContent-Length: 10\r\n\r\n{}");
+        assert_eq!(read(short), Err(String::from("request body truncated")));
+        let long = format!("POST /check HTTP/1.1\r\nX: {}\r\n\r\n", "a".repeat(1 << 20));
+        assert_eq!(
+            read(long),
+            Err(String::from("request head too large or truncated"))
+        );
+    }
+
+    #[test]
+    fn connection_deadline_test() {
+        // a client trickling its request in stays under any one read
+        // timeout, the deadline still drops it.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = std::thread::spawn(move || {
+            let mut client = TcpStream::connect(addr).unwrap();
+            for b in "POST /check HTTP/1.1\r\nX: slow".bytes().cycle().take(100) {
+                if client.write_all(&[b]).is_err() {
+                    return;
+                }
+                std::thread::sleep(Duration::from_millis(20));
+            }
+        });
+        let (stream, _) = listener.accept().unwrap();
+        let config = ServerConfig {
+            io_deadline: Duration::from_millis(200),
+            ..Default::default()
+        };
+        let start = Instant::now();
+        let _ = handle_connection(stream, &config);
+        assert!(start.elapsed() < Duration::from_secs(1));
+        client.join().unwrap();
+    }
+}
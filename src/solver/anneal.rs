@@ -0,0 +1,375 @@
+// simulated annealing over the assignment of placed entities.
+
+use std::collections::HashMap;
+
+use super::balance::LoadStats;
+use super::capacity::{tolerated_capacity, Loads};
+use super::config::Limits;
+use super::index::RelationIndex;
+use super::intern::Assignment;
+use super::rng::Rng;
+use super::trace;
+use super::{
+    Board, Bounded, IDRelationKind, Move, MovePlan, Priority, SolverConfig, ViolationKind,
+};
+
+// how the temperature falls over the iterations.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TemperatureSchedule {
+    // multiplied by the factor after every iteration.
+    Geometric(f64),
+    // falls linearly to zero at the last iteration.
+    Linear,
+}
+
+// options of Board::anneal, see SolverConfig::anneal. A zero
+// initial_temperature gives plain hill climbing that only accepts moves
+// which don't make the score worse.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnnealConfig {
+    pub iterations: usize,
+    pub initial_temperature: f64,
+    pub schedule: TemperatureSchedule,
+    // weights of the score, lower is better. Violations count every hard
+    // relation violation and every unit of capacity overage, soft
+    // violations count their own weight instead, imbalance is
+    // the summed per-metric standard deviation scored by the board's
+    // ScoringConfig, move cost sums the
+    // move_cost of the entities that end up elsewhere.
+    pub violation_weight: f64,
+    pub imbalance_weight: f64,
+    pub move_cost_weight: f64,
+}
+
+impl Default for AnnealConfig {
+    fn default() -> Self {
+        AnnealConfig {
+            iterations: 10_000,
+            initial_temperature: 1.0,
+            schedule: TemperatureSchedule::Geometric(0.999),
+            violation_weight: 100.0,
+            imbalance_weight: 1.0,
+            move_cost_weight: 1.0,
+        }
+    }
+}
+
+struct Anneal<'a> {
+    board: &'a Board,
+    index: RelationIndex<'a>,
+    config: &'a AnnealConfig,
+    assignment: Assignment,
+    loads: Loads,
+    // loads of the board's assignment, tolerated above the buffer.
+    start_loads: Loads,
+    stats: LoadStats,
+    imbalance: f64,
+}
+
+impl Anneal<'_> {
+    // weighted relation violations the entity causes on the resource,
+    // counting both sides of broken entity pairs. Hard violations weigh
+    // violation_weight, soft ones their own weight.
+    fn relation_score(&self, entity_id: &str, resource_id: &str) -> f64 {
+        self.board
+            .entity_violations_at(&self.index, entity_id, resource_id, &self.assignment)
+            .iter()
+            .map(|v| {
+                let sides = match v.kind {
+                    ViolationKind::Id(
+                        IDRelationKind::EEAffinity | IDRelationKind::EEAntiAffinity,
+                    ) => 2.0,
+                    ViolationKind::Property(kind) if kind.is_ee() => 2.0,
+                    _ => 1.0,
+                };
+                let weight = match v.priority {
+                    Priority::Hard => self.config.violation_weight,
+                    Priority::Soft(w) => w as f64,
+                };
+                sides * weight
+            })
+            .sum()
+    }
+
+    fn load(&self, resource_id: &str) -> &HashMap<String, i64> {
+        self.board
+            .load_of(&self.loads, resource_id)
+            .expect("resource loaded")
+    }
+
+    fn overage(&self, resource_id: &str, load: &HashMap<String, i64>) -> i64 {
+        let r = &self.board.resources[resource_id];
+        let start = self
+            .board
+            .load_of(&self.start_loads, resource_id)
+            .expect("resource loaded");
+        load.iter()
+            .filter_map(|(metric, used)| {
+                let start = start.get(metric).copied().unwrap_or(0);
+                tolerated_capacity(r, metric, start).map(|cap| (used - cap).max(0))
+            })
+            .sum()
+    }
+
+    fn move_cost(&self, entity_id: &str, resource_id: &str) -> i64 {
+        if self.board.assignment()[entity_id] == resource_id {
+            0
+        } else {
+            self.board.entities[entity_id].move_cost
+        }
+    }
+
+    // score change of moving the entity to the resource.
+    fn delta(&self, entity_id: &str, to: &str) -> f64 {
+        let from = self
+            .board
+            .resource_in(&self.assignment, entity_id)
+            .expect("assigned");
+        let e = &self.board.entities[entity_id];
+        let c = self.config;
+
+        let relations = self.relation_score(entity_id, to) - self.relation_score(entity_id, from);
+
+        let (from_load, to_load) = (self.load(from), self.load(to));
+        let mut from_after = from_load.clone();
+        let mut to_after = to_load.clone();
+        for (metric, v) in &e.metrics {
+            *from_after.entry(metric.clone()).or_insert(0) -= v;
+            *to_after.entry(metric.clone()).or_insert(0) += v;
+        }
+        let overage = self.overage(from, &from_after) + self.overage(to, &to_after)
+            - self.overage(from, from_load)
+            - self.overage(to, to_load);
+
+        let imbalance = self.stats.total_after(&e.metrics, from_load, to_load) - self.imbalance;
+        let cost = self.move_cost(entity_id, to) - self.move_cost(entity_id, from);
+
+        relations
+            + c.violation_weight * overage as f64
+            + c.imbalance_weight * imbalance
+            + c.move_cost_weight * cost as f64
+    }
+
+    fn apply(&mut self, entity_id: &str, to: &str) {
+        let e = &self.board.entities[entity_id];
+        let from = self
+            .board
+            .place_in(&mut self.assignment, entity_id, to)
+            .expect("assigned");
+        let resources = &self.board.resources;
+        let (from, to) = (
+            resources.handle(from).expect("resource exist"),
+            resources.handle(to).expect("resource exist"),
+        );
+        self.stats
+            .apply(&e.metrics, &self.loads[&from], &self.loads[&to]);
+        for (metric, v) in &e.metrics {
+            *self
+                .loads
+                .get_mut(&from)
+                .expect("resource loaded")
+                .entry(metric.clone())
+                .or_insert(0) -= v;
+            *self
+                .loads
+                .get_mut(&to)
+                .expect("resource loaded")
+                .entry(metric.clone())
+                .or_insert(0) += v;
+        }
+        self.imbalance = self.stats.total();
+    }
+}
+
+impl Board {
+    // searches for a better assignment by randomly moving single entities,
+    // leaving chain members where they are, accepting worse moves with a
+    // probability that shrinks with the temperature, and returns the moves
+    // to the best assignment seen.
+    // Moves are drawn from config.seed and kept within move_cost_budget.
+    // The board is not modified; see apply_move_plan.
+    pub fn anneal(&self, config: &SolverConfig) -> MovePlan {
+        self.anneal_bounded(config).value
+    }
+
+    // anneal that stops at the config's limits with the best assignment
+    // seen so far.
+    pub fn anneal_bounded(&self, config: &SolverConfig) -> Bounded<MovePlan> {
+        let _span = trace::span("anneal");
+        let mut limits = Limits::new(config);
+        let solver_config = config;
+        let config = &solver_config.anneal;
+        let index = RelationIndex::new(self);
+        let mut entity_ids: Vec<&String> = self
+            .assignment()
+            .keys()
+            .filter(|e| !self.entities[*e].pinned && index.chains(e).next().is_none())
+            .collect();
+        entity_ids.sort();
+        let mut resource_ids: Vec<&String> = self
+            .resources
+            .values()
+            .filter(|r| r.state.accepts_entities())
+            .map(|r| &r.id)
+            .collect();
+        resource_ids.sort();
+        if entity_ids.is_empty() || resource_ids.len() < 2 {
+            return Bounded {
+                value: MovePlan::default(),
+                complete: true,
+            };
+        }
+
+        let loads = self.all_loads_with(&self.assignment);
+        let stats = LoadStats::new(
+            self.resources.len(),
+            &loads,
+            &self.metric_names(),
+            &self.scoring,
+        );
+        let mut state = Anneal {
+            board: self,
+            index,
+            config,
+            assignment: self.assignment.clone(),
+            start_loads: loads.clone(),
+            loads,
+            imbalance: stats.total(),
+            stats,
+        };
+        let mut rng = Rng::new(solver_config.seed);
+        let budget = solver_config.move_cost_budget;
+        let mut spent = 0;
+        // score relative to the start, and the best seen.
+        let mut score = 0.0;
+        let mut best = (0.0, state.assignment.clone());
+
+        let mut temperature = config.initial_temperature;
+        for k in 0..config.iterations {
+            if !limits.tick() {
+                break;
+            }
+            let entity_id = entity_ids[rng.below(entity_ids.len())];
+            let to = resource_ids[rng.below(resource_ids.len())];
+            let from = self
+                .resource_in(&state.assignment, entity_id)
+                .expect("assigned");
+            let cost = state.move_cost(entity_id, to) - state.move_cost(entity_id, from);
+            if from != to && budget.is_none_or(|b| spent + cost <= b) {
+                let delta = state.delta(entity_id, to);
+                let accept = delta <= 0.0
+                    || (temperature > 0.0 && rng.next_f64() < (-delta / temperature).exp());
+                if accept {
+                    state.apply(entity_id, to);
+                    spent += cost;
+                    score += delta;
+                    if score < best.0 - 1e-9 {
+                        best = (score, state.assignment.clone());
+                    }
+                }
+            }
+            temperature = match config.schedule {
+                TemperatureSchedule::Geometric(f) => temperature * f,
+                TemperatureSchedule::Linear => {
+                    config.initial_temperature * (1.0 - (k + 1) as f64 / config.iterations as f64)
+                }
+            };
+        }
+
+        let (before, after) = (self.assignment(), self.placements(&best.1));
+        let moves = entity_ids
+            .into_iter()
+            .filter(|e| after[*e] != before[*e])
+            .map(|e| Move {
+                entity_id: e.clone(),
+                from: before[e].clone(),
+                to: after[e].clone(),
+                cost: self.entities[e].move_cost,
+            })
+            .collect();
+        Bounded {
+            value: MovePlan {
+                moves,
+                evictions: Vec::new(),
+            },
+            complete: !limits.stopped(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::solver::{
+        AnnealConfig, Board, Entity, IDRelation, IDRelationKind, Priority, Resource, SolverConfig,
+        TemperatureSchedule,
+    };
+
+    fn board() -> Board {
+        let mut b = Board::new();
+        for id in ["node1", "node2", "node3"] {
+            let mut r = Resource::new(String::from(id));
+            r.capacities.insert(String::from("cpu"), 4);
+            b.add_resource(r).expect("added");
+        }
+        for id in ["a", "b", "c", "d", "e", "f"] {
+            let mut e = Entity::new(String::from(id));
+            e.metrics.insert(String::from("cpu"), 1);
+            e.move_cost = 1;
+            b.add_entity(String::from("node1"), e).expect("added");
+        }
+        b.add_id_relation(IDRelation {
+            id: String::from("apart"),
+            kind: IDRelationKind::EEAntiAffinity,
+            id1: String::from("a"),
+            id2: String::from("b"),
+            priority: Priority::Hard,
+        })
+        .expect("ok");
+        b
+    }
+
+    #[test]
+    fn anneal_test() {
+        let mut b = board();
+        let mut config = SolverConfig {
+            seed: 7,
+            anneal: AnnealConfig {
+                iterations: 2_000,
+                move_cost_weight: 0.1,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        config.move_cost_budget = Some(2);
+        assert!(b.anneal(&config).total_cost() <= 2);
+
+        config.move_cost_budget = None;
+        let plan = b.anneal(&config);
+        assert_eq!(plan, b.anneal(&config));
+        b.apply_move_plan(&plan).expect("applies");
+        // the overload and the pair are fixed, and load is spread evenly.
+        assert!(b.check_all().is_empty());
+        assert!(b.load_imbalance()["cpu"] < 1e-9);
+        assert_eq!(plan.total_cost(), 4);
+    }
+
+    #[test]
+    fn hill_climb_test() {
+        let mut b = board();
+        let config = SolverConfig {
+            anneal: AnnealConfig {
+                iterations: 2_000,
+                initial_temperature: 0.0,
+                schedule: TemperatureSchedule::Linear,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let plan = b.anneal(&config);
+        b.apply_move_plan(&plan).expect("applies");
+        assert!(b.check_all().is_empty());
+
+        // a board that is already good is left alone.
+        assert!(b.anneal(&config).is_empty());
+    }
+}
@@ -0,0 +1,461 @@
+// balancing metric load across resources.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use super::capacity::Loads;
+use super::config::Limits;
+use super::index::RelationIndex;
+use super::intern::Assignment;
+use super::{Board, Move, ScoringConfig};
+
+// per-metric sums of resource loads, enough to get the standard deviation
+// and to update it when one entity moves.
+pub(crate) struct LoadStats {
+    n: f64,
+    // metric -> (sum, sum of squares), ordered so float totals don't
+    // depend on hash order. Metrics without weight are left out.
+    sums: BTreeMap<String, (f64, f64)>,
+    // metric -> (weight, balancing threshold)
+    scoring: HashMap<String, (f64, f64)>,
+}
+
+impl LoadStats {
+    pub(crate) fn new(
+        n: usize,
+        loads: &Loads,
+        metrics: &HashSet<String>,
+        config: &ScoringConfig,
+    ) -> LoadStats {
+        let mut sums = BTreeMap::new();
+        let mut scoring = HashMap::new();
+        for metric in metrics {
+            let mut s = (0.0, 0.0);
+            for load in loads.values() {
+                let v = load.get(metric).copied().unwrap_or(0) as f64;
+                s.0 += v;
+                s.1 += v * v;
+            }
+            let weight = config.effective_weight(metric, s.0 as i64);
+            if weight == 0.0 {
+                continue;
+            }
+            sums.insert(metric.clone(), s);
+            let threshold = config.metric(metric).balancing_threshold;
+            scoring.insert(metric.clone(), (weight, threshold));
+        }
+        LoadStats {
+            n: n as f64,
+            sums,
+            scoring,
+        }
+    }
+
+    // the weighted imbalance above the metric's balancing threshold.
+    fn score(&self, metric: &str, s: (f64, f64)) -> f64 {
+        let (weight, threshold) = self.scoring[metric];
+        weight * (Self::stddev(self.n, s) - threshold).max(0.0)
+    }
+
+    fn stddev(n: f64, (sum, sq): (f64, f64)) -> f64 {
+        if n == 0.0 {
+            return 0.0;
+        }
+        let mean = sum / n;
+        (sq / n - mean * mean).max(0.0).sqrt()
+    }
+
+    pub(crate) fn total(&self) -> f64 {
+        self.sums.iter().map(|(m, s)| self.score(m, *s)).sum()
+    }
+
+    // total imbalance after moving metrics from one load to another.
+    pub(crate) fn total_after(
+        &self,
+        metrics: &HashMap<String, i64>,
+        from: &HashMap<String, i64>,
+        to: &HashMap<String, i64>,
+    ) -> f64 {
+        let mut total = 0.0;
+        for (metric, s) in &self.sums {
+            let mut s = *s;
+            if let Some(v) = metrics.get(metric) {
+                let v = *v as f64;
+                let f = from.get(metric).copied().unwrap_or(0) as f64;
+                let t = to.get(metric).copied().unwrap_or(0) as f64;
+                s.1 += (f - v) * (f - v) - f * f + (t + v) * (t + v) - t * t;
+            }
+            total += self.score(metric, s);
+        }
+        total
+    }
+
+    // records a move of metrics between two loads, given before the move.
+    pub(crate) fn apply(
+        &mut self,
+        metrics: &HashMap<String, i64>,
+        from: &HashMap<String, i64>,
+        to: &HashMap<String, i64>,
+    ) {
+        for (metric, s) in self.sums.iter_mut() {
+            if let Some(v) = metrics.get(metric) {
+                let v = *v as f64;
+                let f = from.get(metric).copied().unwrap_or(0) as f64;
+                let t = to.get(metric).copied().unwrap_or(0) as f64;
+                s.1 += (f - v) * (f - v) - f * f + (t + v) * (t + v) - t * t;
+            }
+        }
+    }
+}
+
+impl Board {
+    // standard deviation of the load of each metric across all resources.
+    // Resources without entities count with zero load. Not weighted, see
+    // ScoringConfig.
+    pub fn load_imbalance(&self) -> HashMap<String, f64> {
+        let loads = self.all_loads_with(&self.assignment);
+        let metrics = self.metric_names();
+        let stats = LoadStats::new(
+            self.resources.len(),
+            &loads,
+            &metrics,
+            &ScoringConfig::default(),
+        );
+        stats
+            .sums
+            .iter()
+            .map(|(m, s)| (m.clone(), LoadStats::stddev(stats.n, *s)))
+            .collect()
+    }
+
+    pub(crate) fn metric_names(&self) -> HashSet<String> {
+        self.entities
+            .values()
+            .flat_map(|e| e.metrics.keys().cloned())
+            .collect()
+    }
+
+    // loads including an empty entry for every resource.
+    pub(crate) fn all_loads_with(&self, assignment: &Assignment) -> Loads {
+        let mut loads = self.loads_with(assignment);
+        for (r, _) in self.resources.handles() {
+            loads.entry(r).or_default();
+        }
+        loads
+    }
+
+    // greedy moves reducing the summed per-metric standard deviation,
+    // weighted and thresholded by the board's scoring.
+    // Each step takes the move with the best improvement per unit of
+    // move_cost that keeps relations and capacities satisfied and adds no
+    // soft penalty, or else the best swap of two entities; an entity
+    // moves at most once and moves beyond the budget are skipped. Chains
+    // move as a whole and only from a single resource. The assignment is
+    // updated with the moves, a reached limit ends the steps.
+    pub(crate) fn balance_moves(
+        &self,
+        assignment: &mut Assignment,
+        budget: Option<i64>,
+        limits: &mut Limits,
+    ) -> Vec<Move> {
+        let mut spent = 0;
+        let index = RelationIndex::new(self);
+        let metrics = self.metric_names();
+        let mut loads = self.all_loads_with(assignment);
+        let mut moved: HashSet<String> = HashSet::new();
+        let mut moves = Vec::new();
+
+        let mut entity_ids: Vec<&String> = self.entities.keys().collect();
+        entity_ids.sort();
+        // entities moving together, each listed once.
+        let mut units: Vec<Vec<String>> = Vec::new();
+        let mut seen: HashSet<String> = HashSet::new();
+        for entity_id in entity_ids {
+            if !seen.contains(entity_id) {
+                let unit = self.move_unit(&index, entity_id, assignment);
+                seen.extend(unit.iter().cloned());
+                units.push(unit);
+            }
+        }
+
+        while limits.tick() {
+            let stats = LoadStats::new(self.resources.len(), &loads, &metrics, &self.scoring);
+            let current = stats.total();
+            // (weighted gain, [(entity, to)])
+            let mut best: Option<(f64, Vec<(String, String)>)> = None;
+            for unit in &units {
+                let unit_metrics = self.unit_metrics(unit);
+                if unit_metrics.is_empty()
+                    || unit
+                        .iter()
+                        .any(|id| moved.contains(id) || self.entities[id].pinned)
+                {
+                    continue;
+                }
+                let cost: i64 = unit.iter().map(|id| self.entities[id].move_cost).sum();
+                if budget.is_some_and(|b| spent + cost > b) {
+                    continue;
+                }
+                let Some(from) = self.resource_in(assignment, &unit[0]) else {
+                    continue;
+                };
+                if unit
+                    .iter()
+                    .any(|id| self.resource_in(assignment, id) != Some(from))
+                {
+                    continue;
+                }
+                for to in self.unit_candidates(&index, unit, assignment) {
+                    if &to == from || !self.fits_metrics(&unit_metrics, &to, &loads) {
+                        continue;
+                    }
+                    // balancing never trades in soft violations.
+                    if self.unit_soft_penalty(&index, unit, &to, assignment)
+                        > self.unit_soft_penalty(&index, unit, from, assignment)
+                    {
+                        continue;
+                    }
+                    let (from_load, to_load) = (
+                        self.load_of(&loads, from).expect("resource loaded"),
+                        self.load_of(&loads, &to).expect("resource loaded"),
+                    );
+                    let gain = current - stats.total_after(&unit_metrics, from_load, to_load);
+                    if gain <= 1e-9 {
+                        continue;
+                    }
+                    let weighted = gain / (1 + cost.max(0)) as f64;
+                    if best.as_ref().is_none_or(|(w, _)| weighted > *w + 1e-12) {
+                        let step = unit.iter().map(|id| (id.clone(), to.clone())).collect();
+                        best = Some((weighted, step));
+                    }
+                }
+            }
+            if best.is_none() {
+                let singles: Vec<&String> = units
+                    .iter()
+                    .filter_map(|unit| match unit.as_slice() {
+                        [id] if !moved.contains(id) => Some(id),
+                        _ => None,
+                    })
+                    .collect();
+                best = self.best_swap(
+                    &index,
+                    &singles,
+                    assignment,
+                    &loads,
+                    &stats,
+                    budget.map(|b| b - spent),
+                );
+            }
+
+            let Some((_, step)) = best else {
+                break;
+            };
+            for (entity_id, to) in step {
+                let e = &self.entities[&entity_id];
+                let from = self
+                    .place_in(assignment, &entity_id, &to)
+                    .expect("assigned")
+                    .clone();
+                let from_r = self.resources.handle(&from).expect("resource exist");
+                let to_r = self.resources.handle(&to).expect("resource exist");
+                for (metric, v) in &e.metrics {
+                    *loads
+                        .get_mut(&from_r)
+                        .expect("resource loaded")
+                        .entry(metric.clone())
+                        .or_insert(0) -= v;
+                    *loads
+                        .get_mut(&to_r)
+                        .expect("resource loaded")
+                        .entry(metric.clone())
+                        .or_insert(0) += v;
+                }
+                moved.insert(entity_id.clone());
+                spent += e.move_cost;
+                moves.push(Move {
+                    entity_id,
+                    from,
+                    to,
+                    cost: e.move_cost,
+                });
+            }
+        }
+        moves
+    }
+
+    // the swap of two of the entities on different resources with the
+    // best improvement per unit of their summed move_cost, for when every
+    // resource is too full to take an entity without giving one back.
+    // Swaps follow the rules of single balancing moves; they are only
+    // searched once no single move helps, since every pair is tried.
+    fn best_swap(
+        &self,
+        index: &RelationIndex,
+        entity_ids: &[&String],
+        assignment: &Assignment,
+        loads: &Loads,
+        stats: &LoadStats,
+        // budget left for the swap, unlimited if None.
+        left: Option<i64>,
+    ) -> Option<(f64, Vec<(String, String)>)> {
+        let current = stats.total();
+        let open = |r_id: &str| self.resources[r_id].state.accepts_entities();
+        let movable: Vec<(&String, &String)> = entity_ids
+            .iter()
+            .copied()
+            .filter(|id| {
+                let e = &self.entities[*id];
+                !e.pinned && !e.metrics.is_empty()
+            })
+            .filter_map(|id| self.resource_in(assignment, id).map(|r| (id, r)))
+            .filter(|(_, r)| open(r))
+            .collect();
+
+        let mut best: Option<(f64, Vec<(String, String)>)> = None;
+        for (i, &(a, ra)) in movable.iter().enumerate() {
+            for &(b, rb) in &movable[i + 1..] {
+                if ra == rb {
+                    continue;
+                }
+                let (ea, eb) = (&self.entities[a], &self.entities[b]);
+                let cost = ea.move_cost + eb.move_cost;
+                if left.is_some_and(|left| cost > left) {
+                    continue;
+                }
+                // a's metrics go to rb and b's come back.
+                let mut delta = ea.metrics.clone();
+                for (metric, v) in &eb.metrics {
+                    *delta.entry(metric.clone()).or_insert(0) -= v;
+                }
+                let (load_a, load_b) = (
+                    self.load_of(loads, ra).expect("resource loaded"),
+                    self.load_of(loads, rb).expect("resource loaded"),
+                );
+                let gain = current - stats.total_after(&delta, load_a, load_b);
+                if gain <= 1e-9 {
+                    continue;
+                }
+                let weighted = gain / (1 + cost.max(0)) as f64;
+                if best.as_ref().is_some_and(|(w, _)| weighted <= *w + 1e-12) {
+                    continue;
+                }
+
+                // each entity must fit where the other one left.
+                let mut without = Loads::new();
+                for (r_id, e) in [(ra, ea), (rb, eb)] {
+                    let r = self.resources.handle(r_id).expect("resource exist");
+                    let mut load = loads[&r].clone();
+                    for (metric, v) in &e.metrics {
+                        *load.entry(metric.clone()).or_insert(0) -= v;
+                    }
+                    without.insert(r, load);
+                }
+                if !self.fits_metrics(&eb.metrics, ra, &without)
+                    || !self.fits_metrics(&ea.metrics, rb, &without)
+                {
+                    continue;
+                }
+
+                let mut trial = assignment.clone();
+                self.place_in(&mut trial, a, rb);
+                self.place_in(&mut trial, b, ra);
+                let after =
+                    |id: &str, r_id: &str| self.entity_violations_at(index, id, r_id, &trial);
+                let (va, vb) = (after(a, rb), after(b, ra));
+                if va.iter().chain(&vb).any(|v| v.is_hard()) {
+                    continue;
+                }
+                let penalty: i64 = va.iter().chain(&vb).map(|v| v.penalty()).sum();
+                if penalty
+                    > self.soft_penalty_at(index, a, ra, assignment)
+                        + self.soft_penalty_at(index, b, rb, assignment)
+                {
+                    continue;
+                }
+                best = Some((
+                    weighted,
+                    vec![(a.clone(), rb.clone()), (b.clone(), ra.clone())],
+                ));
+            }
+        }
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::solver::{Board, Entity, Move, Resource};
+
+    #[test]
+    fn balance_test() {
+        let mut b = Board::new();
+        for id in ["node1", "node2"] {
+            b.add_resource(Resource::new(String::from(id)))
+                .expect("added");
+        }
+        for (id, cost) in [("app1", 2), ("app2", 1), ("app3", 3)] {
+            let mut e = Entity::new(String::from(id));
+            e.metrics.insert(String::from("cpu"), 2);
+            e.move_cost = cost;
+            b.add_entity(String::from("node1"), e).expect("added");
+        }
+        assert!((b.load_imbalance()["cpu"] - 3.0).abs() < 1e-9);
+
+        let plan = b.rebalance();
+        // one move of the cheapest entity is enough, a second can't help.
+        assert_eq!(
+            plan.moves,
+            vec![Move {
+                entity_id: String::from("app2"),
+                from: String::from("node1"),
+                to: String::from("node2"),
+                cost: 1,
+            }]
+        );
+        b.apply_move_plan(&plan).expect("applies");
+        assert!((b.load_imbalance()["cpu"] - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn swap_test() {
+        // both nodes are full, so only trading big for small evens them out.
+        let mut b = Board::new();
+        for id in ["node1", "node2"] {
+            let mut r = Resource::new(String::from(id));
+            r.capacities.insert(String::from("cpu"), 8);
+            b.add_resource(r).expect("added");
+        }
+        for (id, r, cpu, cost) in [
+            ("big1", "node1", 4, 1),
+            ("big2", "node1", 4, 2),
+            ("small1", "node2", 2, 1),
+            ("small2", "node2", 2, 1),
+        ] {
+            let mut e = Entity::new(String::from(id));
+            e.metrics.insert(String::from("cpu"), cpu);
+            e.move_cost = cost;
+            b.add_entity(String::from(r), e).expect("added");
+        }
+        let plan = b.rebalance();
+        assert_eq!(
+            plan.moves,
+            vec![
+                Move {
+                    entity_id: String::from("big1"),
+                    from: String::from("node1"),
+                    to: String::from("node2"),
+                    cost: 1,
+                },
+                Move {
+                    entity_id: String::from("small1"),
+                    from: String::from("node2"),
+                    to: String::from("node1"),
+                    cost: 1,
+                },
+            ]
+        );
+        b.apply_move_plan(&plan).expect("applies");
+        assert!(b.load_imbalance()["cpu"].abs() < 1e-9);
+        assert!(b.check_all().is_empty());
+    }
+}
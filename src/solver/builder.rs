@@ -0,0 +1,426 @@
+// builders for the board and its objects.
+
+use super::property::parse_tag;
+use super::{
+    AffinityChain, Board, DomainRelation, Entity, EntityGroup, IDPropertyRelation, IDRelation,
+    PlacementConstraint, PropertyRelation, PropertyValue, Resource, ScoringConfig, SolverError,
+};
+
+// fluent construction of a resource:
+// ResourceBuilder::new("node1").property("red").capacity("cpu", 8).
+pub struct ResourceBuilder {
+    resource: Resource,
+}
+
+impl ResourceBuilder {
+    pub fn new(id: impl Into<String>) -> ResourceBuilder {
+        ResourceBuilder {
+            resource: Resource::new(id.into()),
+        }
+    }
+
+    // a tag, "key=value" or a bare "key" set to true. Replaces an earlier
+    // value of the key.
+    pub fn property(mut self, tag: impl AsRef<str>) -> Self {
+        let (key, value) = parse_tag(tag.as_ref());
+        self.resource.set_property(key, value);
+        self
+    }
+
+    pub fn typed_property(
+        mut self,
+        key: impl Into<String>,
+        value: impl Into<PropertyValue>,
+    ) -> Self {
+        self.resource.set_property(key.into(), value.into());
+        self
+    }
+
+    pub fn capacity(mut self, metric: impl Into<String>, value: i64) -> Self {
+        self.resource.capacities.insert(metric.into(), value);
+        self
+    }
+
+    pub fn reserved(mut self, metric: impl Into<String>, value: i64) -> Self {
+        self.resource.reserved.insert(metric.into(), value);
+        self
+    }
+
+    pub fn buffer_percent(mut self, percent: u8) -> Self {
+        self.resource.buffer_percent = percent;
+        self
+    }
+
+    pub fn fault_domain(mut self, domain: impl Into<String>) -> Self {
+        self.resource.fault_domain = Some(domain.into());
+        self
+    }
+
+    pub fn upgrade_domain(mut self, domain: impl Into<String>) -> Self {
+        self.resource.upgrade_domain = Some(domain.into());
+        self
+    }
+
+    pub fn build(self) -> Resource {
+        self.resource
+    }
+}
+
+impl From<ResourceBuilder> for Resource {
+    fn from(b: ResourceBuilder) -> Self {
+        b.build()
+    }
+}
+
+// fluent construction of an entity, like ResourceBuilder.
+pub struct EntityBuilder {
+    entity: Entity,
+}
+
+impl EntityBuilder {
+    pub fn new(id: impl Into<String>) -> EntityBuilder {
+        EntityBuilder {
+            entity: Entity::new(id.into()),
+        }
+    }
+
+    // a tag, see ResourceBuilder::property.
+    pub fn property(mut self, tag: impl AsRef<str>) -> Self {
+        let (key, value) = parse_tag(tag.as_ref());
+        self.entity.set_property(key, value);
+        self
+    }
+
+    pub fn typed_property(
+        mut self,
+        key: impl Into<String>,
+        value: impl Into<PropertyValue>,
+    ) -> Self {
+        self.entity.set_property(key.into(), value.into());
+        self
+    }
+
+    pub fn metric(mut self, metric: impl Into<String>, value: i64) -> Self {
+        self.entity.metrics.insert(metric.into(), value);
+        self
+    }
+
+    pub fn move_cost(mut self, cost: i64) -> Self {
+        self.entity.move_cost = cost;
+        self
+    }
+
+    pub fn constraint(mut self, constraint: PlacementConstraint) -> Self {
+        self.entity.constraint = Some(constraint);
+        self
+    }
+
+    pub fn pinned(mut self, pinned: bool) -> Self {
+        self.entity.pinned = pinned;
+        self
+    }
+
+    pub fn priority(mut self, priority: i64) -> Self {
+        self.entity.priority = priority;
+        self
+    }
+
+    pub fn build(self) -> Entity {
+        self.entity
+    }
+}
+
+impl From<EntityBuilder> for Entity {
+    fn from(b: EntityBuilder) -> Self {
+        b.build()
+    }
+}
+
+// collects resources, entities and relations in any order and produces a
+// validated board. Dependencies are resolved at build time: resources are
+// added first, then entities, then relations.
+#[derive(Default)]
+pub struct BoardBuilder {
+    resources: Vec<Resource>,
+    // (resource id, entity)
+    entities: Vec<(String, Entity)>,
+    id_relations: Vec<IDRelation>,
+    property_relations: Vec<PropertyRelation>,
+    id_property_relations: Vec<IDPropertyRelation>,
+    domain_relations: Vec<DomainRelation>,
+    chains: Vec<AffinityChain>,
+    groups: Vec<EntityGroup>,
+    scoring: ScoringConfig,
+}
+
+impl BoardBuilder {
+    pub fn new() -> BoardBuilder {
+        BoardBuilder::default()
+    }
+
+    pub fn resource(mut self, resource: impl Into<Resource>) -> Self {
+        self.resources.push(resource.into());
+        self
+    }
+
+    pub fn resources<R: Into<Resource>>(mut self, resources: impl IntoIterator<Item = R>) -> Self {
+        self.resources.extend(resources.into_iter().map(Into::into));
+        self
+    }
+
+    // entity assigned to resource_id.
+    pub fn entity(mut self, resource_id: impl Into<String>, entity: impl Into<Entity>) -> Self {
+        self.entities.push((resource_id.into(), entity.into()));
+        self
+    }
+
+    // (resource id, entity) pairs.
+    pub fn entities<S: Into<String>, E: Into<Entity>>(
+        mut self,
+        entities: impl IntoIterator<Item = (S, E)>,
+    ) -> Self {
+        self.entities
+            .extend(entities.into_iter().map(|(r, e)| (r.into(), e.into())));
+        self
+    }
+
+    pub fn id_relation(mut self, relation: IDRelation) -> Self {
+        self.id_relations.push(relation);
+        self
+    }
+
+    pub fn id_relations(mut self, relations: impl IntoIterator<Item = IDRelation>) -> Self {
+        self.id_relations.extend(relations);
+        self
+    }
+
+    pub fn property_relation(mut self, relation: PropertyRelation) -> Self {
+        self.property_relations.push(relation);
+        self
+    }
+
+    pub fn property_relations(
+        mut self,
+        relations: impl IntoIterator<Item = PropertyRelation>,
+    ) -> Self {
+        self.property_relations.extend(relations);
+        self
+    }
+
+    pub fn id_property_relation(mut self, relation: IDPropertyRelation) -> Self {
+        self.id_property_relations.push(relation);
+        self
+    }
+
+    pub fn id_property_relations(
+        mut self,
+        relations: impl IntoIterator<Item = IDPropertyRelation>,
+    ) -> Self {
+        self.id_property_relations.extend(relations);
+        self
+    }
+
+    pub fn domain_relation(mut self, relation: DomainRelation) -> Self {
+        self.domain_relations.push(relation);
+        self
+    }
+
+    pub fn domain_relations(mut self, relations: impl IntoIterator<Item = DomainRelation>) -> Self {
+        self.domain_relations.extend(relations);
+        self
+    }
+
+    pub fn chain(mut self, chain: AffinityChain) -> Self {
+        self.chains.push(chain);
+        self
+    }
+
+    // registers a group whose replicas are given as entities and whose
+    // relation is given as a property relation, see Board::add_group.
+    pub fn group(mut self, group: EntityGroup) -> Self {
+        self.groups.push(group);
+        self
+    }
+
+    pub fn scoring(mut self, scoring: ScoringConfig) -> Self {
+        self.scoring = scoring;
+        self
+    }
+
+    // builds the board, or returns every problem found instead of stopping
+    // at the first one.
+    pub fn build(self) -> Result<Board, Vec<SolverError>> {
+        let mut b = Board::new();
+        b.scoring = self.scoring;
+        let mut errors = Vec::new();
+
+        for r in self.resources {
+            if let Err(e) = b.add_resource(r) {
+                errors.push(e);
+            }
+        }
+
+        for (resource_id, e) in self.entities {
+            if let Err(e) = b.add_entity(resource_id, e) {
+                errors.push(e);
+            }
+        }
+
+        for rel in self.id_relations {
+            let problems = b.id_relation_problems(&rel);
+            if problems.is_empty() {
+                b.set_id_relation(&rel.id.clone(), Some(rel));
+            } else {
+                errors.extend(problems);
+            }
+        }
+        for rel in self.property_relations {
+            let problems = b.property_relation_problems(&rel);
+            if problems.is_empty() {
+                b.set_property_relation(&rel.id.clone(), Some(rel));
+            } else {
+                errors.extend(problems);
+            }
+        }
+        for rel in self.id_property_relations {
+            let problems = b.id_property_relation_problems(&rel);
+            if problems.is_empty() {
+                b.set_id_property_relation(&rel.id.clone(), Some(rel));
+            } else {
+                errors.extend(problems);
+            }
+        }
+        for rel in self.domain_relations {
+            let problems = b.domain_relation_problems(&rel);
+            if problems.is_empty() {
+                b.set_domain_relation(&rel.id.clone(), Some(rel));
+            } else {
+                errors.extend(problems);
+            }
+        }
+        for chain in self.chains {
+            let problems = b.chain_problems(&chain);
+            if problems.is_empty() {
+                b.set_chain(&chain.id.clone(), Some(chain));
+            } else {
+                errors.extend(problems);
+            }
+        }
+        for g in self.groups {
+            if b.groups.contains_key(&g.id) {
+                errors.push(SolverError::DuplicateId(g.id.clone()));
+                continue;
+            }
+            b.set_group(&g.id.clone(), Some(g));
+        }
+
+        if errors.is_empty() {
+            Ok(b)
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::solver::{
+        BoardBuilder, Entity, EntityBuilder, IDRelation, IDRelationKind, PlacementConstraint,
+        Priority, Resource, ResourceBuilder, SolverError,
+    };
+
+    #[test]
+    fn builder_any_order_test() {
+        // relations come before the entities they reference.
+        let b = BoardBuilder::new()
+            .id_relation(IDRelation {
+                id: String::from("together"),
+                kind: IDRelationKind::EEAffinity,
+                id1: String::from("app1"),
+                id2: String::from("app2"),
+                priority: Priority::Hard,
+            })
+            .entity(String::from("node1"), Entity::new(String::from("app1")))
+            .entity(String::from("node1"), Entity::new(String::from("app2")))
+            .resource(Resource::new(String::from("node1")))
+            .build()
+            .expect("builds");
+        assert_eq!(b.entities.len(), 2);
+        assert_eq!(b.assignment().get("app2").unwrap(), "node1");
+        assert!(b.id_relations.contains_key("together"));
+    }
+
+    #[test]
+    fn builder_reports_all_errors_test() {
+        let errors = BoardBuilder::new()
+            .resource(Resource::new(String::from("node1")))
+            .resource(Resource::new(String::from("node1")))
+            .entity(String::from("node9"), Entity::new(String::from("app1")))
+            .id_relation(IDRelation {
+                id: String::from("pin"),
+                kind: IDRelationKind::ERAffinity,
+                id1: String::from("app1"),
+                id2: String::from("node2"),
+                priority: Priority::Hard,
+            })
+            .build()
+            .expect_err("fails");
+        assert_eq!(
+            errors,
+            vec![
+                SolverError::DuplicateId(String::from("node1")),
+                SolverError::ResourceNotFound(String::from("node9")),
+                SolverError::EntityNotFound(String::from("app1")),
+                SolverError::ResourceNotFound(String::from("node2")),
+            ]
+        );
+    }
+
+    #[test]
+    fn fluent_builder_test() {
+        let nodes = (1..=3).map(|i| {
+            ResourceBuilder::new(format!("node{}", i))
+                .property(if i == 2 { "blue" } else { "red" })
+                .property("ram=64")
+                .capacity("cpu", 8)
+        });
+        let apps = ["app1", "app2"].map(|id| {
+            (
+                "node1",
+                EntityBuilder::new(id)
+                    .property("red")
+                    .metric("cpu", 2)
+                    .move_cost(1),
+            )
+        });
+        let b = BoardBuilder::new()
+            .resources(nodes)
+            .entities(apps)
+            .entity(
+                "node2",
+                EntityBuilder::new("db")
+                    .typed_property("tier", "data")
+                    .constraint(PlacementConstraint::parse("ram >= 64").expect("parses"))
+                    .pinned(true),
+            )
+            .build()
+            .expect("builds");
+        assert_eq!(b.resources.len(), 3);
+        assert!(b.resources["node3"].has_property("ram>=64"));
+        assert_eq!(b.resources["node1"].capacities["cpu"], 8);
+        assert_eq!(b.entities["app2"].metrics["cpu"], 2);
+        assert!(b.entities["db"].pinned);
+        assert!(b.check_all().is_empty());
+
+        // every problem comes back from build.
+        let errors = BoardBuilder::new()
+            .resources([ResourceBuilder::new("node1"), ResourceBuilder::new("node1")])
+            .entities([
+                ("node9", EntityBuilder::new("a")),
+                ("node1", EntityBuilder::new("b")),
+            ])
+            .build()
+            .expect_err("fails");
+        assert_eq!(errors.len(), 2);
+    }
+}
@@ -0,0 +1,404 @@
+// capacity accounting of entity metrics against resource capacities.
+
+use std::collections::{HashMap, HashSet};
+
+use super::intern::Assignment;
+use super::{Board, Resource, ResourceId, SolverError};
+
+// resource -> metric name -> summed load.
+pub(crate) type Loads = HashMap<ResourceId, HashMap<String, i64>>;
+
+// a resource whose assigned entities exceed one of its capacities.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CapacityViolation {
+    pub resource_id: String,
+    pub metric: String,
+    // summed metric of the entities on the resource.
+    pub load: i64,
+    pub capacity: i64,
+    // load - capacity, always positive.
+    pub overage: i64,
+}
+
+// cluster-wide load of one metric, see Board::cluster_load_summary.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetricLoadSummary {
+    pub metric: String,
+    pub load: i64,
+    // usable capacity summed over the resources declaring one.
+    pub capacity: i64,
+    // highest load / usable capacity of a single resource.
+    pub max_utilization: f64,
+}
+
+impl Board {
+    // reports every resource and metric where the summed entity metrics
+    // exceed the declared capacity, sorted by resource id then metric.
+    pub fn check_capacity_violations(&self) -> Vec<CapacityViolation> {
+        self.capacity_violations_with(&self.assignment)
+    }
+
+    pub(crate) fn capacity_violations_with(
+        &self,
+        assignment: &Assignment,
+    ) -> Vec<CapacityViolation> {
+        let loads = self.loads_with(assignment);
+        let mut violations = Vec::new();
+        for (resource, load) in &loads {
+            let r = self.resources.at(*resource).expect("resouce not found");
+            for (metric, used) in load {
+                if let Some(cap) = r.usable_capacity(metric) {
+                    if *used > cap {
+                        violations.push(CapacityViolation {
+                            resource_id: r.id.clone(),
+                            metric: metric.clone(),
+                            load: *used,
+                            capacity: cap,
+                            overage: used - cap,
+                        });
+                    }
+                }
+            }
+        }
+        violations.sort_by(|a, b| (&a.resource_id, &a.metric).cmp(&(&b.resource_id, &b.metric)));
+        violations
+    }
+
+    // updates a metric of a placed or unplaced entity, as a monitoring loop
+    // reports it, and returns the capacity violations the new value creates
+    // on the entity's resource. A violation that already existed is not
+    // reported again.
+    pub fn report_load(
+        &mut self,
+        entity_id: &str,
+        metric: &str,
+        value: i64,
+    ) -> Result<Vec<CapacityViolation>, SolverError> {
+        if !self.entities.contains_key(entity_id) {
+            return Err(SolverError::EntityNotFound(entity_id.to_string()));
+        }
+        self.save_entity(entity_id);
+        let e = self.entities.get_mut(entity_id).expect("entity exist");
+        let old = e.metrics.insert(metric.to_string(), value).unwrap_or(0);
+        self.notify(|l| l.on_load_reported(entity_id, metric, value));
+        let Some(resource_id) = self.assignment().get(entity_id).cloned() else {
+            return Ok(Vec::new());
+        };
+        let resource = self.resources.handle(&resource_id).expect("resource exist");
+        self.save_load(resource);
+        let used = self
+            .loads
+            .entry(resource)
+            .or_default()
+            .entry(metric.to_string())
+            .or_insert(0);
+        let before = *used;
+        *used += value - old;
+        let created = match self.resources[&resource_id].usable_capacity(metric) {
+            Some(cap) if *used > cap && before <= cap => vec![CapacityViolation {
+                resource_id: resource_id.clone(),
+                metric: metric.to_string(),
+                load: *used,
+                capacity: cap,
+                overage: *used - cap,
+            }],
+            _ => Vec::new(),
+        };
+        self.notify_violations();
+        Ok(created)
+    }
+
+    // summed entity metrics on the resource, empty for unknown resources.
+    pub fn resource_load(&self, resource_id: &str) -> HashMap<String, i64> {
+        self.load_of(&self.loads, resource_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    // usable capacity minus load of every metric the resource declares a
+    // capacity for, negative when overloaded.
+    pub fn remaining_capacity(&self, resource_id: &str) -> HashMap<String, i64> {
+        let Some(r) = self.resources.get(resource_id) else {
+            return HashMap::new();
+        };
+        let load = self.load_of(&self.loads, resource_id);
+        r.capacities
+            .keys()
+            .map(|metric| {
+                let used = load.and_then(|l| l.get(metric)).copied().unwrap_or(0);
+                let cap = r.usable_capacity(metric).unwrap_or(0);
+                (metric.clone(), cap - used)
+            })
+            .collect()
+    }
+
+    // per-metric totals over all resources, sorted by metric.
+    pub fn cluster_load_summary(&self) -> Vec<MetricLoadSummary> {
+        let mut totals: HashMap<&str, MetricLoadSummary> = HashMap::new();
+        for (resource, r) in self.resources.handles() {
+            let load = self.loads.get(&resource);
+            let metrics: HashSet<&String> = r
+                .capacities
+                .keys()
+                .chain(load.into_iter().flat_map(|l| l.keys()))
+                .collect();
+            for metric in metrics {
+                let s = totals.entry(metric).or_insert_with(|| MetricLoadSummary {
+                    metric: metric.clone(),
+                    load: 0,
+                    capacity: 0,
+                    max_utilization: 0.0,
+                });
+                let used = load.and_then(|l| l.get(metric)).copied().unwrap_or(0);
+                s.load += used;
+                if let Some(cap) = r.usable_capacity(metric) {
+                    s.capacity += cap;
+                    if cap > 0 {
+                        s.max_utilization = s.max_utilization.max(used as f64 / cap as f64);
+                    }
+                }
+            }
+        }
+        let mut summary: Vec<MetricLoadSummary> = totals.into_values().collect();
+        summary.sort_by(|a, b| a.metric.cmp(&b.metric));
+        summary
+    }
+
+    // recomputes the maintained loads, needed after writing to the public
+    // entities map directly.
+    pub fn refresh_loads(&mut self) {
+        let loads = self.loads_with(&self.assignment);
+        let resources: Vec<ResourceId> = self.loads.keys().chain(loads.keys()).copied().collect();
+        for r in resources {
+            self.save_load(r);
+        }
+        self.loads = loads;
+    }
+
+    // adds (sign 1) or takes away (sign -1) the entity's metrics from the
+    // maintained load of the resource.
+    pub(crate) fn track_load(&mut self, entity_id: &str, resource_id: &str, sign: i64) {
+        let resource = self
+            .resources
+            .handle(resource_id)
+            .expect("resource not found");
+        self.save_load(resource);
+        let e = &self.entities[entity_id];
+        let load = self.loads.entry(resource).or_default();
+        for (metric, v) in &e.metrics {
+            *load.entry(metric.clone()).or_insert(0) += sign * v;
+        }
+    }
+
+    // sums entity metrics per resource for the given assignment.
+    pub(crate) fn loads_with(&self, assignment: &Assignment) -> Loads {
+        let mut loads: Loads = HashMap::new();
+        for (e, r) in assignment.iter() {
+            let e = self.entities.at(e).expect("entity not found");
+            let load = loads.entry(r).or_default();
+            for (metric, v) in &e.metrics {
+                *load.entry(metric.clone()).or_insert(0) += v;
+            }
+        }
+        loads
+    }
+
+    // the load of the resource in loads of this board.
+    pub(crate) fn load_of<'a>(
+        &self,
+        loads: &'a Loads,
+        resource_id: &str,
+    ) -> Option<&'a HashMap<String, i64>> {
+        loads.get(&self.resources.handle(resource_id)?)
+    }
+
+    // true if adding the entity to the resource keeps every buffered
+    // capacity of the resource. Metrics the resource does not declare a
+    // capacity for are unlimited.
+    pub(crate) fn fits_capacity(&self, entity_id: &str, resource_id: &str, loads: &Loads) -> bool {
+        let e = self.entities.get(entity_id).expect("entity not found");
+        self.fits_metrics(&e.metrics, resource_id, loads)
+    }
+
+    // fits_capacity for summed metrics, like those of a chain.
+    pub(crate) fn fits_metrics(
+        &self,
+        metrics: &HashMap<String, i64>,
+        resource_id: &str,
+        loads: &Loads,
+    ) -> bool {
+        let r = self.resources.get(resource_id).expect("resouce not found");
+        let load = self.load_of(loads, resource_id);
+        metrics
+            .iter()
+            .all(|(metric, v)| match r.buffered_capacity(metric) {
+                None => true,
+                Some(cap) => {
+                    let used = load.and_then(|l| l.get(metric)).copied().unwrap_or(0);
+                    used + v <= cap
+                }
+            })
+    }
+}
+
+// the load of the metric the resource carries without overage: its
+// buffered capacity, or the load it started with up to the usable capacity.
+pub(crate) fn tolerated_capacity(r: &Resource, metric: &str, start: i64) -> Option<i64> {
+    let buffered = r.buffered_capacity(metric)?;
+    let usable = r.usable_capacity(metric)?;
+    Some(buffered.max(start.min(usable)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::solver::{
+        Board, BoardBuilder, CapacityViolation, Entity, EntityBuilder, MetricLoadSummary, Pending,
+        Resource, ResourceBuilder,
+    };
+
+    #[test]
+    fn capacity_violation_test() {
+        let mut r1 = Resource::new(String::from("node1"));
+        r1.capacities.insert(String::from("cpu"), 4);
+        r1.capacities.insert(String::from("mem"), 8);
+        let mut b = Board::new();
+        b.add_resource(r1).expect("added");
+        for (id, cpu, mem) in [("app1", 3, 2), ("app2", 3, 2)] {
+            let mut e = Entity::new(String::from(id));
+            e.metrics.insert(String::from("cpu"), cpu);
+            e.metrics.insert(String::from("mem"), mem);
+            // disk has no declared capacity so it is unlimited.
+            e.metrics.insert(String::from("disk"), 100);
+            b.add_entity(String::from("node1"), e).expect("added");
+        }
+
+        assert_eq!(
+            b.check_capacity_violations(),
+            vec![CapacityViolation {
+                resource_id: String::from("node1"),
+                metric: String::from("cpu"),
+                load: 6,
+                capacity: 4,
+                overage: 2,
+            }]
+        );
+    }
+
+    #[test]
+    fn reserved_and_buffered_capacity_test() {
+        // 12 cpu less 2 reserved leaves 10, a 20% buffer keeps 2 of those free.
+        let node = |id: &str| {
+            ResourceBuilder::new(id)
+                .capacity("cpu", 12)
+                .reserved("cpu", 2)
+                .buffer_percent(20)
+        };
+        assert_eq!(node("n").build().usable_capacity("cpu"), Some(10));
+        assert_eq!(node("n").build().buffered_capacity("cpu"), Some(8));
+        assert_eq!(node("n").build().buffered_capacity("mem"), None);
+
+        let mut b = BoardBuilder::new()
+            .resource(node("node1"))
+            .resource(node("node2"))
+            .entity("node1", EntityBuilder::new("a").metric("cpu", 9))
+            .entity("node2", EntityBuilder::new("b").metric("cpu", 7))
+            .build()
+            .expect("builds");
+        // node1 is above its buffer but within the usable capacity.
+        assert!(b.check_capacity_violations().is_empty());
+        assert!(b.rebalance().is_empty());
+
+        // 1 more fits node2's buffer, 2 more only the usable capacity.
+        for (id, cpu, placed) in [("c", 1, true), ("d", 2, false)] {
+            let mut p = Pending::new();
+            p.add_entity(EntityBuilder::new(id).metric("cpu", cpu).build());
+            assert_eq!(b.solve(p).is_ok(), placed, "{}", id);
+        }
+
+        b.entities
+            .get_mut("a")
+            .unwrap()
+            .metrics
+            .insert(String::from("cpu"), 11);
+        let v = b.check_capacity_violations();
+        assert_eq!((v[0].capacity, v[0].overage), (10, 1));
+    }
+
+    #[test]
+    fn report_load_test() {
+        let mut b = BoardBuilder::new()
+            .resource(ResourceBuilder::new("node1").capacity("cpu", 4))
+            .resource(ResourceBuilder::new("node2").capacity("cpu", 4))
+            .entity("node1", EntityBuilder::new("a").metric("cpu", 1))
+            .entity("node1", EntityBuilder::new("b").metric("cpu", 1))
+            .build()
+            .expect("builds");
+        assert!(b.report_load("a", "cpu", 3).unwrap().is_empty());
+        let v = b.report_load("b", "cpu", 2).unwrap();
+        assert_eq!((v[0].resource_id.as_str(), v[0].load), ("node1", 5));
+        // still over, nothing new.
+        assert!(b.report_load("b", "cpu", 3).unwrap().is_empty());
+        assert_eq!(b.check_capacity_violations()[0].load, 6);
+        assert!(b.report_load("ghost", "cpu", 1).is_err());
+
+        // the maintained loads follow the board api.
+        b.apply_move_plan(&b.rebalance()).expect("applies");
+        b.remove_entity("a").expect("removed");
+        b.report_load("b", "mem", 2).unwrap();
+        let nonzero = |loads: &super::Loads| -> Vec<(String, String, i64)> {
+            let mut l: Vec<_> = loads
+                .iter()
+                .flat_map(|(r, m)| {
+                    let r = b.resources.name(*r);
+                    m.iter().map(move |(k, v)| (r.clone(), k.clone(), *v))
+                })
+                .filter(|t| t.2 != 0)
+                .collect();
+            l.sort();
+            l
+        };
+        assert_eq!(nonzero(&b.loads), nonzero(&b.loads_with(&b.assignment)));
+    }
+
+    #[test]
+    fn load_query_test() {
+        let b = BoardBuilder::new()
+            .resource(
+                ResourceBuilder::new("node1")
+                    .capacity("cpu", 8)
+                    .reserved("cpu", 2),
+            )
+            .resource(ResourceBuilder::new("node2").capacity("cpu", 4))
+            .entity("node1", EntityBuilder::new("a").metric("cpu", 3))
+            .entity(
+                "node2",
+                EntityBuilder::new("b").metric("cpu", 3).metric("disk", 5),
+            )
+            .build()
+            .expect("builds");
+        assert_eq!(b.resource_load("node1")["cpu"], 3);
+        assert!(b.resource_load("ghost").is_empty());
+        let remaining = b.remaining_capacity("node2");
+        assert_eq!((remaining.len(), remaining["cpu"]), (1, 1));
+        assert_eq!(b.remaining_capacity("node1")["cpu"], 3);
+
+        let summary = b.cluster_load_summary();
+        assert_eq!(
+            summary,
+            vec![
+                MetricLoadSummary {
+                    metric: String::from("cpu"),
+                    load: 6,
+                    capacity: 10,
+                    max_utilization: 0.75,
+                },
+                MetricLoadSummary {
+                    metric: String::from("disk"),
+                    load: 5,
+                    capacity: 0,
+                    max_utilization: 0.0,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,229 @@
+// affinity chains: entities kept on one resource and moved as a unit.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use super::index::RelationIndex;
+use super::intern::Assignment;
+use super::{Board, Priority, SolverError};
+
+// entities with entity_property are kept on one resource, without one
+// relation per pair. With a parent the members follow it: a member away
+// from the parent is reported, the parent never is. Without one every
+// member not sharing a resource with all the others is reported. Repair
+// and balance move all members of a chain together.
+#[derive(Debug, Clone)]
+pub struct AffinityChain {
+    pub id: String,
+    pub entity_property: String,
+    pub parent: Option<String>,
+    pub priority: Priority,
+}
+
+impl Board {
+    pub fn add_chain(&mut self, chain: AffinityChain) -> Result<(), SolverError> {
+        if let Some(e) = self.chain_problems(&chain).into_iter().next() {
+            return Err(e);
+        }
+        self.touch_all();
+        let id = chain.id.clone();
+        let op = self.set_chain(&id, Some(chain));
+        assert!(op.is_none());
+        self.notify_violations();
+        Ok(())
+    }
+
+    pub(crate) fn chain_problems(&self, chain: &AffinityChain) -> Vec<SolverError> {
+        let mut problems = Vec::new();
+        if self.chains.contains_key(&chain.id) {
+            problems.push(SolverError::DuplicateId(chain.id.clone()));
+        }
+        if let Some(p) = &chain.parent {
+            if !self.entities.contains_key(p) {
+                problems.push(SolverError::EntityNotFound(p.clone()));
+            }
+        }
+        problems
+    }
+
+    // the placed entities moving together with the entity, itself
+    // included: the members and parents of its chains. Sorted.
+    pub(crate) fn move_unit(
+        &self,
+        index: &RelationIndex,
+        entity_id: &str,
+        assignment: &Assignment,
+    ) -> Vec<String> {
+        let mut unit: BTreeSet<&str> = BTreeSet::from([entity_id]);
+        for chain in index.chains(entity_id) {
+            unit.extend(index.members(&chain.entity_property));
+            unit.extend(chain.parent.as_deref());
+        }
+        unit.into_iter()
+            .filter(|id| *id == entity_id || self.resource_in(assignment, id).is_some())
+            .map(String::from)
+            .collect()
+    }
+
+    // resources every entity of the unit can move to together without a
+    // hard violation, sorted.
+    pub(crate) fn unit_candidates(
+        &self,
+        index: &RelationIndex,
+        unit: &[String],
+        assignment: &Assignment,
+    ) -> Vec<String> {
+        if let [entity_id] = unit {
+            return self.candidates_with(index, entity_id, assignment);
+        }
+        let mut shared: Option<HashSet<&str>> = None;
+        for id in unit {
+            let set: HashSet<&str> = index.resource_candidates(id).into_iter().collect();
+            shared = Some(match shared {
+                None => set,
+                Some(prev) => prev.intersection(&set).copied().collect(),
+            });
+        }
+        let mut ids: Vec<&str> = shared.unwrap_or_default().into_iter().collect();
+        ids.sort();
+
+        let mut trial = assignment.clone();
+        ids.into_iter()
+            .filter(|r_id| {
+                for id in unit {
+                    self.place_in(&mut trial, id, r_id);
+                }
+                unit.iter().all(|id| {
+                    self.entity_violations_at(index, id, r_id, &trial)
+                        .iter()
+                        .all(|v| !v.is_hard())
+                })
+            })
+            .map(String::from)
+            .collect()
+    }
+
+    // summed weight of the soft relations the unit would break on the
+    // resource.
+    pub(crate) fn unit_soft_penalty(
+        &self,
+        index: &RelationIndex,
+        unit: &[String],
+        resource_id: &str,
+        assignment: &Assignment,
+    ) -> i64 {
+        if let [entity_id] = unit {
+            return self.soft_penalty_at(index, entity_id, resource_id, assignment);
+        }
+        let mut trial = assignment.clone();
+        for id in unit {
+            self.place_in(&mut trial, id, resource_id);
+        }
+        unit.iter()
+            .map(|id| self.soft_penalty_at(index, id, resource_id, &trial))
+            .sum()
+    }
+
+    // summed metrics of the unit.
+    pub(crate) fn unit_metrics(&self, unit: &[String]) -> HashMap<String, i64> {
+        let mut metrics: HashMap<String, i64> = HashMap::new();
+        for id in unit {
+            for (metric, v) in &self.entities[id].metrics {
+                *metrics.entry(metric.clone()).or_insert(0) += v;
+            }
+        }
+        metrics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::solver::{
+        AffinityChain, Board, BoardBuilder, EntityBuilder, Pending, Priority, ResourceBuilder,
+        SolverError, ViolationKind,
+    };
+
+    // web with its log and proxy sidecars next to another entity, all on
+    // node1.
+    fn board(parent: Option<&str>) -> Board {
+        let mut b = BoardBuilder::new();
+        for id in ["node1", "node2"] {
+            b = b.resource(ResourceBuilder::new(id).capacity("cpu", 10));
+        }
+        for id in ["web", "log", "proxy"] {
+            b = b.entity(
+                "node1",
+                EntityBuilder::new(id)
+                    .property("chain=web")
+                    .metric("cpu", 2),
+            );
+        }
+        let other = EntityBuilder::new("other").metric("cpu", 2).move_cost(10);
+        b.entity("node1", other)
+            .chain(AffinityChain {
+                id: String::from("web-chain"),
+                entity_property: String::from("chain=web"),
+                parent: parent.map(String::from),
+                priority: Priority::Hard,
+            })
+            .build()
+            .expect("builds")
+    }
+
+    #[test]
+    fn chain_violation_test() {
+        let mut b = board(Some("web"));
+        assert!(b.check_all().is_empty());
+        b.move_entity("log", "node2").expect("moved");
+        let report = b.check_all();
+        assert_eq!(report.len(), 1);
+        let v = &report.entries[0];
+        assert_eq!(v.kind, ViolationKind::Chain);
+        assert_eq!(v.relation_id.as_deref(), Some("web-chain"));
+        assert_eq!(v.entity_id.as_deref(), Some("log"));
+
+        // a new member follows the parent.
+        let mut p = Pending::new();
+        p.add_entity(EntityBuilder::new("cache").property("chain=web").build());
+        assert_eq!(b.solve(p).expect("placed").assignment["cache"], "node1");
+
+        // without a parent every member away from the rest is reported.
+        let mut b = board(None);
+        b.move_entity("log", "node2").expect("moved");
+        assert_eq!(b.check_all().len(), 3);
+
+        assert_eq!(
+            b.add_chain(AffinityChain {
+                id: String::from("x"),
+                entity_property: String::from("chain=x"),
+                parent: Some(String::from("ghost")),
+                priority: Priority::Hard,
+            }),
+            Err(SolverError::EntityNotFound(String::from("ghost")))
+        );
+    }
+
+    #[test]
+    fn chain_moves_together_test() {
+        // moving the chain evens out cpu as well as moving the costly
+        // other entity, no single member may go alone.
+        let mut b = board(Some("web"));
+        let plan = b.rebalance();
+        let mut moved: Vec<&str> = plan.moves.iter().map(|m| m.entity_id.as_str()).collect();
+        moved.sort();
+        assert_eq!(moved, ["log", "proxy", "web"]);
+        b.apply_move_plan(&plan).expect("applies");
+        assert!(b.check_all().is_empty());
+
+        // a broken chain is repaired by moving the stray member back.
+        let mut b = board(Some("web"));
+        b.move_entity("log", "node2").expect("moved");
+        assert_eq!(
+            b.suggest_repairs(),
+            vec![(
+                String::from("log"),
+                String::from("node2"),
+                String::from("node1")
+            )]
+        );
+    }
+}
@@ -0,0 +1,953 @@
+// json conversion of the board and its objects.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::json::{array_field, field, shape_error, str_field, FromJson, JsonError, ToJson, Value};
+
+use super::property::parse_tag;
+use super::{
+    AffinityChain, Board, BoardBuilder, BoardDiff, DomainKind, DomainRelation, Entity, EntityGroup,
+    Eviction, IDPropertyRelation, IDRelation, IDRelationKind, MetricScoring, Move, MovePlan,
+    ObjectChanges, Pending, Placement, PlacementConstraint, Priority, Properties, PropertyRelation,
+    PropertyRelationKind, PropertyValue, Resource, ResourceState, ScoringConfig, Violation,
+    ViolationReport,
+};
+
+fn properties(props: &Properties) -> Value {
+    Value::Object(
+        props
+            .iter()
+            .map(|(k, v)| {
+                let v = match v {
+                    PropertyValue::Str(s) => Value::String(s.clone()),
+                    PropertyValue::Int(i) => Value::Int(*i),
+                    PropertyValue::Bool(b) => Value::Bool(*b),
+                };
+                (k.clone(), v)
+            })
+            .collect(),
+    )
+}
+
+fn int_map(map: &HashMap<String, i64>) -> Value {
+    Value::Object(
+        map.iter()
+            .map(|(k, v)| (k.clone(), Value::Int(*v)))
+            .collect(),
+    )
+}
+
+fn object(fields: Vec<(&str, Value)>) -> Value {
+    Value::Object(
+        fields
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect(),
+    )
+}
+
+// the objects of a board or pending map, sorted by id.
+fn sorted_values<'a, T: ToJson + 'a>(map: impl IntoIterator<Item = (&'a String, &'a T)>) -> Value {
+    let mut objects: Vec<(&String, &T)> = map.into_iter().collect();
+    objects.sort_by(|a, b| a.0.cmp(b.0));
+    Value::Array(objects.into_iter().map(|(_, x)| x.to_value()).collect())
+}
+
+// an object of typed values, or a list of tags as in Resource::add_property.
+fn properties_field(v: &Value, key: &str, path: &str) -> Result<Properties, JsonError> {
+    match v.get(key) {
+        None | Some(Value::Null) => Ok(Properties::new()),
+        Some(Value::Object(m)) => m
+            .iter()
+            .map(|(k, x)| {
+                let value = match x {
+                    Value::String(s) => PropertyValue::Str(s.clone()),
+                    Value::Int(i) => PropertyValue::Int(*i),
+                    Value::Bool(b) => PropertyValue::Bool(*b),
+                    _ => {
+                        return Err(shape_error(
+                            &format!("{}.{}.{}", path, key, k),
+                            "expected string, integer or bool",
+                        ))
+                    }
+                };
+                Ok((k.clone(), value))
+            })
+            .collect(),
+        Some(Value::Array(tags)) => tags
+            .iter()
+            .enumerate()
+            .map(|(i, s)| {
+                s.as_str().map(parse_tag).ok_or_else(|| {
+                    shape_error(&format!("{}.{}[{}]", path, key, i), "expected string")
+                })
+            })
+            .collect(),
+        Some(_) => Err(shape_error(
+            &format!("{}.{}", path, key),
+            "expected object or array",
+        )),
+    }
+}
+
+fn int_map_field(v: &Value, key: &str, path: &str) -> Result<HashMap<String, i64>, JsonError> {
+    match v.get(key) {
+        None | Some(Value::Null) => Ok(HashMap::new()),
+        Some(Value::Object(m)) => m
+            .iter()
+            .map(|(k, x)| {
+                x.as_i64().map(|i| (k.clone(), i)).ok_or_else(|| {
+                    shape_error(&format!("{}.{}.{}", path, key, k), "expected integer")
+                })
+            })
+            .collect(),
+        Some(_) => Err(shape_error(&format!("{}.{}", path, key), "expected object")),
+    }
+}
+
+fn items<T: FromJson>(v: &Value, key: &str) -> Result<Vec<T>, JsonError> {
+    array_field(v, key, "$")?
+        .iter()
+        .enumerate()
+        .map(|(i, x)| T::from_value(x).map_err(|e| JsonError(format!("$.{}[{}]{}", key, i, e.0))))
+        .collect()
+}
+
+// hard relations carry no extra fields, soft ones their weight:
+// "priority": "Soft", "weight": 3
+fn priority_fields(priority: Priority, fields: &mut Vec<(&str, Value)>) {
+    match priority {
+        Priority::Hard => fields.push(("priority", Value::String(String::from("Hard")))),
+        Priority::Soft(w) => {
+            fields.push(("priority", Value::String(String::from("Soft"))));
+            fields.push(("weight", Value::Int(w)));
+        }
+    }
+}
+
+fn priority_field(v: &Value) -> Result<Priority, JsonError> {
+    let weight = match v.get("weight") {
+        None | Some(Value::Null) => 1,
+        Some(w) => w
+            .as_i64()
+            .ok_or_else(|| shape_error(".weight", "expected integer"))?,
+    };
+    match v.get("priority") {
+        None | Some(Value::Null) => Ok(Priority::Hard),
+        Some(p) => match p.as_str() {
+            Some("Hard") => Ok(Priority::Hard),
+            Some("Soft") => Ok(Priority::Soft(weight)),
+            _ => Err(shape_error(".priority", "expected \"Hard\" or \"Soft\"")),
+        },
+    }
+}
+
+pub(crate) fn id_relation_kind_name(kind: IDRelationKind) -> &'static str {
+    match kind {
+        IDRelationKind::EEAffinity => "EEAffinity",
+        IDRelationKind::EEAntiAffinity => "EEAntiAffinity",
+        IDRelationKind::ERAffinity => "ERAffinity",
+        IDRelationKind::ERAntiAffinity => "ERAntiAffinity",
+    }
+}
+
+pub(crate) fn parse_id_relation_kind(s: &str) -> Option<IDRelationKind> {
+    match s {
+        "EEAffinity" => Some(IDRelationKind::EEAffinity),
+        "EEAntiAffinity" => Some(IDRelationKind::EEAntiAffinity),
+        "ERAffinity" => Some(IDRelationKind::ERAffinity),
+        "ERAntiAffinity" => Some(IDRelationKind::ERAntiAffinity),
+        _ => None,
+    }
+}
+
+pub(crate) fn property_relation_kind_name(kind: PropertyRelationKind) -> &'static str {
+    match kind {
+        PropertyRelationKind::Affinity => "Affinity",
+        PropertyRelationKind::AntiAffinity => "AntiAffinity",
+        PropertyRelationKind::EEAffinity => "EEAffinity",
+        PropertyRelationKind::EEAntiAffinity => "EEAntiAffinity",
+    }
+}
+
+pub(crate) fn parse_property_relation_kind(s: &str) -> Option<PropertyRelationKind> {
+    match s {
+        "Affinity" => Some(PropertyRelationKind::Affinity),
+        "AntiAffinity" => Some(PropertyRelationKind::AntiAffinity),
+        "EEAffinity" => Some(PropertyRelationKind::EEAffinity),
+        "EEAntiAffinity" => Some(PropertyRelationKind::EEAntiAffinity),
+        _ => None,
+    }
+}
+
+pub(crate) fn domain_kind_name(kind: DomainKind) -> &'static str {
+    match kind {
+        DomainKind::Fault => "Fault",
+        DomainKind::Upgrade => "Upgrade",
+    }
+}
+
+pub(crate) fn parse_domain_kind(s: &str) -> Option<DomainKind> {
+    match s {
+        "Fault" => Some(DomainKind::Fault),
+        "Upgrade" => Some(DomainKind::Upgrade),
+        _ => None,
+    }
+}
+
+fn resource_state_name(state: ResourceState) -> &'static str {
+    match state {
+        ResourceState::Active => "Active",
+        ResourceState::Paused => "Paused",
+        ResourceState::Draining => "Draining",
+        ResourceState::Removed => "Removed",
+    }
+}
+
+fn parse_resource_state(s: &str) -> Option<ResourceState> {
+    match s {
+        "Active" => Some(ResourceState::Active),
+        "Paused" => Some(ResourceState::Paused),
+        "Draining" => Some(ResourceState::Draining),
+        "Removed" => Some(ResourceState::Removed),
+        _ => None,
+    }
+}
+
+fn optional_str_field(v: &Value, key: &str) -> Result<Option<String>, JsonError> {
+    match v.get(key) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(s)) => Ok(Some(s.clone())),
+        Some(_) => Err(shape_error(&format!(".{}", key), "expected string")),
+    }
+}
+
+impl ToJson for Resource {
+    fn to_value(&self) -> Value {
+        let mut fields = vec![
+            ("id", Value::String(self.id.clone())),
+            ("properties", properties(&self.properties)),
+            ("capacities", int_map(&self.capacities)),
+        ];
+        if !self.reserved.is_empty() {
+            fields.push(("reserved", int_map(&self.reserved)));
+        }
+        if self.buffer_percent != 0 {
+            fields.push(("buffer_percent", Value::Int(i64::from(self.buffer_percent))));
+        }
+        if let Some(d) = &self.fault_domain {
+            fields.push(("fault_domain", Value::String(d.clone())));
+        }
+        if let Some(d) = &self.upgrade_domain {
+            fields.push(("upgrade_domain", Value::String(d.clone())));
+        }
+        if self.state != ResourceState::Active {
+            fields.push((
+                "state",
+                Value::String(resource_state_name(self.state).to_string()),
+            ));
+        }
+        object(fields)
+    }
+}
+
+impl FromJson for Resource {
+    fn from_value(v: &Value) -> Result<Self, JsonError> {
+        let mut r = Resource::new(str_field(v, "id", "")?);
+        r.properties = properties_field(v, "properties", "")?;
+        r.capacities = int_map_field(v, "capacities", "")?;
+        r.reserved = int_map_field(v, "reserved", "")?;
+        if let Some(p) = v
+            .get("buffer_percent")
+            .filter(|p| !matches!(p, Value::Null))
+        {
+            r.buffer_percent = p
+                .as_i64()
+                .and_then(|p| u8::try_from(p).ok())
+                .filter(|p| *p <= 100)
+                .ok_or_else(|| shape_error(".buffer_percent", "expected percentage"))?;
+        }
+        r.fault_domain = optional_str_field(v, "fault_domain")?;
+        r.upgrade_domain = optional_str_field(v, "upgrade_domain")?;
+        if let Some(state) = optional_str_field(v, "state")? {
+            r.state = parse_resource_state(&state)
+                .ok_or_else(|| shape_error(".state", "unknown resource state"))?;
+        }
+        Ok(r)
+    }
+}
+
+impl ToJson for Entity {
+    fn to_value(&self) -> Value {
+        let mut fields = vec![
+            ("id", Value::String(self.id.clone())),
+            ("properties", properties(&self.properties)),
+            ("metrics", int_map(&self.metrics)),
+            ("move_cost", Value::Int(self.move_cost)),
+        ];
+        if let Some(c) = &self.constraint {
+            fields.push(("constraint", Value::String(c.source().into())));
+        }
+        if self.pinned {
+            fields.push(("pinned", Value::Bool(true)));
+        }
+        if self.priority != 0 {
+            fields.push(("priority", Value::Int(self.priority)));
+        }
+        object(fields)
+    }
+}
+
+impl FromJson for Entity {
+    fn from_value(v: &Value) -> Result<Self, JsonError> {
+        let mut e = Entity::new(str_field(v, "id", "")?);
+        e.properties = properties_field(v, "properties", "")?;
+        e.metrics = int_map_field(v, "metrics", "")?;
+        if let Some(c) = v.get("move_cost") {
+            e.move_cost = c
+                .as_i64()
+                .ok_or_else(|| shape_error(".move_cost", "expected integer"))?;
+        }
+        e.constraint = constraint_field(v)?;
+        if let Some(p) = v.get("pinned") {
+            e.pinned = p
+                .as_bool()
+                .ok_or_else(|| shape_error(".pinned", "expected boolean"))?;
+        }
+        if let Some(p) = v.get("priority") {
+            e.priority = p
+                .as_i64()
+                .ok_or_else(|| shape_error(".priority", "expected integer"))?;
+        }
+        Ok(e)
+    }
+}
+
+fn constraint_field(v: &Value) -> Result<Option<PlacementConstraint>, JsonError> {
+    let Some(c) = v.get("constraint") else {
+        return Ok(None);
+    };
+    let source = c
+        .as_str()
+        .ok_or_else(|| shape_error(".constraint", "expected string"))?;
+    PlacementConstraint::parse(source)
+        .map(Some)
+        .map_err(|err| shape_error(".constraint", &err.to_string()))
+}
+
+impl ToJson for EntityGroup {
+    fn to_value(&self) -> Value {
+        let mut fields = vec![
+            ("id", Value::String(self.id.clone())),
+            ("replicas", Value::Int(self.replicas as i64)),
+            ("primary", Value::Int(self.primary as i64)),
+            ("properties", properties(&self.properties)),
+            ("metrics", int_map(&self.metrics)),
+            ("move_cost", Value::Int(self.move_cost)),
+        ];
+        if let Some(m) = &self.secondary_metrics {
+            fields.push(("secondary_metrics", int_map(m)));
+        }
+        if let Some(c) = &self.constraint {
+            fields.push(("constraint", Value::String(c.source().into())));
+        }
+        object(fields)
+    }
+}
+
+impl FromJson for EntityGroup {
+    fn from_value(v: &Value) -> Result<Self, JsonError> {
+        let replicas = field(v, "replicas", "")?
+            .as_i64()
+            .and_then(|n| usize::try_from(n).ok())
+            .ok_or_else(|| shape_error(".replicas", "expected non-negative integer"))?;
+        let mut g = EntityGroup::new(str_field(v, "id", "")?, replicas);
+        if let Some(n) = v.get("primary") {
+            g.primary = n
+                .as_i64()
+                .and_then(|n| usize::try_from(n).ok())
+                .filter(|n| *n < replicas.max(1))
+                .ok_or_else(|| shape_error(".primary", "expected replica number"))?;
+        }
+        g.properties = properties_field(v, "properties", "")?;
+        g.metrics = int_map_field(v, "metrics", "")?;
+        if v.get("secondary_metrics").is_some() {
+            g.secondary_metrics = Some(int_map_field(v, "secondary_metrics", "")?);
+        }
+        if let Some(c) = v.get("move_cost") {
+            g.move_cost = c
+                .as_i64()
+                .ok_or_else(|| shape_error(".move_cost", "expected integer"))?;
+        }
+        g.constraint = constraint_field(v)?;
+        Ok(g)
+    }
+}
+
+impl ToJson for IDRelation {
+    fn to_value(&self) -> Value {
+        let mut fields = vec![
+            ("id", Value::String(self.id.clone())),
+            (
+                "kind",
+                Value::String(id_relation_kind_name(self.kind).into()),
+            ),
+            ("id1", Value::String(self.id1.clone())),
+            ("id2", Value::String(self.id2.clone())),
+        ];
+        priority_fields(self.priority, &mut fields);
+        object(fields)
+    }
+}
+
+impl FromJson for IDRelation {
+    fn from_value(v: &Value) -> Result<Self, JsonError> {
+        let kind = str_field(v, "kind", "")?;
+        Ok(IDRelation {
+            id: str_field(v, "id", "")?,
+            kind: parse_id_relation_kind(&kind)
+                .ok_or_else(|| shape_error(".kind", "unknown id relation kind"))?,
+            id1: str_field(v, "id1", "")?,
+            id2: str_field(v, "id2", "")?,
+            priority: priority_field(v)?,
+        })
+    }
+}
+
+impl ToJson for PropertyRelation {
+    fn to_value(&self) -> Value {
+        let mut fields = vec![
+            ("id", Value::String(self.id.clone())),
+            (
+                "kind",
+                Value::String(property_relation_kind_name(self.kind).into()),
+            ),
+            (
+                "entity_property",
+                Value::String(self.entity_property.clone()),
+            ),
+            (
+                "resource_property",
+                Value::String(self.resource_property.clone()),
+            ),
+        ];
+        priority_fields(self.priority, &mut fields);
+        object(fields)
+    }
+}
+
+impl FromJson for PropertyRelation {
+    fn from_value(v: &Value) -> Result<Self, JsonError> {
+        let kind = str_field(v, "kind", "")?;
+        Ok(PropertyRelation {
+            id: str_field(v, "id", "")?,
+            kind: parse_property_relation_kind(&kind)
+                .ok_or_else(|| shape_error(".kind", "unknown property relation kind"))?,
+            entity_property: str_field(v, "entity_property", "")?,
+            resource_property: str_field(v, "resource_property", "")?,
+            priority: priority_field(v)?,
+        })
+    }
+}
+
+impl ToJson for IDPropertyRelation {
+    fn to_value(&self) -> Value {
+        let mut fields = vec![
+            ("id", Value::String(self.id.clone())),
+            ("entity_id", Value::String(self.entity_id.clone())),
+            (
+                "kind",
+                Value::String(property_relation_kind_name(self.kind).into()),
+            ),
+            (
+                "resource_property",
+                Value::String(self.resource_property.clone()),
+            ),
+        ];
+        priority_fields(self.priority, &mut fields);
+        object(fields)
+    }
+}
+
+impl FromJson for IDPropertyRelation {
+    fn from_value(v: &Value) -> Result<Self, JsonError> {
+        let kind = str_field(v, "kind", "")?;
+        Ok(IDPropertyRelation {
+            id: str_field(v, "id", "")?,
+            entity_id: str_field(v, "entity_id", "")?,
+            kind: parse_property_relation_kind(&kind)
+                .ok_or_else(|| shape_error(".kind", "unknown property relation kind"))?,
+            resource_property: str_field(v, "resource_property", "")?,
+            priority: priority_field(v)?,
+        })
+    }
+}
+
+impl ToJson for DomainRelation {
+    fn to_value(&self) -> Value {
+        let mut fields = vec![
+            ("id", Value::String(self.id.clone())),
+            (
+                "domain",
+                Value::String(domain_kind_name(self.domain).into()),
+            ),
+            (
+                "entity_property",
+                Value::String(self.entity_property.clone()),
+            ),
+            ("min_domains", Value::Int(self.min_domains as i64)),
+        ];
+        priority_fields(self.priority, &mut fields);
+        object(fields)
+    }
+}
+
+impl FromJson for DomainRelation {
+    fn from_value(v: &Value) -> Result<Self, JsonError> {
+        let domain = str_field(v, "domain", "")?;
+        let min_domains = field(v, "min_domains", "")?
+            .as_i64()
+            .and_then(|n| usize::try_from(n).ok())
+            .ok_or_else(|| shape_error(".min_domains", "expected non-negative integer"))?;
+        Ok(DomainRelation {
+            id: str_field(v, "id", "")?,
+            domain: parse_domain_kind(&domain)
+                .ok_or_else(|| shape_error(".domain", "unknown domain kind"))?,
+            entity_property: str_field(v, "entity_property", "")?,
+            min_domains,
+            priority: priority_field(v)?,
+        })
+    }
+}
+
+impl ToJson for AffinityChain {
+    fn to_value(&self) -> Value {
+        let mut fields = vec![
+            ("id", Value::String(self.id.clone())),
+            (
+                "entity_property",
+                Value::String(self.entity_property.clone()),
+            ),
+        ];
+        if let Some(p) = &self.parent {
+            fields.push(("parent", Value::String(p.clone())));
+        }
+        priority_fields(self.priority, &mut fields);
+        object(fields)
+    }
+}
+
+impl FromJson for AffinityChain {
+    fn from_value(v: &Value) -> Result<Self, JsonError> {
+        Ok(AffinityChain {
+            id: str_field(v, "id", "")?,
+            entity_property: str_field(v, "entity_property", "")?,
+            parent: optional_str_field(v, "parent")?,
+            priority: priority_field(v)?,
+        })
+    }
+}
+
+impl ToJson for Pending {
+    fn to_value(&self) -> Value {
+        let mut fields = vec![
+            ("entities", sorted_values(&self.entities)),
+            ("id_relations", sorted_values(&self.id_relations)),
+            (
+                "property_relations",
+                sorted_values(&self.property_relations),
+            ),
+            (
+                "id_property_relations",
+                sorted_values(&self.id_property_relations),
+            ),
+            ("domain_relations", sorted_values(&self.domain_relations)),
+        ];
+        if !self.chains.is_empty() {
+            fields.push(("chains", sorted_values(&self.chains)));
+        }
+        object(fields)
+    }
+}
+
+impl FromJson for Pending {
+    fn from_value(v: &Value) -> Result<Self, JsonError> {
+        let mut p = Pending::new();
+        for e in items::<Entity>(v, "entities")? {
+            p.entities.insert(e.id.clone(), e);
+        }
+        for r in items::<IDRelation>(v, "id_relations")? {
+            p.id_relations.insert(r.id.clone(), r);
+        }
+        for r in items::<PropertyRelation>(v, "property_relations")? {
+            p.property_relations.insert(r.id.clone(), r);
+        }
+        for r in items::<IDPropertyRelation>(v, "id_property_relations")? {
+            p.id_property_relations.insert(r.id.clone(), r);
+        }
+        for r in items::<DomainRelation>(v, "domain_relations")? {
+            p.domain_relations.insert(r.id.clone(), r);
+        }
+        for c in items::<AffinityChain>(v, "chains")? {
+            p.chains.insert(c.id.clone(), c);
+        }
+        Ok(p)
+    }
+}
+
+impl ToJson for Board {
+    fn to_value(&self) -> Value {
+        let assignment: BTreeMap<String, Value> = self
+            .assignment()
+            .iter()
+            .map(|(e, r)| (e.clone(), Value::String(r.clone())))
+            .collect();
+        let mut fields = vec![
+            ("resources", sorted_values(&self.resources)),
+            ("entities", sorted_values(&self.entities)),
+            ("assignment", Value::Object(assignment)),
+            ("id_relations", sorted_values(&self.id_relations)),
+            (
+                "property_relations",
+                sorted_values(&self.property_relations),
+            ),
+            (
+                "id_property_relations",
+                sorted_values(&self.id_property_relations),
+            ),
+            ("domain_relations", sorted_values(&self.domain_relations)),
+            ("groups", sorted_values(&self.groups)),
+        ];
+        if !self.chains.is_empty() {
+            fields.push(("chains", sorted_values(&self.chains)));
+        }
+        if !self.scoring.metrics.is_empty() {
+            fields.push(("scoring", self.scoring.to_value()));
+        }
+        object(fields)
+    }
+}
+
+// {"cpu": {"weight": 2.0, "balancing_threshold": 1.5, "activity_threshold": 10}},
+// missing settings take their defaults.
+impl ToJson for ScoringConfig {
+    fn to_value(&self) -> Value {
+        Value::Object(
+            self.metrics
+                .iter()
+                .map(|(metric, m)| {
+                    let v = object(vec![
+                        ("weight", Value::Float(m.weight)),
+                        ("balancing_threshold", Value::Float(m.balancing_threshold)),
+                        ("activity_threshold", Value::Int(m.activity_threshold)),
+                    ]);
+                    (metric.clone(), v)
+                })
+                .collect(),
+        )
+    }
+}
+
+impl FromJson for ScoringConfig {
+    fn from_value(v: &Value) -> Result<Self, JsonError> {
+        let metrics = v
+            .as_object()
+            .ok_or_else(|| shape_error("", "expected table of metrics"))?;
+        let mut config = ScoringConfig::new();
+        for (metric, m) in metrics {
+            let path = format!(".{}", metric);
+            let mut scoring = MetricScoring::default();
+            let float = |key: &str| match m.get(key) {
+                None => Ok(None),
+                Some(x) => x
+                    .as_f64()
+                    .map(Some)
+                    .ok_or_else(|| shape_error(&format!("{}.{}", path, key), "expected number")),
+            };
+            if let Some(w) = float("weight")? {
+                scoring.weight = w;
+            }
+            if let Some(t) = float("balancing_threshold")? {
+                scoring.balancing_threshold = t;
+            }
+            if let Some(t) = m.get("activity_threshold") {
+                scoring.activity_threshold = t.as_i64().ok_or_else(|| {
+                    shape_error(&format!("{}.activity_threshold", path), "expected integer")
+                })?;
+            }
+            config.metrics.insert(metric.clone(), scoring);
+        }
+        Ok(config)
+    }
+}
+
+impl FromJson for Board {
+    // the board is rebuilt through BoardBuilder so references are validated.
+    fn from_value(v: &Value) -> Result<Self, JsonError> {
+        let assignment = field(v, "assignment", "$")?
+            .as_object()
+            .ok_or_else(|| shape_error("$.assignment", "expected object"))?;
+
+        let mut builder = BoardBuilder::new();
+        for r in items::<Resource>(v, "resources")? {
+            builder = builder.resource(r);
+        }
+        for e in items::<Entity>(v, "entities")? {
+            let r = assignment
+                .get(&e.id)
+                .and_then(|r| r.as_str())
+                .ok_or_else(|| {
+                    shape_error(&format!("$.assignment.{}", e.id), "missing resource")
+                })?;
+            builder = builder.entity(r.to_string(), e);
+        }
+        for r in items::<IDRelation>(v, "id_relations")? {
+            builder = builder.id_relation(r);
+        }
+        for r in items::<PropertyRelation>(v, "property_relations")? {
+            builder = builder.property_relation(r);
+        }
+        for r in items::<IDPropertyRelation>(v, "id_property_relations")? {
+            builder = builder.id_property_relation(r);
+        }
+        for r in items::<DomainRelation>(v, "domain_relations")? {
+            builder = builder.domain_relation(r);
+        }
+        for c in items::<AffinityChain>(v, "chains")? {
+            builder = builder.chain(c);
+        }
+        for g in items::<EntityGroup>(v, "groups")? {
+            builder = builder.group(g);
+        }
+        if let Some(scoring) = v.get("scoring") {
+            let scoring = ScoringConfig::from_value(scoring)
+                .map_err(|e| JsonError(format!("$.scoring{}", e.0)))?;
+            builder = builder.scoring(scoring);
+        }
+        builder.build().map_err(|errors| {
+            let msgs: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+            JsonError(format!("invalid board: {}", msgs.join(", ")))
+        })
+    }
+}
+
+fn optional_string(s: &Option<String>) -> Value {
+    match s {
+        Some(s) => Value::String(s.clone()),
+        None => Value::Null,
+    }
+}
+
+impl ToJson for Violation {
+    fn to_value(&self) -> Value {
+        let mut fields = vec![
+            ("kind", Value::String(self.kind.to_string())),
+            ("relation_id", optional_string(&self.relation_id)),
+            ("entity_id", optional_string(&self.entity_id)),
+            ("resource_id", Value::String(self.resource_id.clone())),
+            ("metric", optional_string(&self.metric)),
+            ("overage", self.overage.map_or(Value::Null, Value::Int)),
+        ];
+        priority_fields(self.priority, &mut fields);
+        object(fields)
+    }
+}
+
+impl ToJson for ViolationReport {
+    fn to_value(&self) -> Value {
+        Value::Array(self.iter().map(|v| v.to_value()).collect())
+    }
+}
+
+impl ToJson for Move {
+    fn to_value(&self) -> Value {
+        object(vec![
+            ("entity_id", Value::String(self.entity_id.clone())),
+            ("from", Value::String(self.from.clone())),
+            ("to", Value::String(self.to.clone())),
+            ("cost", Value::Int(self.cost)),
+        ])
+    }
+}
+
+impl ToJson for Eviction {
+    fn to_value(&self) -> Value {
+        object(vec![
+            ("entity_id", Value::String(self.entity_id.clone())),
+            ("from", Value::String(self.from.clone())),
+            ("preempted_by", Value::String(self.preempted_by.clone())),
+            ("cost", Value::Int(self.cost)),
+        ])
+    }
+}
+
+impl ToJson for MovePlan {
+    fn to_value(&self) -> Value {
+        let mut fields = vec![(
+            "moves",
+            Value::Array(self.moves.iter().map(|m| m.to_value()).collect()),
+        )];
+        if !self.evictions.is_empty() {
+            fields.push((
+                "evictions",
+                Value::Array(self.evictions.iter().map(|e| e.to_value()).collect()),
+            ));
+        }
+        fields.push(("total_cost", Value::Int(self.total_cost())));
+        object(fields)
+    }
+}
+
+fn strings(ids: &[String]) -> Value {
+    Value::Array(ids.iter().map(|id| Value::String(id.clone())).collect())
+}
+
+impl ToJson for ObjectChanges {
+    fn to_value(&self) -> Value {
+        object(vec![
+            ("added", strings(&self.added)),
+            ("removed", strings(&self.removed)),
+            ("changed", strings(&self.changed)),
+        ])
+    }
+}
+
+impl ToJson for BoardDiff {
+    fn to_value(&self) -> Value {
+        object(vec![
+            ("resources", self.resources.to_value()),
+            ("entities", self.entities.to_value()),
+            ("id_relations", self.id_relations.to_value()),
+            ("property_relations", self.property_relations.to_value()),
+            (
+                "id_property_relations",
+                self.id_property_relations.to_value(),
+            ),
+            ("domain_relations", self.domain_relations.to_value()),
+            ("chains", self.chains.to_value()),
+            ("groups", self.groups.to_value()),
+            (
+                "moves",
+                Value::Array(self.moves.iter().map(|m| m.to_value()).collect()),
+            ),
+        ])
+    }
+}
+
+impl ToJson for Placement {
+    fn to_value(&self) -> Value {
+        Value::Object(
+            self.assignment
+                .iter()
+                .map(|(e, r)| (e.clone(), Value::String(r.clone())))
+                .collect(),
+        )
+    }
+}
+
+impl Board {
+    pub fn to_json(&self) -> String {
+        self.to_value().to_string_pretty()
+    }
+
+    pub fn from_json(s: &str) -> Result<Board, JsonError> {
+        Board::from_value(&Value::parse(s)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::json::{FromJson, ToJson, Value};
+    use crate::solver::{
+        AffinityChain, Board, BoardBuilder, Entity, IDPropertyRelation, IDRelation, IDRelationKind,
+        Pending, Priority, PropertyRelation, PropertyRelationKind, Resource,
+    };
+
+    fn board() -> Board {
+        let mut r1 = Resource::new(String::from("node1"));
+        r1.add_property(String::from("red"));
+        r1.capacities.insert(String::from("cpu"), 8);
+        r1.reserved.insert(String::from("cpu"), 1);
+        r1.buffer_percent = 20;
+        let mut e1 = Entity::new(String::from("app1"));
+        e1.add_property(String::from("red"));
+        e1.metrics.insert(String::from("cpu"), 2);
+        e1.move_cost = 3;
+        BoardBuilder::new()
+            .resource(r1)
+            .resource(Resource::new(String::from("node2")))
+            .entity(String::from("node1"), e1)
+            .entity(String::from("node2"), Entity::new(String::from("app2")))
+            .id_relation(IDRelation {
+                id: String::from("apart"),
+                kind: IDRelationKind::EEAntiAffinity,
+                id1: String::from("app1"),
+                id2: String::from("app2"),
+                priority: Priority::Hard,
+            })
+            .property_relation(PropertyRelation {
+                id: String::from("color"),
+                kind: PropertyRelationKind::Affinity,
+                entity_property: String::from("red"),
+                resource_property: String::from("red"),
+                priority: Priority::Hard,
+            })
+            .id_property_relation(IDPropertyRelation {
+                id: String::from("no-red"),
+                entity_id: String::from("app2"),
+                kind: PropertyRelationKind::AntiAffinity,
+                resource_property: String::from("red"),
+                priority: Priority::Soft(3),
+            })
+            .chain(AffinityChain {
+                id: String::from("with-app1"),
+                entity_property: String::from("red"),
+                parent: Some(String::from("app1")),
+                priority: Priority::Hard,
+            })
+            .build()
+            .expect("builds")
+    }
+
+    #[test]
+    fn board_json_round_trip_test() {
+        let b = board();
+        let json = b.to_json();
+        let again = Board::from_json(&json).expect("parses");
+        assert_eq!(again.to_json(), json);
+        assert_eq!(again.entities["app1"].move_cost, 3);
+        assert_eq!(again.resources["node1"].capacities["cpu"], 8);
+        assert_eq!(again.resources["node1"].reserved["cpu"], 1);
+        assert_eq!(again.resources["node1"].buffer_percent, 20);
+        assert_eq!(again.chains["with-app1"].parent.as_deref(), Some("app1"));
+        assert_eq!(again.assignment()["app2"], "node2");
+        assert_eq!(
+            again.id_property_relations["no-red"].priority,
+            Priority::Soft(3)
+        );
+        assert_eq!(
+            again.id_relations["apart"].kind,
+            IDRelationKind::EEAntiAffinity
+        );
+    }
+
+    #[test]
+    fn board_json_errors_test() {
+        let err =
+            Board::from_json(r#"{"assignment": {}, "resources": [{"id": 1}]}"#).expect_err("fails");
+        assert_eq!(err.0, "$.resources[0].id: expected string");
+
+        let err = Board::from_json(r#"{"assignment": {"a": "n9"}, "entities": [{"id": "a"}]}"#)
+            .expect_err("fails");
+        assert_eq!(err.0, "invalid board: resource does not exist: n9");
+    }
+
+    #[test]
+    fn pending_json_round_trip_test() {
+        let mut p = Pending::new();
+        p.add_entity(Entity::new(String::from("app3")));
+        let v = p.to_value();
+        let again = Pending::from_value(&Value::parse(&v.to_string()).unwrap()).expect("parses");
+        assert!(again.entities.contains_key("app3"));
+    }
+}
@@ -0,0 +1,241 @@
+// options of the solve and rebalance paths.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::AnnealConfig;
+
+// tuning knobs passed to the *_with variants of solve and rebalance.
+// Results only depend on the board contents and the config: ties are
+// broken by id and never by hash order, so identical inputs give
+// identical placements and plans.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SolverConfig {
+    // upper bound on the summed move_cost of a plan, unlimited if None.
+    pub move_cost_budget: Option<i64>,
+    // seed of every randomized decision, see Board::anneal.
+    pub seed: u64,
+    pub anneal: AnnealConfig,
+    // what rebalance optimizes once violations are repaired.
+    pub objective: Objective,
+    // limits of the bounded searches like solve_bounded and
+    // rebalance_bounded, which return the best result found when one is
+    // reached. An iteration is one search step: a node of the backtracking
+    // search, an anneal iteration or a repair or balancing step.
+    pub max_duration: Option<Duration>,
+    pub max_iterations: Option<u64>,
+    pub cancel: Option<CancelToken>,
+    // solve_bounded places what it can instead of failing the whole
+    // batch, see Placement::unplaced.
+    pub partial_placement: bool,
+    // solve_bounded may evict entities of lower priority to make room for
+    // pending entities that fit nowhere, see Board::preemption_plan.
+    pub preemption: bool,
+}
+
+// shared flag that stops the bounded searches using it, for example from
+// another thread or a signal handler.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> CancelToken {
+        CancelToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+// tokens are equal when they share the flag.
+impl PartialEq for CancelToken {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+// result of a bounded search. complete is false if a limit of the config
+// stopped the search early, the value is then the best found until then.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bounded<T> {
+    pub value: T,
+    pub complete: bool,
+}
+
+// the limits of a config while a search runs.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Limits {
+    deadline: Option<Instant>,
+    max_iterations: Option<u64>,
+    cancel: Option<CancelToken>,
+    iterations: u64,
+    stopped: bool,
+}
+
+impl Limits {
+    pub(crate) fn new(config: &SolverConfig) -> Limits {
+        Limits {
+            deadline: config.max_duration.map(|d| Instant::now() + d),
+            max_iterations: config.max_iterations,
+            cancel: config.cancel.clone(),
+            ..Default::default()
+        }
+    }
+
+    // counts an iteration, false once a limit is reached.
+    pub(crate) fn tick(&mut self) -> bool {
+        if !self.stopped {
+            self.iterations += 1;
+            self.stopped = self.max_iterations.is_some_and(|m| self.iterations > m)
+                || self.deadline.is_some_and(|d| Instant::now() >= d)
+                || self.cancel.as_ref().is_some_and(CancelToken::is_cancelled);
+        }
+        !self.stopped
+    }
+
+    pub(crate) fn stopped(&self) -> bool {
+        self.stopped
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Objective {
+    // even out metric load across resources.
+    #[default]
+    Balance,
+    // pack entities onto fewer resources until this many are empty, to
+    // free them for scale-down.
+    Defragment {
+        target_empty_resources: usize,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::solver::{
+        Board, CancelToken, Entity, IDRelation, IDRelationKind, Pending, Priority, Resource,
+        SolveError, SolverConfig,
+    };
+
+    // same contents, inserted in the given order.
+    fn board(order: &[usize]) -> Board {
+        let mut b = Board::new();
+        for i in [2, 0, 1, 3] {
+            let mut r = Resource::new(format!("node{}", i));
+            r.capacities.insert(String::from("cpu"), 6);
+            b.add_resource(r).expect("added");
+        }
+        for &i in order {
+            let mut e = Entity::new(format!("app{}", i));
+            e.metrics.insert(String::from("cpu"), 1 + (i as i64 % 3));
+            e.metrics.insert(String::from("mem"), 2);
+            e.move_cost = 1;
+            b.add_entity(String::from("node0"), e).expect("added");
+        }
+        b.add_id_relation(IDRelation {
+            id: String::from("apart"),
+            kind: IDRelationKind::EEAntiAffinity,
+            id1: String::from("app1"),
+            id2: String::from("app4"),
+            priority: Priority::Hard,
+        })
+        .expect("ok");
+        b
+    }
+
+    #[test]
+    fn deterministic_test() {
+        let config = SolverConfig {
+            seed: 42,
+            ..Default::default()
+        };
+        let first = board(&[0, 1, 2, 3, 4, 5]);
+        let plan = first.rebalance_with(&config);
+        let annealed = first.anneal(&config);
+        for order in [[5, 4, 3, 2, 1, 0], [3, 0, 5, 1, 4, 2]] {
+            let b = board(&order);
+            assert_eq!(b.rebalance_with(&config), plan);
+            assert_eq!(b.anneal(&config), annealed);
+        }
+
+        let mut p = Pending::new();
+        for i in 0..4 {
+            let mut e = Entity::new(format!("new{}", i));
+            e.metrics.insert(String::from("cpu"), 2);
+            p.add_entity(e);
+        }
+        let placement = board(&[0, 1, 2, 3, 4, 5]).solve(p.clone()).expect("solves");
+        assert_eq!(
+            board(&[5, 4, 3, 2, 1, 0]).solve(p).expect("solves"),
+            placement
+        );
+
+        // another seed explores differently but still ends valid.
+        let other = first.anneal(&SolverConfig {
+            seed: 7,
+            ..Default::default()
+        });
+        let mut b = board(&[0, 1, 2, 3, 4, 5]);
+        b.apply_move_plan(&other).expect("applies");
+        assert!(b.check_all().is_empty());
+    }
+
+    #[test]
+    fn bounded_test() {
+        let mut p = Pending::new();
+        for i in 0..2 {
+            let mut e = Entity::new(format!("new{}", i));
+            e.metrics.insert(String::from("cpu"), 2);
+            p.add_entity(e);
+        }
+        let mut b = board(&[0, 1, 2, 3, 4, 5]);
+        let done = b
+            .solve_bounded(p.clone(), &SolverConfig::default())
+            .expect("solves");
+        assert!(done.complete);
+
+        // stopped before the first step, the batch is still placed
+        // greedily.
+        let stopped = SolverConfig {
+            max_iterations: Some(0),
+            ..Default::default()
+        };
+        let mut b = board(&[0, 1, 2, 3, 4, 5]);
+        let placed = b.solve_bounded(p.clone(), &stopped).expect("solves");
+        assert!(!placed.complete);
+        assert_eq!(placed.value, done.value);
+        assert_eq!(b.assignment()["new0"], placed.value.assignment["new0"]);
+
+        // greedy can not place what does not fit at all.
+        let mut big = Entity::new(String::from("big"));
+        big.metrics.insert(String::from("cpu"), 100);
+        let mut p = Pending::new();
+        p.add_entity(big);
+        assert!(matches!(
+            b.solve_bounded(p, &stopped),
+            Err(SolveError::Interrupted(ids)) if ids == ["big"]
+        ));
+
+        // a cancelled token plans nothing, unlimited the plan is complete.
+        let token = CancelToken::new();
+        token.cancel();
+        let cancelled = SolverConfig {
+            cancel: Some(token),
+            ..Default::default()
+        };
+        let b = board(&[0, 1, 2, 3, 4, 5]);
+        let plan = b.rebalance_bounded(&cancelled);
+        assert!(!plan.complete);
+        assert!(plan.value.is_empty());
+        let plan = b.rebalance_bounded(&SolverConfig::default());
+        assert!(plan.complete);
+        assert_eq!(plan.value, b.rebalance());
+        assert!(!b.anneal_bounded(&cancelled).complete);
+    }
+}
@@ -0,0 +1,211 @@
+// consolidation: packing entities onto fewer resources.
+
+use std::collections::HashSet;
+
+use super::capacity::Loads;
+use super::config::Limits;
+use super::index::RelationIndex;
+use super::intern::Assignment;
+use super::{Board, Move};
+
+impl Board {
+    // empties resources one at a time, fewest entities first, by moving all
+    // of their entities onto the fullest other non-empty resources that
+    // take them without breaking a hard relation or capacity or adding soft
+    // penalty. A resource is only emptied if all of its entities can go and
+    // the moves fit the budget; resources with pinned entities are skipped,
+    // and resources that received entities are kept. Stops once target
+    // resources are empty or a limit is reached. The assignment is updated
+    // with the moves.
+    pub(crate) fn defrag_moves(
+        &self,
+        assignment: &mut Assignment,
+        budget: Option<i64>,
+        target: usize,
+        limits: &mut Limits,
+    ) -> Vec<Move> {
+        let index = RelationIndex::new(self);
+        let mut spent = 0;
+        let mut moves = Vec::new();
+        let mut loads = self.all_loads_with(assignment);
+
+        let mut resource_ids: Vec<&String> = self.resources.keys().collect();
+        resource_ids.sort();
+        let hosted = |assignment: &Assignment, r_id: &str| -> Vec<String> {
+            let mut ids: Vec<String> = self
+                .placements(assignment)
+                .iter()
+                .filter(|(_, r)| *r == r_id)
+                .map(|(e, _)| e.clone())
+                .collect();
+            ids.sort_by(|a, b| {
+                (self.entities[a].move_cost, a).cmp(&(self.entities[b].move_cost, b))
+            });
+            ids
+        };
+
+        // resources already tried, or packed into and so kept.
+        let mut done: HashSet<&str> = HashSet::new();
+        while limits.tick() {
+            let empty = resource_ids
+                .iter()
+                .filter(|r| !self.placements(assignment).values().any(|a| a == **r))
+                .count();
+            if empty >= target {
+                break;
+            }
+            let next = resource_ids
+                .iter()
+                .filter(|r| !done.contains(r.as_str()))
+                .map(|r| (hosted(assignment, r).len(), *r))
+                .filter(|(n, _)| *n > 0)
+                .min();
+            let Some((_, r_id)) = next else {
+                break;
+            };
+            done.insert(r_id);
+            let entity_ids = hosted(assignment, r_id);
+            if entity_ids.iter().any(|e| self.entities[e].pinned) {
+                continue;
+            }
+
+            let mut trial = assignment.clone();
+            let mut trial_loads = loads.clone();
+            let mut trial_moves = Vec::new();
+            let mut cost = 0;
+            for entity_id in &entity_ids {
+                let e = &self.entities[entity_id];
+                let before = self.soft_penalty_at(&index, entity_id, r_id, &trial);
+                let best = self
+                    .candidates_with(&index, entity_id, &trial)
+                    .into_iter()
+                    .filter(|to| to != r_id && self.placements(&trial).values().any(|a| a == to))
+                    .filter(|to| self.fits_capacity(entity_id, to, &trial_loads))
+                    .filter(|to| self.soft_penalty_at(&index, entity_id, to, &trial) <= before)
+                    .map(|to| (utilization_after(self, entity_id, &to, &trial_loads), to))
+                    // fullest first, ties by id.
+                    .min_by(|a, b| b.0.total_cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+                let Some((_, to)) = best else {
+                    break;
+                };
+                let from_r = self.resources.handle(r_id).expect("resource exist");
+                let to_r = self.resources.handle(&to).expect("resource exist");
+                for (metric, v) in &e.metrics {
+                    *trial_loads
+                        .get_mut(&from_r)
+                        .expect("resource loaded")
+                        .entry(metric.clone())
+                        .or_insert(0) -= v;
+                    *trial_loads
+                        .get_mut(&to_r)
+                        .expect("resource loaded")
+                        .entry(metric.clone())
+                        .or_insert(0) += v;
+                }
+                self.place_in(&mut trial, entity_id, &to);
+                cost += e.move_cost;
+                trial_moves.push(Move {
+                    entity_id: entity_id.clone(),
+                    from: r_id.clone(),
+                    to,
+                    cost: e.move_cost,
+                });
+            }
+            if trial_moves.len() < entity_ids.len() || budget.is_some_and(|b| spent + cost > b) {
+                continue;
+            }
+            for m in &trial_moves {
+                done.insert(self.resources[&m.to].id.as_str());
+            }
+            *assignment = trial;
+            loads = trial_loads;
+            spent += cost;
+            moves.extend(trial_moves);
+        }
+        moves
+    }
+}
+
+// highest used / capacity over the entity's metrics on the resource after
+// adding it, 0 without capacities.
+fn utilization_after(board: &Board, entity_id: &str, resource_id: &str, loads: &Loads) -> f64 {
+    let r = &board.resources[resource_id];
+    let load = board.load_of(loads, resource_id).expect("resource loaded");
+    let mut util: f64 = 0.0;
+    for metric in r.capacities.keys() {
+        let cap = r.usable_capacity(metric).unwrap_or(0);
+        if cap <= 0 {
+            continue;
+        }
+        let used = load.get(metric).copied().unwrap_or(0)
+            + board.entities[entity_id]
+                .metrics
+                .get(metric)
+                .copied()
+                .unwrap_or(0);
+        util = util.max(used as f64 / cap as f64);
+    }
+    util
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::solver::{Board, Entity, Objective, Resource, SolverConfig};
+
+    #[test]
+    fn defragment_test() {
+        let mut b = Board::new();
+        for id in ["node1", "node2", "node3", "node4"] {
+            let mut r = Resource::new(String::from(id));
+            r.capacities.insert(String::from("cpu"), 6);
+            b.add_resource(r).expect("added");
+        }
+        for (id, r) in [
+            ("a", "node1"),
+            ("b", "node2"),
+            ("c", "node3"),
+            ("d", "node4"),
+        ] {
+            let mut e = Entity::new(String::from(id));
+            e.metrics.insert(String::from("cpu"), 2);
+            e.move_cost = 1;
+            b.add_entity(String::from(r), e).expect("added");
+        }
+        b.entities.get_mut("d").unwrap().pinned = true;
+
+        let config = SolverConfig {
+            objective: Objective::Defragment {
+                target_empty_resources: 3,
+            },
+            ..Default::default()
+        };
+        let plan = b.rebalance_with(&config);
+        // node4 keeps the pinned d and node2 is full after a and c.
+        let moved: Vec<(&str, &str)> = plan
+            .moves
+            .iter()
+            .map(|m| (m.entity_id.as_str(), m.to.as_str()))
+            .collect();
+        assert_eq!(moved, vec![("a", "node2"), ("c", "node2")]);
+        b.apply_move_plan(&plan).expect("applies");
+        assert!(b.check_all().is_empty());
+
+        // a lower target stops early.
+        b.move_entity("a", "node1").expect("moved");
+        let plan = b.rebalance_with(&SolverConfig {
+            objective: Objective::Defragment {
+                target_empty_resources: 2,
+            },
+            ..Default::default()
+        });
+        assert_eq!(plan.len(), 1);
+        assert_eq!(plan.moves[0].entity_id, "a");
+
+        // the budget leaves the moves out.
+        let plan = b.rebalance_with(&SolverConfig {
+            move_cost_budget: Some(0),
+            ..config
+        });
+        assert!(plan.is_empty());
+    }
+}
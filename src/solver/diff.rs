@@ -0,0 +1,207 @@
+// differences between two boards, like snapshots around a rebalance.
+
+use std::collections::BTreeMap;
+
+use crate::json::{ToJson, Value};
+
+use super::{Board, IdMap, Move};
+
+// ids of objects of one kind that differ, each list sorted.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ObjectChanges {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    // on both boards with different fields.
+    pub changed: Vec<String>,
+}
+
+impl ObjectChanges {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    // compares objects by their json form, so every field the codec
+    // writes counts.
+    fn between(before: &BTreeMap<&String, Value>, after: &BTreeMap<&String, Value>) -> Self {
+        let mut changes = ObjectChanges::default();
+        for (id, v) in before {
+            match after.get(id) {
+                None => changes.removed.push((*id).clone()),
+                Some(w) if w != v => changes.changed.push((*id).clone()),
+                Some(_) => {}
+            }
+        }
+        changes.added = after
+            .keys()
+            .filter(|id| !before.contains_key(*id))
+            .map(|id| (*id).clone())
+            .collect();
+        changes
+    }
+}
+
+// see Board::diff.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct BoardDiff {
+    pub resources: ObjectChanges,
+    pub entities: ObjectChanges,
+    // relations of each kind apart, ids are only unique within a kind.
+    pub id_relations: ObjectChanges,
+    pub property_relations: ObjectChanges,
+    pub id_property_relations: ObjectChanges,
+    pub domain_relations: ObjectChanges,
+    pub chains: ObjectChanges,
+    pub groups: ObjectChanges,
+    // entities on both boards now on another resource, sorted by entity
+    // id, with the move_cost of the entity on the other board.
+    pub moves: Vec<Move>,
+}
+
+impl BoardDiff {
+    pub fn is_empty(&self) -> bool {
+        self.kinds().iter().all(|(_, changes)| changes.is_empty()) && self.moves.is_empty()
+    }
+
+    // the changes of every kind of object with its name, in a fixed order.
+    pub fn kinds(&self) -> [(&'static str, &ObjectChanges); 8] {
+        [
+            ("resource", &self.resources),
+            ("entity", &self.entities),
+            ("id relation", &self.id_relations),
+            ("property relation", &self.property_relations),
+            ("id property relation", &self.id_property_relations),
+            ("domain relation", &self.domain_relations),
+            ("chain", &self.chains),
+            ("group", &self.groups),
+        ]
+    }
+}
+
+fn values<T: ToJson>(map: &IdMap<T>) -> BTreeMap<&String, Value> {
+    map.iter().map(|(id, x)| (id, x.to_value())).collect()
+}
+
+fn changes<T: ToJson>(before: &IdMap<T>, after: &IdMap<T>) -> ObjectChanges {
+    ObjectChanges::between(&values(before), &values(after))
+}
+
+impl Board {
+    // what changed from this board to other. Objects are matched by id;
+    // an entity that only moved is listed in moves, not as changed.
+    pub fn diff(&self, other: &Board) -> BoardDiff {
+        let mut moves: Vec<Move> = other
+            .assignment()
+            .iter()
+            .filter_map(|(e_id, to)| {
+                let from = self.assignment().get(e_id)?;
+                (from != to).then(|| Move {
+                    entity_id: e_id.clone(),
+                    from: from.clone(),
+                    to: to.clone(),
+                    cost: other.entities[e_id].move_cost,
+                })
+            })
+            .collect();
+        moves.sort_by(|a, b| a.entity_id.cmp(&b.entity_id));
+        BoardDiff {
+            resources: changes(&self.resources, &other.resources),
+            entities: changes(&self.entities, &other.entities),
+            id_relations: changes(&self.id_relations, &other.id_relations),
+            property_relations: changes(&self.property_relations, &other.property_relations),
+            id_property_relations: changes(
+                &self.id_property_relations,
+                &other.id_property_relations,
+            ),
+            domain_relations: changes(&self.domain_relations, &other.domain_relations),
+            chains: changes(&self.chains, &other.chains),
+            groups: changes(&self.groups, &other.groups),
+            moves,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::json::ToJson;
+    use crate::solver::{
+        Board, BoardBuilder, EntityBuilder, IDRelation, IDRelationKind, Priority, PropertyRelation,
+        PropertyRelationKind, ResourceBuilder,
+    };
+
+    fn board() -> Board {
+        BoardBuilder::new()
+            .resource(ResourceBuilder::new("node1").capacity("cpu", 4))
+            .resource(ResourceBuilder::new("node2").capacity("cpu", 4))
+            .entity("node1", EntityBuilder::new("a").metric("cpu", 1))
+            .entity(
+                "node1",
+                EntityBuilder::new("c").metric("cpu", 1).move_cost(2),
+            )
+            .id_relation(IDRelation {
+                id: String::from("apart"),
+                kind: IDRelationKind::EEAntiAffinity,
+                id1: String::from("a"),
+                id2: String::from("c"),
+                priority: Priority::Hard,
+            })
+            .build()
+            .expect("builds")
+    }
+
+    #[test]
+    fn diff_test() {
+        let before = board();
+        assert!(before.diff(&before.snapshot()).is_empty());
+
+        let mut after = before.snapshot();
+        after.apply_move_plan(&before.rebalance()).expect("applied");
+        after
+            .add_resource(ResourceBuilder::new("node3").build())
+            .expect("added");
+        let mut a = after.entities["a"].clone();
+        a.metrics.insert(String::from("cpu"), 2);
+        after.update_entity(a).expect("updated");
+        after.remove_relation("apart").expect("removed");
+
+        let diff = before.diff(&after);
+        assert_eq!(diff.resources.added, ["node3"]);
+        assert!(diff.resources.removed.is_empty() && diff.resources.changed.is_empty());
+        assert_eq!(diff.id_relations.removed, ["apart"]);
+        // a moved as the cheaper one, and its load changed.
+        assert_eq!(diff.entities.changed, ["a"]);
+        assert_eq!(diff.moves.len(), 1);
+        assert_eq!(diff.moves[0].entity_id, "a");
+        assert_eq!(diff.moves[0].to, "node2");
+
+        let reverse = after.diff(&before);
+        assert_eq!(reverse.resources.removed, ["node3"]);
+        assert_eq!(reverse.id_relations.added, ["apart"]);
+        assert_eq!(
+            diff.to_value()
+                .get("moves")
+                .and_then(|m| m.as_array())
+                .map(|m| m.len()),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn diff_kinds_test() {
+        let before = board();
+        let mut after = before.snapshot();
+        // a property relation sharing the id of the id relation.
+        after
+            .add_property_relation(PropertyRelation {
+                id: String::from("apart"),
+                kind: PropertyRelationKind::AntiAffinity,
+                entity_property: String::from("tier"),
+                resource_property: String::from("gpu"),
+                priority: Priority::Soft(1),
+            })
+            .expect("added");
+        let diff = before.diff(&after);
+        assert_eq!(diff.property_relations.added, ["apart"]);
+        assert!(diff.id_relations.is_empty());
+        assert!(after.diff(&after.snapshot()).is_empty());
+    }
+}
@@ -0,0 +1,275 @@
+// graphviz export of the board for debugging placement decisions.
+
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use super::{Board, IDRelationKind, PropertyRelationKind, ViolationKind};
+
+// quote a string as a dot id.
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn resource_node(id: &str) -> String {
+    quote(&format!("r:{}", id))
+}
+
+fn entity_node(id: &str) -> String {
+    quote(&format!("e:{}", id))
+}
+
+fn sorted<'a, I: Iterator<Item = &'a String>>(it: I) -> Vec<&'a String> {
+    let mut v: Vec<&String> = it.collect();
+    v.sort();
+    v
+}
+
+impl Board {
+    // renders the board as a graphviz digraph. Resources are boxes, entities
+    // are ellipses, the assignment is drawn as bold black edges and relations
+    // as labeled gray edges, solid for affinity and dashed for anti-affinity.
+    // Entities and assignments involved in a violation reported by
+    // `check_all` are drawn red, as are resources over capacity and the
+    // relation edges of the entities breaking them.
+    pub fn to_dot(&self) -> String {
+        let violations = self.check_all();
+        let bad_entities: HashSet<&str> = violations
+            .iter()
+            .filter_map(|v| v.entity_id.as_deref())
+            .collect();
+        let bad_resources: HashSet<&str> = violations
+            .iter()
+            .filter(|v| v.kind == ViolationKind::Capacity)
+            .map(|v| v.resource_id.as_str())
+            .collect();
+
+        let mut out = String::from("digraph board {\n");
+
+        for id in sorted(self.resources.keys()) {
+            let style = if bad_resources.contains(id.as_str()) {
+                ", color=red, style=bold, penwidth=2"
+            } else {
+                ""
+            };
+            writeln!(
+                out,
+                "  {} [shape=box, label={}{}];",
+                resource_node(id),
+                quote(id),
+                style
+            )
+            .unwrap();
+        }
+        for id in sorted(self.entities.keys()) {
+            let style = if bad_entities.contains(id.as_str()) {
+                ", color=red, style=bold, penwidth=2"
+            } else {
+                ""
+            };
+            writeln!(
+                out,
+                "  {} [shape=ellipse, label={}{}];",
+                entity_node(id),
+                quote(id),
+                style
+            )
+            .unwrap();
+        }
+
+        for e_id in sorted(self.assignment().keys()) {
+            let r_id = &self.assignment()[e_id];
+            let style = if bad_entities.contains(e_id.as_str()) {
+                "style=bold, color=red, penwidth=2"
+            } else {
+                "style=bold"
+            };
+            writeln!(
+                out,
+                "  {} -> {} [{}];",
+                entity_node(e_id),
+                resource_node(r_id),
+                style
+            )
+            .unwrap();
+        }
+
+        // entities breaking each relation, so only their edges are red.
+        let breaking: HashSet<(&str, &str)> = violations
+            .iter()
+            .filter_map(|v| Some((v.relation_id.as_deref()?, v.entity_id.as_deref()?)))
+            .collect();
+        let broken =
+            |rel_id: &str, e_ids: &[&str]| e_ids.iter().any(|e| breaking.contains(&(rel_id, *e)));
+
+        // relation edges as (from, to, relation id, affinity, broken)
+        let mut edges: Vec<(String, String, &String, bool, bool)> = Vec::new();
+        for rel in self.id_relations.values() {
+            let (to, affinity) = match rel.kind {
+                IDRelationKind::EEAffinity => (entity_node(&rel.id2), true),
+                IDRelationKind::EEAntiAffinity => (entity_node(&rel.id2), false),
+                IDRelationKind::ERAffinity => (resource_node(&rel.id2), true),
+                IDRelationKind::ERAntiAffinity => (resource_node(&rel.id2), false),
+            };
+            edges.push((
+                entity_node(&rel.id1),
+                to,
+                &rel.id,
+                affinity,
+                broken(&rel.id, &[&rel.id1, &rel.id2]),
+            ));
+        }
+        for rel in self.property_relations.values() {
+            let affinity = matches!(
+                rel.kind,
+                PropertyRelationKind::Affinity | PropertyRelationKind::EEAffinity
+            );
+            if rel.kind.is_ee() {
+                // a chain through the entities sharing the property.
+                let members = sorted(
+                    self.entities
+                        .values()
+                        .filter(|e| e.has_property(&rel.entity_property))
+                        .map(|e| &e.id),
+                );
+                for pair in members.windows(2) {
+                    edges.push((
+                        entity_node(pair[0]),
+                        entity_node(pair[1]),
+                        &rel.id,
+                        affinity,
+                        broken(&rel.id, &[pair[0], pair[1]]),
+                    ));
+                }
+                continue;
+            }
+            for e in self.entities.values() {
+                if !e.has_property(&rel.entity_property) {
+                    continue;
+                }
+                for r in self.resources.values() {
+                    if r.has_property(&rel.resource_property) {
+                        edges.push((
+                            entity_node(&e.id),
+                            resource_node(&r.id),
+                            &rel.id,
+                            affinity,
+                            broken(&rel.id, &[&e.id]),
+                        ));
+                    }
+                }
+            }
+        }
+        for rel in self.id_property_relations.values() {
+            let affinity = rel.kind == PropertyRelationKind::Affinity;
+            for r in self.resources.values() {
+                if r.has_property(&rel.resource_property) {
+                    edges.push((
+                        entity_node(&rel.entity_id),
+                        resource_node(&r.id),
+                        &rel.id,
+                        affinity,
+                        broken(&rel.id, &[&rel.entity_id]),
+                    ));
+                }
+            }
+        }
+        edges.sort();
+
+        for (from, to, rel_id, affinity, broken) in edges {
+            let style = if affinity { "solid" } else { "dashed" };
+            let color = if broken { "red, penwidth=2" } else { "gray40" };
+            writeln!(
+                out,
+                "  {} -> {} [label={}, style={}, color={}];",
+                from,
+                to,
+                quote(rel_id),
+                style,
+                color
+            )
+            .unwrap();
+        }
+
+        out.push_str("}\n");
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::solver::{
+        Board, Entity, IDRelation, IDRelationKind, Priority, PropertyRelation,
+        PropertyRelationKind, Resource,
+    };
+
+    #[test]
+    fn to_dot_test() {
+        let mut r1 = Resource::new(String::from("node1"));
+        r1.add_property(String::from("red"));
+        let mut r2 = Resource::new(String::from("node2"));
+        r2.add_property(String::from("blue"));
+        let mut e1 = Entity::new(String::from("app1"));
+        e1.add_property(String::from("red"));
+
+        let mut b = Board::new();
+        b.add_resource(r1).expect("added");
+        b.add_resource(r2).expect("added");
+        b.add_entity(String::from("node2"), e1).expect("added");
+        b.add_property_relation(PropertyRelation {
+            id: String::from("color"),
+            kind: PropertyRelationKind::Affinity,
+            entity_property: String::from("red"),
+            resource_property: String::from("red"),
+            priority: Priority::Hard,
+        })
+        .expect("ok");
+
+        let dot = b.to_dot();
+        assert!(dot.starts_with("digraph board {\n"));
+        assert!(dot.ends_with("}\n"));
+        assert!(dot.contains("\"r:node1\" [shape=box, label=\"node1\"];"));
+        assert!(dot.contains("\"r:node2\" [shape=box, label=\"node2\"];"));
+        // app1 is misplaced so it is highlighted.
+        assert!(dot.contains(
+            "\"e:app1\" [shape=ellipse, label=\"app1\", color=red, style=bold, penwidth=2];"
+        ));
+        assert!(dot.contains("\"e:app1\" -> \"r:node2\" [style=bold, color=red, penwidth=2];"));
+        assert!(dot.contains(
+            "\"e:app1\" -> \"r:node1\" [label=\"color\", style=solid, color=red, penwidth=2];"
+        ));
+    }
+
+    #[test]
+    fn to_dot_relation_style_test() {
+        let mut b = Board::new();
+        for id in ["node1", "node2"] {
+            b.add_resource(Resource::new(String::from(id)))
+                .expect("added");
+        }
+        for (id, r) in [("a", "node1"), ("b", "node1"), ("c", "node2")] {
+            b.add_entity(String::from(r), Entity::new(String::from(id)))
+                .expect("added");
+        }
+        for (id, kind, id2) in [
+            ("apart", IDRelationKind::EEAntiAffinity, "b"),
+            ("near", IDRelationKind::EEAffinity, "c"),
+        ] {
+            b.add_id_relation(IDRelation {
+                id: String::from(id),
+                kind,
+                id1: String::from("a"),
+                id2: String::from(id2),
+                priority: Priority::Soft(1),
+            })
+            .expect("ok");
+        }
+        b.move_entity("b", "node2").expect("moved");
+
+        // only the broken affinity is red.
+        let dot = b.to_dot();
+        assert!(dot.contains("\"e:a\" -> \"e:b\" [label=\"apart\", style=dashed, color=gray40];"));
+        assert!(dot
+            .contains("\"e:a\" -> \"e:c\" [label=\"near\", style=solid, color=red, penwidth=2];"));
+        assert!(dot.contains("\"e:b\" -> \"r:node2\" [style=bold];"));
+    }
+}
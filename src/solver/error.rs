@@ -0,0 +1,104 @@
+// domain errors reported by board construction and validation.
+
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SolverError {
+    // an object with this id already exists.
+    DuplicateId(String),
+    // a referenced entity does not exist.
+    EntityNotFound(String),
+    // a referenced resource does not exist.
+    ResourceNotFound(String),
+    // an entity was given no resource to be assigned to.
+    Unassigned(String),
+    // a referenced relation does not exist.
+    RelationNotFound(String),
+    // a referenced entity group does not exist.
+    GroupNotFound(String),
+    // the resource still has entities assigned to it.
+    ResourceInUse(String),
+    // a move does not start from the entity's current resource.
+    MoveMismatch(String),
+    // the relation's kind is not allowed for its relation type.
+    UnsupportedKind(String),
+    // the entity is pinned to its resource and can't be moved.
+    Pinned(String),
+}
+
+impl fmt::Display for SolverError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SolverError::DuplicateId(id) => write!(f, "id already exist: {}", id),
+            SolverError::EntityNotFound(id) => write!(f, "entity does not exist: {}", id),
+            SolverError::ResourceNotFound(id) => write!(f, "resource does not exist: {}", id),
+            SolverError::Unassigned(id) => write!(f, "entity has no assignment: {}", id),
+            SolverError::RelationNotFound(id) => write!(f, "relation does not exist: {}", id),
+            SolverError::GroupNotFound(id) => write!(f, "group does not exist: {}", id),
+            SolverError::ResourceInUse(id) => write!(f, "resource has entities: {}", id),
+            SolverError::MoveMismatch(id) => {
+                write!(f, "entity is not on the move source: {}", id)
+            }
+            SolverError::UnsupportedKind(id) => write!(f, "relation kind not supported: {}", id),
+            SolverError::Pinned(id) => write!(f, "entity is pinned: {}", id),
+        }
+    }
+}
+
+impl std::error::Error for SolverError {}
+
+impl From<SolverError> for std::io::Error {
+    fn from(e: SolverError) -> Self {
+        let kind = match e {
+            SolverError::DuplicateId(_) => std::io::ErrorKind::AlreadyExists,
+            SolverError::EntityNotFound(_)
+            | SolverError::ResourceNotFound(_)
+            | SolverError::RelationNotFound(_)
+            | SolverError::GroupNotFound(_) => std::io::ErrorKind::NotFound,
+            SolverError::Unassigned(_)
+            | SolverError::ResourceInUse(_)
+            | SolverError::MoveMismatch(_)
+            | SolverError::UnsupportedKind(_)
+            | SolverError::Pinned(_) => std::io::ErrorKind::InvalidInput,
+        };
+        std::io::Error::new(kind, e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::solver::{
+        Board, Entity, IDRelation, IDRelationKind, Priority, Resource, SolverError,
+    };
+
+    #[test]
+    fn add_errors_test() {
+        let mut b = Board::new();
+        b.add_resource(Resource::new(String::from("node1")))
+            .expect("added");
+        assert_eq!(
+            b.add_resource(Resource::new(String::from("node1"))),
+            Err(SolverError::DuplicateId(String::from("node1")))
+        );
+        assert_eq!(
+            b.add_entity(String::from("node9"), Entity::new(String::from("a"))),
+            Err(SolverError::ResourceNotFound(String::from("node9")))
+        );
+        b.add_entity(String::from("node1"), Entity::new(String::from("a")))
+            .expect("added");
+        let err = b
+            .add_id_relation(IDRelation {
+                id: String::from("ab"),
+                kind: IDRelationKind::EEAffinity,
+                id1: String::from("a"),
+                id2: String::from("b"),
+                priority: Priority::Hard,
+            })
+            .expect_err("fails");
+        assert!(matches!(err, SolverError::EntityNotFound(id) if id == "b"));
+
+        // still usable where io errors are expected.
+        let io: std::io::Error = SolverError::DuplicateId(String::from("a")).into();
+        assert_eq!(io.kind(), std::io::ErrorKind::AlreadyExists);
+    }
+}
@@ -0,0 +1,261 @@
+// exact placement of pending batches by branch and bound. The search of
+// Board::solve stops at the first placement satisfying the hard relations
+// and capacities; this one goes on until it has proven the one with the
+// lowest soft penalty optimal, or that no placement exists.
+//
+// It is not an integer program handed to a MILP solver: the crate builds
+// with std alone, offline, so there is none to link. The branch and bound
+// works on the same model, the hard relations and capacities as
+// constraints and the soft penalty as objective, and gives the same
+// guarantees when it finishes. Like the MILP solvers it is stopped at a
+// bound, MAX_STATES unless the config sets one, and then answers the best
+// placement found as incomplete.
+
+use super::config::Limits;
+use super::solve::Search;
+use super::trace;
+use super::{
+    Board, Bounded, Pending, Placement, SolveError, Solver, SolverConfig, Violation, ViolationKind,
+};
+
+// states place_exact visits when the config has no max_iterations.
+const MAX_STATES: u64 = 1_000_000;
+
+// places the batch with the lowest summed soft penalty of the board's
+// relations, see Board::place_exact. Exponential in the batch size, so
+// meant for small batches where the heuristics give no guarantee. Stopped
+// at MAX_STATES it answers the best placement found.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExactSolver;
+
+impl Solver for ExactSolver {
+    fn place(&self, board: &Board, pending: &Pending) -> Result<Placement, SolveError> {
+        board
+            .place_exact(pending, &SolverConfig::default())
+            .map(|bounded| bounded.value)
+    }
+}
+
+// penalty of the violations that entities placed later can not clear.
+// A domain relation broken by the entities placed so far may still be met
+// once more of them are spread out, so it is left out of the bounds.
+fn lasting(violations: &[Violation]) -> (bool, i64) {
+    let mut hard = false;
+    let mut penalty = 0;
+    for v in violations {
+        if matches!(v.kind, ViolationKind::Domain(_)) {
+            continue;
+        }
+        hard |= v.is_hard();
+        penalty += v.penalty();
+    }
+    (hard, penalty)
+}
+
+struct BranchAndBound<'a> {
+    board: &'a Board,
+    search: Search<'a>,
+    entity_ids: &'a [String],
+    // the lowest penalty found and its placement.
+    best: Option<(i64, Placement)>,
+}
+
+impl BranchAndBound<'_> {
+    // resources the entity fits on as the others are placed, each with the
+    // least penalty its own relations will break there, cheapest first.
+    // Hard domain relations are only checked once every entity is placed.
+    fn options(&self, entity_id: &str) -> Vec<(i64, String)> {
+        let b = self.board;
+        let index = self.search.index();
+        let mut options: Vec<(i64, String)> = index
+            .resource_candidates(entity_id)
+            .into_iter()
+            .filter(|r_id| self.search.fits(entity_id, r_id))
+            .filter_map(|r_id| {
+                let violations =
+                    b.entity_violations_at(index, entity_id, r_id, self.search.assignment());
+                let (hard, penalty) = lasting(&violations);
+                (!hard).then(|| (penalty, r_id.to_string()))
+            })
+            .collect();
+        options.sort();
+        options
+    }
+
+    fn branch(&mut self, remaining: &mut Vec<String>) {
+        if !self.search.limits.tick() {
+            return;
+        }
+        trace::count("exact.states", 1);
+        let b = self.board;
+        let violations = b.violations_indexed(self.search.index(), self.search.assignment());
+        if remaining.is_empty() {
+            let placed_hard = violations.iter().any(|v| {
+                v.is_hard()
+                    && v.entity_id
+                        .as_ref()
+                        .is_some_and(|e| self.entity_ids.contains(e))
+            });
+            let penalty: i64 = violations.iter().map(|v| v.penalty()).sum();
+            if !placed_hard && self.best.as_ref().is_none_or(|(best, _)| penalty < *best) {
+                self.best = Some((penalty, self.search.placement(self.entity_ids)));
+            }
+            return;
+        }
+
+        // what is broken already, plus the cheapest spot of every entity
+        // left, bounds any placement below this node from below.
+        let options: Vec<Vec<(i64, String)>> = remaining.iter().map(|e| self.options(e)).collect();
+        let mut bound = lasting(&violations).1;
+        for o in &options {
+            match o.first() {
+                Some((penalty, _)) => bound += penalty,
+                None => return,
+            }
+        }
+        if self.best.as_ref().is_some_and(|(best, _)| bound >= *best) {
+            return;
+        }
+
+        let idx = (0..remaining.len())
+            .min_by_key(|i| (options[*i].len(), *i))
+            .expect("not empty");
+        let entity_id = remaining.swap_remove(idx);
+        for (_, r_id) in &options[idx] {
+            self.search.place(&entity_id, r_id);
+            self.branch(remaining);
+            self.search.unplace(&entity_id);
+            if self.search.limits.stopped() {
+                break;
+            }
+        }
+        // restore the order for the caller.
+        remaining.push(entity_id);
+        let last = remaining.len() - 1;
+        remaining.swap(idx, last);
+    }
+}
+
+impl Board {
+    // the placement of the pending batch with the lowest summed soft
+    // penalty of the board's relations once placed, among those keeping
+    // every hard relation and capacity. Ties go to the first found, in the
+    // order Board::solve tries resources. Complete means the search ran
+    // to the end, so the placement is optimal; stopped early at a limit of
+    // the config, or after MAX_STATES states if it sets no max_iterations,
+    // it is the best found, or Interrupted if none was. A finished search
+    // without placement proves there is none and fails like solve. The
+    // board is not modified.
+    pub fn place_exact(
+        &self,
+        pending: &Pending,
+        config: &SolverConfig,
+    ) -> Result<Bounded<Placement>, SolveError> {
+        let _span = trace::span("exact");
+        self.with_staged(pending, |b, entity_ids| {
+            let mut search = Search::new(b);
+            search.limits = Limits::new(&SolverConfig {
+                max_iterations: config.max_iterations.or(Some(MAX_STATES)),
+                ..config.clone()
+            });
+            let mut bb = BranchAndBound {
+                board: b,
+                search,
+                entity_ids,
+                best: None,
+            };
+            bb.branch(&mut entity_ids.to_vec());
+            let complete = !bb.search.limits.stopped();
+            match bb.best {
+                Some((_, placement)) => Ok(Bounded {
+                    value: placement,
+                    complete,
+                }),
+                None if complete => Err(b.solve_failure(entity_ids)),
+                None => Err(SolveError::Interrupted(entity_ids.to_vec())),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::solver::{
+        BacktrackingSolver, Board, BoardBuilder, EntityBuilder, ExactSolver, IDRelation,
+        IDRelationKind, Pending, Priority, ResourceBuilder, SolveError, Solver, SolverConfig,
+    };
+
+    // z fits on either node alone and would rather be on node1, where the
+    // first placement found puts a and b.
+    fn setup() -> (Board, Pending) {
+        let b = BoardBuilder::new()
+            .resource(ResourceBuilder::new("node1").capacity("cpu", 2))
+            .resource(ResourceBuilder::new("node2").capacity("cpu", 2))
+            .build()
+            .expect("builds");
+        let mut p = Pending::new();
+        for (id, cpu) in [("a", 1), ("b", 1), ("z", 2)] {
+            p.add_entity(EntityBuilder::new(id).metric("cpu", cpu).build());
+        }
+        p.id_relations.insert(
+            String::from("z-on-1"),
+            IDRelation {
+                id: String::from("z-on-1"),
+                kind: IDRelationKind::ERAffinity,
+                id1: String::from("z"),
+                id2: String::from("node1"),
+                priority: Priority::Soft(10),
+            },
+        );
+        (b, p)
+    }
+
+    #[test]
+    fn exact_test() {
+        let (mut b, p) = setup();
+        let first = BacktrackingSolver.place(&b, &p).expect("solves");
+        assert_eq!(first.assignment["z"], "node2");
+
+        let exact = b.place_exact(&p, &SolverConfig::default()).expect("solves");
+        assert!(exact.complete);
+        assert_eq!(exact.value.assignment["z"], "node1");
+        assert_eq!(exact.value.assignment["a"], "node2");
+        assert!(b.entities.is_empty());
+
+        b.solve_with(&ExactSolver, p).expect("solves");
+        assert!(b.check_all().is_empty());
+    }
+
+    #[test]
+    fn exact_failure_test() {
+        let (b, mut p) = setup();
+        p.add_entity(EntityBuilder::new("c").metric("cpu", 1).build());
+        assert!(matches!(
+            b.place_exact(&p, &SolverConfig::default()),
+            Err(SolveError::Unsatisfiable(ids)) if ids.len() == 4
+        ));
+
+        let (b, p) = setup();
+        let config = SolverConfig {
+            max_iterations: Some(1),
+            ..Default::default()
+        };
+        assert!(matches!(
+            b.place_exact(&p, &config),
+            Err(SolveError::Interrupted(_))
+        ));
+    }
+
+    #[test]
+    fn exact_bound_test() {
+        // stopped after the first placement, before proving it optimal.
+        let (b, p) = setup();
+        let config = SolverConfig {
+            max_iterations: Some(4),
+            ..Default::default()
+        };
+        let bounded = b.place_exact(&p, &config).expect("found one");
+        assert!(!bounded.complete);
+        assert_eq!(bounded.value.assignment["z"], "node2");
+    }
+}
@@ -0,0 +1,223 @@
+// explaining why an entity can't go on a resource.
+
+use std::fmt;
+
+use super::index::RelationIndex;
+use super::{Board, ResourceState, ViolationKind};
+
+// one reason a resource can't take an entity.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Rejection {
+    // the resource takes no new entities.
+    Inactive(ResourceState),
+    // the entity would break this hard relation.
+    Relation {
+        kind: ViolationKind,
+        relation_id: String,
+    },
+    // the entity's placement constraint doesn't match the resource.
+    Constraint(String),
+    Capacity {
+        metric: String,
+        needed: i64,
+        free: i64,
+    },
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rejection::Inactive(state) => write!(f, "resource is {:?}", state),
+            Rejection::Relation { kind, relation_id } => {
+                write!(f, "fails {} '{}'", kind, relation_id)
+            }
+            Rejection::Constraint(source) => write!(f, "fails constraint '{}'", source),
+            Rejection::Capacity {
+                metric,
+                needed,
+                free,
+            } => write!(
+                f,
+                "metric '{}' needs {}, only {} free",
+                metric, needed, free
+            ),
+        }
+    }
+}
+
+// the reasons one resource can't take the entity.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResourceRejection {
+    pub resource_id: String,
+    pub reasons: Vec<Rejection>,
+}
+
+// where an entity could go and why every other resource is ruled out.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PlacementExplanation {
+    pub entity_id: String,
+    // resources that can take the entity, sorted.
+    pub candidates: Vec<String>,
+    // every other resource, sorted by resource id.
+    pub rejections: Vec<ResourceRejection>,
+}
+
+impl PlacementExplanation {
+    pub fn is_placeable(&self) -> bool {
+        !self.candidates.is_empty()
+    }
+}
+
+// "node2: fails PropertyAffinity 'color'; node3: metric 'cpu' needs 4,
+// only 2 free".
+impl fmt::Display for PlacementExplanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts: Vec<String> = self
+            .rejections
+            .iter()
+            .map(|r| {
+                let reasons: Vec<String> = r.reasons.iter().map(|x| x.to_string()).collect();
+                format!("{}: {}", r.resource_id, reasons.join(", "))
+            })
+            .collect();
+        f.write_str(&parts.join("; "))
+    }
+}
+
+impl Board {
+    // checks every resource for the entity, given where the other entities
+    // are: its state, the hard relations and constraint the entity would
+    // break, and the metrics that wouldn't fit. An entity that is already
+    // placed is explained as if it were lifted off its resource. Empty for
+    // unknown entities.
+    pub fn explain_placement(&self, entity_id: &str) -> PlacementExplanation {
+        let mut explanation = PlacementExplanation {
+            entity_id: entity_id.to_string(),
+            ..Default::default()
+        };
+        let Some(e) = self.entities.get(entity_id) else {
+            return explanation;
+        };
+        let mut assignment = self.assignment.clone();
+        assignment.remove(self.entities.handle(entity_id).expect("entity exist"));
+        let loads = self.loads_with(&assignment);
+        let index = RelationIndex::new(self);
+
+        let mut resource_ids: Vec<&String> = self.resources.keys().collect();
+        resource_ids.sort();
+        for r_id in resource_ids {
+            let r = &self.resources[r_id];
+            let mut reasons = Vec::new();
+            if !r.state.accepts_entities() {
+                reasons.push(Rejection::Inactive(r.state));
+            }
+            for v in self.entity_violations_at(&index, entity_id, r_id, &assignment) {
+                if !v.is_hard() {
+                    continue;
+                }
+                reasons.push(match (v.kind, v.relation_id) {
+                    (_, Some(relation_id)) => Rejection::Relation {
+                        kind: v.kind,
+                        relation_id,
+                    },
+                    _ => Rejection::Constraint(
+                        e.constraint
+                            .as_ref()
+                            .map(|c| c.source().to_string())
+                            .unwrap_or_default(),
+                    ),
+                });
+            }
+            let mut metrics: Vec<(&String, &i64)> = e.metrics.iter().collect();
+            metrics.sort();
+            for (metric, needed) in metrics {
+                let Some(cap) = r.buffered_capacity(metric) else {
+                    continue;
+                };
+                let used = self
+                    .load_of(&loads, r_id)
+                    .and_then(|l| l.get(metric))
+                    .copied()
+                    .unwrap_or(0);
+                if used + needed > cap {
+                    reasons.push(Rejection::Capacity {
+                        metric: metric.clone(),
+                        needed: *needed,
+                        free: cap - used,
+                    });
+                }
+            }
+
+            if reasons.is_empty() {
+                explanation.candidates.push(r_id.clone());
+            } else {
+                explanation.rejections.push(ResourceRejection {
+                    resource_id: r_id.clone(),
+                    reasons,
+                });
+            }
+        }
+        explanation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::solver::{
+        Board, DeactivationIntent, Entity, Pending, Priority, PropertyRelation,
+        PropertyRelationKind, Rejection, Resource, SolveError, ViolationKind,
+    };
+
+    #[test]
+    fn explain_placement_test() {
+        let mut b = Board::new();
+        for (id, color) in [("node1", "red"), ("node2", "blue"), ("node3", "red")] {
+            let mut r = Resource::new(String::from(id));
+            r.add_property(String::from(color));
+            r.capacities.insert(String::from("cpu"), 4);
+            b.add_resource(r).expect("added");
+        }
+        let mut web = Entity::new(String::from("web"));
+        web.metrics.insert(String::from("cpu"), 2);
+        b.add_entity(String::from("node3"), web).expect("added");
+        b.deactivate_resource("node1", DeactivationIntent::Pause)
+            .expect("paused");
+        b.add_property_relation(PropertyRelation {
+            id: String::from("color"),
+            kind: PropertyRelationKind::Affinity,
+            entity_property: String::from("red"),
+            resource_property: String::from("red"),
+            priority: Priority::Hard,
+        })
+        .expect("ok");
+
+        let mut p = Pending::new();
+        let mut app = Entity::new(String::from("app"));
+        app.add_property(String::from("red"));
+        app.metrics.insert(String::from("cpu"), 4);
+        p.add_entity(app);
+        let Err(SolveError::NoCandidates(explanations)) = b.solve(p) else {
+            panic!("expected no candidates");
+        };
+        let ex = &explanations[0];
+        assert_eq!(ex.entity_id, "app");
+        assert!(!ex.is_placeable());
+        assert_eq!(
+            ex.rejections[1].reasons,
+            vec![Rejection::Relation {
+                kind: ViolationKind::Property(PropertyRelationKind::Affinity),
+                relation_id: String::from("color"),
+            }]
+        );
+        assert_eq!(
+            ex.to_string(),
+            "node1: resource is Paused; node2: fails PropertyAffinity 'color'; \
+             node3: metric 'cpu' needs 4, only 2 free"
+        );
+
+        // a placed entity is explained without its own load.
+        let ex = b.explain_placement("web");
+        assert_eq!(ex.candidates, vec!["node2", "node3"]);
+        assert!(b.explain_placement("ghost").rejections.is_empty());
+    }
+}
@@ -0,0 +1,277 @@
+// placement constraint expressions like "color==red && !(zone==z1) && disk>=100",
+// evaluated against the properties of a resource.
+//
+//   or      := and ("||" and)*
+//   and     := unary ("&&" unary)*
+//   unary   := "!" unary | "(" or ")" | compare
+//   compare := key (op value)?      op is == != < <= > >=
+//
+// A bare key holds when the property is set and not false. Comparisons
+// against a missing property are false, see Op::compare for the rest.
+
+use std::fmt;
+
+use super::property::{Op, Properties, PropertyValue};
+use super::Resource;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConstraintError {
+    // character offset into the expression.
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ConstraintError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at column {}", self.message, self.position + 1)
+    }
+}
+
+impl std::error::Error for ConstraintError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Has(String),
+    Compare(String, Op, PropertyValue),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+// parsed constraint, keeping its source for display and serialization.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlacementConstraint {
+    source: String,
+    expr: Expr,
+}
+
+impl PlacementConstraint {
+    pub fn parse(s: &str) -> Result<PlacementConstraint, ConstraintError> {
+        let tokens = lex(s)?;
+        let mut p = Parser {
+            tokens,
+            pos: 0,
+            end: s.chars().count(),
+        };
+        let expr = p.or()?;
+        if let Some((_, at)) = p.tokens.get(p.pos) {
+            return Err(error(*at, "unexpected token"));
+        }
+        Ok(PlacementConstraint {
+            source: s.to_string(),
+            expr,
+        })
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn matches(&self, resource: &Resource) -> bool {
+        eval(&self.expr, &resource.properties)
+    }
+}
+
+impl fmt::Display for PlacementConstraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+fn eval(expr: &Expr, props: &Properties) -> bool {
+    match expr {
+        Expr::Has(key) => props
+            .get(key)
+            .is_some_and(|v| *v != PropertyValue::Bool(false)),
+        Expr::Compare(key, op, rhs) => props.get(key).is_some_and(|lhs| op.compare(lhs, rhs)),
+        Expr::Not(e) => !eval(e, props),
+        Expr::And(a, b) => eval(a, props) && eval(b, props),
+        Expr::Or(a, b) => eval(a, props) || eval(b, props),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Op(Op),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+fn error(position: usize, message: &str) -> ConstraintError {
+    ConstraintError {
+        position,
+        message: message.to_string(),
+    }
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | '/' | ':')
+}
+
+// tokens with their character offset.
+fn lex(s: &str) -> Result<Vec<(Token, usize)>, ConstraintError> {
+    let chars: Vec<char> = s.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let start = i;
+        let token = match (c, next) {
+            (c, _) if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            ('&', Some('&')) => Token::And,
+            ('|', Some('|')) => Token::Or,
+            ('=', Some('=')) => Token::Op(Op::Eq),
+            ('!', Some('=')) => Token::Op(Op::Ne),
+            ('<', Some('=')) => Token::Op(Op::Le),
+            ('>', Some('=')) => Token::Op(Op::Ge),
+            ('<', _) => Token::Op(Op::Lt),
+            ('>', _) => Token::Op(Op::Gt),
+            ('!', _) => Token::Not,
+            ('(', _) => Token::Open,
+            (')', _) => Token::Close,
+            ('"' | '\'', _) => {
+                let end = chars[i + 1..]
+                    .iter()
+                    .position(|x| *x == c)
+                    .ok_or_else(|| error(i, "unterminated string"))?;
+                let text: String = chars[i + 1..i + 1 + end].iter().collect();
+                i += end + 2;
+                tokens.push((Token::Quoted(text), start));
+                continue;
+            }
+            (c, _) if is_word(c) => {
+                let len = chars[i..].iter().take_while(|x| is_word(**x)).count();
+                let text: String = chars[i..i + len].iter().collect();
+                i += len;
+                tokens.push((Token::Word(text), start));
+                continue;
+            }
+            _ => return Err(error(i, "unexpected character")),
+        };
+        i += match token {
+            Token::Op(Op::Lt | Op::Gt) | Token::Not | Token::Open | Token::Close => 1,
+            _ => 2,
+        };
+        tokens.push((token, start));
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    // offset reported for errors at the end of input.
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(t, _)| t)
+    }
+
+    fn at(&self) -> usize {
+        self.tokens.get(self.pos).map_or(self.end, |(_, at)| *at)
+    }
+
+    fn or(&mut self) -> Result<Expr, ConstraintError> {
+        let mut e = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            e = Expr::Or(Box::new(e), Box::new(self.and()?));
+        }
+        Ok(e)
+    }
+
+    fn and(&mut self) -> Result<Expr, ConstraintError> {
+        let mut e = self.unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.pos += 1;
+            e = Expr::And(Box::new(e), Box::new(self.unary()?));
+        }
+        Ok(e)
+    }
+
+    fn unary(&mut self) -> Result<Expr, ConstraintError> {
+        match self.peek().cloned() {
+            Some(Token::Not) => {
+                self.pos += 1;
+                Ok(Expr::Not(Box::new(self.unary()?)))
+            }
+            Some(Token::Open) => {
+                self.pos += 1;
+                let e = self.or()?;
+                if self.peek() != Some(&Token::Close) {
+                    return Err(error(self.at(), "expected ')'"));
+                }
+                self.pos += 1;
+                Ok(e)
+            }
+            Some(Token::Word(key)) => {
+                self.pos += 1;
+                let Some(Token::Op(op)) = self.peek().cloned() else {
+                    return Ok(Expr::Has(key));
+                };
+                self.pos += 1;
+                let value = match self.peek().cloned() {
+                    Some(Token::Word(w)) => PropertyValue::parse(&w),
+                    Some(Token::Quoted(q)) => PropertyValue::Str(q),
+                    _ => return Err(error(self.at(), "expected value")),
+                };
+                self.pos += 1;
+                Ok(Expr::Compare(key, op, value))
+            }
+            _ => Err(error(self.at(), "expected property")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PlacementConstraint;
+    use crate::solver::Resource;
+
+    fn resource(props: &[&str]) -> Resource {
+        let mut r = Resource::new(String::from("node1"));
+        for p in props {
+            r.add_property(p.to_string());
+        }
+        r
+    }
+
+    #[test]
+    fn constraint_eval_test() {
+        let c = PlacementConstraint::parse("color==red && !(zone==z1) && disk>=100").unwrap();
+        assert!(c.matches(&resource(&["color=red", "zone=z2", "disk=200"])));
+        assert!(!c.matches(&resource(&["color=red", "zone=z1", "disk=200"])));
+        assert!(!c.matches(&resource(&["color=red", "zone=z2", "disk=50"])));
+        // missing disk fails the comparison, missing zone passes the negation.
+        assert!(!c.matches(&resource(&["color=red"])));
+        assert!(c.matches(&resource(&["color=red", "disk=100"])));
+
+        let c = PlacementConstraint::parse("ssd || tier == 'gold' || tier < 2").unwrap();
+        assert!(c.matches(&resource(&["ssd"])));
+        assert!(c.matches(&resource(&["tier=gold"])));
+        assert!(c.matches(&resource(&["tier=1"])));
+        assert!(!c.matches(&resource(&["tier=3"])));
+        assert_eq!(c.to_string(), "ssd || tier == 'gold' || tier < 2");
+    }
+
+    #[test]
+    fn constraint_error_test() {
+        let err = PlacementConstraint::parse("color== && x").unwrap_err();
+        assert_eq!(err.to_string(), "expected value at column 9");
+        let err = PlacementConstraint::parse("(a || b").unwrap_err();
+        assert_eq!(err.to_string(), "expected ')' at column 8");
+        let err = PlacementConstraint::parse("a b").unwrap_err();
+        assert_eq!(err.to_string(), "unexpected token at column 3");
+    }
+}
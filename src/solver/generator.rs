@@ -0,0 +1,231 @@
+// seeded random boards for benchmarks and tests.
+
+use super::rng::Rng;
+use super::{
+    Board, DomainKind, DomainRelation, Entity, IDRelation, IDRelationKind, Pending, Priority,
+    PropertyRelation, PropertyRelationKind, Resource,
+};
+
+// shape of a generated board, see Board::generate. Resources have cpu and
+// mem capacity 100, zones, racks and an ssd tag on every other one.
+// Entities belong to one service per 20 entities.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeneratorConfig {
+    pub resources: usize,
+    pub entities: usize,
+    // relations per entity, mostly pair relations plus service spreads
+    // and a property affinity.
+    pub relation_density: f64,
+    // summed entity metrics over summed capacity, per metric. Well below 1
+    // every entity finds a resource with room.
+    pub tightness: f64,
+    // share of the relations that are soft.
+    pub soft_share: f64,
+    pub seed: u64,
+}
+
+impl Default for GeneratorConfig {
+    fn default() -> Self {
+        GeneratorConfig {
+            resources: 10,
+            entities: 100,
+            relation_density: 0.1,
+            tightness: 0.6,
+            soft_share: 0.3,
+            seed: 0,
+        }
+    }
+}
+
+const CAPACITY: i64 = 100;
+const METRICS: [&str; 2] = ["cpu", "mem"];
+
+impl GeneratorConfig {
+    fn services(&self) -> usize {
+        (self.entities / 20).max(1)
+    }
+
+    // a random entity with metrics averaging the share of capacity the
+    // tightness asks for.
+    fn entity(&self, rng: &mut Rng, id: String, service: usize) -> Entity {
+        let mut e = Entity::new(id);
+        e.add_property(format!("svc=s{}", service));
+        if rng.below(3) == 0 {
+            e.add_property(String::from("fast"));
+        }
+        let mean = self.tightness * (self.resources as i64 * CAPACITY) as f64
+            / self.entities.max(1) as f64;
+        let spread = ((2.0 * mean) as usize).max(1);
+        for metric in METRICS {
+            e.metrics
+                .insert(String::from(metric), 1 + rng.below(spread) as i64);
+        }
+        e.move_cost = 1 + rng.below(10) as i64;
+        e
+    }
+
+    fn priority(&self, rng: &mut Rng) -> Priority {
+        if rng.next_f64() < self.soft_share {
+            Priority::Soft(1 + rng.below(5) as i64)
+        } else {
+            Priority::Hard
+        }
+    }
+
+    // count pending entities of the board's services, to be placed with
+    // solve. Seeded apart from the board.
+    pub fn pending(&self, count: usize) -> Pending {
+        let mut rng = Rng::new(self.seed ^ 0x5eed);
+        let mut p = Pending::new();
+        for i in 0..count {
+            let service = rng.below(self.services());
+            p.add_entity(self.entity(&mut rng, format!("new{}", i), service));
+        }
+        p
+    }
+}
+
+impl Board {
+    // a random board of the config's shape, the same for the same config.
+    // Entities go to a random resource with room for them, or to the
+    // least loaded one; the relations are drawn without regard to the
+    // placement, so the board usually starts with some violations.
+    pub fn generate(config: &GeneratorConfig) -> Board {
+        let mut rng = Rng::new(config.seed);
+        let mut b = Board::new();
+        let zones = (config.resources / 10).max(1);
+        let racks = (config.resources / 4).max(1);
+        for i in 0..config.resources {
+            let mut r = Resource::new(format!("node{}", i));
+            r.add_property(format!("zone=z{}", i % zones));
+            if i % 2 == 0 {
+                r.add_property(String::from("ssd"));
+            }
+            r.fault_domain = Some(format!("/dc/rack{}", i % racks));
+            for metric in METRICS {
+                r.capacities.insert(String::from(metric), CAPACITY);
+            }
+            b.add_resource(r).expect("unique id");
+        }
+        if config.resources == 0 {
+            return b;
+        }
+
+        let mut used = vec![[0i64; 2]; config.resources];
+        for i in 0..config.entities {
+            let e = config.entity(&mut rng, format!("app{}", i), i % config.services());
+            let need = METRICS.map(|m| e.metrics[m]);
+            let fits = |u: &[i64; 2]| (0..2).all(|k| u[k] + need[k] <= CAPACITY);
+            let r = (0..8)
+                .map(|_| rng.below(config.resources))
+                .find(|r| fits(&used[*r]))
+                .unwrap_or_else(|| {
+                    (0..config.resources)
+                        .min_by_key(|r| (used[*r].iter().sum::<i64>(), *r))
+                        .expect("resources")
+                });
+            for k in 0..2 {
+                used[r][k] += need[k];
+            }
+            b.add_entity(format!("node{}", r), e).expect("unique id");
+        }
+        if config.entities == 0 {
+            return b;
+        }
+
+        let relations = (config.entities as f64 * config.relation_density).round() as usize;
+        for i in 0..relations {
+            let id1 = format!("app{}", rng.below(config.entities));
+            let (kind, id2) = match rng.below(10) {
+                0..=5 => (
+                    IDRelationKind::EEAntiAffinity,
+                    format!("app{}", rng.below(config.entities)),
+                ),
+                6 | 7 => (
+                    IDRelationKind::EEAffinity,
+                    format!("app{}", rng.below(config.entities)),
+                ),
+                8 => (
+                    IDRelationKind::ERAffinity,
+                    format!("node{}", rng.below(config.resources)),
+                ),
+                _ => (
+                    IDRelationKind::ERAntiAffinity,
+                    format!("node{}", rng.below(config.resources)),
+                ),
+            };
+            if id1 == id2 {
+                continue;
+            }
+            let priority = config.priority(&mut rng);
+            b.add_id_relation(IDRelation {
+                id: format!("rel{}", i),
+                kind,
+                id1,
+                id2,
+                priority,
+            })
+            .expect("valid relation");
+        }
+        // a relation_density share of the services is spread over racks.
+        for s in 0..config.services() {
+            if rng.next_f64() < config.relation_density {
+                let priority = config.priority(&mut rng);
+                b.add_domain_relation(DomainRelation {
+                    id: format!("spread-s{}", s),
+                    domain: DomainKind::Fault,
+                    entity_property: format!("svc=s{}", s),
+                    min_domains: 2,
+                    priority,
+                })
+                .expect("unique id");
+            }
+        }
+        if relations > 0 {
+            b.add_property_relation(PropertyRelation {
+                id: String::from("fast-ssd"),
+                kind: PropertyRelationKind::Affinity,
+                entity_property: String::from("fast"),
+                resource_property: String::from("ssd"),
+                priority: Priority::Soft(1),
+            })
+            .expect("unique id");
+        }
+        b
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::solver::{Board, GeneratorConfig, ViolationKind};
+
+    #[test]
+    fn generate_test() {
+        let config = GeneratorConfig {
+            resources: 20,
+            entities: 300,
+            seed: 3,
+            ..Default::default()
+        };
+        let b = Board::generate(&config);
+        assert_eq!(b.resources.len(), 20);
+        assert_eq!(b.entities.len(), 300);
+        assert!(!b.id_relations.is_empty());
+        assert!(b.validate().is_empty());
+        assert!(b
+            .check_all()
+            .iter()
+            .all(|v| v.kind != ViolationKind::Capacity));
+        assert_eq!(Board::generate(&config).to_json(), b.to_json());
+        let other = GeneratorConfig {
+            seed: 4,
+            ..config.clone()
+        };
+        assert_ne!(Board::generate(&other).to_json(), b.to_json());
+
+        let mut b = b;
+        let p = config.pending(5);
+        assert_eq!(p.entities.len(), 5);
+        b.solve(p).expect("fits");
+    }
+}
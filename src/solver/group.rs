@@ -0,0 +1,470 @@
+// entity groups: a service with a number of identical replicas.
+//
+// Replicas are ordinary entities with their own place in the board's
+// assignment, so every check and solver works on them unchanged. The
+// group holds their roles, and the board keeps the role-aware assignment
+// of every group, group id -> resource and role of each placed replica,
+// up to date as replicas are placed, moved and removed, see
+// Board::role_assignment.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use super::{
+    Board, Entity, IdMap, Pending, Placement, PlacementConstraint, Placements, Priority,
+    Properties, PropertyRelation, PropertyRelationKind, PropertyValue, SolverError,
+};
+
+// role of a replica within its group.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Role {
+    Primary,
+    Secondary,
+}
+
+impl Role {
+    pub fn name(self) -> &'static str {
+        match self {
+            Role::Primary => "primary",
+            Role::Secondary => "secondary",
+        }
+    }
+}
+
+// replicas are entities "<id>-<n>" sharing the group's properties and
+// constraint, plus a "group" property holding the group id. Replica
+// primary is the primary, the others are secondaries; each takes the
+// metrics of its role. The replicas are kept apart from each other by an
+// EEAntiAffinity property relation with id "group:<id>".
+#[derive(Debug, Clone)]
+pub struct EntityGroup {
+    pub id: String,
+    pub replicas: usize,
+    // number of the primary replica, 0 at first.
+    pub primary: usize,
+    pub properties: Properties,
+    // metrics of the primary, and of the secondaries unless set apart.
+    pub metrics: HashMap<String, i64>,
+    pub secondary_metrics: Option<HashMap<String, i64>>,
+    pub move_cost: i64,
+    pub constraint: Option<PlacementConstraint>,
+}
+
+impl EntityGroup {
+    pub fn new(id: String, replicas: usize) -> EntityGroup {
+        EntityGroup {
+            id,
+            replicas,
+            primary: 0,
+            properties: Properties::new(),
+            metrics: HashMap::new(),
+            secondary_metrics: None,
+            move_cost: 0,
+            constraint: None,
+        }
+    }
+
+    pub fn replica_id(&self, n: usize) -> String {
+        format!("{}-{}", self.id, n)
+    }
+
+    pub fn relation_id(&self) -> String {
+        format!("group:{}", self.id)
+    }
+
+    // the number of the replica with the id.
+    pub fn replica_index(&self, replica_id: &str) -> Option<usize> {
+        let n: usize = replica_id
+            .strip_prefix(self.id.as_str())?
+            .strip_prefix('-')?
+            .parse()
+            .ok()?;
+        (n < self.replicas && self.replica_id(n) == replica_id).then_some(n)
+    }
+
+    pub fn role(&self, n: usize) -> Role {
+        if n == self.primary {
+            Role::Primary
+        } else {
+            Role::Secondary
+        }
+    }
+
+    pub fn role_metrics(&self, role: Role) -> &HashMap<String, i64> {
+        match role {
+            Role::Primary => &self.metrics,
+            Role::Secondary => self.secondary_metrics.as_ref().unwrap_or(&self.metrics),
+        }
+    }
+
+    pub fn replica(&self, n: usize) -> Entity {
+        let role = self.role(n);
+        let mut e = Entity::new(self.replica_id(n));
+        e.properties = self.properties.clone();
+        e.set_property(String::from("group"), PropertyValue::parse(&self.id));
+        e.metrics = self.role_metrics(role).clone();
+        e.move_cost = self.move_cost;
+        e.constraint = self.constraint.clone();
+        e
+    }
+
+    // the relation keeping the replicas on distinct resources.
+    pub fn relation(&self) -> PropertyRelation {
+        PropertyRelation {
+            id: self.relation_id(),
+            kind: PropertyRelationKind::EEAntiAffinity,
+            entity_property: format!("group={}", self.id),
+            resource_property: String::new(),
+            priority: Priority::Hard,
+        }
+    }
+}
+
+// a group with fewer replicas placed than requested.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupShortfall {
+    pub group_id: String,
+    pub placed: usize,
+    pub replicas: usize,
+}
+
+impl fmt::Display for GroupShortfall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "group {} has only {} of {} replicas placed",
+            self.group_id, self.placed, self.replicas
+        )
+    }
+}
+
+// the role-aware assignment of the groups, see Board::role_assignment.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RoleAssignment {
+    // group id -> resource and role of its placed replicas.
+    placements: HashMap<String, Vec<(String, Role)>>,
+    // replica id -> group id.
+    group_of: HashMap<String, String>,
+}
+
+impl RoleAssignment {
+    pub(crate) fn new(groups: &IdMap<EntityGroup>, assignment: Placements) -> RoleAssignment {
+        let mut roles = RoleAssignment::default();
+        for g in groups.values() {
+            roles.group_changed(None, Some(g), assignment);
+        }
+        roles
+    }
+
+    // the group the entity is a replica of.
+    pub(crate) fn group_of(&self, entity_id: &str) -> Option<&str> {
+        self.group_of.get(entity_id).map(String::as_str)
+    }
+
+    pub(crate) fn group_changed(
+        &mut self,
+        old: Option<&EntityGroup>,
+        new: Option<&EntityGroup>,
+        assignment: Placements,
+    ) {
+        if let Some(g) = old {
+            for n in 0..g.replicas {
+                self.group_of.remove(&g.replica_id(n));
+            }
+            self.placements.remove(&g.id);
+        }
+        if let Some(g) = new {
+            for n in 0..g.replicas {
+                self.group_of.insert(g.replica_id(n), g.id.clone());
+            }
+            self.replica_moved(g, assignment);
+        }
+    }
+
+    // after a replica of the group was placed, moved or removed.
+    pub(crate) fn replica_moved(&mut self, g: &EntityGroup, assignment: Placements) {
+        let mut placed: Vec<(String, Role)> = (0..g.replicas)
+            .filter_map(|n| Some((assignment.get(&g.replica_id(n))?.clone(), g.role(n))))
+            .collect();
+        placed.sort_by(|a, b| (a.1, &a.0).cmp(&(b.1, &b.0)));
+        self.placements.insert(g.id.clone(), placed);
+    }
+}
+
+impl Board {
+    // registers the group and places as many of its replicas as fit, one
+    // at a time with solve, the primary first. Replicas that can't be
+    // placed are not added, see check_groups; when that is the primary the
+    // group has none until one is promoted.
+    pub fn add_group(&mut self, group: EntityGroup) -> Result<Placement, SolverError> {
+        if self.groups.contains_key(&group.id) {
+            return Err(SolverError::DuplicateId(group.id));
+        }
+        let relation = group.relation();
+        if self.property_relations.contains_key(&relation.id) {
+            return Err(SolverError::DuplicateId(relation.id));
+        }
+        if let Some(id) = (0..group.replicas)
+            .map(|n| group.replica_id(n))
+            .find(|id| self.entities.contains_key(id))
+        {
+            return Err(SolverError::DuplicateId(id));
+        }
+
+        self.touch_all();
+        let relation_id = relation.id.clone();
+        self.set_property_relation(&relation_id, Some(relation));
+        let mut placement = Placement::default();
+        for n in 0..group.replicas {
+            let mut pending = Pending::new();
+            pending.add_entity(group.replica(n));
+            // secondaries may fit where the primary did not, so every
+            // replica is tried.
+            if let Ok(p) = self.solve(pending) {
+                placement.assignment.extend(p.assignment);
+            }
+        }
+        let id = group.id.clone();
+        self.set_group(&id, Some(group));
+        Ok(placement)
+    }
+
+    // number of replicas of the group on the board.
+    pub fn placed_replicas(&self, group_id: &str) -> usize {
+        self.group_assignment(group_id).len()
+    }
+
+    // group id -> resource and role of every placed replica of the group,
+    // the primary first, then by resource id.
+    pub fn role_assignment(&self) -> &HashMap<String, Vec<(String, Role)>> {
+        &self.roles.placements
+    }
+
+    // the resources and roles of a group, by group id, or of an entity
+    // outside groups, which is its own primary.
+    pub fn assignment_of(&self, id: &str) -> Vec<(String, Role)> {
+        match self.roles.placements.get(id) {
+            Some(placed) => placed.clone(),
+            None => self
+                .assignment()
+                .get(id)
+                .map(|r_id| vec![(r_id.clone(), Role::Primary)])
+                .unwrap_or_default(),
+        }
+    }
+
+    // role_assignment of the group.
+    pub fn group_assignment(&self, group_id: &str) -> &[(String, Role)] {
+        self.roles
+            .placements
+            .get(group_id)
+            .map_or(&[], Vec::as_slice)
+    }
+
+    // group_assignment of every group, by group id.
+    pub fn group_assignments(&self) -> BTreeMap<String, Vec<(String, Role)>> {
+        self.roles
+            .placements
+            .iter()
+            .map(|(id, placed)| (id.clone(), placed.clone()))
+            .collect()
+    }
+
+    // makes the replica the primary of its group and the current primary a
+    // secondary, swapping their metrics. Constraints are not checked.
+    pub fn promote(&mut self, group_id: &str, replica_id: &str) -> Result<(), SolverError> {
+        let g = self
+            .groups
+            .get(group_id)
+            .ok_or_else(|| SolverError::GroupNotFound(group_id.to_string()))?;
+        let n = g
+            .replica_index(replica_id)
+            .filter(|_| self.entities.contains_key(replica_id))
+            .ok_or_else(|| SolverError::EntityNotFound(replica_id.to_string()))?;
+        if n == g.primary {
+            return Ok(());
+        }
+        let mut g = g.clone();
+        let demoted = g.replica_id(g.primary);
+        g.primary = n;
+        let (primary, secondary) = (
+            g.role_metrics(Role::Primary).clone(),
+            g.role_metrics(Role::Secondary).clone(),
+        );
+        self.set_group(group_id, Some(g));
+        // the loads follow the roles.
+        for (id, metrics) in [(demoted.as_str(), secondary), (replica_id, primary)] {
+            if let Some(e) = self.entities.get(id) {
+                let mut e = e.clone();
+                e.metrics = metrics;
+                self.update_entity(e)?;
+            }
+        }
+        Ok(())
+    }
+
+    // groups missing replicas, sorted by group id.
+    pub fn check_groups(&self) -> Vec<GroupShortfall> {
+        let mut ids: Vec<&String> = self.groups.keys().collect();
+        ids.sort();
+        ids.into_iter()
+            .filter_map(|id| {
+                let g = &self.groups[id];
+                let placed = self.placed_replicas(id);
+                (placed < g.replicas).then(|| GroupShortfall {
+                    group_id: id.clone(),
+                    placed,
+                    replicas: g.replicas,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::solver::{Board, EntityGroup, GroupShortfall, Resource, Role, SolverError};
+
+    #[test]
+    fn add_group_test() {
+        let mut b = Board::new();
+        for id in ["node1", "node2", "node3"] {
+            let mut r = Resource::new(String::from(id));
+            r.capacities.insert(String::from("cpu"), 4);
+            b.add_resource(r).expect("added");
+        }
+        let mut g = EntityGroup::new(String::from("web"), 5);
+        g.metrics.insert(String::from("cpu"), 2);
+        g.properties.insert(String::from("tier"), "front".into());
+
+        let placement = b.add_group(g).expect("added");
+        // one replica per node.
+        assert_eq!(placement.assignment.len(), 3);
+        assert_eq!(b.placed_replicas("web"), 3);
+        assert!(b.entities["web-0"].has_property("tier=front"));
+        assert!(b.check_all().is_empty());
+        let shortfalls = b.check_groups();
+        assert_eq!(
+            shortfalls,
+            vec![GroupShortfall {
+                group_id: String::from("web"),
+                placed: 3,
+                replicas: 5,
+            }]
+        );
+        assert_eq!(
+            shortfalls[0].to_string(),
+            "group web has only 3 of 5 replicas placed"
+        );
+
+        assert!(b
+            .add_group(EntityGroup::new(String::from("web"), 1))
+            .is_err());
+    }
+
+    #[test]
+    fn group_roles_test() {
+        let mut b = Board::new();
+        for id in ["node1", "node2", "node3"] {
+            let mut r = Resource::new(String::from(id));
+            r.capacities.insert(String::from("cpu"), 4);
+            b.add_resource(r).expect("added");
+        }
+        let mut g = EntityGroup::new(String::from("db"), 3);
+        g.metrics.insert(String::from("cpu"), 3);
+        g.secondary_metrics = Some(HashMap::from([(String::from("cpu"), 1)]));
+        b.add_group(g).expect("added");
+
+        let roles = b.group_assignment("db");
+        assert_eq!(roles.len(), 3);
+        assert_eq!(roles[0].1, Role::Primary);
+        assert!(roles[1..].iter().all(|(_, role)| *role == Role::Secondary));
+        assert_eq!(b.assignment_of("db"), roles);
+        assert_eq!(
+            b.assignment_of("db-1"),
+            vec![(roles[1].0.clone(), Role::Primary)]
+        );
+        let total: i64 = ["node1", "node2", "node3"]
+            .iter()
+            .map(|r| b.resource_load(r)["cpu"])
+            .sum();
+        assert_eq!(total, 5);
+
+        // failover: the primary load follows the role.
+        let node = b.assignment()["db-2"].clone();
+        b.promote("db", "db-2").expect("promoted");
+        assert_eq!(b.group_assignment("db")[0], (node.clone(), Role::Primary));
+        assert_eq!(b.groups["db"].primary, 2);
+        assert!(!b.entities["db-2"].properties.contains_key("role"));
+        assert_eq!(b.resource_load(&node)["cpu"], 3);
+        assert_eq!(b.entities["db-0"].metrics["cpu"], 1);
+        assert!(b.validate().is_empty());
+        assert_eq!(
+            b.promote("db", "web-0"),
+            Err(SolverError::EntityNotFound(String::from("web-0")))
+        );
+        assert_eq!(
+            b.promote("web", "web-0"),
+            Err(SolverError::GroupNotFound(String::from("web")))
+        );
+        assert_eq!(b.group_assignments()["db"], b.group_assignment("db"));
+    }
+
+    #[test]
+    fn role_assignment_test() {
+        let mut b = Board::new();
+        for id in ["node1", "node2", "node3"] {
+            b.add_resource(Resource::new(String::from(id)))
+                .expect("added");
+        }
+        b.add_group(EntityGroup::new(String::from("db"), 2))
+            .expect("added");
+        let before = b.role_assignment()["db"].clone();
+        assert_eq!(before.len(), 2);
+
+        // the role-aware assignment follows moves, removals and rollbacks.
+        let free = ["node1", "node2", "node3"]
+            .into_iter()
+            .find(|r| before.iter().all(|(used, _)| used != r))
+            .expect("a free node");
+        {
+            let mut txn = b.begin();
+            txn.move_entity("db-0", free).expect("moved");
+            assert_eq!(
+                txn.group_assignment("db")[0],
+                (free.to_string(), Role::Primary)
+            );
+            txn.remove_entity("db-1").expect("removed");
+            assert_eq!(txn.group_assignment("db").len(), 1);
+        }
+        assert_eq!(b.role_assignment()["db"], before);
+
+        b.promote("db", "db-1").expect("promoted");
+        let json = b.to_json();
+        let loaded = Board::from_json(&json).expect("loads");
+        assert_eq!(loaded.groups["db"].primary, 1);
+        assert_eq!(loaded.role_assignment(), b.role_assignment());
+    }
+
+    #[test]
+    fn group_secondaries_fit_test() {
+        let mut b = Board::new();
+        for id in ["node1", "node2"] {
+            let mut r = Resource::new(String::from(id));
+            r.capacities.insert(String::from("cpu"), 4);
+            b.add_resource(r).expect("added");
+        }
+        // the primary fits nowhere, the secondaries still do.
+        let mut g = EntityGroup::new(String::from("db"), 3);
+        g.metrics.insert(String::from("cpu"), 5);
+        g.secondary_metrics = Some(HashMap::from([(String::from("cpu"), 1)]));
+        let placement = b.add_group(g).expect("added");
+        assert_eq!(placement.assignment.len(), 2);
+        assert!(!b.entities.contains_key("db-0"));
+        let roles = b.group_assignment("db");
+        assert!(roles.iter().all(|(_, role)| *role == Role::Secondary));
+        assert_eq!(b.check_groups()[0].placed, 2);
+    }
+}
@@ -0,0 +1,210 @@
+// failure impact: what losing a set of resources at once would cost.
+
+use std::collections::BTreeMap;
+
+use super::{Board, GroupShortfall, MovePlan, ResourceState, SolveError, SolverError};
+
+// displaced load of one metric against the room left on the survivors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetricImpact {
+    pub metric: String,
+    // summed metric of the displaced entities.
+    pub displaced: i64,
+    // usable capacity minus load, summed over the surviving active
+    // resources declaring a capacity for the metric.
+    pub spare: i64,
+}
+
+// see Board::failure_impact.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImpactReport {
+    // the failed resources, sorted.
+    pub resources: Vec<String>,
+    // entities that were on them, sorted.
+    pub displaced: Vec<String>,
+    // groups below their replica count once the displaced entities are
+    // gone, sorted by group id. Groups already short before count only if
+    // they lose replicas.
+    pub shortfalls: Vec<GroupShortfall>,
+    // per metric of the displaced entities, sorted by metric.
+    pub metrics: Vec<MetricImpact>,
+    // moves placing the displaced entities on the survivors, or why they
+    // can't be.
+    pub recovery: Result<MovePlan, SolveError>,
+}
+
+impl ImpactReport {
+    // whether the survivors can take every displaced entity with all hard
+    // relations and capacities satisfied.
+    pub fn can_absorb(&self) -> bool {
+        self.recovery.is_ok()
+    }
+}
+
+impl Board {
+    // simulates the resources failing together on a snapshot of the board.
+    // The recovery is searched jointly, like draining a resource; pinned
+    // entities on the failed resources have no recovery. The board is not
+    // modified.
+    pub fn failure_impact(&self, resource_ids: &[String]) -> Result<ImpactReport, SolverError> {
+        if let Some(id) = resource_ids
+            .iter()
+            .find(|id| !self.resources.contains_key(id))
+        {
+            return Err(SolverError::ResourceNotFound(id.clone()));
+        }
+        let mut resources = resource_ids.to_vec();
+        resources.sort();
+        resources.dedup();
+
+        let mut displaced: Vec<String> = self
+            .assignment()
+            .iter()
+            .filter(|(_, r)| resources.contains(r))
+            .map(|(e, _)| e.clone())
+            .collect();
+        displaced.sort();
+
+        let mut lost = self.groups_losing(&displaced);
+        lost.sort();
+        let shortfalls = lost
+            .into_iter()
+            .filter_map(|id| {
+                let g = &self.groups[id];
+                let placed = self.placed_replicas(id)
+                    - (0..g.replicas)
+                        .filter(|n| displaced.contains(&g.replica_id(*n)))
+                        .count();
+                (placed < g.replicas).then(|| GroupShortfall {
+                    group_id: id.clone(),
+                    placed,
+                    replicas: g.replicas,
+                })
+            })
+            .collect();
+
+        let mut metrics: BTreeMap<String, MetricImpact> = BTreeMap::new();
+        for e in &displaced {
+            for (metric, v) in &self.entities[e].metrics {
+                metrics
+                    .entry(metric.clone())
+                    .or_insert_with(|| MetricImpact {
+                        metric: metric.clone(),
+                        displaced: 0,
+                        spare: 0,
+                    })
+                    .displaced += v;
+            }
+        }
+        for r in self.resources.values() {
+            if resources.contains(&r.id) || !r.state.accepts_entities() {
+                continue;
+            }
+            for (metric, free) in self.remaining_capacity(&r.id) {
+                if let Some(m) = metrics.get_mut(&metric) {
+                    m.spare += free.max(0);
+                }
+            }
+        }
+
+        let mut board = self.snapshot();
+        for r in &resources {
+            board.resources.get_mut(r).expect("resource").state = ResourceState::Removed;
+        }
+        let recovery = board.evacuation_plan(&resources);
+
+        Ok(ImpactReport {
+            resources,
+            displaced,
+            shortfalls,
+            metrics: metrics.into_values().collect(),
+            recovery,
+        })
+    }
+
+    // ids of the groups with a replica among the entities.
+    fn groups_losing(&self, entity_ids: &[String]) -> Vec<&String> {
+        self.groups
+            .values()
+            .filter(|g| (0..g.replicas).any(|n| entity_ids.contains(&g.replica_id(n))))
+            .map(|g| &g.id)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::solver::{
+        Board, BoardBuilder, EntityBuilder, EntityGroup, GroupShortfall, MetricImpact,
+        ResourceBuilder, SolveError, SolverError,
+    };
+
+    fn board() -> Board {
+        let mut b = BoardBuilder::new()
+            .resource(ResourceBuilder::new("node1").capacity("cpu", 4))
+            .resource(ResourceBuilder::new("node2").capacity("cpu", 4))
+            .resource(ResourceBuilder::new("node3").capacity("cpu", 4))
+            .entity("node1", EntityBuilder::new("app").metric("cpu", 1))
+            .build()
+            .expect("builds");
+        let mut g = EntityGroup::new(String::from("web"), 3);
+        g.metrics.insert(String::from("cpu"), 2);
+        b.add_group(g).expect("added");
+        b
+    }
+
+    #[test]
+    fn failure_impact_test() {
+        let b = board();
+        // the replicas are spread one per resource.
+        let nodes = [b.assignment()["web-0"].clone()];
+        let report = b.failure_impact(&nodes).expect("known");
+        assert_eq!(report.resources, nodes);
+        assert!(report.displaced.contains(&String::from("web-0")));
+        assert_eq!(
+            report.shortfalls,
+            [GroupShortfall {
+                group_id: String::from("web"),
+                placed: 2,
+                replicas: 3
+            }]
+        );
+        // the other replicas rule out both survivors for web-0.
+        assert!(!report.can_absorb());
+        assert!(matches!(
+            &report.recovery,
+            Err(SolveError::NoCandidates(ex)) if ex[0].entity_id == "web-0"
+        ));
+
+        assert_eq!(
+            b.failure_impact(&[String::from("node9")]),
+            Err(SolverError::ResourceNotFound(String::from("node9")))
+        );
+    }
+
+    #[test]
+    fn absorb_test() {
+        let mut b = board();
+        b.add_resource(ResourceBuilder::new("node4").capacity("cpu", 4).build())
+            .expect("added");
+        let nodes = [b.assignment()["app"].clone()];
+        let report = b.failure_impact(&nodes).expect("known");
+        assert!(report.can_absorb());
+        let plan = report.recovery.as_ref().expect("absorbed");
+        assert_eq!(plan.len(), report.displaced.len());
+        assert!(plan
+            .moves
+            .iter()
+            .all(|m| m.from == nodes[0] && m.to != nodes[0]));
+        assert_eq!(
+            report.metrics,
+            [MetricImpact {
+                metric: String::from("cpu"),
+                displaced: 3,
+                // node4 and the two others with a replica each.
+                spare: 8
+            }]
+        );
+        assert_eq!(b.assignment()["app"], nodes[0]);
+    }
+}
@@ -0,0 +1,273 @@
+// incremental violation checking: mutations through the board api record
+// the entities they affect, and the next check only re-evaluates those and
+// the entities sharing a relation with them.
+
+use std::collections::{HashMap, HashSet};
+
+use super::index::RelationIndex;
+use super::{Board, IDRelationKind, Violation, ViolationReport};
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ChangeTracker {
+    dirty: HashSet<String>,
+    // everything needs a re-check, like after a relation change.
+    all: bool,
+    // relation violations per entity as of the last check.
+    cache: HashMap<String, Vec<Violation>>,
+}
+
+impl Board {
+    // starts recording changes made through the board api for
+    // check_violation_incremental. Direct writes to the public maps are not
+    // seen; call touch_all after them.
+    pub fn enable_change_tracking(&mut self) {
+        self.tracker = Some(ChangeTracker {
+            all: true,
+            ..Default::default()
+        });
+    }
+
+    pub fn disable_change_tracking(&mut self) {
+        self.tracker = None;
+    }
+
+    // marks the entity for re-evaluation.
+    pub fn touch(&mut self, entity_id: &str) {
+        if let Some(t) = &mut self.tracker {
+            if !t.all {
+                t.dirty.insert(entity_id.to_string());
+            }
+        }
+    }
+
+    // marks the whole board for re-evaluation.
+    pub fn touch_all(&mut self) {
+        if let Some(t) = &mut self.tracker {
+            t.all = true;
+            t.dirty.clear();
+        }
+    }
+
+    // relation violations of the entity at its current resource, as
+    // check_violation reports them. Pair relations report the other side
+    // under the other entity.
+    pub fn check_violation_for(&self, entity_id: &str) -> ViolationReport {
+        let Some(r_id) = self.assignment().get(entity_id) else {
+            return ViolationReport::default();
+        };
+        let index = RelationIndex::new(self);
+        let mut entries = self.entity_violations_at(&index, entity_id, r_id, &self.assignment);
+        sort_entries(&mut entries);
+        ViolationReport { entries }
+    }
+
+    // same report as check_violation, re-evaluating only the entities
+    // changed since the last call and the entities related to them. Turns
+    // on change tracking if it is off, doing a full check.
+    pub fn check_violation_incremental(&mut self) -> ViolationReport {
+        let mut tracker = self.tracker.take().unwrap_or(ChangeTracker {
+            all: true,
+            ..Default::default()
+        });
+        let index = RelationIndex::new(self);
+
+        let affected: Vec<String> = if tracker.all {
+            tracker.cache.clear();
+            self.assignment().keys().cloned().collect()
+        } else {
+            let mut affected: HashSet<String> = HashSet::new();
+            for id in tracker.dirty.drain() {
+                for related in self.related_entities(&index, &id) {
+                    affected.insert(related.to_string());
+                }
+                affected.insert(id);
+            }
+            affected.into_iter().collect()
+        };
+        for id in affected {
+            tracker.cache.remove(&id);
+            if let Some(r_id) = self.assignment().get(&id) {
+                let entries = self.entity_violations_at(&index, &id, r_id, &self.assignment);
+                if !entries.is_empty() {
+                    tracker.cache.insert(id, entries);
+                }
+            }
+        }
+        tracker.all = false;
+
+        let mut entries: Vec<Violation> = tracker.cache.values().flatten().cloned().collect();
+        sort_entries(&mut entries);
+        self.tracker = Some(tracker);
+        ViolationReport { entries }
+    }
+
+    // entities whose violations can change when the entity moves.
+    pub(crate) fn related_entities<'a>(
+        &'a self,
+        index: &RelationIndex<'a>,
+        entity_id: &str,
+    ) -> Vec<&'a str> {
+        let mut related = Vec::new();
+        for rel in index.id_relations(entity_id) {
+            if matches!(
+                rel.kind,
+                IDRelationKind::EEAffinity | IDRelationKind::EEAntiAffinity
+            ) {
+                related.push(if rel.id1 == entity_id {
+                    rel.id2.as_str()
+                } else {
+                    rel.id1.as_str()
+                });
+            }
+        }
+        for rel in index.property_relations(entity_id) {
+            if rel.kind.is_ee() {
+                related.extend(index.members(&rel.entity_property));
+            }
+        }
+        for rel in index.domain_relations(entity_id) {
+            related.extend(index.members(&rel.entity_property));
+        }
+        for chain in index.chains(entity_id) {
+            related.extend(index.members(&chain.entity_property));
+            related.extend(chain.parent.as_deref());
+        }
+        related
+    }
+
+    // marks the entities that have any relation to the entity, for changes
+    // that are about to remove it or its relations.
+    pub(crate) fn touch_related(&mut self, entity_id: &str) {
+        if self.tracker.is_none() {
+            return;
+        }
+        let Some(e) = self.entities.get(entity_id) else {
+            return;
+        };
+        // group relations span many entities.
+        let in_group = self
+            .property_relations
+            .values()
+            .any(|rel| rel.kind.is_ee() && e.has_property(&rel.entity_property))
+            || self
+                .domain_relations
+                .values()
+                .any(|rel| e.has_property(&rel.entity_property))
+            || self.chains.values().any(|chain| {
+                e.has_property(&chain.entity_property) || chain.parent.as_deref() == Some(entity_id)
+            });
+        if in_group {
+            self.touch_all();
+            return;
+        }
+        let partners: Vec<String> = self
+            .id_relations
+            .values()
+            .filter(|rel| rel.id1 == entity_id || rel.id2 == entity_id)
+            .flat_map(|rel| [rel.id1.clone(), rel.id2.clone()])
+            .collect();
+        for id in partners {
+            self.touch(&id);
+        }
+        self.touch(entity_id);
+    }
+
+    // marks the entities a relation applies to, before it is removed or
+    // replaced.
+    pub(crate) fn touch_relation(&mut self, relation_id: &str) {
+        if self.tracker.is_none() {
+            return;
+        }
+        let mut touched = Vec::new();
+        // the id may name relations of several types.
+        if let Some(rel) = self.id_relations.get(relation_id) {
+            touched.extend([rel.id1.clone(), rel.id2.clone()]);
+        }
+        if let Some(rel) = self.id_property_relations.get(relation_id) {
+            touched.push(rel.entity_id.clone());
+        }
+        if self.property_relations.contains_key(relation_id)
+            || self.domain_relations.contains_key(relation_id)
+            || self.chains.contains_key(relation_id)
+        {
+            self.touch_all();
+        }
+        for id in touched {
+            self.touch(&id);
+        }
+    }
+}
+
+fn sort_entries(entries: &mut [Violation]) {
+    entries.sort_by(|a, b| (&a.relation_id, &a.entity_id).cmp(&(&b.relation_id, &b.entity_id)));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::solver::{
+        Board, Entity, IDRelation, IDRelationKind, Priority, PropertyRelation,
+        PropertyRelationKind, Resource,
+    };
+
+    fn board() -> Board {
+        let mut b = Board::new();
+        for id in ["node1", "node2", "node3"] {
+            b.add_resource(Resource::new(String::from(id)))
+                .expect("added");
+        }
+        for (id, r) in [
+            ("a", "node1"),
+            ("b", "node1"),
+            ("c", "node2"),
+            ("d", "node3"),
+        ] {
+            let mut e = Entity::new(String::from(id));
+            if id != "a" {
+                e.add_property(String::from("db"));
+            }
+            b.add_entity(String::from(r), e).expect("added");
+        }
+        b.add_id_relation(IDRelation {
+            id: String::from("ab"),
+            kind: IDRelationKind::EEAntiAffinity,
+            id1: String::from("a"),
+            id2: String::from("b"),
+            priority: Priority::Hard,
+        })
+        .expect("ok");
+        b.add_property_relation(PropertyRelation {
+            id: String::from("spread"),
+            kind: PropertyRelationKind::EEAntiAffinity,
+            entity_property: String::from("db"),
+            resource_property: String::new(),
+            priority: Priority::Hard,
+        })
+        .expect("ok");
+        b
+    }
+
+    #[test]
+    fn incremental_check_test() {
+        let mut b = board();
+        b.enable_change_tracking();
+        assert_eq!(b.check_violation_incremental(), b.check_violation());
+        assert_eq!(b.check_violation_for("a").len(), 1);
+
+        // each step matches a full check.
+        let steps: [(&str, &str); 4] = [
+            ("b", "node2"),
+            ("c", "node3"),
+            ("a", "node3"),
+            ("b", "node1"),
+        ];
+        for (e, r) in steps {
+            b.move_entity(e, r).expect("moved");
+            assert_eq!(b.check_violation_incremental(), b.check_violation());
+        }
+        b.remove_entity("d").expect("removed");
+        assert_eq!(b.check_violation_incremental(), b.check_violation());
+        b.remove_relation("ab").expect("removed");
+        assert_eq!(b.check_violation_incremental(), b.check_violation());
+        assert!(b.check_violation_for("ghost").is_empty());
+    }
+}
@@ -0,0 +1,797 @@
+// lookup tables over the board, so that each entity only visits the
+// relations that can apply to it instead of scanning every relation of the
+// board. The board keeps one BoardIndex, built on first use and updated by
+// the set_* functions as objects are added, replaced and removed; a check
+// or search reads it through a RelationIndex.
+
+use std::collections::{HashMap, HashSet};
+
+use super::intern::{DomainId, EntityId, Handle, IdMap, Interner, ResourceId, SpecId};
+use super::property::parse_spec;
+use super::{
+    AffinityChain, Board, DomainKind, DomainRelation, Entity, IDPropertyRelation, IDRelation,
+    IDRelationKind, Placements, PropertyRelation, PropertyRelationKind, Resource,
+};
+
+// relations are kept by id and looked up in the board when read, so their
+// other fields may change in place. The entity properties and relation ids
+// they are indexed by may not, see Board::refresh_index.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct BoardIndex {
+    // by entity id, whether or not the entity exists.
+    related: HashMap<String, Related>,
+    // entity properties of the property and domain relations and chains.
+    spec_ids: Interner<SpecId>,
+    specs: Vec<Spec>,
+    // fault and upgrade domain of each resource, by resource handle.
+    domain_names: Interner<DomainId>,
+    domains: Vec<[DomainId; 2]>,
+    // property key -> resources that have it set.
+    resources_by_key: HashMap<String, Vec<String>>,
+    // every resource, sorted.
+    resource_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Related {
+    // id relations naming the entity, ER ones on their entity side.
+    id_relations: Vec<String>,
+    id_property_relations: Vec<String>,
+    // chains the entity is the parent of.
+    parent_of: Vec<String>,
+    // entity properties the entity has.
+    specs: Vec<SpecId>,
+}
+
+impl Related {
+    fn is_empty(&self) -> bool {
+        self.id_relations.is_empty()
+            && self.id_property_relations.is_empty()
+            && self.parent_of.is_empty()
+            && self.specs.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Spec {
+    // entities having the property, sorted by id.
+    members: Vec<(String, EntityId)>,
+    // relations and chains of the property.
+    property_relations: Vec<String>,
+    domain_relations: Vec<String>,
+    chains: Vec<String>,
+}
+
+impl Spec {
+    fn is_unused(&self) -> bool {
+        self.property_relations.is_empty()
+            && self.domain_relations.is_empty()
+            && self.chains.is_empty()
+    }
+}
+
+// the entities an id relation is indexed under.
+fn ends(rel: &IDRelation) -> impl Iterator<Item = &str> {
+    let ee = matches!(
+        rel.kind,
+        IDRelationKind::EEAffinity | IDRelationKind::EEAntiAffinity
+    ) && rel.id2 != rel.id1;
+    std::iter::once(rel.id1.as_str()).chain(ee.then_some(rel.id2.as_str()))
+}
+
+fn remove_id(ids: &mut Vec<String>, id: &str) {
+    ids.retain(|x| x != id);
+}
+
+impl BoardIndex {
+    pub(crate) fn new(board: &Board) -> BoardIndex {
+        let mut index = BoardIndex::default();
+        for (handle, r) in board.resources.handles() {
+            index.resource_changed(&r.id, handle, None, Some(r));
+        }
+        for (handle, e) in board.entities.handles() {
+            index.entity_changed(&e.id, handle, None, Some(e));
+        }
+        for rel in board.id_relations.values() {
+            index.id_relation_changed(None, Some(rel));
+        }
+        for rel in board.id_property_relations.values() {
+            index.id_property_relation_changed(None, Some(rel));
+        }
+        for rel in board.property_relations.values() {
+            index.property_relation_changed(&board.entities, None, Some(rel));
+        }
+        for rel in board.domain_relations.values() {
+            index.domain_relation_changed(&board.entities, None, Some(rel));
+        }
+        for chain in board.chains.values() {
+            index.chain_changed(&board.entities, None, Some(chain));
+        }
+        index
+    }
+
+    // the changes below are given the replaced and the new object.
+
+    pub(crate) fn resource_changed(
+        &mut self,
+        id: &str,
+        handle: ResourceId,
+        old: Option<&Resource>,
+        new: Option<&Resource>,
+    ) {
+        if let Some(r) = old {
+            for key in r.properties.keys() {
+                if let Some(ids) = self.resources_by_key.get_mut(key) {
+                    remove_id(ids, id);
+                    if ids.is_empty() {
+                        self.resources_by_key.remove(key);
+                    }
+                }
+            }
+        }
+        let Some(r) = new else {
+            if let Ok(i) = self.resource_ids.binary_search_by(|x| x.as_str().cmp(id)) {
+                self.resource_ids.remove(i);
+            }
+            return;
+        };
+        let domains = [DomainKind::Fault, DomainKind::Upgrade]
+            .map(|kind| self.domain_names.intern(r.domain(kind)));
+        if handle.index() >= self.domains.len() {
+            // removed resources in between are never read.
+            self.domains.resize(handle.index() + 1, domains);
+        }
+        self.domains[handle.index()] = domains;
+        for key in r.properties.keys() {
+            self.resources_by_key
+                .entry(key.clone())
+                .or_default()
+                .push(id.to_string());
+        }
+        if let Err(i) = self.resource_ids.binary_search_by(|x| x.as_str().cmp(id)) {
+            self.resource_ids.insert(i, id.to_string());
+        }
+    }
+
+    pub(crate) fn entity_changed(
+        &mut self,
+        id: &str,
+        handle: EntityId,
+        old: Option<&Entity>,
+        new: Option<&Entity>,
+    ) {
+        if old.is_some() {
+            if let Some(related) = self.related.get_mut(id) {
+                for spec in std::mem::take(&mut related.specs) {
+                    let members = &mut self.specs[spec.index()].members;
+                    if let Ok(i) = members.binary_search_by(|(m, _)| m.as_str().cmp(id)) {
+                        members.remove(i);
+                    }
+                }
+            }
+        }
+        let Some(e) = new else {
+            self.prune(id);
+            return;
+        };
+        let specs: Vec<SpecId> = self
+            .spec_ids
+            .iter()
+            .filter(|(spec, _)| e.has_property(spec))
+            .map(|(_, s)| s)
+            .collect();
+        for spec in &specs {
+            let members = &mut self.specs[spec.index()].members;
+            if let Err(i) = members.binary_search_by(|(m, _)| m.as_str().cmp(id)) {
+                members.insert(i, (id.to_string(), handle));
+            }
+        }
+        if specs.is_empty() {
+            self.prune(id);
+        } else {
+            self.related.entry(id.to_string()).or_default().specs = specs;
+        }
+    }
+
+    pub(crate) fn id_relation_changed(
+        &mut self,
+        old: Option<&IDRelation>,
+        new: Option<&IDRelation>,
+    ) {
+        if let Some(rel) = old {
+            for end in ends(rel) {
+                if let Some(related) = self.related.get_mut(end) {
+                    remove_id(&mut related.id_relations, &rel.id);
+                }
+                self.prune(end);
+            }
+        }
+        if let Some(rel) = new {
+            for end in ends(rel) {
+                self.related
+                    .entry(end.to_string())
+                    .or_default()
+                    .id_relations
+                    .push(rel.id.clone());
+            }
+        }
+    }
+
+    pub(crate) fn id_property_relation_changed(
+        &mut self,
+        old: Option<&IDPropertyRelation>,
+        new: Option<&IDPropertyRelation>,
+    ) {
+        if let Some(rel) = old {
+            if let Some(related) = self.related.get_mut(&rel.entity_id) {
+                remove_id(&mut related.id_property_relations, &rel.id);
+            }
+            self.prune(&rel.entity_id);
+        }
+        if let Some(rel) = new {
+            self.related
+                .entry(rel.entity_id.clone())
+                .or_default()
+                .id_property_relations
+                .push(rel.id.clone());
+        }
+    }
+
+    pub(crate) fn property_relation_changed(
+        &mut self,
+        entities: &IdMap<Entity>,
+        old: Option<&PropertyRelation>,
+        new: Option<&PropertyRelation>,
+    ) {
+        self.spec_changed(
+            entities,
+            |s| &mut s.property_relations,
+            old.map(|rel| (rel.id.as_str(), rel.entity_property.as_str())),
+            new.map(|rel| (rel.id.as_str(), rel.entity_property.as_str())),
+        );
+    }
+
+    pub(crate) fn domain_relation_changed(
+        &mut self,
+        entities: &IdMap<Entity>,
+        old: Option<&DomainRelation>,
+        new: Option<&DomainRelation>,
+    ) {
+        self.spec_changed(
+            entities,
+            |s| &mut s.domain_relations,
+            old.map(|rel| (rel.id.as_str(), rel.entity_property.as_str())),
+            new.map(|rel| (rel.id.as_str(), rel.entity_property.as_str())),
+        );
+    }
+
+    pub(crate) fn chain_changed(
+        &mut self,
+        entities: &IdMap<Entity>,
+        old: Option<&AffinityChain>,
+        new: Option<&AffinityChain>,
+    ) {
+        if let Some(chain) = old {
+            if let Some(parent) = &chain.parent {
+                if let Some(related) = self.related.get_mut(parent) {
+                    remove_id(&mut related.parent_of, &chain.id);
+                }
+                self.prune(parent);
+            }
+        }
+        if let Some(chain) = new {
+            if let Some(parent) = &chain.parent {
+                self.related
+                    .entry(parent.clone())
+                    .or_default()
+                    .parent_of
+                    .push(chain.id.clone());
+            }
+        }
+        self.spec_changed(
+            entities,
+            |s| &mut s.chains,
+            old.map(|chain| (chain.id.as_str(), chain.entity_property.as_str())),
+            new.map(|chain| (chain.id.as_str(), chain.entity_property.as_str())),
+        );
+    }
+
+    // moves a relation, given as id and entity property, between the lists
+    // of its entity property. A property no relation uses any more is
+    // dropped, after the new one is in so that a replaced relation does
+    // not look its members up again.
+    fn spec_changed(
+        &mut self,
+        entities: &IdMap<Entity>,
+        list: fn(&mut Spec) -> &mut Vec<String>,
+        old: Option<(&str, &str)>,
+        new: Option<(&str, &str)>,
+    ) {
+        let old_spec = old.and_then(|(id, spec)| {
+            let spec = self.spec_ids.get(spec)?;
+            remove_id(list(&mut self.specs[spec.index()]), id);
+            Some(spec)
+        });
+        if let Some((id, spec)) = new {
+            let spec = self.spec(entities, spec);
+            list(&mut self.specs[spec.index()]).push(id.to_string());
+        }
+        if let Some(spec) = old_spec {
+            if self.specs[spec.index()].is_unused() {
+                let removed = std::mem::take(&mut self.specs[spec.index()]);
+                for (m, _) in &removed.members {
+                    if let Some(related) = self.related.get_mut(m) {
+                        related.specs.retain(|s| *s != spec);
+                    }
+                    self.prune(m);
+                }
+                let name = self.spec_ids.name(spec).to_string();
+                self.spec_ids.remove(&name);
+            }
+        }
+    }
+
+    // the handle of the entity property, looking up its members the first
+    // time.
+    fn spec(&mut self, entities: &IdMap<Entity>, spec: &str) -> SpecId {
+        if let Some(handle) = self.spec_ids.get(spec) {
+            return handle;
+        }
+        let handle = self.spec_ids.intern(spec);
+        let mut members: Vec<(String, EntityId)> = entities
+            .handles()
+            .filter(|(_, e)| e.has_property(spec))
+            .map(|(handle, e)| (e.id.clone(), handle))
+            .collect();
+        members.sort_by(|a, b| a.0.cmp(&b.0));
+        for (m, _) in &members {
+            self.related
+                .entry(m.clone())
+                .or_default()
+                .specs
+                .push(handle);
+        }
+        let spec = Spec {
+            members,
+            ..Default::default()
+        };
+        if handle.index() == self.specs.len() {
+            self.specs.push(spec);
+        } else {
+            self.specs[handle.index()] = spec;
+        }
+        handle
+    }
+
+    // forgets an entity without relations.
+    fn prune(&mut self, id: &str) {
+        if self.related.get(id).is_some_and(Related::is_empty) {
+            self.related.remove(id);
+        }
+    }
+}
+
+impl Board {
+    // drops the maintained relation index, which is built again on next
+    // use, and rebuilds the role assignment of the groups. Needed after
+    // writing entity properties, relations, resource properties and
+    // domains, groups or the assignment of replicas in the public maps
+    // directly.
+    pub fn refresh_index(&mut self) {
+        self.index = std::sync::OnceLock::new();
+        let assignment = Placements::new(&self.assignment, &self.entities, &self.resources);
+        self.roles = super::group::RoleAssignment::new(&self.groups, assignment);
+    }
+}
+
+// a check's or search's view of the board's index. Entities, resources and
+// relations must not change while it is in use, the assignment may.
+#[derive(Clone, Copy)]
+pub(crate) struct RelationIndex<'a> {
+    board: &'a Board,
+    index: &'a BoardIndex,
+}
+
+impl<'a> RelationIndex<'a> {
+    pub(crate) fn new(board: &'a Board) -> RelationIndex<'a> {
+        RelationIndex {
+            board,
+            index: board.index.get_or_init(|| BoardIndex::new(board)),
+        }
+    }
+
+    fn related(&self, entity_id: &str) -> Option<&'a Related> {
+        self.index.related.get(entity_id)
+    }
+
+    fn specs(&self, entity_id: &str) -> impl Iterator<Item = &'a Spec> + 'a {
+        let specs = &self.index.specs;
+        self.related(entity_id)
+            .into_iter()
+            .flat_map(|r| &r.specs)
+            .map(move |s| &specs[s.index()])
+    }
+
+    pub(crate) fn id_relations(
+        &self,
+        entity_id: &str,
+    ) -> impl Iterator<Item = &'a IDRelation> + 'a {
+        let board = self.board;
+        self.related(entity_id)
+            .into_iter()
+            .flat_map(|r| &r.id_relations)
+            .filter_map(move |id| board.id_relations.get(id))
+    }
+
+    pub(crate) fn id_property_relations(
+        &self,
+        entity_id: &str,
+    ) -> impl Iterator<Item = &'a IDPropertyRelation> + 'a {
+        let board = self.board;
+        self.related(entity_id)
+            .into_iter()
+            .flat_map(|r| &r.id_property_relations)
+            .filter_map(move |id| board.id_property_relations.get(id))
+    }
+
+    // property and domain relations whose entity property the entity has.
+    pub(crate) fn property_relations(
+        &self,
+        entity_id: &str,
+    ) -> impl Iterator<Item = &'a PropertyRelation> + 'a {
+        let board = self.board;
+        self.specs(entity_id)
+            .flat_map(|s| &s.property_relations)
+            .filter_map(move |id| board.property_relations.get(id))
+    }
+
+    pub(crate) fn domain_relations(
+        &self,
+        entity_id: &str,
+    ) -> impl Iterator<Item = &'a DomainRelation> + 'a {
+        let board = self.board;
+        self.specs(entity_id)
+            .flat_map(|s| &s.domain_relations)
+            .filter_map(move |id| board.domain_relations.get(id))
+    }
+
+    // chains the entity is a member or the parent of, each once.
+    pub(crate) fn chains(&self, entity_id: &str) -> impl Iterator<Item = &'a AffinityChain> + 'a {
+        let (board, index) = (self.board, self.index);
+        let related = self.related(entity_id);
+        let member_of = move |chain: &AffinityChain| {
+            index
+                .spec_ids
+                .get(&chain.entity_property)
+                .is_some_and(|s| related.is_some_and(|r| r.specs.contains(&s)))
+        };
+        let parent_of = related
+            .into_iter()
+            .flat_map(|r| &r.parent_of)
+            .filter_map(move |id| board.chains.get(id))
+            .filter(move |chain| !member_of(chain));
+        self.specs(entity_id)
+            .flat_map(|s| &s.chains)
+            .filter_map(move |id| board.chains.get(id))
+            .chain(parent_of)
+    }
+
+    // entities having the entity property of a relation, sorted.
+    pub(crate) fn members(&self, entity_property: &str) -> impl Iterator<Item = &'a str> + 'a {
+        self.peers(entity_property).map(|(id, _)| id)
+    }
+
+    // members with their handles.
+    pub(crate) fn peers(
+        &self,
+        entity_property: &str,
+    ) -> impl Iterator<Item = (&'a str, EntityId)> + 'a {
+        let index = self.index;
+        index
+            .spec_ids
+            .get(entity_property)
+            .into_iter()
+            .flat_map(move |s| &index.specs[s.index()].members)
+            .map(|(id, handle)| (id.as_str(), *handle))
+    }
+
+    pub(crate) fn entity(&self, entity_id: &str) -> Option<EntityId> {
+        self.board.entities.handle(entity_id)
+    }
+
+    pub(crate) fn entity_name(&self, entity: EntityId) -> &'a str {
+        self.board.entities.name(entity)
+    }
+
+    pub(crate) fn resource(&self, resource_id: &str) -> Option<ResourceId> {
+        self.board.resources.handle(resource_id)
+    }
+
+    pub(crate) fn resource_name(&self, resource: ResourceId) -> &'a str {
+        self.board.resources.name(resource)
+    }
+
+    // the handle of Resource::domain.
+    pub(crate) fn domain(&self, resource: ResourceId, kind: DomainKind) -> DomainId {
+        let [fault, upgrade] = self.index.domains[resource.index()];
+        match kind {
+            DomainKind::Fault => fault,
+            DomainKind::Upgrade => upgrade,
+        }
+    }
+
+    // sorted resources that may host the entity as far as its hard
+    // affinities go: an ER affinity pins it to one resource, a property
+    // affinity to the resources having the property's key. Paused and
+    // draining resources are no candidates. The result is a superset of
+    // the candidates and still needs a violation check.
+    pub(crate) fn resource_candidates(&self, entity_id: &str) -> Vec<&'a str> {
+        let board = self.board;
+        let open = |id: &&str| {
+            board
+                .resources
+                .get(id)
+                .is_some_and(|r| r.state.accepts_entities())
+        };
+        let resource_ids = &self.index.resource_ids;
+        let mut narrowed: Option<Vec<&'a str>> = None;
+        let mut narrow = |set: Vec<&'a str>| {
+            narrowed = Some(match narrowed.take() {
+                None => set,
+                Some(prev) => {
+                    let set: HashSet<&str> = set.into_iter().collect();
+                    prev.into_iter().filter(|r| set.contains(r)).collect()
+                }
+            });
+        };
+        for rel in self.id_relations(entity_id) {
+            if rel.kind == IDRelationKind::ERAffinity && rel.priority.is_hard() {
+                let found = resource_ids.binary_search(&rel.id2);
+                narrow(found.map_or(Vec::new(), |i| vec![resource_ids[i].as_str()]));
+            }
+        }
+        let keys = self
+            .property_relations(entity_id)
+            .filter(|rel| rel.kind == PropertyRelationKind::Affinity && rel.priority.is_hard())
+            .map(|rel| rel.resource_property.as_str())
+            .chain(
+                self.id_property_relations(entity_id)
+                    .filter(|rel| {
+                        rel.kind == PropertyRelationKind::Affinity && rel.priority.is_hard()
+                    })
+                    .map(|rel| rel.resource_property.as_str()),
+            );
+        for spec in keys {
+            let (key, _) = parse_spec(spec);
+            let ids = self.index.resources_by_key.get(key).into_iter().flatten();
+            narrow(ids.map(String::as_str).collect());
+        }
+        let mut ids = match narrowed {
+            None => resource_ids.iter().map(String::as_str).collect(),
+            Some(mut ids) => {
+                ids.sort();
+                ids.dedup();
+                ids
+            }
+        };
+        ids.retain(open);
+        ids
+    }
+}
+
+// everything the index answers, sorted, to compare a maintained index with
+// a rebuilt one.
+#[cfg(test)]
+impl RelationIndex<'_> {
+    pub(crate) fn answers(&self) -> Vec<String> {
+        let board = self.board;
+        let mut answers = Vec::new();
+        let mut entity_ids: Vec<&String> = board.entities.keys().collect();
+        entity_ids.sort();
+        for e in entity_ids {
+            let h = self.entity(e).expect("entity indexed");
+            assert_eq!(self.entity_name(h), e);
+            let mut lists: Vec<Vec<&str>> = vec![
+                self.id_relations(e).map(|r| r.id.as_str()).collect(),
+                self.id_property_relations(e)
+                    .map(|r| r.id.as_str())
+                    .collect(),
+                self.property_relations(e).map(|r| r.id.as_str()).collect(),
+                self.domain_relations(e).map(|r| r.id.as_str()).collect(),
+                self.chains(e).map(|c| c.id.as_str()).collect(),
+            ];
+            for list in &mut lists {
+                list.sort();
+            }
+            lists.push(self.resource_candidates(e));
+            answers.push(format!("{}: {:?}", e, lists));
+        }
+        let mut specs: Vec<&str> = board
+            .property_relations
+            .values()
+            .map(|rel| rel.entity_property.as_str())
+            .chain(
+                board
+                    .domain_relations
+                    .values()
+                    .map(|rel| rel.entity_property.as_str()),
+            )
+            .chain(
+                board
+                    .chains
+                    .values()
+                    .map(|chain| chain.entity_property.as_str()),
+            )
+            .collect();
+        specs.sort();
+        specs.dedup();
+        for spec in specs {
+            for (id, h) in self.peers(spec) {
+                assert_eq!(self.entity(id), Some(h));
+            }
+            answers.push(format!(
+                "{}: {:?}",
+                spec,
+                self.members(spec).collect::<Vec<_>>()
+            ));
+        }
+        let mut resource_ids: Vec<&String> = board.resources.keys().collect();
+        resource_ids.sort();
+        for r in &resource_ids {
+            let h = self.resource(r).expect("resource indexed");
+            assert_eq!(self.resource_name(h), r.as_str());
+            // resources in the same domains.
+            let shared = [DomainKind::Fault, DomainKind::Upgrade].map(|kind| {
+                resource_ids
+                    .iter()
+                    .filter(|o| {
+                        self.domain(self.resource(o).expect("resource"), kind)
+                            == self.domain(h, kind)
+                    })
+                    .collect::<Vec<_>>()
+            });
+            answers.push(format!("{}: {:?}", r, shared));
+        }
+        answers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RelationIndex;
+    use crate::solver::{
+        AffinityChain, Board, DomainKind, DomainRelation, Entity, IDRelation, IDRelationKind,
+        Priority, PropertyRelation, PropertyRelationKind, Resource,
+    };
+
+    #[test]
+    fn resource_candidates_test() {
+        let mut b = Board::new();
+        for (id, props) in [("node1", "gpu"), ("node2", "ssd"), ("node3", "gpu")] {
+            let mut r = Resource::new(String::from(id));
+            r.add_property(String::from(props));
+            b.add_resource(r).expect("added");
+        }
+        let mut e = Entity::new(String::from("train"));
+        e.add_property(String::from("ml"));
+        b.add_entity(String::from("node2"), e).expect("added");
+        b.add_entity(String::from("node2"), Entity::new(String::from("web")))
+            .expect("added");
+        b.add_property_relation(PropertyRelation {
+            id: String::from("ml-gpu"),
+            kind: PropertyRelationKind::Affinity,
+            entity_property: String::from("ml"),
+            resource_property: String::from("gpu"),
+            priority: Priority::Hard,
+        })
+        .expect("ok");
+
+        let index = RelationIndex::new(&b);
+        assert_eq!(index.resource_candidates("train"), vec!["node1", "node3"]);
+        assert_eq!(index.resource_candidates("web").len(), 3);
+        assert_eq!(index.property_relations("web").count(), 0);
+
+        // an ER affinity narrows further.
+        b.add_id_relation(IDRelation {
+            id: String::from("pin"),
+            kind: IDRelationKind::ERAffinity,
+            id1: String::from("train"),
+            id2: String::from("node3"),
+            priority: Priority::Hard,
+        })
+        .expect("ok");
+        let index = RelationIndex::new(&b);
+        assert_eq!(index.resource_candidates("train"), vec!["node3"]);
+        assert_eq!(b.find_candidate_resources("train"), vec!["node3"]);
+    }
+
+    // answers of the board's index next to those of a rebuilt one.
+    fn compare(b: &Board) {
+        let mut fresh = b.clone();
+        fresh.refresh_index();
+        assert_eq!(
+            RelationIndex::new(b).answers(),
+            RelationIndex::new(&fresh).answers()
+        );
+        assert!(b.index.get().is_some());
+    }
+
+    #[test]
+    fn maintained_index_test() {
+        let mut b = Board::new();
+        for (id, rack) in [("node1", "r1"), ("node2", "r1"), ("node3", "r2")] {
+            let mut r = Resource::new(String::from(id));
+            r.add_property(String::from("ssd"));
+            r.fault_domain = Some(format!("/dc/{}", rack));
+            b.add_resource(r).expect("added");
+        }
+        for (id, props) in [("a", "svc=db"), ("b", "svc=db"), ("c", "svc=web")] {
+            let mut e = Entity::new(String::from(id));
+            e.add_property(String::from(props));
+            b.add_entity(String::from("node1"), e).expect("added");
+        }
+        b.add_domain_relation(DomainRelation {
+            id: String::from("spread"),
+            domain: DomainKind::Fault,
+            entity_property: String::from("svc=db"),
+            min_domains: 2,
+            priority: Priority::Hard,
+        })
+        .expect("added");
+        b.add_chain(AffinityChain {
+            id: String::from("web"),
+            entity_property: String::from("svc=web"),
+            parent: Some(String::from("a")),
+            priority: Priority::Hard,
+        })
+        .expect("added");
+        b.add_id_relation(IDRelation {
+            id: String::from("apart"),
+            kind: IDRelationKind::EEAntiAffinity,
+            id1: String::from("b"),
+            id2: String::from("c"),
+            priority: Priority::Hard,
+        })
+        .expect("added");
+        // built here, then only updated.
+        assert_eq!(RelationIndex::new(&b).chains("a").count(), 1);
+        compare(&b);
+
+        let mut e = Entity::new(String::from("d"));
+        e.add_property(String::from("svc=db"));
+        b.add_entity(String::from("node2"), e.clone())
+            .expect("added");
+        assert_eq!(
+            RelationIndex::new(&b).members("svc=db").collect::<Vec<_>>(),
+            ["a", "b", "d"]
+        );
+        e.properties = Default::default();
+        e.add_property(String::from("svc=web"));
+        b.update_entity(e).expect("updated");
+        assert_eq!(RelationIndex::new(&b).chains("d").count(), 1);
+        compare(&b);
+
+        b.add_property_relation(PropertyRelation {
+            id: String::from("web-ssd"),
+            kind: PropertyRelationKind::Affinity,
+            entity_property: String::from("svc=web"),
+            resource_property: String::from("ssd"),
+            priority: Priority::Hard,
+        })
+        .expect("added");
+        b.remove_relation("spread").expect("removed");
+        let mut r = Resource::new(String::from("node4"));
+        r.fault_domain = Some(String::from("/dc/r2"));
+        b.add_resource(r).expect("added");
+        assert_eq!(
+            RelationIndex::new(&b).resource_candidates("c"),
+            ["node1", "node2", "node3"]
+        );
+        compare(&b);
+
+        b.remove_entity("a").expect("removed");
+        b.evict_resource("node1").expect("evicted");
+        assert!(RelationIndex::new(&b).id_relations("b").next().is_none());
+        compare(&b);
+    }
+}
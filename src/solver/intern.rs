@@ -0,0 +1,549 @@
+// interned ids: small Copy handles for the objects of a board, so the inner
+// loops of checks and searches index vectors instead of hashing and
+// comparing strings. The board's maps are IdMaps, which hand out the entity
+// and resource handles; the assignment and the loads are kept by handle,
+// and so is the state of the index and the searches. Ids are for the public
+// boundary: lookups by id, and the views by id over the handle-keyed
+// tables, see Board::assignment.
+//
+// Relations are public objects and name their ends by id. The index files
+// them under those ids, since they may name entities the board does not
+// have yet and so may have no handle.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::ops::Index;
+
+use super::index::RelationIndex;
+use super::{Entity, Resource};
+
+pub(crate) trait Handle: Copy + Eq {
+    fn new(index: usize) -> Self;
+    fn index(self) -> usize;
+}
+
+// the handle of an object of type T in an IdMap.
+pub struct Id<T> {
+    index: u32,
+    of: PhantomData<fn() -> T>,
+}
+
+pub type EntityId = Id<Entity>;
+pub type ResourceId = Id<Resource>;
+
+impl<T> Handle for Id<T> {
+    fn new(index: usize) -> Self {
+        Id {
+            index: u32::try_from(index).expect("too many ids"),
+            of: PhantomData,
+        }
+    }
+
+    fn index(self) -> usize {
+        self.index as usize
+    }
+}
+
+// derived, these would only hold for T that has them.
+impl<T> Clone for Id<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Id<T> {}
+
+impl<T> PartialEq for Id<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index
+    }
+}
+
+impl<T> Eq for Id<T> {}
+
+impl<T> Hash for Id<T> {
+    fn hash<S: Hasher>(&self, state: &mut S) {
+        self.index.hash(state);
+    }
+}
+
+impl<T> fmt::Debug for Id<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Id({})", self.index)
+    }
+}
+
+macro_rules! handle {
+    ($name:ident) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub(crate) struct $name(u32);
+
+        impl Handle for $name {
+            fn new(index: usize) -> Self {
+                $name(u32::try_from(index).expect("too many ids"))
+            }
+
+            fn index(self) -> usize {
+                self.0 as usize
+            }
+        }
+    };
+}
+
+// a fault or upgrade domain path.
+handle!(DomainId);
+// an entity property of relations, see RelationIndex::members.
+handle!(SpecId);
+
+// handles of the entity properties and domains the index keeps. Handles
+// are numbered in the order the ids come and the handle of a removed id goes to the next new one, so they are
+// only compared for equality.
+#[derive(Debug, Clone)]
+pub(crate) struct Interner<H> {
+    // by handle, empty for removed ids.
+    names: Vec<String>,
+    handles: HashMap<String, H>,
+    // handles of removed ids.
+    free: Vec<H>,
+}
+
+impl<H> Default for Interner<H> {
+    fn default() -> Self {
+        Interner {
+            names: Vec::new(),
+            handles: HashMap::new(),
+            free: Vec::new(),
+        }
+    }
+}
+
+impl<H: Handle> Interner<H> {
+    // the handle of the id, a new one the first time.
+    pub(crate) fn intern(&mut self, id: &str) -> H {
+        if let Some(handle) = self.handles.get(id) {
+            return *handle;
+        }
+        let handle = match self.free.pop() {
+            Some(handle) => {
+                self.names[handle.index()] = id.to_string();
+                handle
+            }
+            None => {
+                self.names.push(id.to_string());
+                H::new(self.names.len() - 1)
+            }
+        };
+        self.handles.insert(id.to_string(), handle);
+        handle
+    }
+
+    // frees the handle of the id.
+    pub(crate) fn remove(&mut self, id: &str) -> Option<H> {
+        let handle = self.handles.remove(id)?;
+        self.names[handle.index()].clear();
+        self.free.push(handle);
+        Some(handle)
+    }
+
+    pub(crate) fn get(&self, id: &str) -> Option<H> {
+        self.handles.get(id).copied()
+    }
+
+    pub(crate) fn name(&self, handle: H) -> &String {
+        &self.names[handle.index()]
+    }
+
+    // every interned id with its handle.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&str, H)> + '_ {
+        self.handles.iter().map(|(id, h)| (id.as_str(), *h))
+    }
+}
+
+// objects by id, stored by handle: the board's maps. Each id gets its
+// handle when first inserted and keeps it once removed, so the handles the
+// board's other tables hold stay valid, and a clone of the map hands out
+// the same ones. Lookups by id are for the public boundary, the inner
+// loops go by handle.
+#[derive(Debug, Clone)]
+pub struct IdMap<T> {
+    ids: Interner<Id<T>>,
+    // by handle, None for removed ids.
+    slots: Vec<Option<T>>,
+    len: usize,
+}
+
+impl<T> Default for IdMap<T> {
+    fn default() -> Self {
+        IdMap {
+            ids: Interner::default(),
+            slots: Vec::new(),
+            len: 0,
+        }
+    }
+}
+
+impl<T> IdMap<T> {
+    pub fn new() -> Self {
+        IdMap::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // the handle of the id, also once its object is removed.
+    pub fn handle(&self, id: &str) -> Option<Id<T>> {
+        self.ids.get(id)
+    }
+
+    // the id of the handle.
+    pub fn name(&self, handle: Id<T>) -> &String {
+        self.ids.name(handle)
+    }
+
+    // the handle of the id, a new one the first time.
+    pub(crate) fn intern(&mut self, id: &str) -> Id<T> {
+        let handle = self.ids.intern(id);
+        if handle.index() == self.slots.len() {
+            self.slots.push(None);
+        }
+        handle
+    }
+
+    pub fn get(&self, id: &str) -> Option<&T> {
+        self.at(self.handle(id)?)
+    }
+
+    pub fn get_mut(&mut self, id: &str) -> Option<&mut T> {
+        let handle = self.handle(id)?;
+        self.slots[handle.index()].as_mut()
+    }
+
+    // the object of the handle, None once removed.
+    pub fn at(&self, handle: Id<T>) -> Option<&T> {
+        self.slots[handle.index()].as_ref()
+    }
+
+    pub fn contains_key(&self, id: &str) -> bool {
+        self.get(id).is_some()
+    }
+
+    // inserts the object under the id and returns the one it replaces.
+    pub fn insert(&mut self, id: String, value: T) -> Option<T> {
+        let handle = self.intern(&id);
+        let old = self.slots[handle.index()].replace(value);
+        if old.is_none() {
+            self.len += 1;
+        }
+        old
+    }
+
+    pub fn remove(&mut self, id: &str) -> Option<T> {
+        let handle = self.handle(id)?;
+        let old = self.slots[handle.index()].take();
+        if old.is_some() {
+            self.len -= 1;
+        }
+        old
+    }
+
+    // the objects with their handles, in handle order.
+    pub fn handles(&self) -> impl Iterator<Item = (Id<T>, &T)> + '_ {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(|(i, slot)| Some((Id::new(i), slot.as_ref()?)))
+    }
+
+    // in handle order, like keys and values.
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &T)> + '_ {
+        self.handles().map(|(h, value)| (self.ids.name(h), value))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &String> + '_ {
+        self.iter().map(|(id, _)| id)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &T> + '_ {
+        self.slots.iter().flatten()
+    }
+
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut T> + '_ {
+        self.slots.iter_mut().flatten()
+    }
+}
+
+impl<T, Q: AsRef<str> + ?Sized> Index<&Q> for IdMap<T> {
+    type Output = T;
+
+    fn index(&self, id: &Q) -> &T {
+        let id = id.as_ref();
+        self.get(id)
+            .unwrap_or_else(|| panic!("no object with id {}", id))
+    }
+}
+
+impl<'a, T> IntoIterator for &'a IdMap<T> {
+    type Item = (&'a String, &'a T);
+    type IntoIter = Box<dyn Iterator<Item = (&'a String, &'a T)> + 'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Box::new(self.iter())
+    }
+}
+
+impl<T> FromIterator<(String, T)> for IdMap<T> {
+    fn from_iter<I: IntoIterator<Item = (String, T)>>(iter: I) -> Self {
+        let mut map = IdMap::new();
+        for (id, value) in iter {
+            map.insert(id, value);
+        }
+        map
+    }
+}
+
+// entity handle -> resource handle: the board's assignment, and the copies
+// plans and searches move entities around in without copying strings.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Assignment {
+    // by entity handle.
+    resources: Vec<Option<ResourceId>>,
+    len: usize,
+}
+
+impl Assignment {
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    pub(crate) fn get(&self, entity: EntityId) -> Option<ResourceId> {
+        self.resources.get(entity.index()).copied().flatten()
+    }
+
+    // places the entity and returns where it was.
+    pub(crate) fn insert(&mut self, entity: EntityId, resource: ResourceId) -> Option<ResourceId> {
+        if entity.index() >= self.resources.len() {
+            self.resources.resize(entity.index() + 1, None);
+        }
+        let old = self.resources[entity.index()].replace(resource);
+        if old.is_none() {
+            self.len += 1;
+        }
+        old
+    }
+
+    pub(crate) fn remove(&mut self, entity: EntityId) -> Option<ResourceId> {
+        let old = self.resources.get_mut(entity.index())?.take();
+        if old.is_some() {
+            self.len -= 1;
+        }
+        old
+    }
+
+    // every placed entity with its resource, in handle order.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (EntityId, ResourceId)> + '_ {
+        self.resources
+            .iter()
+            .enumerate()
+            .filter_map(|(i, r)| Some((EntityId::new(i), (*r)?)))
+    }
+
+    // the resource of the entity by id, for the checks.
+    pub(crate) fn resource_of<'s>(
+        &'s self,
+        index: &RelationIndex<'s>,
+        entity_id: &str,
+    ) -> Option<&'s str> {
+        let r = self.get(index.entity(entity_id)?)?;
+        Some(index.resource_name(r))
+    }
+
+    // whether the entity is placed on the resource, None if not placed.
+    pub(crate) fn is_on(&self, entity: EntityId, resource: ResourceId) -> Option<bool> {
+        self.get(entity).map(|r| r == resource)
+    }
+
+    // every placed entity with its resource, by id.
+    pub(crate) fn placed<'s>(&'s self, index: &RelationIndex<'s>) -> Vec<(&'s str, &'s str)> {
+        self.iter()
+            .map(|(e, r)| (index.entity_name(e), index.resource_name(r)))
+            .collect()
+    }
+}
+
+// an assignment by id, entity id -> resource id, as Board::assignment shows
+// it.
+#[derive(Debug, Clone, Copy)]
+pub struct Placements<'a> {
+    assignment: &'a Assignment,
+    entities: &'a IdMap<Entity>,
+    resources: &'a IdMap<Resource>,
+}
+
+impl<'a> Placements<'a> {
+    pub(crate) fn new(
+        assignment: &'a Assignment,
+        entities: &'a IdMap<Entity>,
+        resources: &'a IdMap<Resource>,
+    ) -> Self {
+        Placements {
+            assignment,
+            entities,
+            resources,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.assignment.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // the resource of the entity, None if not placed.
+    pub fn get(&self, entity_id: &str) -> Option<&'a String> {
+        let r = self.assignment.get(self.entities.handle(entity_id)?)?;
+        Some(self.resources.name(r))
+    }
+
+    pub fn contains_key(&self, entity_id: &str) -> bool {
+        self.get(entity_id).is_some()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&'a String, &'a String)> + 'a {
+        let (entities, resources) = (self.entities, self.resources);
+        self.assignment
+            .iter()
+            .map(move |(e, r)| (entities.name(e), resources.name(r)))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &'a String> + 'a {
+        self.iter().map(|(e, _)| e)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &'a String> + 'a {
+        self.iter().map(|(_, r)| r)
+    }
+
+    // a copy to keep or change.
+    pub fn to_map(&self) -> HashMap<String, String> {
+        self.iter().map(|(e, r)| (e.clone(), r.clone())).collect()
+    }
+}
+
+impl<Q: AsRef<str> + ?Sized> Index<&Q> for Placements<'_> {
+    type Output = String;
+
+    fn index(&self, entity_id: &Q) -> &String {
+        let entity_id = entity_id.as_ref();
+        self.get(entity_id)
+            .unwrap_or_else(|| panic!("entity {} not placed", entity_id))
+    }
+}
+
+impl<'a> IntoIterator for Placements<'a> {
+    type Item = (&'a String, &'a String);
+    type IntoIter = Box<dyn Iterator<Item = (&'a String, &'a String)> + 'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Box::new(self.iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Handle, IdMap, Interner, SpecId};
+    use crate::solver::index::RelationIndex;
+    use crate::solver::{BoardBuilder, EntityBuilder, ResourceBuilder};
+
+    #[test]
+    fn interner_test() {
+        let mut interner: Interner<SpecId> = Interner::default();
+        for id in ["b", "a", "c"] {
+            interner.intern(id);
+        }
+        assert_eq!(interner.intern("a"), interner.get("a").unwrap());
+        assert_eq!(interner.get("c").unwrap().index(), 2);
+        let (a, b) = (interner.get("a").unwrap(), interner.get("b").unwrap());
+        assert_ne!(a, b);
+        assert_eq!(b.index(), 0);
+        assert_eq!(interner.name(a), "a");
+        assert_eq!(interner.get("d"), None);
+
+        // a removed id's handle is given out again.
+        assert_eq!(interner.remove("a"), Some(a));
+        assert_eq!(interner.get("a"), None);
+        assert_eq!(interner.intern("d"), a);
+        assert_eq!(interner.name(a), "d");
+        assert_eq!(interner.intern("e").index(), 3);
+    }
+
+    #[test]
+    fn id_map_test() {
+        let mut map: IdMap<i64> = IdMap::new();
+        assert_eq!(map.insert(String::from("b"), 1), None);
+        assert_eq!(map.insert(String::from("a"), 2), None);
+        assert_eq!(map.insert(String::from("a"), 3), Some(2));
+        assert_eq!(map.len(), 2);
+        assert_eq!(map["a"], 3);
+        let a = map.handle("a").unwrap();
+        assert_eq!(map.name(a), "a");
+        assert_eq!(map.at(a), Some(&3));
+
+        // a removed id keeps its handle, so tables by handle stay valid.
+        assert_eq!(map.remove("a"), Some(3));
+        assert_eq!(map.len(), 1);
+        assert!(!map.contains_key("a"));
+        assert_eq!(map.at(a), None);
+        map.insert(String::from("c"), 4);
+        assert_ne!(map.handle("c"), Some(a));
+        map.insert(String::from("a"), 5);
+        assert_eq!(map.handle("a"), Some(a));
+        assert_eq!(map.len(), 3);
+
+        // iteration is in handle order, and a clone has the same handles.
+        let copy = map.clone();
+        let ids: Vec<&String> = copy.keys().collect();
+        assert_eq!(ids, ["b", "a", "c"]);
+        assert_eq!(copy.handle("a"), Some(a));
+    }
+
+    #[test]
+    fn assignment_test() {
+        let b = BoardBuilder::new()
+            .resource(ResourceBuilder::new("node1"))
+            .resource(ResourceBuilder::new("node2"))
+            .entity("node1", EntityBuilder::new("a"))
+            .entity("node2", EntityBuilder::new("b"))
+            .build()
+            .expect("builds");
+        let index = RelationIndex::new(&b);
+        let mut assignment = b.assignment.clone();
+        let a = ("a", index.entity("a").unwrap());
+        let node2 = ("node2", index.resource("node2").unwrap());
+        assert_eq!(assignment.get(a.1), index.resource("node1"));
+
+        assert_eq!(b.place_in(&mut assignment, "a", "node2").unwrap(), "node1");
+        assert_eq!(assignment.get(a.1), Some(node2.1));
+        assert_eq!(assignment.is_on(a.1, node2.1), Some(true));
+        assert_eq!(assignment.resource_of(&index, "a"), Some("node2"));
+        // by id, as the public api shows it.
+        let placements = b.placements(&assignment);
+        assert_eq!(placements["a"], "node2");
+        assert_eq!(placements.len(), 2);
+        assert_eq!(placements.to_map()["b"], "node2");
+        assert_eq!(b.assignment()["a"], "node1");
+
+        assert_eq!(assignment.remove(a.1), Some(node2.1));
+        assert_eq!(assignment.is_on(a.1, node2.1), None);
+        assert_eq!(assignment.placed(&index), [("b", "node2")]);
+        assert!(!b.placements(&assignment).contains_key("a"));
+    }
+}
@@ -0,0 +1,175 @@
+// randomized checks of solver invariants over generated boards. Every case
+// is a seed, so a failure names the seed that reproduces it.
+
+use std::collections::HashMap;
+
+use super::index::RelationIndex;
+use super::rng::Rng;
+use super::{Board, DeactivationIntent, GeneratorConfig, MovePlan, SolverConfig};
+
+const CASES: u64 = 24;
+
+// a small board of random shape, small enough for rebalance.
+fn config(seed: u64) -> GeneratorConfig {
+    let mut rng = Rng::new(seed);
+    GeneratorConfig {
+        resources: 2 + rng.below(6),
+        entities: rng.below(40),
+        relation_density: rng.next_f64() * 0.3,
+        tightness: 0.2 + rng.next_f64() * 0.7,
+        soft_share: rng.next_f64(),
+        seed,
+    }
+}
+
+fn hard_violations(b: &Board) -> usize {
+    b.check_all().iter().filter(|v| v.is_hard()).count()
+}
+
+// the board's maintained index answers like one built from scratch.
+fn check_index(seed: u64, b: &Board) {
+    let mut fresh = b.clone();
+    fresh.refresh_index();
+    assert_eq!(
+        RelationIndex::new(b).answers(),
+        RelationIndex::new(&fresh).answers(),
+        "{}",
+        seed
+    );
+}
+
+// where the plan leaves every entity it moves.
+fn net_moves(b: &Board, plan: &MovePlan) -> HashMap<String, String> {
+    let mut to: HashMap<String, String> = HashMap::new();
+    for m in &plan.moves {
+        to.insert(m.entity_id.clone(), m.to.clone());
+    }
+    to.retain(|e, r| b.assignment()[e] != *r);
+    to
+}
+
+// the plan names existing objects, applies, adds no hard violation and
+// is what a diff of the board around it shows.
+fn check_plan(seed: u64, name: &str, b: &Board, plan: &MovePlan) {
+    let mut at = b.assignment().to_map();
+    for m in &plan.moves {
+        assert!(
+            b.resources.contains_key(&m.to),
+            "{} {}: {:?}",
+            name,
+            seed,
+            m
+        );
+        assert_eq!(at.get(&m.entity_id), Some(&m.from), "{} {}", name, seed);
+        at.insert(m.entity_id.clone(), m.to.clone());
+    }
+
+    let mut after = b.snapshot();
+    after.apply_move_plan(plan).expect("plan applies");
+    assert!(
+        hard_violations(&after) <= hard_violations(b),
+        "{} {}: more hard violations",
+        name,
+        seed
+    );
+    assert!(after.validate().is_empty(), "{} {}", name, seed);
+
+    let moved: HashMap<String, String> = b
+        .diff(&after)
+        .moves
+        .into_iter()
+        .map(|m| (m.entity_id, m.to))
+        .collect();
+    assert_eq!(moved, net_moves(b, plan), "{} {}", name, seed);
+}
+
+#[test]
+fn generated_boards_test() {
+    for seed in 0..CASES {
+        let b = Board::generate(&config(seed));
+        assert!(b.validate().is_empty(), "{}", seed);
+    }
+}
+
+#[test]
+fn solve_invariants_test() {
+    let mut solved = 0;
+    for seed in 0..CASES {
+        let config = config(seed);
+        let mut b = Board::generate(&config);
+        let before = hard_violations(&b);
+        let pending = config.pending(1 + seed as usize % 4);
+        let ids: Vec<String> = pending.entities.keys().cloned().collect();
+        let Ok(placement) = b.solve(pending) else {
+            continue;
+        };
+        solved += 1;
+        assert_eq!(placement.assignment.len(), ids.len(), "{}", seed);
+        // placed entities take part in no hard violation.
+        assert!(
+            b.check_all()
+                .iter()
+                .filter(|v| v.is_hard())
+                .all(|v| v.entity_id.as_ref().is_none_or(|e| !ids.contains(e))),
+            "{}",
+            seed
+        );
+        assert!(hard_violations(&b) <= before, "{}", seed);
+        assert!(b.validate().is_empty(), "{}", seed);
+        check_index(seed, &b);
+    }
+    // the cases mostly fit.
+    assert!(solved > CASES / 2, "{}", solved);
+}
+
+#[test]
+fn plan_invariants_test() {
+    let mut drains = 0;
+    for seed in 0..CASES {
+        let b = Board::generate(&config(seed));
+        check_plan(seed, "rebalance", &b, &b.rebalance());
+        let config = SolverConfig {
+            seed,
+            ..Default::default()
+        };
+        check_plan(seed, "anneal", &b, &b.anneal(&config));
+        let mut drained = b.snapshot();
+        if let Ok(plan) = drained.deactivate_resource("node0", DeactivationIntent::Drain) {
+            assert!(plan.moves.iter().all(|m| m.to != "node0"), "{}", seed);
+            check_plan(seed, "drain", &drained, &plan);
+            drains += 1;
+        }
+    }
+    assert!(drains > 0);
+}
+
+#[test]
+fn txn_invariants_test() {
+    for seed in 0..CASES {
+        let config = config(seed);
+        let mut b = Board::generate(&config);
+        b.enable_change_tracking();
+        b.check_violation_incremental();
+        let before = b.snapshot();
+
+        let mut txn = b.begin();
+        let _ = txn.solve(config.pending(2));
+        let plan = txn.rebalance();
+        txn.apply_move_plan(&plan).expect("plan applies");
+        if let Ok((_, pending)) = txn.evict_resource("node0") {
+            let _ = txn.solve(pending);
+        }
+        txn.rollback();
+
+        // a rollback leaves nothing behind, the incremental state included.
+        assert!(before.diff(&b).is_empty(), "{}", seed);
+        assert!(b.validate().is_empty(), "{}", seed);
+        check_index(seed, &b);
+        assert_eq!(
+            b.check_violation_incremental(),
+            b.check_violation(),
+            "{}",
+            seed
+        );
+    }
+}
@@ -0,0 +1,315 @@
+// undo log for transactions. While one is open every change the board api
+// makes records the object or load it replaces, and a rollback writes them
+// back newest first. The board's maps are only written through the set_*
+// and save_* functions here, so nothing is missed; like change tracking,
+// direct writes to the public maps are not recorded. The set_* functions
+// also keep the relation index up to date once it is built, and the role
+// assignment of the groups.
+
+use std::collections::HashMap;
+
+use super::{
+    AffinityChain, Board, DomainRelation, Entity, EntityGroup, EntityId, IDPropertyRelation,
+    IDRelation, IdMap, Placements, PropertyRelation, Resource, ResourceId,
+};
+
+// a replaced value under its id, None when there was none.
+#[derive(Debug)]
+pub(crate) enum Undo {
+    Resource(String, Option<Resource>),
+    Entity(String, Option<Entity>),
+    Assignment(EntityId, Option<ResourceId>),
+    IdRelation(String, Option<IDRelation>),
+    PropertyRelation(String, Option<PropertyRelation>),
+    IdPropertyRelation(String, Option<IDPropertyRelation>),
+    DomainRelation(String, Option<DomainRelation>),
+    Chain(String, Option<AffinityChain>),
+    Group(String, Option<EntityGroup>),
+    Load(ResourceId, Option<HashMap<String, i64>>),
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Journal {
+    // the undo log, None while no transaction is open.
+    log: Option<Vec<Undo>>,
+}
+
+// a copy of the board is not part of the transaction.
+impl Clone for Journal {
+    fn clone(&self) -> Self {
+        Journal::default()
+    }
+}
+
+impl Journal {
+    // starts recording unless a transaction already does. Returns the
+    // savepoint to roll back to and whether the log was opened here.
+    pub(crate) fn open(&mut self) -> (usize, bool) {
+        match &self.log {
+            Some(log) => (log.len(), false),
+            None => {
+                self.log = Some(Vec::new());
+                (0, true)
+            }
+        }
+    }
+
+    // stops recording, the outermost transaction is done.
+    pub(crate) fn close(&mut self) {
+        self.log = None;
+    }
+
+    fn record(&mut self, undo: impl FnOnce() -> Undo) {
+        if let Some(log) = &mut self.log {
+            log.push(undo());
+        }
+    }
+}
+
+// a map by id the undo log writes back to.
+trait Put<T> {
+    // inserts the value or, with None, removes the id.
+    fn put(&mut self, id: &str, value: Option<T>) -> Option<T>;
+}
+
+impl<T> Put<T> for IdMap<T> {
+    fn put(&mut self, id: &str, value: Option<T>) -> Option<T> {
+        match value {
+            Some(value) => self.insert(id.to_string(), value),
+            None => self.remove(id),
+        }
+    }
+}
+
+impl Board {
+    // the set_* functions insert or, with None, remove the object and
+    // return the one they replace.
+
+    pub(crate) fn set_resource(
+        &mut self,
+        id: &str,
+        resource: Option<Resource>,
+    ) -> Option<Resource> {
+        let old = self.resources.put(id, resource);
+        self.journal
+            .record(|| Undo::Resource(id.to_string(), old.clone()));
+        if let Some(index) = self.index.get_mut() {
+            let handle = self.resources.intern(id);
+            index.resource_changed(id, handle, old.as_ref(), self.resources.get(id));
+        }
+        old
+    }
+
+    pub(crate) fn set_entity(&mut self, id: &str, entity: Option<Entity>) -> Option<Entity> {
+        let old = self.entities.put(id, entity);
+        self.journal
+            .record(|| Undo::Entity(id.to_string(), old.clone()));
+        if let Some(index) = self.index.get_mut() {
+            let handle = self.entities.intern(id);
+            index.entity_changed(id, handle, old.as_ref(), self.entities.get(id));
+        }
+        old
+    }
+
+    // the loads are not adjusted, see track_load.
+    pub(crate) fn set_assignment(
+        &mut self,
+        id: &str,
+        resource_id: Option<String>,
+    ) -> Option<String> {
+        let e = self.entities.intern(id);
+        let r = resource_id.map(|r_id| self.resources.intern(&r_id));
+        let old = self.set_placement(e, r)?;
+        Some(self.resources.name(old).clone())
+    }
+
+    fn set_placement(&mut self, e: EntityId, r: Option<ResourceId>) -> Option<ResourceId> {
+        let old = match r {
+            Some(r) => self.assignment.insert(e, r),
+            None => self.assignment.remove(e),
+        };
+        self.journal.record(|| Undo::Assignment(e, old));
+        if let Some(g) = self.roles.group_of(self.entities.name(e)) {
+            let g = &self.groups[g];
+            let assignment = Placements::new(&self.assignment, &self.entities, &self.resources);
+            self.roles.replica_moved(g, assignment);
+        }
+        old
+    }
+
+    pub(crate) fn set_id_relation(
+        &mut self,
+        id: &str,
+        relation: Option<IDRelation>,
+    ) -> Option<IDRelation> {
+        let old = self.id_relations.put(id, relation);
+        self.journal
+            .record(|| Undo::IdRelation(id.to_string(), old.clone()));
+        if let Some(index) = self.index.get_mut() {
+            index.id_relation_changed(old.as_ref(), self.id_relations.get(id));
+        }
+        old
+    }
+
+    pub(crate) fn set_property_relation(
+        &mut self,
+        id: &str,
+        relation: Option<PropertyRelation>,
+    ) -> Option<PropertyRelation> {
+        let old = self.property_relations.put(id, relation);
+        self.journal
+            .record(|| Undo::PropertyRelation(id.to_string(), old.clone()));
+        if let Some(index) = self.index.get_mut() {
+            index.property_relation_changed(
+                &self.entities,
+                old.as_ref(),
+                self.property_relations.get(id),
+            );
+        }
+        old
+    }
+
+    pub(crate) fn set_id_property_relation(
+        &mut self,
+        id: &str,
+        relation: Option<IDPropertyRelation>,
+    ) -> Option<IDPropertyRelation> {
+        let old = self.id_property_relations.put(id, relation);
+        self.journal
+            .record(|| Undo::IdPropertyRelation(id.to_string(), old.clone()));
+        if let Some(index) = self.index.get_mut() {
+            index.id_property_relation_changed(old.as_ref(), self.id_property_relations.get(id));
+        }
+        old
+    }
+
+    pub(crate) fn set_domain_relation(
+        &mut self,
+        id: &str,
+        relation: Option<DomainRelation>,
+    ) -> Option<DomainRelation> {
+        let old = self.domain_relations.put(id, relation);
+        self.journal
+            .record(|| Undo::DomainRelation(id.to_string(), old.clone()));
+        if let Some(index) = self.index.get_mut() {
+            index.domain_relation_changed(
+                &self.entities,
+                old.as_ref(),
+                self.domain_relations.get(id),
+            );
+        }
+        old
+    }
+
+    pub(crate) fn set_chain(
+        &mut self,
+        id: &str,
+        chain: Option<AffinityChain>,
+    ) -> Option<AffinityChain> {
+        let old = self.chains.put(id, chain);
+        self.journal
+            .record(|| Undo::Chain(id.to_string(), old.clone()));
+        if let Some(index) = self.index.get_mut() {
+            index.chain_changed(&self.entities, old.as_ref(), self.chains.get(id));
+        }
+        old
+    }
+
+    pub(crate) fn set_group(
+        &mut self,
+        id: &str,
+        group: Option<EntityGroup>,
+    ) -> Option<EntityGroup> {
+        let old = self.groups.put(id, group);
+        self.journal
+            .record(|| Undo::Group(id.to_string(), old.clone()));
+        let assignment = Placements::new(&self.assignment, &self.entities, &self.resources);
+        self.roles
+            .group_changed(old.as_ref(), self.groups.get(id), assignment);
+        old
+    }
+
+    // the save_* functions record a value before it is changed in place.
+
+    // for changes to the state, which nothing derives from.
+    pub(crate) fn save_resource(&mut self, id: &str) {
+        let resources = &self.resources;
+        self.journal
+            .record(|| Undo::Resource(id.to_string(), resources.get(id).cloned()));
+    }
+
+    // for changes to the metrics; property changes go through set_entity.
+    pub(crate) fn save_entity(&mut self, id: &str) {
+        let entities = &self.entities;
+        self.journal
+            .record(|| Undo::Entity(id.to_string(), entities.get(id).cloned()));
+    }
+
+    pub(crate) fn save_load(&mut self, resource: ResourceId) {
+        let loads = &self.loads;
+        self.journal
+            .record(|| Undo::Load(resource, loads.get(&resource).cloned()));
+    }
+
+    // writes back everything recorded after the savepoint, newest first,
+    // marking what it changes for the next incremental check. Listeners
+    // hear about the violations that change, not about each undone step.
+    pub(crate) fn undo_to(&mut self, savepoint: usize) {
+        let Some(mut log) = self.journal.log.take() else {
+            return;
+        };
+        let undone = log.split_off(savepoint);
+        for undo in undone.into_iter().rev() {
+            match undo {
+                Undo::Resource(id, r) => {
+                    self.touch_all();
+                    self.set_resource(&id, r);
+                }
+                Undo::Entity(id, e) => {
+                    self.touch_related(&id);
+                    self.set_entity(&id, e);
+                    self.touch_related(&id);
+                }
+                Undo::Assignment(e, r) => {
+                    self.touch(&self.entities.name(e).clone());
+                    self.set_placement(e, r);
+                }
+                Undo::IdRelation(id, rel) => {
+                    self.touch_relation(&id);
+                    self.set_id_relation(&id, rel);
+                    self.touch_relation(&id);
+                }
+                Undo::PropertyRelation(id, rel) => {
+                    self.touch_all();
+                    self.set_property_relation(&id, rel);
+                }
+                Undo::IdPropertyRelation(id, rel) => {
+                    self.touch_relation(&id);
+                    self.set_id_property_relation(&id, rel);
+                    self.touch_relation(&id);
+                }
+                Undo::DomainRelation(id, rel) => {
+                    self.touch_all();
+                    self.set_domain_relation(&id, rel);
+                }
+                Undo::Chain(id, chain) => {
+                    self.touch_all();
+                    self.set_chain(&id, chain);
+                }
+                Undo::Group(id, g) => {
+                    self.set_group(&id, g);
+                }
+                Undo::Load(r, load) => match load {
+                    Some(load) => {
+                        self.loads.insert(r, load);
+                    }
+                    None => {
+                        self.loads.remove(&r);
+                    }
+                },
+            }
+        }
+        self.journal.log = Some(log);
+        self.notify_violations();
+    }
+}
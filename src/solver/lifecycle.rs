@@ -0,0 +1,259 @@
+// resource lifecycle: pausing and draining resources.
+
+use super::config::Limits;
+use super::solve::Search;
+use super::{Board, Move, MovePlan, SolveError, SolverError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ResourceState {
+    #[default]
+    Active,
+    // takes no new entities, keeps the ones it has.
+    Paused,
+    // takes no new entities, its entities are being moved off.
+    Draining,
+    // being taken out of the board, its entities are being moved off.
+    // Unlike a draining resource it leaves the board once the last one is
+    // gone.
+    Removed,
+}
+
+impl ResourceState {
+    // whether the solver may place or move entities onto the resource.
+    pub fn accepts_entities(self) -> bool {
+        self == ResourceState::Active
+    }
+}
+
+// why a resource is deactivated, see Board::deactivate_resource.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeactivationIntent {
+    Pause,
+    Drain,
+    Remove,
+}
+
+impl Board {
+    // takes the resource out of placement. Pause keeps its entities where
+    // they are and returns an empty plan; Drain and Remove return a plan
+    // moving every entity on it to active resources with all relations and
+    // capacities satisfied. The board's assignment is not modified, see
+    // apply_move_plan. The state is set even when no evacuation exists. A
+    // removed resource is dropped from the board as soon as no entity is
+    // on it, right away if it is empty.
+    pub fn deactivate_resource(
+        &mut self,
+        resource_id: &str,
+        intent: DeactivationIntent,
+    ) -> Result<MovePlan, SolveError> {
+        if !self.resources.contains_key(resource_id) {
+            return Err(SolveError::Invalid(vec![SolverError::ResourceNotFound(
+                resource_id.to_string(),
+            )]));
+        }
+        self.save_resource(resource_id);
+        let r = self.resources.get_mut(resource_id).expect("resource exist");
+        r.state = match intent {
+            DeactivationIntent::Pause => ResourceState::Paused,
+            DeactivationIntent::Drain => ResourceState::Draining,
+            DeactivationIntent::Remove => ResourceState::Removed,
+        };
+        if intent == DeactivationIntent::Pause {
+            return Ok(MovePlan::default());
+        }
+        let plan = self.drain_plan(resource_id)?;
+        if plan.is_empty() {
+            self.drop_if_removed(resource_id);
+        }
+        Ok(plan)
+    }
+
+    // drops the resource if it is removed and empty, see
+    // ResourceState::Removed.
+    pub(crate) fn drop_if_removed(&mut self, resource_id: &str) {
+        if self
+            .resources
+            .get(resource_id)
+            .is_some_and(|r| r.state == ResourceState::Removed)
+        {
+            // fails while entities are left on it.
+            let _ = self.remove_resource(resource_id);
+        }
+    }
+
+    // puts the resource back into placement.
+    pub fn activate_resource(&mut self, resource_id: &str) -> Result<(), SolverError> {
+        if !self.resources.contains_key(resource_id) {
+            return Err(SolverError::ResourceNotFound(resource_id.to_string()));
+        }
+        self.save_resource(resource_id);
+        let r = self.resources.get_mut(resource_id).expect("resource exist");
+        r.state = ResourceState::Active;
+        Ok(())
+    }
+
+    // moves placing the entities of the resource elsewhere, searched jointly
+    // like solve does for a pending batch. Fails on pinned entities.
+    fn drain_plan(&self, resource_id: &str) -> Result<MovePlan, SolveError> {
+        self.evacuation_plan(&[resource_id.to_string()])
+    }
+
+    // moves placing every entity on the resources elsewhere, see drain_plan.
+    // The resources should not accept entities.
+    pub(crate) fn evacuation_plan(&self, resource_ids: &[String]) -> Result<MovePlan, SolveError> {
+        self.evacuation_plan_within(resource_ids, Limits::default())
+    }
+
+    // evacuation_plan searching within the limits, Interrupted once one is
+    // reached.
+    pub(crate) fn evacuation_plan_within(
+        &self,
+        resource_ids: &[String],
+        limits: Limits,
+    ) -> Result<MovePlan, SolveError> {
+        let mut evacuees: Vec<String> = self
+            .assignment()
+            .iter()
+            .filter(|(_, r)| resource_ids.contains(r))
+            .map(|(e, _)| e.clone())
+            .collect();
+        evacuees.sort();
+        let pinned: Vec<SolverError> = evacuees
+            .iter()
+            .filter(|e| self.entities[*e].pinned)
+            .map(|e| SolverError::Pinned(e.clone()))
+            .collect();
+        if !pinned.is_empty() {
+            return Err(SolveError::Invalid(pinned));
+        }
+
+        let mut search = Search::new(self);
+        search.limits = limits;
+        for e in &evacuees {
+            search.unplace(e);
+        }
+        let mut remaining = evacuees.clone();
+        if !search.run(&mut remaining) {
+            if search.limits.stopped() {
+                return Err(SolveError::Interrupted(evacuees));
+            }
+            return Err(self.solve_failure(&evacuees));
+        }
+        let placement = search.placement(&evacuees);
+        let moves = evacuees
+            .into_iter()
+            .map(|e| Move {
+                to: placement.assignment[&e].clone(),
+                from: self.assignment()[&e].clone(),
+                cost: self.entities[&e].move_cost,
+                entity_id: e,
+            })
+            .collect();
+        Ok(MovePlan {
+            moves,
+            evictions: Vec::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::solver::{
+        Board, DeactivationIntent, Entity, Pending, Resource, ResourceState, SolveError,
+    };
+
+    fn board() -> Board {
+        let mut b = Board::new();
+        for id in ["node1", "node2", "node3"] {
+            let mut r = Resource::new(String::from(id));
+            r.capacities.insert(String::from("cpu"), 4);
+            b.add_resource(r).expect("added");
+        }
+        for (id, r) in [("a", "node1"), ("b", "node1"), ("c", "node2")] {
+            let mut e = Entity::new(String::from(id));
+            e.metrics.insert(String::from("cpu"), 2);
+            b.add_entity(String::from(r), e).expect("added");
+        }
+        b
+    }
+
+    #[test]
+    fn drain_resource_test() {
+        let mut b = board();
+        let plan = b
+            .deactivate_resource("node1", DeactivationIntent::Drain)
+            .expect("drained");
+        assert_eq!(plan.len(), 2);
+        assert!(plan
+            .moves
+            .iter()
+            .all(|m| m.from == "node1" && m.to != "node1"));
+        b.apply_move_plan(&plan).expect("applied");
+        assert!(b.check_all().is_empty());
+        assert!(b.remove_resource("node1").is_ok());
+
+        // nowhere left to go.
+        b.deactivate_resource("node3", DeactivationIntent::Remove)
+            .expect_err("no room");
+        assert_eq!(b.resources["node3"].state, ResourceState::Removed);
+        assert!(matches!(
+            b.deactivate_resource("node9", DeactivationIntent::Pause),
+            Err(SolveError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn remove_resource_state_test() {
+        let mut b = board();
+        let plan = b
+            .deactivate_resource("node2", DeactivationIntent::Drain)
+            .expect("drained");
+        b.apply_move_plan(&plan).expect("applied");
+        assert_eq!(b.assignment()["c"], "node3");
+        // draining keeps the emptied resource, removing drops it, here
+        // right away since it is empty.
+        assert_eq!(b.resources["node2"].state, ResourceState::Draining);
+        b.activate_resource("node2").expect("activated");
+        b.deactivate_resource("node2", DeactivationIntent::Remove)
+            .expect("removed");
+        assert!(!b.resources.contains_key("node2"));
+
+        let mut r = Resource::new(String::from("node4"));
+        r.capacities.insert(String::from("cpu"), 4);
+        b.add_resource(r).expect("added");
+        let plan = b
+            .deactivate_resource("node3", DeactivationIntent::Remove)
+            .expect("removed");
+        assert!(b.resources.contains_key("node3"));
+        b.apply_move_plan(&plan).expect("applied");
+        assert!(!b.resources.contains_key("node3"));
+        assert_eq!(b.assignment()["c"], "node4");
+        assert!(b.check_all().is_empty());
+    }
+
+    #[test]
+    fn pause_resource_test() {
+        let mut b = board();
+        let plan = b
+            .deactivate_resource("node3", DeactivationIntent::Pause)
+            .expect("paused");
+        assert!(plan.is_empty());
+        assert!(!b
+            .find_candidate_resources("c")
+            .contains(&String::from("node3")));
+
+        let mut p = Pending::new();
+        let mut e = Entity::new(String::from("d"));
+        e.metrics.insert(String::from("cpu"), 2);
+        p.add_entity(e);
+        let placement = b.solve(p).expect("placed");
+        assert_eq!(placement.assignment["d"], "node2");
+        let again = Board::from_json(&b.to_json()).expect("parses");
+        assert_eq!(again.resources["node3"].state, ResourceState::Paused);
+
+        b.activate_resource("node3").expect("activated");
+        assert!(b
+            .find_candidate_resources("c")
+            .contains(&String::from("node3")));
+    }
+}
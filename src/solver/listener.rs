@@ -0,0 +1,213 @@
+// observers of board changes, for metrics, audit logs or uis that follow
+// a board without polling it.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
+
+use super::{Board, Violation};
+
+// callbacks for changes made through the board api, every one a no-op by
+// default. Listeners are called in registration order after the change.
+pub trait BoardListener: Send + Sync {
+    // the entity was added on the resource, by add_entity or a pending
+    // batch.
+    fn on_entity_assigned(&self, _entity_id: &str, _resource_id: &str) {}
+    fn on_entity_moved(&self, _entity_id: &str, _from: &str, _to: &str) {}
+    // the entity left the board, from the resource it was on.
+    fn on_entity_removed(&self, _entity_id: &str, _resource_id: &str) {}
+    fn on_load_reported(&self, _entity_id: &str, _metric: &str, _value: i64) {}
+    // an entry of check_all that was not there before the change. A
+    // capacity violation whose overage changes is resolved and created
+    // again.
+    fn on_violation_created(&self, _violation: &Violation) {}
+    fn on_violation_resolved(&self, _violation: &Violation) {}
+}
+
+// registered listeners of a board. Copies of the board, like snapshots
+// and simulations, start without any.
+#[derive(Default)]
+pub(crate) struct Listeners {
+    list: Vec<Arc<dyn BoardListener>>,
+    // check_all as of the last change, kept while listeners are set.
+    violations: HashSet<Violation>,
+}
+
+impl Clone for Listeners {
+    fn clone(&self) -> Self {
+        Listeners::default()
+    }
+}
+
+impl fmt::Debug for Listeners {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Listeners")
+            .field("count", &self.list.len())
+            .finish()
+    }
+}
+
+impl Board {
+    // registers the listener. Changes made by writing the public maps are
+    // not seen. Violation events compare check_all around each change,
+    // which costs a full check per change while any listener is set.
+    pub fn add_listener(&mut self, listener: Arc<dyn BoardListener>) {
+        if self.listeners.list.is_empty() {
+            self.listeners.violations = self.check_all().into_iter().collect();
+        }
+        self.listeners.list.push(listener);
+    }
+
+    pub fn clear_listeners(&mut self) {
+        self.listeners = Listeners::default();
+    }
+
+    pub(crate) fn notify(&self, f: impl Fn(&dyn BoardListener)) {
+        for l in &self.listeners.list {
+            f(l.as_ref());
+        }
+    }
+
+    // reports the violations the last change created and resolved, in
+    // check_all order.
+    pub(crate) fn notify_violations(&mut self) {
+        if self.listeners.list.is_empty() {
+            return;
+        }
+        let now = self.check_all().entries;
+        let before = std::mem::take(&mut self.listeners.violations);
+        let current: HashSet<Violation> = now.iter().cloned().collect();
+        let mut resolved: Vec<&Violation> = before.difference(&current).collect();
+        resolved.sort_by(|a, b| {
+            (&a.relation_id, &a.entity_id, &a.resource_id, &a.metric).cmp(&(
+                &b.relation_id,
+                &b.entity_id,
+                &b.resource_id,
+                &b.metric,
+            ))
+        });
+        for v in resolved {
+            self.notify(|l| l.on_violation_resolved(v));
+        }
+        for v in now.iter().filter(|v| !before.contains(*v)) {
+            self.notify(|l| l.on_violation_created(v));
+        }
+        self.listeners.violations = current;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::solver::{
+        BoardBuilder, BoardListener, EntityBuilder, IDRelation, IDRelationKind, Priority,
+        ResourceBuilder, Violation,
+    };
+
+    // the relation or metric of the violation.
+    fn name(v: &Violation) -> &str {
+        v.relation_id
+            .as_deref()
+            .or(v.metric.as_deref())
+            .unwrap_or("")
+    }
+
+    #[derive(Default)]
+    struct Log(Mutex<Vec<String>>);
+
+    impl Log {
+        fn push(&self, s: String) {
+            self.0.lock().unwrap().push(s);
+        }
+
+        fn take(&self) -> Vec<String> {
+            std::mem::take(&mut self.0.lock().unwrap())
+        }
+    }
+
+    impl BoardListener for Log {
+        fn on_entity_assigned(&self, entity_id: &str, resource_id: &str) {
+            self.push(format!("assigned {} {}", entity_id, resource_id));
+        }
+
+        fn on_entity_moved(&self, entity_id: &str, from: &str, to: &str) {
+            self.push(format!("moved {} {} {}", entity_id, from, to));
+        }
+
+        fn on_entity_removed(&self, entity_id: &str, resource_id: &str) {
+            self.push(format!("removed {} {}", entity_id, resource_id));
+        }
+
+        fn on_load_reported(&self, entity_id: &str, metric: &str, value: i64) {
+            self.push(format!("load {} {} {}", entity_id, metric, value));
+        }
+
+        fn on_violation_created(&self, v: &Violation) {
+            self.push(format!("created {} {}", name(v), v.resource_id));
+        }
+
+        fn on_violation_resolved(&self, v: &Violation) {
+            self.push(format!("resolved {} {}", name(v), v.resource_id));
+        }
+    }
+
+    #[test]
+    fn listener_test() {
+        let mut b = BoardBuilder::new()
+            .resource(ResourceBuilder::new("node1").capacity("cpu", 4))
+            .resource(ResourceBuilder::new("node2").capacity("cpu", 4))
+            .entity("node1", EntityBuilder::new("a").metric("cpu", 2))
+            .build()
+            .expect("builds");
+        let log = Arc::new(Log::default());
+        b.add_listener(log.clone());
+
+        b.add_entity(
+            String::from("node1"),
+            EntityBuilder::new("b").metric("cpu", 1).build(),
+        )
+        .expect("added");
+        b.add_id_relation(IDRelation {
+            id: String::from("apart"),
+            kind: IDRelationKind::EEAntiAffinity,
+            id1: String::from("a"),
+            id2: String::from("b"),
+            priority: Priority::Hard,
+        })
+        .expect("added");
+        assert_eq!(
+            log.take(),
+            [
+                "assigned b node1",
+                "created apart node1",
+                "created apart node1"
+            ]
+        );
+
+        b.move_entity("b", "node2").expect("moved");
+        b.report_load("b", "cpu", 5).expect("reported");
+        assert_eq!(
+            log.take(),
+            [
+                "moved b node1 node2",
+                "resolved apart node1",
+                "resolved apart node1",
+                "load b cpu 5",
+                "created cpu node2"
+            ]
+        );
+
+        // copies do not report, a rolled back transaction reports the
+        // violations it takes back.
+        b.snapshot().remove_entity("b").expect("removed");
+        assert!(log.take().is_empty());
+        let mut txn = b.begin();
+        txn.remove_entity("b").expect("removed");
+        txn.rollback();
+        assert_eq!(
+            log.take(),
+            ["removed b node2", "resolved cpu node2", "created cpu node2"]
+        );
+    }
+}
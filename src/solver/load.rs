@@ -0,0 +1,499 @@
+// loading a board from a declarative toml or json file.
+//
+// [[resources]]
+// id = "node1"
+// properties = ["red"]
+// capacities = { cpu = 8 }
+//
+// [[entities]]
+// id = "app1"
+// resource = "node1"
+// metrics = { cpu = 2 }
+//
+// [[id_relations]]
+// id = "apart"
+// kind = "EEAntiAffinity"
+// id1 = "app1"
+// id2 = "app2"
+//
+// property_relations, id_property_relations, domain_relations, chains and
+// groups use the fields of their types the same way. Groups are only
+// registered, their replicas are listed as entities.
+//
+// [scoring.cpu]
+// weight = 2.0
+// balancing_threshold = 1.5
+
+use std::fmt;
+use std::path::Path;
+
+use crate::json::{str_field, FromJson, JsonError, Value};
+use crate::toml::Document;
+
+use super::{
+    AffinityChain, Board, DomainRelation, Entity, EntityGroup, IDPropertyRelation, IDRelation,
+    Pending, PropertyRelation, Resource, ScoringConfig, SolverError,
+};
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(std::io::Error),
+    // problem at a line of the file. field is the path of the offending
+    // value like "entities[1].resource", empty for syntax errors.
+    Invalid {
+        line: usize,
+        field: String,
+        message: String,
+    },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io(e) => write!(f, "{}", e),
+            LoadError::Invalid {
+                line,
+                field,
+                message,
+            } if field.is_empty() => {
+                write!(f, "line {}: {}", line, message)
+            }
+            LoadError::Invalid {
+                line,
+                field,
+                message,
+            } => write!(f, "line {}: {}: {}", line, field, message),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+// collects problems with the line they were found on.
+struct Loader<'a> {
+    doc: &'a Document,
+    errors: Vec<LoadError>,
+}
+
+impl<'a> Loader<'a> {
+    fn report(&mut self, field: String, message: String) {
+        let line = self.doc.line_of(&field).unwrap_or(0);
+        self.errors.push(LoadError::Invalid {
+            line,
+            field,
+            message,
+        });
+    }
+
+    // decodes every item of a section, reporting the ones that fail.
+    fn items<T: FromJson>(&mut self, section: &str) -> Vec<(String, &'a Value, T)> {
+        let doc = self.doc;
+        let values = match doc.root.get(section) {
+            None => return Vec::new(),
+            Some(Value::Array(a)) => a,
+            Some(_) => {
+                self.report(
+                    section.to_string(),
+                    String::from("expected array of tables"),
+                );
+                return Vec::new();
+            }
+        };
+        let mut out = Vec::new();
+        for (i, v) in values.iter().enumerate() {
+            let path = format!("{}[{}]", section, i);
+            match T::from_value(v) {
+                Ok(t) => out.push((path, v, t)),
+                Err(JsonError(msg)) => {
+                    // decode errors look like ".field: message".
+                    let (field, message) = msg.split_once(": ").unwrap_or(("", &msg));
+                    self.report(format!("{}{}", path, field), message.to_string());
+                }
+            }
+        }
+        out
+    }
+
+    // reports a solver error against the field holding the offending id.
+    fn report_problem(&mut self, path: &str, item: &Value, e: SolverError) {
+        let id = match &e {
+            SolverError::DuplicateId(id)
+            | SolverError::EntityNotFound(id)
+            | SolverError::ResourceNotFound(id)
+            | SolverError::Unassigned(id)
+            | SolverError::RelationNotFound(id)
+            | SolverError::GroupNotFound(id)
+            | SolverError::ResourceInUse(id)
+            | SolverError::MoveMismatch(id)
+            | SolverError::UnsupportedKind(id)
+            | SolverError::Pinned(id) => id.as_str(),
+        };
+        let key = match &e {
+            SolverError::DuplicateId(_) => Some("id"),
+            SolverError::UnsupportedKind(_) => Some("kind"),
+            _ => item.as_object().and_then(|m| {
+                m.iter()
+                    .filter(|(k, _)| k.as_str() != "id")
+                    .find(|(_, v)| v.as_str() == Some(id))
+                    .map(|(k, _)| k.as_str())
+            }),
+        };
+        let field = match key {
+            Some(k) => format!("{}.{}", path, k),
+            None => path.to_string(),
+        };
+        self.report(field, e.to_string());
+    }
+
+    fn load(&mut self) -> Board {
+        let mut b = Board::new();
+
+        for (path, item, r) in self.items::<Resource>("resources") {
+            if let Err(e) = b.add_resource(r) {
+                self.report_problem(&path, item, e);
+            }
+        }
+
+        for (path, item, e) in self.items::<Entity>("entities") {
+            let resource_id = match str_field(item, "resource", "") {
+                Ok(r) => r,
+                Err(_) => {
+                    self.report(
+                        format!("{}.resource", path),
+                        String::from("expected resource id"),
+                    );
+                    continue;
+                }
+            };
+            if let Err(e) = b.add_entity(resource_id, e) {
+                self.report_problem(&path, item, e);
+            }
+        }
+
+        for (path, item, rel) in self.items::<IDRelation>("id_relations") {
+            let problems = b.id_relation_problems(&rel);
+            if problems.is_empty() {
+                b.set_id_relation(&rel.id.clone(), Some(rel));
+            }
+            for e in problems {
+                self.report_problem(&path, item, e);
+            }
+        }
+
+        for (path, item, rel) in self.items::<PropertyRelation>("property_relations") {
+            let problems = b.property_relation_problems(&rel);
+            if problems.is_empty() {
+                b.set_property_relation(&rel.id.clone(), Some(rel));
+            }
+            for e in problems {
+                self.report_problem(&path, item, e);
+            }
+        }
+
+        for (path, item, rel) in self.items::<IDPropertyRelation>("id_property_relations") {
+            let problems = b.id_property_relation_problems(&rel);
+            if problems.is_empty() {
+                b.set_id_property_relation(&rel.id.clone(), Some(rel));
+            }
+            for e in problems {
+                self.report_problem(&path, item, e);
+            }
+        }
+
+        for (path, item, rel) in self.items::<DomainRelation>("domain_relations") {
+            let problems = b.domain_relation_problems(&rel);
+            if problems.is_empty() {
+                b.set_domain_relation(&rel.id.clone(), Some(rel));
+            }
+            for e in problems {
+                self.report_problem(&path, item, e);
+            }
+        }
+
+        for (path, item, chain) in self.items::<AffinityChain>("chains") {
+            let problems = b.chain_problems(&chain);
+            if problems.is_empty() {
+                b.set_chain(&chain.id.clone(), Some(chain));
+            }
+            for e in problems {
+                self.report_problem(&path, item, e);
+            }
+        }
+
+        for (path, item, g) in self.items::<EntityGroup>("groups") {
+            if b.groups.contains_key(&g.id) {
+                self.report_problem(&path, item, SolverError::DuplicateId(g.id));
+                continue;
+            }
+            b.set_group(&g.id.clone(), Some(g));
+        }
+
+        if let Some(v) = self.doc.root.get("scoring") {
+            match ScoringConfig::from_value(v) {
+                Ok(scoring) => b.scoring = scoring,
+                Err(JsonError(msg)) => {
+                    let (field, message) = msg.split_once(": ").unwrap_or(("", &msg));
+                    self.report(format!("scoring{}", field), message.to_string());
+                }
+            }
+        }
+        b
+    }
+
+    fn load_pending(&mut self) -> Pending {
+        let mut p = Pending::new();
+        for (path, item, e) in self.items::<Entity>("entities") {
+            if p.entities.contains_key(&e.id) {
+                self.report_problem(&path, item, SolverError::DuplicateId(e.id));
+                continue;
+            }
+            p.add_entity(e);
+        }
+        for (_, _, rel) in self.items::<IDRelation>("id_relations") {
+            p.id_relations.insert(rel.id.clone(), rel);
+        }
+        for (_, _, rel) in self.items::<PropertyRelation>("property_relations") {
+            p.property_relations.insert(rel.id.clone(), rel);
+        }
+        for (_, _, rel) in self.items::<IDPropertyRelation>("id_property_relations") {
+            p.id_property_relations.insert(rel.id.clone(), rel);
+        }
+        for (_, _, rel) in self.items::<DomainRelation>("domain_relations") {
+            p.domain_relations.insert(rel.id.clone(), rel);
+        }
+        for (_, _, chain) in self.items::<AffinityChain>("chains") {
+            p.chains.insert(chain.id.clone(), chain);
+        }
+        p
+    }
+}
+
+fn invalid(line: usize, message: String) -> Vec<LoadError> {
+    vec![LoadError::Invalid {
+        line,
+        field: String::new(),
+        message,
+    }]
+}
+
+fn read(path: &Path) -> Result<(String, &str), Vec<LoadError>> {
+    let s = std::fs::read_to_string(path).map_err(|e| vec![LoadError::Io(e)])?;
+    match path.extension().and_then(|e| e.to_str()) {
+        Some(ext @ ("toml" | "json")) => Ok((s, ext)),
+        _ => Err(vec![LoadError::Io(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("unsupported file: {}", path.display()),
+        ))]),
+    }
+}
+
+// runs the loader over a toml document.
+fn load_toml<T>(s: &str, f: impl FnOnce(&mut Loader) -> T) -> Result<T, Vec<LoadError>> {
+    let doc = Document::parse(s).map_err(|e| invalid(e.line, e.message))?;
+    let mut loader = Loader {
+        doc: &doc,
+        errors: Vec::new(),
+    };
+    let t = f(&mut loader);
+    if loader.errors.is_empty() {
+        Ok(t)
+    } else {
+        Err(loader.errors)
+    }
+}
+
+impl Board {
+    // loads a board from a .toml or .json file, see the format above. Every
+    // problem is returned instead of stopping at the first one.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Board, Vec<LoadError>> {
+        match read(path.as_ref())? {
+            (s, "toml") => Board::from_toml(&s),
+            (s, _) => Board::from_json(&s).map_err(|e| invalid(0, e.0)),
+        }
+    }
+
+    pub fn from_toml(s: &str) -> Result<Board, Vec<LoadError>> {
+        load_toml(s, |l| l.load())
+    }
+}
+
+impl Pending {
+    // loads a batch from a .toml or .json file in the board format without
+    // resources; entities have no resource. References are checked when
+    // the batch is applied or solved.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Pending, Vec<LoadError>> {
+        match read(path.as_ref())? {
+            (s, "toml") => Pending::from_toml(&s),
+            (s, _) => Value::parse(&s)
+                .and_then(|v| Pending::from_value(&v))
+                .map_err(|e| invalid(0, e.0)),
+        }
+    }
+
+    pub fn from_toml(s: &str) -> Result<Pending, Vec<LoadError>> {
+        load_toml(s, |l| l.load_pending())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::solver::{Board, IDRelationKind, Pending};
+
+    #[test]
+    fn from_toml_test() {
+        let src = r#"
+[[resources]]
+id = "node1"
+properties = ["red"]
+capacities = { cpu = 8 }
+
+[[resources]]
+id = "node2"
+
+[[entities]]
+id = "app1"
+resource = "node1"
+metrics = { cpu = 2 }
+move_cost = 2
+
+[[entities]]
+id = "app2"
+resource = "node2"
+
+[[id_relations]]
+id = "apart"
+kind = "EEAntiAffinity"
+id1 = "app1"
+id2 = "app2"
+"#;
+        let b = Board::from_toml(src).expect("loads");
+        assert_eq!(b.resources["node1"].capacities["cpu"], 8);
+        assert_eq!(b.entities["app1"].move_cost, 2);
+        assert_eq!(b.assignment()["app2"], "node2");
+        assert_eq!(b.id_relations["apart"].kind, IDRelationKind::EEAntiAffinity);
+        assert!(b.check_all().is_empty());
+    }
+
+    #[test]
+    fn from_toml_errors_test() {
+        let src = r#"
+[[resources]]
+id = "node1"
+
+[[entities]]
+id = "app1"
+resource = "node9"
+
+[[id_relations]]
+id = "apart"
+kind = "EEAntiAffinity"
+id1 = "app1"
+id2 = "ghost"
+
+[[property_relations]]
+id = "color"
+kind = "Sideways"
+entity_property = "red"
+resource_property = "red"
+"#;
+        let errors = Board::from_toml(src).expect_err("fails");
+        let msgs: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            msgs,
+            vec![
+                "line 7: entities[0].resource: resource does not exist: node9",
+                "line 12: id_relations[0].id1: entity does not exist: app1",
+                "line 13: id_relations[0].id2: entity does not exist: ghost",
+                "line 17: property_relations[0].kind: unknown property relation kind",
+            ]
+        );
+
+        let errors = Board::from_toml("[[resources]\n").expect_err("fails");
+        assert_eq!(errors[0].to_string(), "line 1: unterminated table header");
+    }
+
+    #[test]
+    fn pending_from_toml_test() {
+        let src = r#"
+[[entities]]
+id = "app3"
+properties = ["red"]
+
+[[entities]]
+id = "app3"
+"#;
+        let errors = Pending::from_toml(src).expect_err("fails");
+        assert_eq!(
+            errors[0].to_string(),
+            "line 7: entities[1].id: id already exist: app3"
+        );
+        let p = Pending::from_toml("[[entities]]\nid = \"app3\"\nproperties = [\"red\"]\n")
+            .expect("loads");
+        assert!(p.entities["app3"].has_property("red"));
+
+        // typed properties come from an inline table.
+        let p = Pending::from_toml(
+            "[[entities]]\nid = \"app3\"\nproperties = { ram = 64, tier = \"gold\" }\n",
+        )
+        .expect("loads");
+        assert!(p.entities["app3"].has_property("ram>=64"));
+        assert!(p.entities["app3"].has_property("tier=gold"));
+    }
+
+    #[test]
+    fn domain_from_toml_test() {
+        let src = r#"
+[[resources]]
+id = "node1"
+fault_domain = "/dc1/rack1"
+
+[[resources]]
+id = "node2"
+fault_domain = "/dc1/rack1"
+
+[[resources]]
+id = "node3"
+fault_domain = "/dc1/rack2"
+
+[[domain_relations]]
+id = "spread"
+domain = "Fault"
+entity_property = "db"
+min_domains = 2
+"#;
+        let mut b = Board::from_toml(src).expect("loads");
+        assert_eq!(
+            b.resources["node3"].fault_domain.as_deref(),
+            Some("/dc1/rack2")
+        );
+        let p = Pending::from_toml(
+            "[[entities]]\nid = \"db1\"\nproperties = [\"db\"]\n\n[[entities]]\nid = \"db2\"\nproperties = [\"db\"]\n",
+        )
+        .expect("loads");
+        let placement = b.solve(p).expect("solves");
+        let racks: std::collections::HashSet<&str> = placement
+            .assignment
+            .values()
+            .map(|r| b.resources[r].fault_domain.as_deref().unwrap())
+            .collect();
+        assert_eq!(racks.len(), 2);
+    }
+
+    #[test]
+    fn scoring_from_toml_test() {
+        let src = "[scoring.cpu]\nweight = 2\nactivity_threshold = 10\n\n[scoring.mem]\nbalancing_threshold = 0.5\n";
+        let b = Board::from_toml(src).expect("loads");
+        assert_eq!(b.scoring.metric("cpu").weight, 2.0);
+        assert_eq!(b.scoring.metric("cpu").activity_threshold, 10);
+        assert_eq!(b.scoring.metric("mem").balancing_threshold, 0.5);
+        assert_eq!(b.scoring.metric("mem").weight, 1.0);
+        let again = Board::from_json(&b.to_json()).expect("parses");
+        assert_eq!(again.scoring, b.scoring);
+
+        let errors = Board::from_toml("[scoring.cpu]\nweight = \"high\"\n").expect_err("fails");
+        assert_eq!(
+            errors[0].to_string(),
+            "line 2: scoring.cpu.weight: expected number"
+        );
+    }
+}
@@ -1,167 +1,332 @@
 // solver lib
 
-use std::{
-    collections::{HashMap, HashSet},
-    io::Error,
-};
+mod anneal;
+mod balance;
+mod builder;
+mod capacity;
+mod chain;
+mod codec;
+mod config;
+mod defrag;
+mod diff;
+mod dot;
+mod error;
+mod exact;
+mod explain;
+mod expr;
+mod generator;
+mod group;
+mod impact;
+mod incremental;
+mod index;
+mod intern;
+#[cfg(test)]
+mod invariants;
+mod journal;
+mod lifecycle;
+mod listener;
+mod load;
+mod moves;
+mod parallel;
+mod partition;
+mod pending;
+mod preempt;
+mod property;
+mod remove;
+mod repair;
+mod rng;
+mod scale;
+mod scoring;
+mod simulation;
+mod solve;
+mod strategy;
+mod trace;
+mod txn;
+mod upgrade;
+mod validate;
+mod violation;
+
+pub use anneal::{AnnealConfig, TemperatureSchedule};
+pub use builder::{BoardBuilder, EntityBuilder, ResourceBuilder};
+pub use capacity::{CapacityViolation, MetricLoadSummary};
+pub use chain::AffinityChain;
+pub use config::{Bounded, CancelToken, Objective, SolverConfig};
+pub use diff::{BoardDiff, ObjectChanges};
+pub use error::SolverError;
+pub use exact::ExactSolver;
+pub use explain::{PlacementExplanation, Rejection, ResourceRejection};
+pub use expr::{ConstraintError, PlacementConstraint};
+pub use generator::GeneratorConfig;
+pub use group::{EntityGroup, GroupShortfall, Role};
+pub use impact::{ImpactReport, MetricImpact};
+pub use intern::{EntityId, Id, IdMap, Placements, ResourceId};
+pub use lifecycle::{DeactivationIntent, ResourceState};
+pub use listener::BoardListener;
+pub use load::LoadError;
+pub use moves::{Eviction, Move, MovePlan};
+pub use property::{Properties, PropertyValue};
+pub use scale::ScaleDownPlan;
+pub use scoring::{MetricScoring, ScoringConfig};
+pub use simulation::Simulation;
+pub use solve::{Placement, SolveError};
+pub use strategy::{BacktrackingSolver, GreedySolver, Solver};
+#[cfg(feature = "trace")]
+pub use trace::{set_global_subscriber, with_subscriber, StderrSubscriber, Subscriber};
+pub use txn::Txn;
+pub use upgrade::{UpgradeBlocker, UpgradeReport, UpgradeStep};
+pub use validate::ConsistencyIssue;
+pub use violation::{Violation, ViolationKind, ViolationReport};
+
+use incremental::ChangeTracker;
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
 
 // board is the root obj that holds all entities
+#[derive(Debug, Clone)]
 pub struct Board {
     // id -> obj
-    pub resources: HashMap<String, Resource>,
-    pub entities: HashMap<String, Entity>,
+    pub resources: IdMap<Resource>,
+    pub entities: IdMap<Entity>,
     // various relations
-    pub id_relations: HashMap<String, IDRelation>,
-    pub property_relations: HashMap<String, PropertyRelation>,
-    pub id_property_relations: HashMap<String, IDPropertyRelation>,
-    // entity id -> resource id.
-    pub assignment: HashMap<String, String>,
+    pub id_relations: IdMap<IDRelation>,
+    pub property_relations: IdMap<PropertyRelation>,
+    pub id_property_relations: IdMap<IDPropertyRelation>,
+    pub domain_relations: IdMap<DomainRelation>,
+    pub chains: IdMap<AffinityChain>,
+    pub groups: IdMap<EntityGroup>,
+    // entity -> resource, see Board::assignment.
+    assignment: intern::Assignment,
+    pub scoring: ScoringConfig,
+    // summed entity metrics per resource, kept up to date by the board api.
+    loads: capacity::Loads,
+    // changes since the last incremental check, when enabled.
+    tracker: Option<ChangeTracker>,
+    // undo log of the open transaction, see Board::begin.
+    journal: journal::Journal,
+    // relations by entity, built on first use and updated with the board.
+    index: OnceLock<index::BoardIndex>,
+    // resources and roles of the groups' replicas, see role_assignment.
+    roles: group::RoleAssignment,
+    listeners: listener::Listeners,
 }
 
 impl Board {
     pub fn new() -> Board {
-        return Board {
-            resources: HashMap::new(),
-            entities: HashMap::new(),
-            id_relations: HashMap::new(),
-            property_relations: HashMap::new(),
-            id_property_relations: HashMap::new(),
-            assignment: HashMap::new(),
-        };
+        Board {
+            resources: IdMap::new(),
+            entities: IdMap::new(),
+            id_relations: IdMap::new(),
+            property_relations: IdMap::new(),
+            id_property_relations: IdMap::new(),
+            domain_relations: IdMap::new(),
+            chains: IdMap::new(),
+            groups: IdMap::new(),
+            assignment: intern::Assignment::default(),
+            scoring: ScoringConfig::default(),
+            loads: capacity::Loads::new(),
+            tracker: None,
+            journal: journal::Journal::default(),
+            index: OnceLock::new(),
+            roles: group::RoleAssignment::default(),
+            listeners: listener::Listeners::default(),
+        }
+    }
+
+    // entity id -> resource id of the placed entities.
+    pub fn assignment(&self) -> Placements<'_> {
+        self.placements(&self.assignment)
+    }
+
+    // an assignment of this board by id.
+    pub(crate) fn placements<'a>(&'a self, assignment: &'a intern::Assignment) -> Placements<'a> {
+        Placements::new(assignment, &self.entities, &self.resources)
+    }
+
+    // the resource of the entity in an assignment of this board.
+    pub(crate) fn resource_in(
+        &self,
+        assignment: &intern::Assignment,
+        entity_id: &str,
+    ) -> Option<&String> {
+        let r = assignment.get(self.entities.handle(entity_id)?)?;
+        Some(self.resources.name(r))
+    }
+
+    // places the entity on the resource in an assignment of this board and
+    // returns where it was.
+    pub(crate) fn place_in(
+        &self,
+        assignment: &mut intern::Assignment,
+        entity_id: &str,
+        resource_id: &str,
+    ) -> Option<&String> {
+        let e = self.entities.handle(entity_id).expect("entity not found");
+        let r = self
+            .resources
+            .handle(resource_id)
+            .expect("resource not found");
+        let old = assignment.insert(e, r)?;
+        Some(self.resources.name(old))
     }
 
-    // success return true
-    pub fn add_resource(&mut self, resource: Resource) -> bool {
+    pub fn add_resource(&mut self, resource: Resource) -> Result<(), SolverError> {
         if self.resources.contains_key(&resource.id) {
-            return false;
+            return Err(SolverError::DuplicateId(resource.id));
         }
-        let op = self.resources.insert(resource.id.clone(), resource);
+        let id = resource.id.clone();
+        let op = self.set_resource(&id, Some(resource));
         assert!(op.is_none());
-        return true;
+        Ok(())
     }
 
-    pub fn add_entity(&mut self, resource_id: String, entity: Entity) -> bool {
+    pub fn add_entity(&mut self, resource_id: String, entity: Entity) -> Result<(), SolverError> {
         if !self.resources.contains_key(&resource_id) {
-            return false; // resource not found.
+            return Err(SolverError::ResourceNotFound(resource_id));
         }
         let entity_id = entity.id.clone();
-        if self.assignment.contains_key(&entity_id) {
-            return false; // entity already assigned
-        }
-        if self.entities.contains_key(&entity_id) {
-            return false; // entity already exist
+        // assigned or not, the id is taken.
+        if self.assignment().contains_key(&entity_id) || self.entities.contains_key(&entity_id) {
+            return Err(SolverError::DuplicateId(entity_id));
         }
-        let op = self.entities.insert(entity_id.clone(), entity);
+        let op = self.set_entity(&entity_id, Some(entity));
         assert!(op.is_none());
 
-        self.assignment.insert(entity_id, resource_id);
-
-        return true;
+        self.touch(&entity_id);
+        self.track_load(&entity_id, &resource_id, 1);
+        self.notify(|l| l.on_entity_assigned(&entity_id, &resource_id));
+        self.set_assignment(&entity_id, Some(resource_id));
+        self.notify_violations();
+        Ok(())
     }
 
     // entities must be added before relations about them
 
-    pub fn add_id_relation(&mut self, relation: IDRelation) -> Result<(), Error> {
-        if self.id_relations.contains_key(&relation.id) {
-            return Err(Error::new(
-                std::io::ErrorKind::AlreadyExists,
-                "id already exist",
-            ));
-        }
-        if relation.kind == IDRelationKind::EEAffinity
-            || relation.kind == IDRelationKind::EEAntiAffinity
-        {
-            // validate ids exist as entities
-            if !self.entities.contains_key(&relation.id1)
-                || !self.entities.contains_key(&relation.id2)
-            {
-                return Err(Error::new(
-                    std::io::ErrorKind::NotFound,
-                    "entity does not exist",
-                ));
-            }
+    pub fn add_id_relation(&mut self, relation: IDRelation) -> Result<(), SolverError> {
+        if let Some(e) = self.id_relation_problems(&relation).into_iter().next() {
+            return Err(e);
         }
-        if relation.kind == IDRelationKind::ERAffinity
-            || relation.kind == IDRelationKind::ERAntiAffinity
+        self.touch(&relation.id1);
+        self.touch(&relation.id2);
+        let id = relation.id.clone();
+        let op = self.set_id_relation(&id, Some(relation));
+        assert!(op.is_none());
+        self.notify_violations();
+        Ok(())
+    }
+
+    pub fn add_property_relation(&mut self, relation: PropertyRelation) -> Result<(), SolverError> {
+        if let Some(e) = self
+            .property_relation_problems(&relation)
+            .into_iter()
+            .next()
         {
-            // validate ids exist as entities
-            if !self.entities.contains_key(&relation.id1) {
-                return Err(Error::new(
-                    std::io::ErrorKind::NotFound,
-                    "entity does not exist",
-                ));
-            }
-            if !self.resources.contains_key(&relation.id2) {
-                return Err(Error::new(
-                    std::io::ErrorKind::NotFound,
-                    "resource does not exist",
-                ));
-            }
+            return Err(e);
         }
-        let op = self.id_relations.insert(relation.id.clone(), relation);
+        self.touch_all();
+        let id = relation.id.clone();
+        let op = self.set_property_relation(&id, Some(relation));
         assert!(op.is_none());
+        self.notify_violations();
         Ok(())
     }
 
-    pub fn add_property_relation(&mut self, relation: PropertyRelation) -> Result<(), Error> {
-        if self.property_relations.contains_key(&relation.id) {
-            return Err(Error::new(
-                std::io::ErrorKind::AlreadyExists,
-                "id already exist",
-            ));
+    pub fn add_id_property_relation(
+        &mut self,
+        relation: IDPropertyRelation,
+    ) -> Result<(), SolverError> {
+        if let Some(e) = self
+            .id_property_relation_problems(&relation)
+            .into_iter()
+            .next()
+        {
+            return Err(e);
         }
-
-        // no further validation done for property values.
-        let op = self
-            .property_relations
-            .insert(relation.id.clone(), relation);
+        self.touch(&relation.entity_id);
+        let id = relation.id.clone();
+        let op = self.set_id_property_relation(&id, Some(relation));
         assert!(op.is_none());
+        self.notify_violations();
         Ok(())
     }
 
-    pub fn add_id_property_relation(&mut self, relation: IDPropertyRelation) -> Result<(), Error> {
-        if !self.entities.contains_key(&relation.entity_id) {
-            return Err(Error::new(
-                std::io::ErrorKind::NotFound,
-                "entity does not exist",
-            ));
+    pub fn add_domain_relation(&mut self, relation: DomainRelation) -> Result<(), SolverError> {
+        if let Some(e) = self.domain_relation_problems(&relation).into_iter().next() {
+            return Err(e);
         }
-        let op = self
-            .id_property_relations
-            .insert(relation.id.clone(), relation);
+        self.touch_all();
+        let id = relation.id.clone();
+        let op = self.set_domain_relation(&id, Some(relation));
         assert!(op.is_none());
+        self.notify_violations();
         Ok(())
     }
 
-    // TODO: change signature
-    // currently it returns the ids of the property relation
-    pub fn check_violation(&self) -> HashSet<String> {
-        let mut property_violation: HashSet<String> = HashSet::new();
-
-        // property check
-        for (_, relation) in &self.property_relations {
-            // check entity property matches resource property
-            let ref ep = relation.entity_property;
-            let ref rp = relation.resource_property;
+    // validation shared by the add_* functions and the builder.
+    // each returns every problem found with the item against the board.
 
-            for (_, e) in &self.entities {
-                if !e.properties.contains(ep) {
-                    continue;
+    fn id_relation_problems(&self, relation: &IDRelation) -> Vec<SolverError> {
+        let mut problems = Vec::new();
+        if self.id_relations.contains_key(&relation.id) {
+            problems.push(SolverError::DuplicateId(relation.id.clone()));
+        }
+        // id1 is always an entity
+        if !self.entities.contains_key(&relation.id1) {
+            problems.push(SolverError::EntityNotFound(relation.id1.clone()));
+        }
+        match relation.kind {
+            IDRelationKind::EEAffinity | IDRelationKind::EEAntiAffinity => {
+                if !self.entities.contains_key(&relation.id2) {
+                    problems.push(SolverError::EntityNotFound(relation.id2.clone()));
                 }
-                // find resource e is assigned to
-                let assiged_r_id = self.assignment.get(&e.id).expect("assignment not found");
-                let r = self.resources.get(assiged_r_id).expect("resouce not found");
-                if !r.properties.contains(rp) {
-                    property_violation.insert(relation.id.clone());
+            }
+            IDRelationKind::ERAffinity | IDRelationKind::ERAntiAffinity => {
+                if !self.resources.contains_key(&relation.id2) {
+                    problems.push(SolverError::ResourceNotFound(relation.id2.clone()));
                 }
             }
         }
-        return property_violation;
+        problems
+    }
+
+    fn property_relation_problems(&self, relation: &PropertyRelation) -> Vec<SolverError> {
+        // no further validation done for property values.
+        if self.property_relations.contains_key(&relation.id) {
+            return vec![SolverError::DuplicateId(relation.id.clone())];
+        }
+        Vec::new()
+    }
+
+    fn id_property_relation_problems(&self, relation: &IDPropertyRelation) -> Vec<SolverError> {
+        let mut problems = Vec::new();
+        if self.id_property_relations.contains_key(&relation.id) {
+            problems.push(SolverError::DuplicateId(relation.id.clone()));
+        }
+        if !self.entities.contains_key(&relation.entity_id) {
+            problems.push(SolverError::EntityNotFound(relation.entity_id.clone()));
+        }
+        if relation.kind.is_ee() {
+            problems.push(SolverError::UnsupportedKind(relation.id.clone()));
+        }
+        problems
+    }
+
+    fn domain_relation_problems(&self, relation: &DomainRelation) -> Vec<SolverError> {
+        if self.domain_relations.contains_key(&relation.id) {
+            return vec![SolverError::DuplicateId(relation.id.clone())];
+        }
+        Vec::new()
     }
 }
 
-// stuff to be added to the board
-// TODO: impl add Pending to Board.
+impl Default for Board {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// stuff to be added to the board, see Board::apply_pending and Board::solve.
+#[derive(Debug, Clone, Default)]
 pub struct Pending {
     // entities to be placed
     pub entities: HashMap<String, Entity>,
@@ -169,56 +334,155 @@ pub struct Pending {
     pub id_relations: HashMap<String, IDRelation>,
     pub property_relations: HashMap<String, PropertyRelation>,
     pub id_property_relations: HashMap<String, IDPropertyRelation>,
+    pub domain_relations: HashMap<String, DomainRelation>,
+    pub chains: HashMap<String, AffinityChain>,
+}
+
+impl Pending {
+    pub fn new() -> Pending {
+        Pending::default()
+    }
+
+    pub fn add_entity(&mut self, entity: Entity) {
+        self.entities.insert(entity.id.clone(), entity);
+    }
 }
 
 // resource is the object that entities can bond to.
+#[derive(Debug, Clone)]
 pub struct Resource {
     pub id: String,
-    pub properties: HashSet<String>,
+    pub properties: Properties,
     pub capacities: HashMap<String, i64>,
+    // capacity always held back, like system overhead.
+    pub reserved: HashMap<String, i64>,
+    // percent of the capacity left after the reservation that new
+    // placements keep free as failover headroom. Existing load is
+    // tolerated up to the full remaining capacity.
+    pub buffer_percent: u8,
+    // failure and maintenance domains as paths like "/dc1/rack2".
+    pub fault_domain: Option<String>,
+    pub upgrade_domain: Option<String>,
+    pub state: ResourceState,
 }
 
 impl Resource {
     pub fn new(id: String) -> Resource {
-        return Resource {
-            id: id,
-            properties: HashSet::new(),
+        Resource {
+            id,
+            properties: Properties::new(),
             capacities: HashMap::new(),
+            reserved: HashMap::new(),
+            buffer_percent: 0,
+            fault_domain: None,
+            upgrade_domain: None,
+            state: ResourceState::Active,
+        }
+    }
+
+    // the capacity of the metric minus its reservation, None if unlimited.
+    pub fn usable_capacity(&self, metric: &str) -> Option<i64> {
+        let cap = self.capacities.get(metric)?;
+        Some(cap - self.reserved.get(metric).copied().unwrap_or(0))
+    }
+
+    // the usable capacity new placements may fill, short of the buffer.
+    pub fn buffered_capacity(&self, metric: &str) -> Option<i64> {
+        let usable = self.usable_capacity(metric)?;
+        Some(usable - usable * i64::from(self.buffer_percent.min(100)) / 100)
+    }
+
+    // the resource's domain of the kind, a resource without one is a
+    // domain of its own.
+    pub fn domain(&self, kind: DomainKind) -> &str {
+        let d = match kind {
+            DomainKind::Fault => &self.fault_domain,
+            DomainKind::Upgrade => &self.upgrade_domain,
         };
+        d.as_deref().unwrap_or(&self.id)
     }
 
+    // adds a tag, "key=value" or a bare "key" that is set to true.
     pub fn add_property(&mut self, p: String) {
-        let ok = self.properties.insert(p);
-        assert!(ok);
+        let (key, value) = property::parse_tag(&p);
+        let old = self.properties.insert(key, value);
+        assert!(old.is_none());
+    }
+
+    pub fn set_property(&mut self, key: String, value: PropertyValue) -> Option<PropertyValue> {
+        self.properties.insert(key, value)
+    }
+
+    // whether a tag like "ssd" or a comparison like "ram>=64" holds.
+    pub fn has_property(&self, spec: &str) -> bool {
+        property::matches(&self.properties, spec)
     }
 }
 
 // entity can be bonded to one resource.
 // different entities can bond to the same resource as long as capacity permits.
+#[derive(Debug, Clone)]
 pub struct Entity {
     pub id: String,
-    pub properties: HashSet<String>,
+    pub properties: Properties,
     pub metrics: HashMap<String, i64>,
     pub move_cost: i64, // move_cost low will be moved fisrt.
+    // resources the entity may be placed on, any if None.
+    pub constraint: Option<PlacementConstraint>,
+    // pinned entities are never moved by the solvers.
+    pub pinned: bool,
+    // higher is more important. Preemption evicts entities of lower
+    // priority only, see SolverConfig::preemption.
+    pub priority: i64,
 }
 
 impl Entity {
     pub fn new(id: String) -> Entity {
-        return Entity {
-            id: id,
-            properties: HashSet::new(),
+        Entity {
+            id,
+            properties: Properties::new(),
             metrics: HashMap::new(),
             move_cost: 0,
-        };
+            constraint: None,
+            pinned: false,
+            priority: 0,
+        }
     }
 
+    // adds a tag, "key=value" or a bare "key" that is set to true.
     pub fn add_property(&mut self, p: String) {
-        let ok = self.properties.insert(p);
-        assert!(ok);
+        let (key, value) = property::parse_tag(&p);
+        let old = self.properties.insert(key, value);
+        assert!(old.is_none());
+    }
+
+    pub fn set_property(&mut self, key: String, value: PropertyValue) -> Option<PropertyValue> {
+        self.properties.insert(key, value)
+    }
+
+    // whether a tag like "ssd" or a comparison like "ram>=64" holds.
+    pub fn has_property(&self, spec: &str) -> bool {
+        property::matches(&self.properties, spec)
+    }
+}
+
+// how strictly a relation is enforced. Hard relations are never broken by
+// the solvers; soft ones may be when needed, at weight penalty per
+// violation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Priority {
+    #[default]
+    Hard,
+    Soft(i64),
+}
+
+impl Priority {
+    pub fn is_hard(self) -> bool {
+        self == Priority::Hard
     }
 }
 
-#[derive(PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum IDRelationKind {
     EEAffinity,
     EEAntiAffinity,
@@ -228,8 +492,8 @@ pub enum IDRelationKind {
 
 // relation specifies relation between entities or entity-resources
 // based on id.
-// TODO: id relation can be replaced with property relation with unique properties,
-// but property relation needs to support EE.
+// TODO: id relation can be replaced with property relation with unique properties.
+#[derive(Debug, Clone)]
 pub struct IDRelation {
     pub id: String,
     pub kind: IDRelationKind,
@@ -237,12 +501,26 @@ pub struct IDRelation {
     // for ER relation, id1 is entity id, id2 is resource id.
     pub id1: String,
     pub id2: String,
+    pub priority: Priority,
 }
 
-#[derive(PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum PropertyRelationKind {
     Affinity,
     AntiAffinity,
+    // entities sharing the entity property must, or must not, share a
+    // resource; the resource property is unused.
+    EEAffinity,
+    EEAntiAffinity,
+}
+
+impl PropertyRelationKind {
+    pub fn is_ee(self) -> bool {
+        matches!(
+            self,
+            PropertyRelationKind::EEAffinity | PropertyRelationKind::EEAntiAffinity
+        )
+    }
 }
 
 // property relation specifies entity's property in relation to resource's property
@@ -250,30 +528,54 @@ pub enum PropertyRelationKind {
 // with resource with inifinit capacity, otherwise 0 capacity?
 // For aniti-affinity, pick the resource with 0 capacity?
 // For EE relation to be supported, select metrics greater than 0 or present?
+#[derive(Debug, Clone)]
 pub struct PropertyRelation {
     pub id: String,
     pub kind: PropertyRelationKind,
     pub entity_property: String,
     pub resource_property: String,
+    pub priority: Priority,
 }
 
-// relation between entity and resource's property
+// relation between entity and resource's property, only the non EE kinds
+// apply.
 // TODO: id property relation can be replaced by
 // a property relation with entity with unique property.
+#[derive(Debug, Clone)]
 pub struct IDPropertyRelation {
     pub id: String,
     pub entity_id: String,
     pub kind: PropertyRelationKind,
     pub resource_property: String,
+    pub priority: Priority,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DomainKind {
+    Fault,
+    Upgrade,
+}
+
+// entities with entity_property must be spread over at least min_domains
+// distinct domains, or over one domain each if there are fewer of them.
+// Members sharing a domain while the spread is short are reported.
+#[derive(Debug, Clone)]
+pub struct DomainRelation {
+    pub id: String,
+    pub domain: DomainKind,
+    pub entity_property: String,
+    pub min_domains: usize,
+    pub priority: Priority,
 }
 
 #[cfg(test)]
 mod tests {
     use crate::solver::PropertyRelation;
 
-    use super::{Board, Entity, Resource};
+    use super::{Board, Entity, Priority, Resource};
 
     #[test]
+    #[allow(clippy::assertions_on_constants)]
     fn fabricclient_test() {
         assert!(true);
     }
@@ -289,23 +591,21 @@ mod tests {
         e1.add_property(String::from("red"));
 
         let mut b = Board::new();
-        let ok = b.add_resource(r1);
-        assert!(ok);
-        let ok = b.add_resource(r2);
-        assert!(ok);
+        b.add_resource(r1).expect("added");
+        b.add_resource(r2).expect("added");
         // violation. blue node has red app
-        let ok = b.add_entity(String::from("node2"), e1);
-        assert!(ok);
+        b.add_entity(String::from("node2"), e1).expect("added");
 
         let rel1 = PropertyRelation {
             id: String::from("color"),
             kind: crate::solver::PropertyRelationKind::Affinity,
             entity_property: String::from("red"),
             resource_property: String::from("red"),
+            priority: Priority::Hard,
         };
         b.add_property_relation(rel1).expect("ok");
 
-        let property_violation = b.check_violation();
+        let property_violation = b.check_violation().relation_ids();
         assert_eq!(property_violation.len(), 1);
         assert!(property_violation.contains(&String::from("color")));
     }
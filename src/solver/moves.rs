@@ -0,0 +1,310 @@
+// moving entities between resources and move plans.
+
+use super::config::Limits;
+use super::trace;
+use super::{Board, Bounded, Objective, SolverConfig, SolverError};
+
+// relocation of one entity.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Move {
+    pub entity_id: String,
+    pub from: String,
+    pub to: String,
+    // move_cost of the entity.
+    pub cost: i64,
+}
+
+// an entity taken off the board to make room for a more important one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Eviction {
+    pub entity_id: String,
+    pub from: String,
+    // the pending entity it makes room for.
+    pub preempted_by: String,
+    // move_cost of the entity.
+    pub cost: i64,
+}
+
+// ordered list of moves for operators to review before applying.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct MovePlan {
+    pub moves: Vec<Move>,
+    // entities removed after the moves, sorted by entity id.
+    pub evictions: Vec<Eviction>,
+}
+
+impl MovePlan {
+    pub fn is_empty(&self) -> bool {
+        self.moves.is_empty() && self.evictions.is_empty()
+    }
+
+    // moves and evictions.
+    pub fn len(&self) -> usize {
+        self.moves.len() + self.evictions.len()
+    }
+
+    // summed move_cost of all moves and evictions.
+    pub fn total_cost(&self) -> i64 {
+        self.moves.iter().map(|m| m.cost).sum::<i64>()
+            + self.evictions.iter().map(|e| e.cost).sum::<i64>()
+    }
+}
+
+impl Board {
+    // reassigns an entity to another resource. Constraints are not checked.
+    // A removed resource it leaves empty is dropped.
+    pub fn move_entity(
+        &mut self,
+        entity_id: &str,
+        target_resource_id: &str,
+    ) -> Result<(), SolverError> {
+        if !self.entities.contains_key(entity_id) {
+            return Err(SolverError::EntityNotFound(entity_id.to_string()));
+        }
+        if !self.resources.contains_key(target_resource_id) {
+            return Err(SolverError::ResourceNotFound(
+                target_resource_id.to_string(),
+            ));
+        }
+        self.touch(entity_id);
+        let from = self.set_assignment(entity_id, Some(target_resource_id.to_string()));
+        match &from {
+            Some(from) => {
+                self.track_load(entity_id, from, -1);
+                self.notify(|l| l.on_entity_moved(entity_id, from, target_resource_id));
+            }
+            None => self.notify(|l| l.on_entity_assigned(entity_id, target_resource_id)),
+        }
+        self.track_load(entity_id, target_resource_id, 1);
+        self.notify_violations();
+        if let Some(from) = from {
+            self.drop_if_removed(&from);
+        }
+        Ok(())
+    }
+
+    // applies the moves in order, then removes the evicted entities. The
+    // plan is checked first: every move and eviction must start from where
+    // the entity is at that point of the plan, otherwise nothing is
+    // applied. Removed resources left empty are dropped.
+    pub fn apply_move_plan(&mut self, plan: &MovePlan) -> Result<(), SolverError> {
+        let mut assignment = self.assignment.clone();
+        for m in &plan.moves {
+            match self.resource_in(&assignment, &m.entity_id) {
+                None => return Err(SolverError::EntityNotFound(m.entity_id.clone())),
+                Some(r) if *r != m.from => {
+                    return Err(SolverError::MoveMismatch(m.entity_id.clone()))
+                }
+                Some(_) => {}
+            }
+            if !self.resources.contains_key(&m.to) {
+                return Err(SolverError::ResourceNotFound(m.to.clone()));
+            }
+            self.place_in(&mut assignment, &m.entity_id, &m.to);
+        }
+        // an entity is evicted once, so a repeated eviction is not found.
+        let mut evicted = assignment;
+        for e in &plan.evictions {
+            let from = self
+                .entities
+                .handle(&e.entity_id)
+                .and_then(|h| evicted.remove(h));
+            match from.map(|r| self.resources.name(r)) {
+                None => return Err(SolverError::EntityNotFound(e.entity_id.clone())),
+                Some(r) if *r != e.from => {
+                    return Err(SolverError::MoveMismatch(e.entity_id.clone()))
+                }
+                Some(_) => {}
+            }
+        }
+        for m in &plan.moves {
+            self.touch(&m.entity_id);
+            self.track_load(&m.entity_id, &m.from, -1);
+            self.track_load(&m.entity_id, &m.to, 1);
+            self.set_assignment(&m.entity_id, Some(m.to.clone()));
+            self.notify(|l| l.on_entity_moved(&m.entity_id, &m.from, &m.to));
+        }
+        self.notify_violations();
+        for e in &plan.evictions {
+            self.remove_entity(&e.entity_id).expect("checked eviction");
+        }
+        for r_id in plan.moves.iter().map(|m| &m.from) {
+            self.drop_if_removed(r_id);
+        }
+        Ok(())
+    }
+
+    // computes moves that first bring the board back to a valid state,
+    // clearing relation and capacity violations where a single move can,
+    // then even out metric load across resources. Pinned entities stay
+    // where they are. The board is not modified; see apply_move_plan.
+    pub fn rebalance(&self) -> MovePlan {
+        self.rebalance_with(&SolverConfig::default())
+    }
+
+    // rebalance keeping the plan within config.move_cost_budget, following
+    // config.objective after the repairs. Repairs take the budget first,
+    // the objective gets what is left.
+    pub fn rebalance_with(&self, config: &SolverConfig) -> MovePlan {
+        self.rebalance_bounded(config).value
+    }
+
+    // rebalance_with within the config's duration, iteration and
+    // cancellation limits, returning the moves chosen until a limit was
+    // reached.
+    pub fn rebalance_bounded(&self, config: &SolverConfig) -> Bounded<MovePlan> {
+        let _span = trace::span("rebalance");
+        let mut limits = Limits::new(config);
+        let mut assignment = self.assignment.clone();
+        let mut moves = {
+            let _span = trace::span("repair");
+            self.repair_moves(&mut assignment, config.move_cost_budget, &mut limits)
+        };
+        let spent: i64 = moves.iter().map(|m| m.cost).sum();
+        let left = config.move_cost_budget.map(|b| b - spent);
+        moves.extend(match config.objective {
+            Objective::Balance => {
+                let _span = trace::span("balance");
+                self.balance_moves(&mut assignment, left, &mut limits)
+            }
+            Objective::Defragment {
+                target_empty_resources,
+            } => {
+                let _span = trace::span("defragment");
+                self.defrag_moves(&mut assignment, left, target_empty_resources, &mut limits)
+            }
+        });
+        trace::count("rebalance.moves", moves.len() as u64);
+        Bounded {
+            value: MovePlan {
+                moves,
+                evictions: Vec::new(),
+            },
+            complete: !limits.stopped(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::solver::{
+        Board, Entity, Eviction, IDRelation, IDRelationKind, Move, MovePlan, Priority, Resource,
+        SolverConfig, SolverError,
+    };
+
+    fn board() -> Board {
+        let mut b = Board::new();
+        for id in ["node1", "node2", "node3"] {
+            b.add_resource(Resource::new(String::from(id)))
+                .expect("added");
+        }
+        let mut a = Entity::new(String::from("a"));
+        a.move_cost = 3;
+        let mut c = Entity::new(String::from("c"));
+        c.move_cost = 1;
+        b.add_entity(String::from("node1"), a).expect("added");
+        b.add_entity(String::from("node1"), c).expect("added");
+        b.add_id_relation(IDRelation {
+            id: String::from("apart"),
+            kind: IDRelationKind::EEAntiAffinity,
+            id1: String::from("a"),
+            id2: String::from("c"),
+            priority: Priority::Hard,
+        })
+        .expect("ok");
+        b
+    }
+
+    #[test]
+    fn move_entity_test() {
+        let mut b = board();
+        b.move_entity("a", "node2").expect("moved");
+        assert_eq!(b.assignment()["a"], "node2");
+        assert!(b.check_all().is_empty());
+        assert_eq!(
+            b.move_entity("a", "node9"),
+            Err(SolverError::ResourceNotFound(String::from("node9")))
+        );
+    }
+
+    #[test]
+    fn rebalance_test() {
+        let mut b = board();
+        let plan = b.rebalance();
+        // the cheaper entity moves, and nothing changes until applied.
+        assert_eq!(
+            plan.moves,
+            vec![Move {
+                entity_id: String::from("c"),
+                from: String::from("node1"),
+                to: String::from("node2"),
+                cost: 1,
+            }]
+        );
+        assert_eq!(b.assignment()["c"], "node1");
+
+        b.apply_move_plan(&plan).expect("applies");
+        assert!(b.check_all().is_empty());
+        // applying again is rejected since c is no longer on node1.
+        assert_eq!(
+            b.apply_move_plan(&plan),
+            Err(SolverError::MoveMismatch(String::from("c")))
+        );
+    }
+
+    #[test]
+    fn apply_evictions_test() {
+        let mut b = board();
+        let evict = |id: &str| Eviction {
+            entity_id: String::from(id),
+            from: String::from("node1"),
+            preempted_by: String::from("z"),
+            cost: 1,
+        };
+        let mut plan = MovePlan {
+            moves: b.rebalance().moves,
+            evictions: vec![evict("a"), evict("a")],
+        };
+        // the second eviction of a fails before anything is applied.
+        assert_eq!(
+            b.apply_move_plan(&plan),
+            Err(SolverError::EntityNotFound(String::from("a")))
+        );
+        assert_eq!(b.assignment()["c"], "node1");
+        assert!(b.entities.contains_key("a"));
+
+        plan.evictions.pop();
+        b.apply_move_plan(&plan).expect("applies");
+        assert_eq!(b.assignment()["c"], "node2");
+        assert!(!b.entities.contains_key("a"));
+    }
+
+    #[test]
+    fn rebalance_budget_test() {
+        let mut b = Board::new();
+        for id in ["node1", "node2", "node3"] {
+            b.add_resource(Resource::new(String::from(id)))
+                .expect("added");
+        }
+        for (id, cost) in [("app1", 4), ("app2", 2), ("app3", 1)] {
+            let mut e = Entity::new(String::from(id));
+            e.metrics.insert(String::from("cpu"), 2);
+            e.move_cost = cost;
+            b.add_entity(String::from("node1"), e).expect("added");
+        }
+
+        let plan = b.rebalance();
+        assert_eq!(plan.len(), 2);
+        assert_eq!(plan.total_cost(), 3);
+
+        // only the cheapest move fits a budget of 2.
+        let config = SolverConfig {
+            move_cost_budget: Some(2),
+            ..Default::default()
+        };
+        let plan = b.rebalance_with(&config);
+        assert_eq!(plan.len(), 1);
+        assert_eq!(plan.moves[0].entity_id, "app3");
+        assert!(plan.total_cost() <= 2);
+    }
+}
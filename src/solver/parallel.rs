@@ -0,0 +1,64 @@
+// evaluation of independent items on several threads, with the parallel
+// feature. Results keep the order of the items, so output stays the same
+// with and without the feature.
+
+// below this many items the threads cost more than they save.
+#[cfg(feature = "parallel")]
+const MIN_ITEMS: usize = 64;
+
+// f applied to every item, in order.
+#[cfg(feature = "parallel")]
+pub(crate) fn map<T: Sync, R: Send>(items: &[T], f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    map_on(threads, items, f)
+}
+
+#[cfg(feature = "parallel")]
+fn map_on<T: Sync, R: Send>(threads: usize, items: &[T], f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    if threads < 2 || items.len() < MIN_ITEMS {
+        return items.iter().map(f).collect();
+    }
+    let chunk = items.len().div_ceil(threads);
+    let f = &f;
+    #[cfg(feature = "trace")]
+    let subscriber = super::trace::current();
+    std::thread::scope(|s| {
+        let handles: Vec<_> = items
+            .chunks(chunk)
+            .map(|part| {
+                let run = move || part.iter().map(f).collect::<Vec<R>>();
+                // workers report to the subscriber of the caller.
+                #[cfg(feature = "trace")]
+                if let Some(sub) = subscriber.clone() {
+                    return s.spawn(move || super::trace::with_subscriber(sub, run));
+                }
+                s.spawn(run)
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|h| h.join().expect("worker panicked"))
+            .collect()
+    })
+}
+
+#[cfg(not(feature = "parallel"))]
+pub(crate) fn map<T, R>(items: &[T], f: impl Fn(&T) -> R) -> Vec<R> {
+    items.iter().map(f).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::map;
+
+    #[test]
+    fn map_order_test() {
+        let items: Vec<usize> = (0..1000).collect();
+        let doubled = map(&items, |i| i * 2);
+        assert_eq!(doubled, (0..1000).map(|i| i * 2).collect::<Vec<_>>());
+        assert!(map(&[] as &[usize], |i| *i).is_empty());
+
+        #[cfg(feature = "parallel")]
+        assert_eq!(super::map_on(3, &items, |i| i * 2), doubled);
+    }
+}
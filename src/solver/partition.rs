@@ -0,0 +1,259 @@
+// splitting a board into independent subproblems.
+
+use std::collections::{HashMap, HashSet};
+
+use super::index::RelationIndex;
+use super::{Board, IDRelationKind};
+
+// minimal union-find over entity indexes.
+struct DisjointSet {
+    parent: Vec<usize>,
+}
+
+impl DisjointSet {
+    fn new(n: usize) -> DisjointSet {
+        DisjointSet {
+            parent: (0..n).collect(),
+        }
+    }
+
+    fn find(&mut self, x: usize) -> usize {
+        let mut root = x;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        // path compression
+        let mut cur = x;
+        while self.parent[cur] != root {
+            let next = self.parent[cur];
+            self.parent[cur] = root;
+            cur = next;
+        }
+        root
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let ra = self.find(a);
+        let rb = self.find(b);
+        if ra == rb {
+            return;
+        }
+        // keep the smaller index as root so the result is stable.
+        if ra < rb {
+            self.parent[rb] = ra;
+        } else {
+            self.parent[ra] = rb;
+        }
+    }
+}
+
+impl Board {
+    // groups entities into sets that share no relation, so each set can be
+    // solved on its own. Only relations that mention several entities couple
+    // them: EE id relations, and the EE property relations, domain relations
+    // and chains, which join every entity having their entity property. A
+    // chain joins its parent as well. Relations constraining a single
+    // entity against resources do not.
+    // Resource capacity is shared by all groups and is not considered here.
+    // Groups are ordered by their smallest entity id.
+    pub fn independent_subproblems(&self) -> Vec<HashSet<String>> {
+        let mut ids: Vec<&String> = self.entities.keys().collect();
+        ids.sort();
+        let index: HashMap<&str, usize> = ids
+            .iter()
+            .enumerate()
+            .map(|(i, id)| (id.as_str(), i))
+            .collect();
+
+        let mut set = DisjointSet::new(ids.len());
+        for relation in self.id_relations.values() {
+            if relation.kind != IDRelationKind::EEAffinity
+                && relation.kind != IDRelationKind::EEAntiAffinity
+            {
+                continue;
+            }
+            if let (Some(a), Some(b)) = (
+                index.get(relation.id1.as_str()),
+                index.get(relation.id2.as_str()),
+            ) {
+                set.union(*a, *b);
+            }
+        }
+        let relations = RelationIndex::new(self);
+        // joins the entities having the property, and the extra entity.
+        let mut join = |spec: &str, extra: Option<&str>| {
+            let mut members = relations
+                .members(spec)
+                .chain(extra)
+                .filter_map(|id| index.get(id));
+            if let Some(first) = members.next() {
+                for m in members {
+                    set.union(*first, *m);
+                }
+            }
+        };
+        for relation in self.property_relations.values() {
+            if relation.kind.is_ee() {
+                join(&relation.entity_property, None);
+            }
+        }
+        // a spread depends on the domains of all its members.
+        for relation in self.domain_relations.values() {
+            join(&relation.entity_property, None);
+        }
+        // a chain keeps its members with each other and with the parent.
+        for chain in self.chains.values() {
+            join(&chain.entity_property, chain.parent.as_deref());
+        }
+
+        // roots are the smallest member index, so collecting in index order
+        // yields groups sorted by their smallest id.
+        let mut groups: Vec<HashSet<String>> = Vec::new();
+        let mut group_of_root: HashMap<usize, usize> = HashMap::new();
+        for (i, id) in ids.iter().enumerate() {
+            let root = set.find(i);
+            let g = *group_of_root.entry(root).or_insert_with(|| {
+                groups.push(HashSet::new());
+                groups.len() - 1
+            });
+            groups[g].insert((*id).clone());
+        }
+        groups
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::solver::{
+        AffinityChain, Board, DomainKind, DomainRelation, Entity, IDRelation, IDRelationKind,
+        Priority, PropertyRelation, PropertyRelationKind, Resource,
+    };
+
+    #[test]
+    fn independent_subproblems_test() {
+        let mut b = Board::new();
+        b.add_resource(Resource::new(String::from("node1")))
+            .expect("added");
+        for id in ["a1", "a2", "a3", "b1", "b2"] {
+            b.add_entity(String::from("node1"), Entity::new(String::from(id)))
+                .expect("added");
+        }
+        // cluster a: a1 - a2 - a3, cluster b: b1 - b2
+        let pairs = [
+            ("ra1", IDRelationKind::EEAffinity, "a1", "a2"),
+            ("ra2", IDRelationKind::EEAntiAffinity, "a3", "a2"),
+            ("rb1", IDRelationKind::EEAffinity, "b2", "b1"),
+        ];
+        for (id, kind, id1, id2) in pairs {
+            b.add_id_relation(IDRelation {
+                id: String::from(id),
+                kind,
+                id1: String::from(id1),
+                id2: String::from(id2),
+                priority: Priority::Hard,
+            })
+            .expect("ok");
+        }
+        // an ER relation does not couple entities.
+        b.add_id_relation(IDRelation {
+            id: String::from("er"),
+            kind: IDRelationKind::ERAffinity,
+            id1: String::from("a1"),
+            id2: String::from("node1"),
+            priority: Priority::Hard,
+        })
+        .expect("ok");
+
+        let groups = b.independent_subproblems();
+        assert_eq!(groups.len(), 2);
+        let expect_a: Vec<String> = ["a1", "a2", "a3"].iter().map(|s| s.to_string()).collect();
+        let expect_b: Vec<String> = ["b1", "b2"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(groups[0], expect_a.into_iter().collect());
+        assert_eq!(groups[1], expect_b.into_iter().collect());
+    }
+
+    // a board with the entities on one resource, each having its property.
+    fn board(entities: &[(&str, &str)]) -> Board {
+        let mut b = Board::new();
+        b.add_resource(Resource::new(String::from("node1")))
+            .expect("added");
+        for (id, property) in entities {
+            let mut e = Entity::new(id.to_string());
+            e.add_property(property.to_string());
+            b.add_entity(String::from("node1"), e).expect("added");
+        }
+        b
+    }
+
+    fn ids(group: &[&str]) -> HashSet<String> {
+        group.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn property_relation_subproblems_test() {
+        let mut b = board(&[("a", "svc=db"), ("b", "svc=db"), ("c", "svc=web")]);
+        b.add_property_relation(PropertyRelation {
+            id: String::from("spread-db"),
+            kind: PropertyRelationKind::EEAntiAffinity,
+            entity_property: String::from("svc=db"),
+            resource_property: String::new(),
+            priority: Priority::Hard,
+        })
+        .expect("added");
+        // an ER property relation constrains each entity alone.
+        b.add_property_relation(PropertyRelation {
+            id: String::from("web-ssd"),
+            kind: PropertyRelationKind::Affinity,
+            entity_property: String::from("svc=web"),
+            resource_property: String::from("ssd"),
+            priority: Priority::Hard,
+        })
+        .expect("added");
+        assert_eq!(b.independent_subproblems(), [ids(&["a", "b"]), ids(&["c"])]);
+    }
+
+    #[test]
+    fn domain_relation_subproblems_test() {
+        let mut b = board(&[
+            ("a", "svc=db"),
+            ("b", "svc=db"),
+            ("c", "svc=db"),
+            ("d", "x"),
+        ]);
+        b.add_domain_relation(DomainRelation {
+            id: String::from("spread"),
+            domain: DomainKind::Fault,
+            entity_property: String::from("svc=db"),
+            min_domains: 3,
+            priority: Priority::Hard,
+        })
+        .expect("added");
+        assert_eq!(
+            b.independent_subproblems(),
+            [ids(&["a", "b", "c"]), ids(&["d"])]
+        );
+    }
+
+    #[test]
+    fn chain_subproblems_test() {
+        let mut b = board(&[
+            ("app", "x"),
+            ("log1", "sidecar"),
+            ("log2", "sidecar"),
+            ("d", "x"),
+        ]);
+        b.add_chain(AffinityChain {
+            id: String::from("sidecars"),
+            entity_property: String::from("sidecar"),
+            parent: Some(String::from("app")),
+            priority: Priority::Hard,
+        })
+        .expect("added");
+        assert_eq!(
+            b.independent_subproblems(),
+            [ids(&["app", "log1", "log2"]), ids(&["d"])]
+        );
+    }
+}
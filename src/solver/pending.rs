@@ -0,0 +1,310 @@
+// staging pending batches into the board.
+
+use super::{Board, Pending, Placement, SolverError};
+
+impl Pending {
+    // drops the entities and the relations and chains naming them.
+    pub(crate) fn remove_entities(&mut self, entity_ids: &[String]) {
+        for id in entity_ids {
+            self.entities.remove(id);
+        }
+        let named = |id: &String| entity_ids.contains(id);
+        self.id_relations
+            .retain(|_, rel| !named(&rel.id1) && !named(&rel.id2));
+        self.id_property_relations
+            .retain(|_, rel| !named(&rel.entity_id));
+        self.chains
+            .retain(|_, chain| !chain.parent.as_ref().is_some_and(named));
+    }
+}
+
+// ids of the objects a staged batch added, used to take them back out.
+#[derive(Default)]
+pub(crate) struct Staged {
+    pub entity_ids: Vec<String>,
+    pub id_relation_ids: Vec<String>,
+    pub property_relation_ids: Vec<String>,
+    pub id_property_relation_ids: Vec<String>,
+    pub domain_relation_ids: Vec<String>,
+    pub chain_ids: Vec<String>,
+}
+
+impl Board {
+    // adds a pending batch with the given assignment for its entities.
+    // Either everything is added or, if any entity or relation conflicts
+    // with the board or the placement is incomplete, nothing is and every
+    // offending item is returned.
+    pub fn apply_pending(
+        &mut self,
+        pending: Pending,
+        placement: &Placement,
+    ) -> Result<(), Vec<SolverError>> {
+        let mut errors = Vec::new();
+        let mut placed: Vec<&String> = placement.assignment.keys().collect();
+        placed.sort();
+        for entity_id in placed {
+            if !pending.entities.contains_key(entity_id) {
+                errors.push(SolverError::EntityNotFound(entity_id.clone()));
+            }
+        }
+
+        // entities the new relations name, for change tracking.
+        let group_relations = !pending.property_relations.is_empty()
+            || !pending.domain_relations.is_empty()
+            || !pending.chains.is_empty();
+        let touched: Vec<String> = pending
+            .id_relations
+            .values()
+            .flat_map(|rel| [rel.id1.clone(), rel.id2.clone()])
+            .chain(
+                pending
+                    .id_property_relations
+                    .values()
+                    .map(|rel| rel.entity_id.clone()),
+            )
+            .collect();
+
+        let staged = match self.stage(pending) {
+            Ok(staged) => staged,
+            Err(mut stage_errors) => {
+                stage_errors.extend(errors);
+                return Err(stage_errors);
+            }
+        };
+        for entity_id in &staged.entity_ids {
+            match placement.assignment.get(entity_id) {
+                None => errors.push(SolverError::Unassigned(entity_id.clone())),
+                Some(r_id) if !self.resources.contains_key(r_id) => {
+                    errors.push(SolverError::ResourceNotFound(r_id.clone()))
+                }
+                Some(_) => {}
+            }
+        }
+        if !errors.is_empty() {
+            self.unstage(staged);
+            return Err(errors);
+        }
+
+        if group_relations {
+            self.touch_all();
+        }
+        for entity_id in &touched {
+            self.touch(entity_id);
+        }
+        for entity_id in staged.entity_ids {
+            self.touch(&entity_id);
+            let r_id = placement.assignment[&entity_id].clone();
+            self.track_load(&entity_id, &r_id, 1);
+            self.notify(|l| l.on_entity_assigned(&entity_id, &r_id));
+            self.set_assignment(&entity_id, Some(r_id));
+        }
+        self.notify_violations();
+        Ok(())
+    }
+
+    // inserts the pending entities, unassigned, and relations into the board.
+    // All items are validated first and nothing is inserted if any of them
+    // conflicts; the returned errors list every offending item.
+    pub(crate) fn stage(&mut self, pending: Pending) -> Result<Staged, Vec<SolverError>> {
+        let mut staged = Staged::default();
+        let mut errors = Vec::new();
+
+        let mut entities: Vec<_> = pending.entities.into_values().collect();
+        entities.sort_by(|a, b| a.id.cmp(&b.id));
+        for e in entities {
+            if self.entities.contains_key(&e.id) {
+                errors.push(SolverError::DuplicateId(e.id.clone()));
+                continue;
+            }
+            staged.entity_ids.push(e.id.clone());
+            self.set_entity(&e.id.clone(), Some(e));
+        }
+
+        // relations are validated against board and pending entities.
+        let mut id_relations: Vec<_> = pending.id_relations.into_values().collect();
+        id_relations.sort_by(|a, b| a.id.cmp(&b.id));
+        for rel in id_relations {
+            let problems = self.id_relation_problems(&rel);
+            if problems.is_empty() {
+                staged.id_relation_ids.push(rel.id.clone());
+                self.set_id_relation(&rel.id.clone(), Some(rel));
+            } else {
+                errors.extend(problems);
+            }
+        }
+        let mut property_relations: Vec<_> = pending.property_relations.into_values().collect();
+        property_relations.sort_by(|a, b| a.id.cmp(&b.id));
+        for rel in property_relations {
+            let problems = self.property_relation_problems(&rel);
+            if problems.is_empty() {
+                staged.property_relation_ids.push(rel.id.clone());
+                self.set_property_relation(&rel.id.clone(), Some(rel));
+            } else {
+                errors.extend(problems);
+            }
+        }
+        let mut id_property_relations: Vec<_> =
+            pending.id_property_relations.into_values().collect();
+        id_property_relations.sort_by(|a, b| a.id.cmp(&b.id));
+        for rel in id_property_relations {
+            let problems = self.id_property_relation_problems(&rel);
+            if problems.is_empty() {
+                staged.id_property_relation_ids.push(rel.id.clone());
+                self.set_id_property_relation(&rel.id.clone(), Some(rel));
+            } else {
+                errors.extend(problems);
+            }
+        }
+        let mut domain_relations: Vec<_> = pending.domain_relations.into_values().collect();
+        domain_relations.sort_by(|a, b| a.id.cmp(&b.id));
+        for rel in domain_relations {
+            let problems = self.domain_relation_problems(&rel);
+            if problems.is_empty() {
+                staged.domain_relation_ids.push(rel.id.clone());
+                self.set_domain_relation(&rel.id.clone(), Some(rel));
+            } else {
+                errors.extend(problems);
+            }
+        }
+        let mut chains: Vec<_> = pending.chains.into_values().collect();
+        chains.sort_by(|a, b| a.id.cmp(&b.id));
+        for chain in chains {
+            let problems = self.chain_problems(&chain);
+            if problems.is_empty() {
+                staged.chain_ids.push(chain.id.clone());
+                self.set_chain(&chain.id.clone(), Some(chain));
+            } else {
+                errors.extend(problems);
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(staged)
+        } else {
+            self.unstage(staged);
+            Err(errors)
+        }
+    }
+
+    // removes a staged batch again, returning it as pending.
+    pub(crate) fn unstage(&mut self, staged: Staged) -> Pending {
+        let mut pending = Pending::new();
+        for id in staged.entity_ids {
+            if let Some(r_id) = self.set_assignment(&id, None) {
+                self.track_load(&id, &r_id, -1);
+            }
+            if let Some(e) = self.set_entity(&id, None) {
+                pending.entities.insert(id, e);
+            }
+        }
+        for id in staged.id_relation_ids {
+            if let Some(rel) = self.set_id_relation(&id, None) {
+                pending.id_relations.insert(id, rel);
+            }
+        }
+        for id in staged.property_relation_ids {
+            if let Some(rel) = self.set_property_relation(&id, None) {
+                pending.property_relations.insert(id, rel);
+            }
+        }
+        for id in staged.id_property_relation_ids {
+            if let Some(rel) = self.set_id_property_relation(&id, None) {
+                pending.id_property_relations.insert(id, rel);
+            }
+        }
+        for id in staged.domain_relation_ids {
+            if let Some(rel) = self.set_domain_relation(&id, None) {
+                pending.domain_relations.insert(id, rel);
+            }
+        }
+        for id in staged.chain_ids {
+            if let Some(chain) = self.set_chain(&id, None) {
+                pending.chains.insert(id, chain);
+            }
+        }
+        pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::solver::{
+        Board, Entity, IDRelation, IDRelationKind, Pending, Placement, Priority, Resource,
+        SolverError,
+    };
+
+    fn board() -> Board {
+        let mut b = Board::new();
+        b.add_resource(Resource::new(String::from("node1")))
+            .expect("added");
+        b.add_entity(String::from("node1"), Entity::new(String::from("app1")))
+            .expect("added");
+        b
+    }
+
+    fn relation(id: &str, id1: &str, id2: &str) -> IDRelation {
+        IDRelation {
+            id: String::from(id),
+            kind: IDRelationKind::EEAffinity,
+            id1: String::from(id1),
+            id2: String::from(id2),
+            priority: Priority::Hard,
+        }
+    }
+
+    #[test]
+    fn apply_pending_test() {
+        let mut b = board();
+        let mut p = Pending::new();
+        p.add_entity(Entity::new(String::from("app2")));
+        p.id_relations.insert(
+            String::from("together"),
+            relation("together", "app1", "app2"),
+        );
+        let mut placement = Placement::default();
+        placement
+            .assignment
+            .insert(String::from("app2"), String::from("node1"));
+
+        b.apply_pending(p, &placement).expect("applies");
+        assert_eq!(b.assignment()["app2"], "node1");
+        assert!(b.id_relations.contains_key("together"));
+    }
+
+    #[test]
+    fn apply_pending_rollback_test() {
+        let mut b = board();
+        let mut p = Pending::new();
+        // duplicate of an existing entity
+        p.add_entity(Entity::new(String::from("app1")));
+        p.add_entity(Entity::new(String::from("app2")));
+        p.add_entity(Entity::new(String::from("app3")));
+        // references an entity that exists nowhere
+        p.id_relations
+            .insert(String::from("r1"), relation("r1", "app2", "ghost"));
+        let mut placement = Placement::default();
+        placement
+            .assignment
+            .insert(String::from("app2"), String::from("node1"));
+
+        let errors = b.apply_pending(p, &placement).expect_err("conflicts");
+        assert_eq!(
+            errors,
+            vec![
+                SolverError::DuplicateId(String::from("app1")),
+                SolverError::EntityNotFound(String::from("ghost")),
+            ]
+        );
+        assert_eq!(b.entities.len(), 1);
+        assert!(b.id_relations.is_empty());
+
+        // a valid batch with an incomplete placement is rejected too.
+        let mut p = Pending::new();
+        p.add_entity(Entity::new(String::from("app2")));
+        p.add_entity(Entity::new(String::from("app3")));
+        let errors = b.apply_pending(p, &placement).expect_err("unassigned");
+        assert_eq!(errors, vec![SolverError::Unassigned(String::from("app3"))]);
+        assert_eq!(b.entities.len(), 1);
+        assert_eq!(b.assignment().len(), 1);
+    }
+}
@@ -0,0 +1,245 @@
+// preemption: evicting less important entities to make room.
+
+use std::cmp::Reverse;
+
+use super::solve::Search;
+use super::trace;
+use super::{Board, Entity, Eviction, MovePlan, Pending, Placement, SolveError};
+
+impl Board {
+    // evictions letting the pending batch be placed, for pending entities
+    // that fit nowhere as the board is. Only unpinned entities of a lower
+    // priority than the pending entity are evicted, on the resource where
+    // that costs the least important, then the fewest and the cheapest
+    // entities. The board is not modified; apply the plan, then solve.
+    pub fn preemption_plan(&self, pending: &Pending) -> Result<MovePlan, SolveError> {
+        self.with_staged(pending, |b, entity_ids| {
+            let placement = b.preempt(entity_ids)?;
+            Ok(MovePlan {
+                moves: Vec::new(),
+                evictions: placement.evictions,
+            })
+        })
+    }
+
+    // placement of the staged entities with the evictions it needs. The
+    // most important entity without a candidate is made room for first,
+    // until the batch can be placed or no eviction helps.
+    pub(crate) fn preempt(&self, entity_ids: &[String]) -> Result<Placement, SolveError> {
+        let mut evictions: Vec<Eviction> = Vec::new();
+        loop {
+            let mut search = Search::new(self);
+            for v in &evictions {
+                search.unplace(&v.entity_id);
+            }
+            let mut remaining = entity_ids.to_vec();
+            if search.run(&mut remaining) {
+                evictions.sort_by(|a, b| a.entity_id.cmp(&b.entity_id));
+                let mut placement = search.placement(entity_ids);
+                placement.evictions = evictions;
+                return Ok(placement);
+            }
+            let mut stuck: Vec<&String> = entity_ids
+                .iter()
+                .filter(|e| search.candidates(e).is_empty())
+                .collect();
+            stuck.sort_by_key(|e| (Reverse(self.entities[*e].priority), *e));
+            match stuck
+                .first()
+                .and_then(|e| self.victims(&mut search, e, &evictions))
+            {
+                Some(victims) => evictions.extend(victims),
+                None => return Err(self.solve_failure(entity_ids)),
+            }
+        }
+    }
+
+    // cheapest evictions on a single resource after which the entity can
+    // take it, None if there are none.
+    fn victims(
+        &self,
+        search: &mut Search,
+        entity_id: &str,
+        evicted: &[Eviction],
+    ) -> Option<Vec<Eviction>> {
+        let priority = self.entities[entity_id].priority;
+        let mut resources: Vec<&String> = self
+            .resources
+            .values()
+            .filter(|r| r.state.accepts_entities())
+            .map(|r| &r.id)
+            .collect();
+        resources.sort();
+
+        let mut best: Option<((i64, usize, i64), Vec<Eviction>)> = None;
+        for r_id in resources {
+            let mut lower: Vec<&Entity> = self
+                .assignment()
+                .iter()
+                .filter(|(e, r)| *r == r_id && !evicted.iter().any(|v| &v.entity_id == *e))
+                .map(|(e, _)| &self.entities[e])
+                .filter(|e| !e.pinned && e.priority < priority)
+                .collect();
+            lower.sort_by(|a, b| {
+                (a.priority, a.move_cost, &a.id).cmp(&(b.priority, b.move_cost, &b.id))
+            });
+
+            let fits = |search: &Search| search.candidates(entity_id).contains(r_id);
+            let mut taken: Vec<&Entity> = Vec::new();
+            for e in lower {
+                search.unplace(&e.id);
+                taken.push(e);
+                if fits(search) {
+                    break;
+                }
+            }
+            if fits(search) {
+                // take back the ones not needed, most important first.
+                for i in (0..taken.len()).rev() {
+                    search.place(&taken[i].id, r_id);
+                    if fits(search) {
+                        taken.remove(i);
+                    } else {
+                        search.unplace(&taken[i].id);
+                    }
+                }
+                let key = (
+                    taken.iter().map(|e| e.priority).max().unwrap_or(i64::MIN),
+                    taken.len(),
+                    taken.iter().map(|e| e.move_cost).sum(),
+                );
+                if best.as_ref().is_none_or(|(k, _)| key < *k) {
+                    let evictions = taken
+                        .iter()
+                        .map(|e| Eviction {
+                            entity_id: e.id.clone(),
+                            from: r_id.clone(),
+                            preempted_by: entity_id.to_string(),
+                            cost: e.move_cost,
+                        })
+                        .collect();
+                    best = Some((key, evictions));
+                }
+            }
+            for e in taken {
+                search.place(&e.id, r_id);
+            }
+        }
+
+        let (_, evictions) = best?;
+        for v in &evictions {
+            trace::event("preempt", || {
+                format!("{} evicts {} on {}", entity_id, v.entity_id, v.from)
+            });
+        }
+        Some(evictions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::solver::{
+        Board, BoardBuilder, EntityBuilder, IDRelation, IDRelationKind, Pending, Priority,
+        ResourceBuilder, SolveError, SolverConfig,
+    };
+
+    fn board() -> Board {
+        BoardBuilder::new()
+            .resource(ResourceBuilder::new("node1").capacity("cpu", 4))
+            .resource(ResourceBuilder::new("node2").capacity("cpu", 4))
+            .entity("node1", EntityBuilder::new("batch1").metric("cpu", 2))
+            .entity(
+                "node1",
+                EntityBuilder::new("batch2").metric("cpu", 2).move_cost(5),
+            )
+            .entity(
+                "node2",
+                EntityBuilder::new("web").metric("cpu", 3).priority(5),
+            )
+            .build()
+            .expect("builds")
+    }
+
+    fn urgent(priority: i64) -> Pending {
+        let mut p = Pending::new();
+        p.add_entity(
+            EntityBuilder::new("db")
+                .metric("cpu", 2)
+                .priority(priority)
+                .build(),
+        );
+        p
+    }
+
+    #[test]
+    fn preemption_plan_test() {
+        let b = board();
+        let plan = b.preemption_plan(&urgent(10)).expect("room made");
+        // the cheaper of the batch entities goes. web is less important
+        // than db too, but more than they are.
+        assert!(plan.moves.is_empty());
+        let evicted: Vec<(&str, &str, &str)> = plan
+            .evictions
+            .iter()
+            .map(|e| {
+                (
+                    e.entity_id.as_str(),
+                    e.from.as_str(),
+                    e.preempted_by.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(evicted, [("batch1", "node1", "db")]);
+        assert_eq!(plan.total_cost(), 0);
+        let mut applied = b.clone();
+        applied.apply_move_plan(&plan).expect("applied");
+        assert!(!applied.entities.contains_key("batch1"));
+        applied.solve(urgent(10)).expect("placed");
+
+        // never the reverse: nothing is less important than priority 0.
+        assert!(matches!(
+            b.preemption_plan(&urgent(0)),
+            Err(SolveError::NoCandidates(_))
+        ));
+    }
+
+    #[test]
+    fn solve_preemption_test() {
+        let mut b = board();
+        b.add_id_relation(IDRelation {
+            id: String::from("pair"),
+            kind: IDRelationKind::EEAffinity,
+            id1: String::from("batch1"),
+            id2: String::from("batch2"),
+            priority: Priority::Soft(1),
+        })
+        .expect("added");
+        let config = SolverConfig {
+            preemption: true,
+            ..Default::default()
+        };
+        let mut p = urgent(10);
+        p.id_relations.insert(
+            String::from("away"),
+            IDRelation {
+                id: String::from("away"),
+                kind: IDRelationKind::EEAntiAffinity,
+                id1: String::from("db"),
+                id2: String::from("batch1"),
+                priority: Priority::Soft(1),
+            },
+        );
+        let placement = b.solve_bounded(p, &config).expect("placed").value;
+        assert_eq!(placement.assignment["db"], "node1");
+        assert_eq!(placement.evictions.len(), 1);
+        assert!(!b.entities.contains_key("batch1"));
+        assert!(!b.id_relations.contains_key("pair"));
+        assert!(!b.id_relations.contains_key("away"));
+        assert!(b.validate().is_empty());
+        assert!(b.check_all().is_empty());
+
+        // without preemption the batch fails as before.
+        let mut b = board();
+        assert!(b.solve(urgent(10)).is_err());
+    }
+}
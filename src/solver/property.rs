@@ -0,0 +1,155 @@
+// typed resource and entity properties.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PropertyValue {
+    Str(String),
+    Int(i64),
+    Bool(bool),
+}
+
+impl PropertyValue {
+    // integers and true/false are typed, anything else is a string.
+    pub fn parse(s: &str) -> PropertyValue {
+        if let Ok(i) = s.parse::<i64>() {
+            return PropertyValue::Int(i);
+        }
+        match s {
+            "true" => PropertyValue::Bool(true),
+            "false" => PropertyValue::Bool(false),
+            _ => PropertyValue::Str(s.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for PropertyValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PropertyValue::Str(s) => f.write_str(s),
+            PropertyValue::Int(i) => write!(f, "{}", i),
+            PropertyValue::Bool(b) => write!(f, "{}", b),
+        }
+    }
+}
+
+impl From<&str> for PropertyValue {
+    fn from(s: &str) -> Self {
+        PropertyValue::Str(s.to_string())
+    }
+}
+
+impl From<i64> for PropertyValue {
+    fn from(i: i64) -> Self {
+        PropertyValue::Int(i)
+    }
+}
+
+impl From<bool> for PropertyValue {
+    fn from(b: bool) -> Self {
+        PropertyValue::Bool(b)
+    }
+}
+
+pub type Properties = HashMap<String, PropertyValue>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Op {
+    // ordering needs integers on both sides and values of different
+    // types are never equal.
+    pub(crate) fn compare(self, lhs: &PropertyValue, rhs: &PropertyValue) -> bool {
+        match self {
+            Op::Eq => lhs == rhs,
+            Op::Ne => lhs != rhs,
+            _ => {
+                let (PropertyValue::Int(l), PropertyValue::Int(r)) = (lhs, rhs) else {
+                    return false;
+                };
+                let ord = l.cmp(r);
+                match self {
+                    Op::Lt => ord == Ordering::Less,
+                    Op::Le => ord != Ordering::Greater,
+                    Op::Gt => ord == Ordering::Greater,
+                    _ => ord != Ordering::Less,
+                }
+            }
+        }
+    }
+}
+
+// splits a "key=value" tag into its key and typed value, a bare tag is
+// a true flag.
+pub(crate) fn parse_tag(tag: &str) -> (String, PropertyValue) {
+    match tag.split_once('=') {
+        Some((k, v)) => (k.to_string(), PropertyValue::parse(v)),
+        None => (tag.to_string(), PropertyValue::Bool(true)),
+    }
+}
+
+// splits a relation's property into its key and the comparison, if any.
+// The property is either a tag like "ssd" or a comparison like
+// "ram>=64", where a single "=" means "==".
+pub(crate) fn parse_spec(spec: &str) -> (&str, Option<(Op, &str)>) {
+    const OPS: [(&str, Op); 7] = [
+        ("==", Op::Eq),
+        ("!=", Op::Ne),
+        ("<=", Op::Le),
+        (">=", Op::Ge),
+        ("<", Op::Lt),
+        (">", Op::Gt),
+        ("=", Op::Eq),
+    ];
+    for (text, op) in OPS {
+        if let Some((key, value)) = spec.split_once(text) {
+            return (key.trim(), Some((op, value.trim())));
+        }
+    }
+    (spec, None)
+}
+
+// whether the properties satisfy a relation's property, see parse_spec. A
+// tag holds when it is set and not false.
+pub(crate) fn matches(properties: &Properties, spec: &str) -> bool {
+    match parse_spec(spec) {
+        (key, Some((op, value))) => properties
+            .get(key)
+            .is_some_and(|v| op.compare(v, &PropertyValue::parse(value))),
+        (key, None) => properties
+            .get(key)
+            .is_some_and(|v| *v != PropertyValue::Bool(false)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{matches, parse_tag, Properties, PropertyValue};
+
+    #[test]
+    fn property_match_test() {
+        let props: Properties = ["ssd", "ram=64", "zone=z1", "spot=false"]
+            .into_iter()
+            .map(parse_tag)
+            .collect();
+        assert_eq!(props["ram"], PropertyValue::Int(64));
+        assert!(matches(&props, "ssd"));
+        assert!(!matches(&props, "spot"));
+        assert!(!matches(&props, "gpu"));
+        assert!(matches(&props, "ram>=64"));
+        assert!(!matches(&props, "ram > 64"));
+        assert!(matches(&props, "zone=z1"));
+        assert!(matches(&props, "zone!=z2"));
+        // ordering on strings never holds.
+        assert!(!matches(&props, "zone<z2"));
+    }
+}
@@ -0,0 +1,427 @@
+// removal and update of board objects, keeping the board consistent.
+
+use super::{
+    Board, DomainRelation, Entity, IDPropertyRelation, IDRelation, IDRelationKind, IdMap, Pending,
+    PropertyRelation, Resource, SolverError,
+};
+
+impl Board {
+    // removes the entity with its assignment and every relation that
+    // references it, including the chains it is the parent of. A removed
+    // resource it leaves empty is dropped.
+    pub fn remove_entity(&mut self, entity_id: &str) -> Result<Entity, SolverError> {
+        if !self.entities.contains_key(entity_id) {
+            return Err(SolverError::EntityNotFound(entity_id.to_string()));
+        }
+        self.touch_related(entity_id);
+        if let Some(r_id) = self.assignment().get(entity_id).cloned() {
+            self.track_load(entity_id, &r_id, -1);
+        }
+        let e = self.set_entity(entity_id, None).expect("entity exist");
+        let from = self.set_assignment(entity_id, None);
+        if let Some(r_id) = &from {
+            self.notify(|l| l.on_entity_removed(entity_id, r_id));
+        }
+        for id in ids_where(&self.id_relations, |rel| references_entity(rel, entity_id)) {
+            self.set_id_relation(&id, None);
+        }
+        for id in ids_where(&self.id_property_relations, |rel| {
+            rel.entity_id == entity_id
+        }) {
+            self.set_id_property_relation(&id, None);
+        }
+        for id in ids_where(&self.chains, |chain| {
+            chain.parent.as_deref() == Some(entity_id)
+        }) {
+            self.set_chain(&id, None);
+        }
+        self.notify_violations();
+        if let Some(r_id) = from {
+            self.drop_if_removed(&r_id);
+        }
+        Ok(e)
+    }
+
+    // removes a resource that has no entities assigned. ER relations that
+    // point at it are removed as well.
+    pub fn remove_resource(&mut self, resource_id: &str) -> Result<Resource, SolverError> {
+        if !self.resources.contains_key(resource_id) {
+            return Err(SolverError::ResourceNotFound(resource_id.to_string()));
+        }
+        if self.assignment().values().any(|r| r == resource_id) {
+            return Err(SolverError::ResourceInUse(resource_id.to_string()));
+        }
+        self.touch_all();
+        let resource = self.take_resource(resource_id);
+        self.notify_violations();
+        Ok(resource)
+    }
+
+    // removes a resource and evicts its entities into the returned pending,
+    // together with the relations that reference them and the chains they
+    // are the parent of, so they can be placed again with solve. ER relations that point at the removed
+    // resource are dropped since they can no longer be satisfied.
+    pub fn evict_resource(
+        &mut self,
+        resource_id: &str,
+    ) -> Result<(Resource, Pending), SolverError> {
+        if !self.resources.contains_key(resource_id) {
+            return Err(SolverError::ResourceNotFound(resource_id.to_string()));
+        }
+        self.touch_all();
+        let resource = self.take_resource(resource_id);
+
+        let mut evicted: Vec<String> = self
+            .assignment()
+            .iter()
+            .filter(|(_, r)| *r == resource_id)
+            .map(|(e, _)| e.clone())
+            .collect();
+        evicted.sort();
+
+        let mut pending = Pending::new();
+        for entity_id in &evicted {
+            for id in ids_where(&self.id_relations, |rel| references_entity(rel, entity_id)) {
+                let rel = self.set_id_relation(&id, None).expect("relation exist");
+                pending.id_relations.insert(id, rel);
+            }
+            for id in ids_where(&self.id_property_relations, |rel| {
+                &rel.entity_id == entity_id
+            }) {
+                let rel = self
+                    .set_id_property_relation(&id, None)
+                    .expect("relation exist");
+                pending.id_property_relations.insert(id, rel);
+            }
+            for id in ids_where(&self.chains, |chain| {
+                chain.parent.as_ref() == Some(entity_id)
+            }) {
+                let chain = self.set_chain(&id, None).expect("chain exist");
+                pending.chains.insert(id, chain);
+            }
+            self.set_assignment(entity_id, None);
+            self.notify(|l| l.on_entity_removed(entity_id, resource_id));
+            let e = self.set_entity(entity_id, None).expect("entity exist");
+            pending.add_entity(e);
+        }
+        self.notify_violations();
+        Ok((resource, pending))
+    }
+
+    fn take_resource(&mut self, resource_id: &str) -> Resource {
+        for id in ids_where(&self.id_relations, |rel| {
+            matches!(
+                rel.kind,
+                IDRelationKind::ERAffinity | IDRelationKind::ERAntiAffinity
+            ) && rel.id2 == resource_id
+        }) {
+            self.set_id_relation(&id, None);
+        }
+        let r = self.resources.handle(resource_id).expect("resource exist");
+        self.save_load(r);
+        self.loads.remove(&r);
+        self.set_resource(resource_id, None)
+            .expect("resource exist")
+    }
+
+    // removes the relations of any type with the id. Ids are only unique
+    // within a type, so relations of several types may go at once.
+    pub fn remove_relation(&mut self, relation_id: &str) -> Result<(), SolverError> {
+        self.touch_relation(relation_id);
+        let found = [
+            self.set_id_relation(relation_id, None).is_some(),
+            self.set_property_relation(relation_id, None).is_some(),
+            self.set_id_property_relation(relation_id, None).is_some(),
+            self.set_domain_relation(relation_id, None).is_some(),
+            self.set_chain(relation_id, None).is_some(),
+        ];
+        if found.contains(&true) {
+            self.notify_violations();
+            Ok(())
+        } else {
+            Err(SolverError::RelationNotFound(relation_id.to_string()))
+        }
+    }
+
+    // update functions replace an existing object with the same id and
+    // return the previous value.
+
+    pub fn update_resource(&mut self, resource: Resource) -> Result<Resource, SolverError> {
+        if !self.resources.contains_key(&resource.id) {
+            return Err(SolverError::ResourceNotFound(resource.id));
+        }
+        self.touch_all();
+        let id = resource.id.clone();
+        let old = self
+            .set_resource(&id, Some(resource))
+            .expect("resource exist");
+        self.notify_violations();
+        Ok(old)
+    }
+
+    // the entity keeps its assignment.
+    pub fn update_entity(&mut self, entity: Entity) -> Result<Entity, SolverError> {
+        if !self.entities.contains_key(&entity.id) {
+            return Err(SolverError::EntityNotFound(entity.id));
+        }
+        self.touch_all();
+        let r_id = self.assignment().get(&entity.id).cloned();
+        if let Some(r_id) = &r_id {
+            self.track_load(&entity.id, r_id, -1);
+        }
+        let id = entity.id.clone();
+        let old = self.set_entity(&id, Some(entity)).expect("entity exist");
+        if let Some(r_id) = r_id {
+            self.track_load(&old.id, &r_id, 1);
+        }
+        self.notify_violations();
+        Ok(old)
+    }
+
+    pub fn update_id_relation(&mut self, relation: IDRelation) -> Result<IDRelation, SolverError> {
+        self.touch_relation(&relation.id);
+        self.touch(&relation.id1);
+        self.touch(&relation.id2);
+        let id = relation.id.clone();
+        let old = self
+            .set_id_relation(&id, None)
+            .ok_or_else(|| SolverError::RelationNotFound(id.clone()))?;
+        if let Some(e) = self.id_relation_problems(&relation).into_iter().next() {
+            self.set_id_relation(&id, Some(old));
+            return Err(e);
+        }
+        self.set_id_relation(&id, Some(relation));
+        self.notify_violations();
+        Ok(old)
+    }
+
+    pub fn update_property_relation(
+        &mut self,
+        relation: PropertyRelation,
+    ) -> Result<PropertyRelation, SolverError> {
+        self.touch_all();
+        if !self.property_relations.contains_key(&relation.id) {
+            return Err(SolverError::RelationNotFound(relation.id));
+        }
+        let id = relation.id.clone();
+        let old = self
+            .set_property_relation(&id, Some(relation))
+            .expect("relation exist");
+        self.notify_violations();
+        Ok(old)
+    }
+
+    pub fn update_id_property_relation(
+        &mut self,
+        relation: IDPropertyRelation,
+    ) -> Result<IDPropertyRelation, SolverError> {
+        self.touch_relation(&relation.id);
+        self.touch(&relation.entity_id);
+        let id = relation.id.clone();
+        let old = self
+            .set_id_property_relation(&id, None)
+            .ok_or_else(|| SolverError::RelationNotFound(id.clone()))?;
+        if let Some(e) = self
+            .id_property_relation_problems(&relation)
+            .into_iter()
+            .next()
+        {
+            self.set_id_property_relation(&id, Some(old));
+            return Err(e);
+        }
+        self.set_id_property_relation(&id, Some(relation));
+        self.notify_violations();
+        Ok(old)
+    }
+
+    pub fn update_domain_relation(
+        &mut self,
+        relation: DomainRelation,
+    ) -> Result<DomainRelation, SolverError> {
+        self.touch_all();
+        if !self.domain_relations.contains_key(&relation.id) {
+            return Err(SolverError::RelationNotFound(relation.id));
+        }
+        let id = relation.id.clone();
+        let old = self
+            .set_domain_relation(&id, Some(relation))
+            .expect("relation exist");
+        self.notify_violations();
+        Ok(old)
+    }
+}
+
+// ids of the objects matching f, collected so they can be removed.
+fn ids_where<T>(map: &IdMap<T>, f: impl Fn(&T) -> bool) -> Vec<String> {
+    map.iter()
+        .filter(|(_, v)| f(v))
+        .map(|(id, _)| id.clone())
+        .collect()
+}
+
+fn references_entity(rel: &IDRelation, entity_id: &str) -> bool {
+    match rel.kind {
+        IDRelationKind::EEAffinity | IDRelationKind::EEAntiAffinity => {
+            rel.id1 == entity_id || rel.id2 == entity_id
+        }
+        IDRelationKind::ERAffinity | IDRelationKind::ERAntiAffinity => rel.id1 == entity_id,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::solver::{
+        AffinityChain, Board, Entity, IDRelation, IDRelationKind, Priority, PropertyRelation,
+        PropertyRelationKind, Resource, SolverError,
+    };
+
+    fn board() -> Board {
+        let mut b = Board::new();
+        for id in ["node1", "node2"] {
+            b.add_resource(Resource::new(String::from(id)))
+                .expect("added");
+        }
+        for (id, r) in [("a", "node1"), ("b", "node1"), ("c", "node2")] {
+            b.add_entity(String::from(r), Entity::new(String::from(id)))
+                .expect("added");
+        }
+        let relations = [
+            ("ab", IDRelationKind::EEAffinity, "a", "b"),
+            ("bc", IDRelationKind::EEAntiAffinity, "b", "c"),
+            ("c-on-2", IDRelationKind::ERAffinity, "c", "node2"),
+        ];
+        for (id, kind, id1, id2) in relations {
+            b.add_id_relation(IDRelation {
+                id: String::from(id),
+                kind,
+                id1: String::from(id1),
+                id2: String::from(id2),
+                priority: Priority::Hard,
+            })
+            .expect("ok");
+        }
+        b
+    }
+
+    #[test]
+    fn remove_entity_test() {
+        let mut b = board();
+        let e = b.remove_entity("b").expect("removed");
+        assert_eq!(e.id, "b");
+        assert!(!b.assignment().contains_key("b"));
+        let mut left: Vec<&String> = b.id_relations.keys().collect();
+        left.sort();
+        assert_eq!(left, vec!["c-on-2"]);
+        assert_eq!(
+            b.remove_entity("b").err(),
+            Some(SolverError::EntityNotFound(String::from("b")))
+        );
+    }
+
+    #[test]
+    fn remove_resource_test() {
+        let mut b = board();
+        assert_eq!(
+            b.remove_resource("node2").err(),
+            Some(SolverError::ResourceInUse(String::from("node2")))
+        );
+
+        let (r, pending) = b.evict_resource("node2").expect("evicted");
+        assert_eq!(r.id, "node2");
+        assert!(!b.resources.contains_key("node2"));
+        assert!(pending.entities.contains_key("c"));
+        assert!(!b.entities.contains_key("c"));
+        // the pair relation travels with the evicted entity and the ER
+        // relation to the removed resource is dropped.
+        assert!(pending.id_relations.contains_key("bc"));
+        assert!(!pending.id_relations.contains_key("c-on-2"));
+        assert!(!b.id_relations.contains_key("c-on-2"));
+
+        // the evicted batch can be placed again, away from b.
+        b.add_resource(Resource::new(String::from("node3")))
+            .expect("added");
+        b.solve(pending).expect("solves");
+        assert_eq!(b.assignment()["c"], "node3");
+        assert!(b.check_violation().is_empty());
+    }
+
+    #[test]
+    fn evict_chain_test() {
+        let mut b = board();
+        b.add_chain(AffinityChain {
+            id: String::from("under-c"),
+            entity_property: String::from("tier"),
+            parent: Some(String::from("c")),
+            priority: Priority::Hard,
+        })
+        .expect("added");
+        // the chain leaves with its parent and comes back with it.
+        let (_, pending) = b.evict_resource("node2").expect("evicted");
+        assert!(b.chains.is_empty());
+        assert!(pending.chains.contains_key("under-c"));
+        assert!(b.check_violation().is_empty());
+        b.add_resource(Resource::new(String::from("node3")))
+            .expect("added");
+        b.solve(pending).expect("solves");
+        assert!(b.chains.contains_key("under-c"));
+    }
+
+    #[test]
+    fn update_test() {
+        let mut b = board();
+        let mut e = Entity::new(String::from("a"));
+        e.move_cost = 5;
+        b.update_entity(e).expect("updated");
+        assert_eq!(b.entities["a"].move_cost, 5);
+        assert_eq!(b.assignment()["a"], "node1");
+
+        let bad = IDRelation {
+            id: String::from("ab"),
+            kind: IDRelationKind::EEAffinity,
+            id1: String::from("a"),
+            id2: String::from("ghost"),
+            priority: Priority::Hard,
+        };
+        assert_eq!(
+            b.update_id_relation(bad).err(),
+            Some(SolverError::EntityNotFound(String::from("ghost")))
+        );
+        assert_eq!(b.id_relations["ab"].id2, "b");
+
+        b.remove_relation("ab").expect("removed");
+        assert!(b.remove_relation("ab").is_err());
+    }
+
+    #[test]
+    fn remove_shared_relation_id_test() {
+        // ids are unique per type, removing one removes all of them.
+        let mut b = board();
+        b.add_property_relation(PropertyRelation {
+            id: String::from("ab"),
+            kind: PropertyRelationKind::EEAntiAffinity,
+            entity_property: String::from("tier"),
+            resource_property: String::new(),
+            priority: Priority::Hard,
+        })
+        .expect("added");
+        b.remove_relation("ab").expect("removed");
+        assert!(!b.id_relations.contains_key("ab"));
+        assert!(!b.property_relations.contains_key("ab"));
+        assert_eq!(
+            b.remove_relation("ab").err(),
+            Some(SolverError::RelationNotFound(String::from("ab")))
+        );
+    }
+
+    #[test]
+    fn update_missing_entity_test() {
+        // a failed update leaves the loads alone.
+        let mut b = board();
+        let loads = b.loads.clone();
+        assert_eq!(
+            b.update_entity(Entity::new(String::from("ghost"))).err(),
+            Some(SolverError::EntityNotFound(String::from("ghost")))
+        );
+        assert_eq!(b.loads, loads);
+    }
+}
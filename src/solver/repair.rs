@@ -0,0 +1,348 @@
+// finding alternative resources and repairing violations.
+
+use std::collections::HashSet;
+
+use super::config::Limits;
+use super::index::RelationIndex;
+use super::intern::Assignment;
+use super::parallel;
+use super::trace;
+use super::{Board, Move, Violation, ViolationKind, ViolationReport};
+
+impl Board {
+    // resources the entity could be assigned to without breaking any of its
+    // hard relations, given where the other entities currently are. Sorted
+    // by id, and includes the current resource if it fits.
+    pub fn find_candidate_resources(&self, entity_id: &str) -> Vec<String> {
+        if !self.entities.contains_key(entity_id) {
+            return Vec::new();
+        }
+        self.candidates_with(&RelationIndex::new(self), entity_id, &self.assignment)
+    }
+
+    pub(crate) fn candidates_with(
+        &self,
+        index: &RelationIndex,
+        entity_id: &str,
+        assignment: &Assignment,
+    ) -> Vec<String> {
+        let r_ids = index.resource_candidates(entity_id);
+        let fits = parallel::map(&r_ids, |r_id| {
+            let violations = self.entity_violations_at(index, entity_id, r_id, assignment);
+            let hard: Vec<&Violation> = violations.iter().filter(|v| v.is_hard()).collect();
+            if !hard.is_empty() {
+                trace::event("candidate", || {
+                    let reasons: Vec<String> = hard
+                        .iter()
+                        .map(|v| match &v.relation_id {
+                            Some(id) => format!("{:?} {}", v.kind, id),
+                            None => format!("{:?}", v.kind),
+                        })
+                        .collect();
+                    format!("{} rejected on {}: {}", entity_id, r_id, reasons.join(", "))
+                });
+            }
+            hard.is_empty()
+        });
+        r_ids
+            .into_iter()
+            .zip(fits)
+            .filter(|(_, ok)| *ok)
+            .map(|(r_id, _)| String::from(r_id))
+            .collect()
+    }
+
+    // suggests entity moves, as (entity_id, from, to), that clear the
+    // violations reported by check_all without introducing new hard ones.
+    // Entities with a low move_cost are tried first. Violations that no
+    // single move can fix are left in place, so the plan may be partial and
+    // the caller can inspect what remains. The board is not modified.
+    pub fn suggest_repairs(&self) -> Vec<(String, String, String)> {
+        self.repair_moves(&mut self.assignment.clone(), None, &mut Limits::default())
+            .into_iter()
+            .map(|m| (m.entity_id, m.from, m.to))
+            .collect()
+    }
+
+    // hard violations that stay because the entities a repair would move
+    // are pinned: left after repairing around the pinned entities, but
+    // cleared when they may move too. Sorted like check_all.
+    pub fn pinning_conflicts(&self) -> ViolationReport {
+        let mut free = self.clone();
+        for e in free.entities.values_mut() {
+            e.pinned = false;
+        }
+        let mut assignment = self.assignment.clone();
+        free.repair_moves(&mut assignment, None, &mut Limits::default());
+        let unpinned: HashSet<ViolationKey> = free
+            .all_violations_with(&assignment)
+            .iter()
+            .map(key)
+            .collect();
+
+        let mut assignment = self.assignment.clone();
+        self.repair_moves(&mut assignment, None, &mut Limits::default());
+        ViolationReport {
+            entries: self
+                .all_violations_with(&assignment)
+                .into_iter()
+                .filter(|v| v.is_hard() && !unpinned.contains(&key(v)))
+                .collect(),
+        }
+    }
+
+    // greedy repair of the violations of assignment, which is updated with
+    // the chosen moves. Pinned entities are never moved, entities of a
+    // chain only together with the rest of it. Moves are skipped once
+    // their summed cost would exceed the budget. Stops with the moves so
+    // far once a limit is reached.
+    pub(crate) fn repair_moves(
+        &self,
+        assignment: &mut Assignment,
+        budget: Option<i64>,
+        limits: &mut Limits,
+    ) -> Vec<Move> {
+        let index = RelationIndex::new(self);
+        let mut current = self.all_violations_indexed(&index, assignment);
+        let mut spent = 0;
+
+        let mut movers: Vec<String> = self
+            .repair_movers(&current, assignment)
+            .into_iter()
+            .filter(|e| !self.entities[e].pinned)
+            .collect();
+        movers
+            .sort_by(|a, b| (self.entities[a].move_cost, a).cmp(&(self.entities[b].move_cost, b)));
+
+        let mut moves = Vec::new();
+        for entity_id in &movers {
+            if !limits.tick() {
+                break;
+            }
+            if !self.repair_movers(&current, assignment).contains(entity_id) {
+                continue; // fixed by an earlier move
+            }
+            let unit = self.move_unit(&index, entity_id, assignment);
+            if unit.iter().any(|id| self.entities[id].pinned) {
+                continue;
+            }
+            let before: HashSet<ViolationKey> = current.iter().map(key).collect();
+
+            // pick the candidate leaving the least violation at the least
+            // cost, ties by id. Members already on it stay.
+            let mut best: Option<(String, i64, Vec<Violation>)> = None;
+            for to in self.unit_candidates(&index, &unit, assignment) {
+                let placements = self.placements(assignment);
+                let cost: i64 = unit
+                    .iter()
+                    .filter(|id| placements[*id] != to)
+                    .map(|id| self.entities[id].move_cost)
+                    .sum();
+                if !unit.iter().any(|id| placements[id] != to)
+                    || budget.is_some_and(|b| spent + cost > b)
+                {
+                    continue;
+                }
+                let mut trial = assignment.clone();
+                for id in &unit {
+                    self.place_in(&mut trial, id, &to);
+                }
+                let after = self.all_violations_indexed(&index, &trial);
+                if !after
+                    .iter()
+                    .all(|v| !v.is_hard() || before.contains(&key(v)))
+                {
+                    continue; // creates a new hard violation
+                }
+                if score(&after) >= score(&current) {
+                    continue;
+                }
+                if best
+                    .as_ref()
+                    .is_none_or(|(_, c, b)| (score(&after), cost) < (score(b), *c))
+                {
+                    best = Some((to, cost, after));
+                }
+            }
+
+            if let Some((to, cost, after)) = best {
+                trace::event("repair", || {
+                    format!(
+                        "{} to {}, {} violation(s) left",
+                        unit.join(", "),
+                        to,
+                        after.len()
+                    )
+                });
+                for id in &unit {
+                    let from = self
+                        .place_in(assignment, id, &to)
+                        .expect("assigned")
+                        .clone();
+                    if from != to {
+                        moves.push(Move {
+                            cost: self.entities[id].move_cost,
+                            entity_id: id.clone(),
+                            from,
+                            to: to.clone(),
+                        });
+                    }
+                }
+                current = after;
+                spent += cost;
+            }
+        }
+        moves
+    }
+
+    // entities that break a relation or sit on a resource over capacity in
+    // a metric they use.
+    fn repair_movers(&self, violations: &[Violation], assignment: &Assignment) -> HashSet<String> {
+        let mut movers = HashSet::new();
+        for v in violations {
+            if let Some(e) = &v.entity_id {
+                movers.insert(e.clone());
+            }
+            if let (ViolationKind::Capacity, Some(metric)) = (v.kind, &v.metric) {
+                for (e_id, r_id) in self.placements(assignment) {
+                    if *r_id == v.resource_id
+                        && self.entities[e_id]
+                            .metrics
+                            .get(metric)
+                            .is_some_and(|m| *m > 0)
+                    {
+                        movers.insert(e_id.clone());
+                    }
+                }
+            }
+        }
+        movers
+    }
+}
+
+// identity of a violation regardless of its overage.
+type ViolationKey = (
+    ViolationKind,
+    Option<String>,
+    Option<String>,
+    String,
+    Option<String>,
+);
+
+fn key(v: &Violation) -> ViolationKey {
+    (
+        v.kind,
+        v.relation_id.clone(),
+        v.entity_id.clone(),
+        v.resource_id.clone(),
+        v.metric.clone(),
+    )
+}
+
+// lower is better: number of hard violations, then summed overage, then
+// the penalty of soft violations.
+fn score(violations: &[Violation]) -> (usize, i64, i64) {
+    let hard = violations.iter().filter(|v| v.is_hard()).count();
+    let overage = violations.iter().filter_map(|v| v.overage).sum();
+    let penalty = violations.iter().map(|v| v.penalty()).sum();
+    (hard, overage, penalty)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::solver::{
+        Board, Entity, Priority, PropertyRelation, PropertyRelationKind, Resource, ViolationKind,
+    };
+
+    #[test]
+    fn suggest_repairs_test() {
+        let mut b = Board::new();
+        let mut red = Resource::new(String::from("node1"));
+        red.add_property(String::from("red"));
+        let mut blue = Resource::new(String::from("node2"));
+        blue.add_property(String::from("blue"));
+        b.add_resource(red).expect("added");
+        b.add_resource(blue).expect("added");
+
+        let mut app = Entity::new(String::from("app1"));
+        app.add_property(String::from("red"));
+        b.add_entity(String::from("node2"), app).expect("added");
+        b.add_property_relation(PropertyRelation {
+            id: String::from("color"),
+            kind: PropertyRelationKind::Affinity,
+            entity_property: String::from("red"),
+            resource_property: String::from("red"),
+            priority: Priority::Hard,
+        })
+        .expect("ok");
+
+        assert_eq!(b.find_candidate_resources("app1"), vec!["node1"]);
+        let repairs = b.suggest_repairs();
+        assert_eq!(
+            repairs,
+            vec![(
+                String::from("app1"),
+                String::from("node2"),
+                String::from("node1")
+            )]
+        );
+        // board untouched
+        assert_eq!(b.assignment()["app1"], "node2");
+    }
+
+    #[test]
+    fn suggest_repairs_capacity_test() {
+        let mut b = Board::new();
+        for id in ["node1", "node2"] {
+            let mut r = Resource::new(String::from(id));
+            r.capacities.insert(String::from("cpu"), 4);
+            b.add_resource(r).expect("added");
+        }
+        for (id, cost) in [("app1", 5), ("app2", 1)] {
+            let mut e = Entity::new(String::from(id));
+            e.metrics.insert(String::from("cpu"), 3);
+            e.move_cost = cost;
+            b.add_entity(String::from("node1"), e).expect("added");
+        }
+        // the cheaper entity is moved off the overloaded node.
+        assert_eq!(
+            b.suggest_repairs(),
+            vec![(
+                String::from("app2"),
+                String::from("node1"),
+                String::from("node2")
+            )]
+        );
+    }
+
+    #[test]
+    fn pinned_entity_test() {
+        let mut b = Board::new();
+        for id in ["node1", "node2"] {
+            let mut r = Resource::new(String::from(id));
+            r.capacities.insert(String::from("cpu"), 4);
+            b.add_resource(r).expect("added");
+        }
+        for id in ["app1", "app2"] {
+            let mut e = Entity::new(String::from(id));
+            e.metrics.insert(String::from("cpu"), 3);
+            e.pinned = true;
+            b.add_entity(String::from("node1"), e).expect("added");
+        }
+        assert!(b.suggest_repairs().is_empty());
+        assert!(b.rebalance().is_empty());
+        let conflicts = b.pinning_conflicts();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts.entries[0].kind, ViolationKind::Capacity);
+
+        // one free entity is enough to repair.
+        b.entities.get_mut("app2").unwrap().pinned = false;
+        assert_eq!(b.suggest_repairs().len(), 1);
+        assert!(b.pinning_conflicts().is_empty());
+
+        let json = b.to_json();
+        let again = Board::from_json(&json).expect("parses");
+        assert!(again.entities["app1"].pinned);
+        assert!(!again.entities["app2"].pinned);
+    }
+}
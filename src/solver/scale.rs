@@ -0,0 +1,162 @@
+// scale-down recommendations: which resources to give up for the least
+// disruption.
+
+use std::collections::HashMap;
+
+use super::config::Limits;
+use super::{Board, MovePlan, ResourceState, SolverConfig};
+
+// see Board::recommend_scale_down.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ScaleDownPlan {
+    // the resources to empty, sorted.
+    pub resources: Vec<String>,
+    // moves placing their entities on the resources kept.
+    pub plan: MovePlan,
+}
+
+impl ScaleDownPlan {
+    // summed move_cost of the plan.
+    pub fn cost(&self) -> i64 {
+        self.plan.total_cost()
+    }
+}
+
+// search steps each trial evacuation of recommend_scale_down may take.
+const TRIAL_ITERATIONS: u64 = 100_000;
+
+impl Board {
+    // recommend_scale_down_with each trial evacuation limited to
+    // TRIAL_ITERATIONS search steps.
+    pub fn recommend_scale_down(&self, count: usize) -> ScaleDownPlan {
+        let config = SolverConfig {
+            max_iterations: Some(TRIAL_ITERATIONS),
+            ..Default::default()
+        };
+        self.recommend_scale_down_with(count, &config)
+    }
+
+    // picks up to count active resources to empty and the moves doing it.
+    // Every entity on a picked resource moves, so its cost is the summed
+    // move_cost of what it hosts: resources are tried cheapest first, then
+    // by id, and kept if the entities of all picked so far can be placed
+    // jointly on the rest with every hard relation and capacity, buffers
+    // included, satisfied. This is a greedy heuristic, a costlier resource
+    // picked early can rule out a cheaper combination. Each trial searches
+    // within the limits of the config, a resource whose trial reaches one
+    // is taken as not removable. Resources hosting pinned entities are
+    // never picked. Fewer than count are returned when no more can go. The
+    // board is not modified.
+    pub fn recommend_scale_down_with(&self, count: usize, config: &SolverConfig) -> ScaleDownPlan {
+        // summed move_cost of the entities on each resource, None once
+        // one of them is pinned.
+        let mut hosted: HashMap<&str, Option<i64>> = HashMap::new();
+        for (e_id, r_id) in self.assignment() {
+            let e = &self.entities[e_id];
+            let cost = hosted.entry(r_id).or_insert(Some(0));
+            *cost = cost.filter(|_| !e.pinned).map(|c| c + e.move_cost);
+        }
+        let mut costs: Vec<(i64, &String)> = self
+            .resources
+            .values()
+            .filter(|r| r.state.accepts_entities())
+            .filter_map(|r| {
+                Some((
+                    hosted.get(r.id.as_str()).copied().unwrap_or(Some(0))?,
+                    &r.id,
+                ))
+            })
+            .collect();
+        costs.sort();
+
+        let mut best = ScaleDownPlan::default();
+        for (_, r_id) in costs {
+            if best.resources.len() >= count {
+                break;
+            }
+            let mut trial = best.resources.clone();
+            trial.push(r_id.clone());
+            let mut board = self.snapshot();
+            for r in &trial {
+                board.resources.get_mut(r).expect("resource").state = ResourceState::Draining;
+            }
+            if let Ok(plan) = board.evacuation_plan_within(&trial, Limits::new(config)) {
+                best = ScaleDownPlan {
+                    resources: trial,
+                    plan,
+                };
+            }
+        }
+        best.resources.sort();
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::solver::{
+        Board, BoardBuilder, EntityBuilder, ResourceBuilder, ResourceState, SolverConfig,
+    };
+
+    fn board() -> Board {
+        BoardBuilder::new()
+            .resource(ResourceBuilder::new("node1").capacity("cpu", 6))
+            .resource(ResourceBuilder::new("node2").capacity("cpu", 4))
+            .resource(ResourceBuilder::new("node3").capacity("cpu", 4))
+            .resource(ResourceBuilder::new("node4").capacity("cpu", 4))
+            .entity(
+                "node1",
+                EntityBuilder::new("a").metric("cpu", 2).move_cost(5),
+            )
+            .entity(
+                "node2",
+                EntityBuilder::new("b").metric("cpu", 2).move_cost(1),
+            )
+            .entity(
+                "node2",
+                EntityBuilder::new("c").metric("cpu", 1).move_cost(1),
+            )
+            .entity(
+                "node3",
+                EntityBuilder::new("d").metric("cpu", 4).pinned(true),
+            )
+            .build()
+            .expect("builds")
+    }
+
+    #[test]
+    fn scale_down_test() {
+        let b = board();
+        // node4 is empty and free to remove, node2 is cheaper than node1.
+        let plan = b.recommend_scale_down(2);
+        assert_eq!(plan.resources, ["node2", "node4"]);
+        assert_eq!(plan.cost(), 2);
+        assert!(plan.plan.moves.iter().all(|m| m.to == "node1"));
+
+        let mut after = b.snapshot();
+        after.apply_move_plan(&plan.plan).expect("applied");
+        assert!(after.check_all().is_empty());
+        assert!(after.assignment().values().all(|r| r != "node2"));
+    }
+
+    #[test]
+    fn scale_down_limits_test() {
+        let mut b = board();
+        // node3 hosts a pinned entity, and nothing is left to take node1's.
+        let plan = b.recommend_scale_down(4);
+        assert_eq!(plan.resources, ["node2", "node4"]);
+
+        b.resources.get_mut("node4").expect("resource").state = ResourceState::Paused;
+        let plan = b.recommend_scale_down(4);
+        assert_eq!(plan.resources, ["node2"]);
+        assert!(b.recommend_scale_down(0).resources.is_empty());
+
+        // a trial stopped at a limit counts as not removable.
+        let config = SolverConfig {
+            max_iterations: Some(0),
+            ..Default::default()
+        };
+        let plan = b.recommend_scale_down_with(4, &config);
+        assert!(plan.resources.is_empty());
+    }
+}
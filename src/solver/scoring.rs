@@ -0,0 +1,136 @@
+// how metrics count against each other when balancing and placing.
+
+use std::collections::HashMap;
+
+// settings of one metric, see ScoringConfig.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MetricScoring {
+    // multiplies the metric's imbalance and utilization.
+    pub weight: f64,
+    // imbalance, as in Board::load_imbalance, the metric may have without
+    // counting; balancing only moves entities for what lies above it.
+    pub balancing_threshold: f64,
+    // total load below which the metric is ignored.
+    pub activity_threshold: i64,
+}
+
+impl Default for MetricScoring {
+    fn default() -> Self {
+        MetricScoring {
+            weight: 1.0,
+            balancing_threshold: 0.0,
+            activity_threshold: 0,
+        }
+    }
+}
+
+// per-metric scoring of the board, used by solve when ranking candidates
+// and by rebalance and anneal when measuring imbalance. Metrics not listed
+// use MetricScoring::default:
+// ScoringConfig::new().weight("cpu", 2.0).balancing_threshold("mem", 10.0).
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ScoringConfig {
+    pub metrics: HashMap<String, MetricScoring>,
+}
+
+impl ScoringConfig {
+    pub fn new() -> ScoringConfig {
+        ScoringConfig::default()
+    }
+
+    pub fn weight(mut self, metric: impl Into<String>, weight: f64) -> Self {
+        self.metrics.entry(metric.into()).or_default().weight = weight;
+        self
+    }
+
+    pub fn balancing_threshold(mut self, metric: impl Into<String>, threshold: f64) -> Self {
+        self.metrics
+            .entry(metric.into())
+            .or_default()
+            .balancing_threshold = threshold;
+        self
+    }
+
+    pub fn activity_threshold(mut self, metric: impl Into<String>, threshold: i64) -> Self {
+        self.metrics
+            .entry(metric.into())
+            .or_default()
+            .activity_threshold = threshold;
+        self
+    }
+
+    pub fn metric(&self, metric: &str) -> MetricScoring {
+        self.metrics.get(metric).copied().unwrap_or_default()
+    }
+
+    // the weight of the metric given its total load, 0 while inactive.
+    pub(crate) fn effective_weight(&self, metric: &str, total: i64) -> f64 {
+        let m = self.metric(metric);
+        if total < m.activity_threshold {
+            0.0
+        } else {
+            m.weight
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::solver::{Board, Entity, Pending, Resource, ScoringConfig};
+
+    // node1 holds more cpu, node2 more mem.
+    fn board() -> Board {
+        let mut b = Board::new();
+        for id in ["node1", "node2"] {
+            let mut r = Resource::new(String::from(id));
+            r.capacities.insert(String::from("cpu"), 10);
+            r.capacities.insert(String::from("mem"), 10);
+            b.add_resource(r).expect("added");
+        }
+        for (id, r, cpu, mem) in [("a", "node1", 6, 1), ("b", "node2", 2, 4)] {
+            let mut e = Entity::new(String::from(id));
+            e.metrics.insert(String::from("cpu"), cpu);
+            e.metrics.insert(String::from("mem"), mem);
+            b.add_entity(String::from(r), e).expect("added");
+        }
+        b
+    }
+
+    #[test]
+    fn metric_weight_test() {
+        let place = |scoring: ScoringConfig| {
+            let mut b = board();
+            b.scoring = scoring;
+            let mut p = Pending::new();
+            let mut e = Entity::new(String::from("c"));
+            e.metrics.insert(String::from("cpu"), 1);
+            e.metrics.insert(String::from("mem"), 1);
+            p.add_entity(e);
+            b.solve(p).expect("placed").assignment["c"].clone()
+        };
+        // node1 is 70% used by cpu, node2 50% by mem.
+        assert_eq!(place(ScoringConfig::new()), "node2");
+
+        // mem weighs more, so node2 looks fuller.
+        let heavy_mem = ScoringConfig::new().weight("mem", 3.0);
+        assert_eq!(place(heavy_mem.clone()), "node1");
+
+        // with too little mem in use, only cpu counts again.
+        assert_eq!(place(heavy_mem.activity_threshold("mem", 100)), "node2");
+    }
+
+    #[test]
+    fn balancing_threshold_test() {
+        let mut b = board();
+        let mut e = Entity::new(String::from("c"));
+        e.metrics.insert(String::from("cpu"), 2);
+        b.add_entity(String::from("node1"), e).expect("added");
+        // cpu is 8 against 2, a stddev of 3.
+        assert_eq!(b.rebalance().len(), 1);
+
+        b.scoring = ScoringConfig::new().balancing_threshold("cpu", 3.0);
+        assert!(b.rebalance().is_empty());
+        b.scoring = ScoringConfig::new().balancing_threshold("cpu", 2.5);
+        assert_eq!(b.rebalance().len(), 1);
+    }
+}
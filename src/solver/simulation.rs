@@ -0,0 +1,153 @@
+// what-if simulation: hypothetical changes on a copy of the board.
+
+use std::collections::HashMap;
+use std::ops::Deref;
+
+use super::{Board, Entity, Move, MovePlan, Pending, Placement, SolveError, SolverError};
+
+impl Board {
+    // independent copy of the board. The board is plain maps, so this is a
+    // clone; change tracking does not carry over.
+    pub fn snapshot(&self) -> Board {
+        let mut b = self.clone();
+        b.disable_change_tracking();
+        b
+    }
+
+    // starts a simulation on a snapshot of the board.
+    pub fn simulate(&self) -> Simulation<'_> {
+        Simulation {
+            base: self,
+            board: self.snapshot(),
+        }
+    }
+}
+
+// a snapshot that takes hypothetical changes while the real board stays
+// untouched. Queries like check_all or load_imbalance go to the simulated
+// board through deref.
+#[derive(Debug, Clone)]
+pub struct Simulation<'a> {
+    base: &'a Board,
+    board: Board,
+}
+
+impl Simulation<'_> {
+    pub fn move_entity(
+        &mut self,
+        entity_id: &str,
+        target_resource_id: &str,
+    ) -> Result<(), SolverError> {
+        self.board.move_entity(entity_id, target_resource_id)
+    }
+
+    pub fn apply_move_plan(&mut self, plan: &MovePlan) -> Result<(), SolverError> {
+        self.board.apply_move_plan(plan)
+    }
+
+    // adds the entity on the given resource. Constraints are not checked.
+    pub fn add_entity(&mut self, resource_id: &str, entity: Entity) -> Result<(), SolverError> {
+        self.board.add_entity(resource_id.to_string(), entity)
+    }
+
+    // places the batch the way Board::solve would.
+    pub fn solve(&mut self, pending: Pending) -> Result<Placement, SolveError> {
+        self.board.solve(pending)
+    }
+
+    // takes the resource away as if it failed. Its entities and their
+    // relations are returned unplaced; solve them to see whether they fit
+    // elsewhere.
+    pub fn fail_resource(&mut self, resource_id: &str) -> Result<Pending, SolverError> {
+        self.board
+            .evict_resource(resource_id)
+            .map(|(_, pending)| pending)
+    }
+
+    // resource id -> metric -> summed load, with every resource present.
+    pub fn loads(&self) -> HashMap<String, HashMap<String, i64>> {
+        self.board
+            .all_loads_with(&self.board.assignment)
+            .into_iter()
+            .map(|(r, load)| (self.board.resources.name(r).clone(), load))
+            .collect()
+    }
+
+    // entities on both boards now on a different resource, sorted by
+    // entity id.
+    pub fn moves(&self) -> MovePlan {
+        let mut moves: Vec<Move> = self
+            .board
+            .assignment()
+            .iter()
+            .filter_map(|(e_id, to)| {
+                let from = self.base.assignment().get(e_id)?;
+                (from != to).then(|| Move {
+                    entity_id: e_id.clone(),
+                    from: from.clone(),
+                    to: to.clone(),
+                    cost: self.board.entities[e_id].move_cost,
+                })
+            })
+            .collect();
+        moves.sort_by(|a, b| a.entity_id.cmp(&b.entity_id));
+        MovePlan {
+            moves,
+            evictions: Vec::new(),
+        }
+    }
+
+    pub fn into_board(self) -> Board {
+        self.board
+    }
+}
+
+impl Deref for Simulation<'_> {
+    type Target = Board;
+
+    fn deref(&self) -> &Board {
+        &self.board
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::solver::{Board, Entity, Resource};
+
+    #[test]
+    fn simulation_test() {
+        let mut b = Board::new();
+        for id in ["node1", "node2", "node3"] {
+            let mut r = Resource::new(String::from(id));
+            r.capacities.insert(String::from("cpu"), 4);
+            b.add_resource(r).expect("added");
+        }
+        for (id, r) in [("a", "node1"), ("b", "node2"), ("c", "node3")] {
+            let mut e = Entity::new(String::from(id));
+            e.metrics.insert(String::from("cpu"), 3);
+            b.add_entity(String::from(r), e).expect("added");
+        }
+
+        // node3 dying leaves c without room.
+        let mut sim = b.simulate();
+        let evicted = sim.fail_resource("node3").expect("failed");
+        assert!(evicted.entities.contains_key("c"));
+        assert!(sim.solve(evicted).is_err());
+        assert_eq!(sim.resources.len(), 2);
+        assert_eq!(b.resources.len(), 3);
+
+        // moves overload and show up against the real board.
+        let mut sim = b.simulate();
+        sim.move_entity("b", "node1").expect("moved");
+        assert_eq!(sim.loads()["node1"]["cpu"], 6);
+        assert_eq!(sim.check_all().len(), 1);
+        assert_eq!(sim.moves().len(), 1);
+        assert!(b.check_all().is_empty());
+        assert!(sim
+            .add_entity("node9", Entity::new(String::from("d")))
+            .is_err());
+        assert!(sim
+            .add_entity("node2", Entity::new(String::from("a")))
+            .is_err());
+    }
+}
@@ -0,0 +1,851 @@
+// placement of pending entities onto resources.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use super::capacity::Loads;
+use super::config::Limits;
+use super::index::RelationIndex;
+use super::intern::Assignment;
+use super::parallel;
+use super::trace;
+use super::{
+    BacktrackingSolver, Board, Bounded, Eviction, Pending, PlacementExplanation, Solver,
+    SolverConfig, SolverError,
+};
+
+// assignment chosen for the entities of a pending batch.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Placement {
+    // entity id -> resource id.
+    pub assignment: HashMap<String, String>,
+    // entities left out with why no resource could take them, sorted by
+    // id. Only a partial placement, see SolverConfig, leaves any out.
+    pub unplaced: Vec<(String, PlacementExplanation)>,
+    // entities taken off the board to make room, see
+    // SolverConfig::preemption.
+    pub evictions: Vec<Eviction>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SolveError {
+    // the pending batch conflicts with the board.
+    Invalid(Vec<SolverError>),
+    // these entities fit on no resource even on their own, with the
+    // reasons each resource was ruled out.
+    NoCandidates(Vec<PlacementExplanation>),
+    // every entity fits somewhere, but no combination satisfies all
+    // relations and capacities together.
+    Unsatisfiable(Vec<String>),
+    // the search stopped at a limit of the SolverConfig before placing
+    // these entities, and no placement was found without backtracking.
+    Interrupted(Vec<String>),
+}
+
+impl fmt::Display for SolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SolveError::Invalid(errors) => {
+                let msgs: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+                write!(f, "invalid pending batch: {}", msgs.join(", "))
+            }
+            SolveError::NoCandidates(explanations) => {
+                let parts: Vec<String> = explanations
+                    .iter()
+                    .map(|ex| format!("{} ({})", ex.entity_id, ex))
+                    .collect();
+                write!(f, "no resource can host: {}", parts.join(", "))
+            }
+            SolveError::Unsatisfiable(ids) => {
+                write!(f, "no joint placement exists for: {}", ids.join(", "))
+            }
+            SolveError::Interrupted(ids) => {
+                write!(f, "search stopped before placing: {}", ids.join(", "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for SolveError {}
+
+// search state over the pending entities, starting from the board's
+// assignment.
+pub(crate) struct Search<'a> {
+    board: &'a Board,
+    index: RelationIndex<'a>,
+    assignment: Assignment,
+    loads: Loads,
+    // effective metric weights of the board's scoring, by the total load
+    // at the start.
+    weights: HashMap<String, f64>,
+    // stops run once reached, unlimited by default.
+    pub(crate) limits: Limits,
+}
+
+impl<'a> Search<'a> {
+    pub(crate) fn new(board: &'a Board) -> Search<'a> {
+        let loads = board.loads_with(&board.assignment);
+        let mut totals: HashMap<&String, i64> = HashMap::new();
+        for load in loads.values() {
+            for (metric, v) in load {
+                *totals.entry(metric).or_insert(0) += v;
+            }
+        }
+        let weights = board
+            .scoring
+            .metrics
+            .keys()
+            .map(|m| {
+                let total = totals.get(m).copied().unwrap_or(0);
+                (m.clone(), board.scoring.effective_weight(m, total))
+            })
+            .collect();
+        let index = RelationIndex::new(board);
+        Search {
+            board,
+            assignment: board.assignment.clone(),
+            index,
+            loads,
+            weights,
+            limits: Limits::default(),
+        }
+    }
+
+    // resources the entity can take given the partial assignment, ordered
+    // by the soft penalty they cost, then so the least utilized resource
+    // after placement comes first. Utilization is weighted by the board's
+    // scoring.
+    pub(crate) fn candidates(&self, entity_id: &str) -> Vec<String> {
+        let b = self.board;
+        let e = &b.entities[entity_id];
+        let fitting: Vec<String> = b
+            .candidates_with(&self.index, entity_id, &self.assignment)
+            .into_iter()
+            .filter(|r_id| {
+                let fits = b.fits_capacity(entity_id, r_id, &self.loads);
+                if !fits {
+                    trace::event("candidate", || {
+                        let r = &b.resources[r_id];
+                        let load = b.load_of(&self.loads, r_id);
+                        let mut full: Vec<&str> = e
+                            .metrics
+                            .iter()
+                            .filter(|(m, v)| {
+                                let used = load.and_then(|l| l.get(*m)).copied().unwrap_or(0);
+                                r.buffered_capacity(m).is_some_and(|cap| used + *v > cap)
+                            })
+                            .map(|(m, _)| m.as_str())
+                            .collect();
+                        full.sort();
+                        format!(
+                            "{} does not fit on {}: {}",
+                            entity_id,
+                            r_id,
+                            full.join(", ")
+                        )
+                    });
+                }
+                fits
+            })
+            .collect();
+        let scores = parallel::map(&fitting, |r_id| {
+            let r = &b.resources[r_id];
+            let load = b.load_of(&self.loads, r_id);
+            let mut util: f64 = 0.0;
+            for (metric, v) in &e.metrics {
+                if let Some(cap) = r.usable_capacity(metric).filter(|c| *c > 0) {
+                    let used = load.and_then(|l| l.get(metric)).copied().unwrap_or(0);
+                    let weight = self.weights.get(metric).copied().unwrap_or(1.0);
+                    util = util.max(weight * (used + v) as f64 / cap as f64);
+                }
+            }
+            let penalty = b.soft_penalty_at(&self.index, entity_id, r_id, &self.assignment);
+            (penalty, util)
+        });
+        let mut scored: Vec<(i64, f64, String)> = scores
+            .into_iter()
+            .zip(fitting)
+            .map(|((penalty, util), r_id)| (penalty, util, r_id))
+            .collect();
+        scored.sort_by(|a, b| {
+            a.0.cmp(&b.0)
+                .then_with(|| a.1.total_cmp(&b.1))
+                .then_with(|| a.2.cmp(&b.2))
+        });
+        scored.into_iter().map(|(_, _, r_id)| r_id).collect()
+    }
+
+    pub(crate) fn assignment(&self) -> &Assignment {
+        &self.assignment
+    }
+
+    pub(crate) fn index(&self) -> &RelationIndex<'a> {
+        &self.index
+    }
+
+    // whether the entity fits the capacities of the resource as loaded.
+    pub(crate) fn fits(&self, entity_id: &str, resource_id: &str) -> bool {
+        self.board
+            .fits_capacity(entity_id, resource_id, &self.loads)
+    }
+
+    pub(crate) fn place(&mut self, entity_id: &str, resource_id: &str) {
+        let e = &self.board.entities[entity_id];
+        let r = self
+            .board
+            .resources
+            .handle(resource_id)
+            .expect("resource exist");
+        let load = self.loads.entry(r).or_default();
+        for (metric, v) in &e.metrics {
+            *load.entry(metric.clone()).or_insert(0) += v;
+        }
+        self.board
+            .place_in(&mut self.assignment, entity_id, resource_id);
+    }
+
+    pub(crate) fn unplace(&mut self, entity_id: &str) {
+        let entity = self.index.entity(entity_id).expect("entity not found");
+        let resource = self.assignment.remove(entity).expect("not placed");
+        let e = &self.board.entities[entity_id];
+        let load = self.loads.get_mut(&resource).expect("load not found");
+        for (metric, v) in &e.metrics {
+            *load.get_mut(metric).expect("metric not found") -= v;
+        }
+    }
+
+    // the resources the entity can take, unordered.
+    fn fitting(&self, entity_id: &str) -> HashSet<String> {
+        self.board
+            .candidates_with(&self.index, entity_id, &self.assignment)
+            .into_iter()
+            .filter(|r_id| self.fits(entity_id, r_id))
+            .collect()
+    }
+
+    // index of the entity with the fewest candidates, and its candidates.
+    fn most_constrained(&self, counts: &Counts, remaining: &[String]) -> (usize, Vec<String>) {
+        let (idx, _) = remaining
+            .iter()
+            .enumerate()
+            .min_by_key(|(i, e)| (counts.count(e), *i))
+            .expect("not empty");
+        (idx, self.candidates(&remaining[idx]))
+    }
+
+    // assignment of the given entities, explaining the ones not placed
+    // against the others' placement.
+    pub(crate) fn placement(&self, entity_ids: &[String]) -> Placement {
+        let mut placement = Placement::default();
+        let mut unplaced = Vec::new();
+        for e in entity_ids {
+            match self.assignment.resource_of(&self.index, e) {
+                Some(r_id) => {
+                    placement.assignment.insert(e.clone(), r_id.to_string());
+                }
+                None => unplaced.push(e),
+            }
+        }
+        if !unplaced.is_empty() {
+            let mut partial = self.board.clone();
+            partial.assignment = self.assignment.clone();
+            partial.refresh_loads();
+            unplaced.sort();
+            placement.unplaced = unplaced
+                .into_iter()
+                .map(|e| (e.clone(), partial.explain_placement(e)))
+                .collect();
+        }
+        placement
+    }
+
+    // places every entity in remaining, most constrained entity first,
+    // backtracking on dead ends. Gives up once a limit is reached, with
+    // remaining and the search state as they were.
+    pub(crate) fn run(&mut self, remaining: &mut Vec<String>) -> bool {
+        let mut counts = Counts::new(self, remaining);
+        // the entities being placed, outermost first.
+        let mut stack: Vec<Frame> = Vec::new();
+        loop {
+            if remaining.is_empty() {
+                return true;
+            }
+            if self.limits.tick() {
+                trace::count("search.states", 1);
+                let (idx, candidates) = self.most_constrained(&counts, remaining);
+                stack.push(Frame {
+                    entity_id: remaining.swap_remove(idx),
+                    idx,
+                    candidates: candidates.into_iter(),
+                    placed: false,
+                });
+            }
+            // moves the innermost entity with a candidate left onto it,
+            // backing out of the ones without.
+            loop {
+                let Some(frame) = stack.last_mut() else {
+                    return false;
+                };
+                if frame.placed {
+                    self.unplace(&frame.entity_id);
+                    counts.unplace();
+                    frame.placed = false;
+                }
+                let next = if self.limits.stopped() {
+                    None
+                } else {
+                    frame.candidates.next()
+                };
+                if let Some(r_id) = next {
+                    self.place(&frame.entity_id, &r_id);
+                    counts.place(self, &frame.entity_id, &r_id, remaining);
+                    frame.placed = true;
+                    break;
+                }
+                // restore the order for the entity placed before.
+                let frame = stack.pop().expect("not empty");
+                remaining.push(frame.entity_id);
+                let last = remaining.len() - 1;
+                remaining.swap(frame.idx, last);
+            }
+        }
+    }
+
+    // places every entity in remaining on the best candidate of the most
+    // constrained one, without going back.
+    pub(crate) fn run_greedy(&mut self, remaining: &mut Vec<String>) -> bool {
+        let mut counts = Counts::new(self, remaining);
+        while !remaining.is_empty() {
+            let (idx, candidates) = self.most_constrained(&counts, remaining);
+            let Some(r_id) = candidates.first() else {
+                return false;
+            };
+            let entity_id = remaining.swap_remove(idx);
+            self.place(&entity_id, r_id);
+            counts.place(self, &entity_id, r_id, remaining);
+        }
+        true
+    }
+
+    // run_greedy that skips the entities left without a candidate,
+    // leaving them in remaining.
+    pub(crate) fn run_greedy_partial(&mut self, remaining: &mut Vec<String>) {
+        let mut counts = Counts::new(self, remaining);
+        let mut skipped = Vec::new();
+        while !remaining.is_empty() {
+            let (idx, candidates) = self.most_constrained(&counts, remaining);
+            let entity_id = remaining.swap_remove(idx);
+            match candidates.first() {
+                Some(r_id) => {
+                    self.place(&entity_id, r_id);
+                    counts.place(self, &entity_id, r_id, remaining);
+                }
+                None => skipped.push(entity_id),
+            }
+        }
+        *remaining = skipped;
+    }
+}
+
+// an entity of the search placed on its candidates in turn.
+struct Frame {
+    entity_id: String,
+    // its index in remaining before it was taken out.
+    idx: usize,
+    candidates: std::vec::IntoIter<String>,
+    placed: bool,
+}
+
+// the resources each entity left can take, kept up to date as the search
+// places and unplaces entities rather than computed again for all of them
+// at every step. Placing an entity changes the candidates of the entities
+// related to it, and of the others only by the capacity it takes.
+struct Counts {
+    fitting: HashMap<String, HashSet<String>>,
+    // what each place changed, newest last, for unplace to restore.
+    undo: Vec<Vec<Change>>,
+}
+
+enum Change {
+    // the entity's candidates before they were computed again.
+    Replaced(String, HashSet<String>),
+    // the resource the entity no longer fit on.
+    Removed(String, String),
+}
+
+impl Counts {
+    fn new(search: &Search, remaining: &[String]) -> Counts {
+        Counts {
+            fitting: remaining
+                .iter()
+                .map(|e| (e.clone(), search.fitting(e)))
+                .collect(),
+            undo: Vec::new(),
+        }
+    }
+
+    fn count(&self, entity_id: &str) -> usize {
+        self.fitting[entity_id].len()
+    }
+
+    // after the search placed the entity on the resource.
+    fn place(&mut self, search: &Search, entity_id: &str, resource_id: &str, remaining: &[String]) {
+        let related: HashSet<&str> = search
+            .board
+            .related_entities(&search.index, entity_id)
+            .into_iter()
+            .collect();
+        let mut changes = Vec::new();
+        for e in remaining {
+            let fitting = self.fitting.get_mut(e).expect("counted");
+            if related.contains(e.as_str()) {
+                let old = std::mem::replace(fitting, search.fitting(e));
+                changes.push(Change::Replaced(e.clone(), old));
+            } else if fitting.contains(resource_id) && !search.fits(e, resource_id) {
+                fitting.remove(resource_id);
+                changes.push(Change::Removed(e.clone(), resource_id.to_string()));
+            }
+        }
+        self.undo.push(changes);
+    }
+
+    // after the search unplaced the entity placed last.
+    fn unplace(&mut self) {
+        for change in self.undo.pop().expect("placed").into_iter().rev() {
+            match change {
+                Change::Replaced(e, old) => {
+                    self.fitting.insert(e, old);
+                }
+                Change::Removed(e, r_id) => {
+                    self.fitting.get_mut(&e).expect("counted").insert(r_id);
+                }
+            }
+        }
+    }
+}
+
+impl Board {
+    // places the pending entities on resources so that every relation and
+    // capacity is satisfied, and adds the batch to the board. Existing
+    // assignments are left untouched. On failure the board is unchanged.
+    pub fn solve(&mut self, pending: Pending) -> Result<Placement, SolveError> {
+        self.solve_with(&BacktrackingSolver, pending)
+    }
+
+    // solve with the given strategy. The placement is added with
+    // apply_pending, so it must cover every pending entity.
+    pub fn solve_with(
+        &mut self,
+        solver: &dyn Solver,
+        pending: Pending,
+    ) -> Result<Placement, SolveError> {
+        let _span = trace::span("solve");
+        let placement = solver.place(self, &pending)?;
+        self.apply_pending(pending, &placement)
+            .map_err(SolveError::Invalid)?;
+        Ok(placement)
+    }
+
+    // solve within the config's duration, iteration and cancellation
+    // limits. When the search is stopped the batch is placed greedily
+    // instead and the result is marked incomplete: it satisfies every
+    // relation and capacity, but a failed greedy pass proves nothing, so
+    // it is reported as Interrupted.
+    //
+    // With partial_placement set the entities that can not be placed are
+    // left out rather than failing the batch, and listed as unplaced. The
+    // relations of the batch naming them are left out too.
+    //
+    // With preemption set a batch that can not be placed as the board is
+    // may evict entities instead, see Board::preemption_plan. They are
+    // removed from the board and listed as the placement's evictions.
+    pub fn solve_bounded(
+        &mut self,
+        mut pending: Pending,
+        config: &SolverConfig,
+    ) -> Result<Bounded<Placement>, SolveError> {
+        let _span = trace::span("solve");
+        let bounded = self.with_staged(&pending, |b, entity_ids| {
+            let mut search = Search::new(b);
+            search.limits = Limits::new(config);
+            let mut remaining = entity_ids.to_vec();
+            if config.partial_placement {
+                // entities fitting nowhere on their own stay out up front.
+                remaining.retain(|e| !search.candidates(e).is_empty());
+            }
+            if search.run(&mut remaining) {
+                return Ok(Bounded {
+                    value: search.placement(entity_ids),
+                    complete: true,
+                });
+            }
+            let stopped = search.limits.stopped();
+            if !stopped && config.preemption {
+                if let Ok(placement) = b.preempt(entity_ids) {
+                    return Ok(Bounded {
+                        value: placement,
+                        complete: true,
+                    });
+                }
+            }
+            if !stopped && !config.partial_placement {
+                return Err(b.solve_failure(entity_ids));
+            }
+            let mut search = Search::new(b);
+            let mut remaining = entity_ids.to_vec();
+            if config.partial_placement {
+                search.run_greedy_partial(&mut remaining);
+            } else if !search.run_greedy(&mut remaining) {
+                return Err(SolveError::Interrupted(entity_ids.to_vec()));
+            }
+            Ok(Bounded {
+                value: search.placement(entity_ids),
+                complete: !stopped,
+            })
+        })?;
+        let unplaced: Vec<String> = bounded
+            .value
+            .unplaced
+            .iter()
+            .map(|(e, _)| e.clone())
+            .collect();
+        pending.remove_entities(&unplaced);
+        // relations of the batch naming evicted entities go with them.
+        let evicted: Vec<String> = bounded
+            .value
+            .evictions
+            .iter()
+            .map(|e| e.entity_id.clone())
+            .collect();
+        pending.remove_entities(&evicted);
+        for e in &evicted {
+            self.remove_entity(e)
+                .map_err(|err| SolveError::Invalid(vec![err]))?;
+        }
+        self.apply_pending(pending, &bounded.value)
+            .map_err(SolveError::Invalid)?;
+        Ok(bounded)
+    }
+
+    // runs f on a copy of the board with the pending batch staged, passing
+    // the ids of the staged entities.
+    pub(crate) fn with_staged<T>(
+        &self,
+        pending: &Pending,
+        f: impl FnOnce(&Board, &[String]) -> Result<T, SolveError>,
+    ) -> Result<T, SolveError> {
+        let mut scratch = self.clone();
+        let staged = scratch
+            .stage(pending.clone())
+            .map_err(SolveError::Invalid)?;
+        f(&scratch, &staged.entity_ids)
+    }
+
+    // error for a failed search over the staged entities, telling apart
+    // entities that fit nowhere from joint conflicts.
+    pub(crate) fn solve_failure(&self, entity_ids: &[String]) -> SolveError {
+        let base = Search::new(self);
+        let stuck: Vec<PlacementExplanation> = entity_ids
+            .iter()
+            .filter(|e| base.candidates(e).is_empty())
+            .map(|e| self.explain_placement(e))
+            .collect();
+        if stuck.is_empty() {
+            SolveError::Unsatisfiable(entity_ids.to_vec())
+        } else {
+            SolveError::NoCandidates(stuck)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Counts, Search};
+    use crate::solver::{
+        Board, Entity, IDRelation, IDRelationKind, Pending, PlacementConstraint, Priority,
+        PropertyRelation, PropertyRelationKind, Resource, SolveError, SolverConfig, ViolationKind,
+    };
+
+    fn board() -> Board {
+        let mut b = Board::new();
+        for (id, color) in [("node1", "red"), ("node2", "blue"), ("node3", "red")] {
+            let mut r = Resource::new(String::from(id));
+            r.add_property(String::from(color));
+            r.capacities.insert(String::from("cpu"), 4);
+            b.add_resource(r).expect("added");
+        }
+        b
+    }
+
+    fn app(id: &str, cpu: i64) -> Entity {
+        let mut e = Entity::new(String::from(id));
+        e.add_property(String::from("red"));
+        e.metrics.insert(String::from("cpu"), cpu);
+        e
+    }
+
+    #[test]
+    fn solve_test() {
+        let mut b = board();
+        let mut p = Pending::new();
+        p.add_entity(app("app1", 3));
+        p.add_entity(app("app2", 3));
+        p.property_relations.insert(
+            String::from("color"),
+            PropertyRelation {
+                id: String::from("color"),
+                kind: PropertyRelationKind::Affinity,
+                entity_property: String::from("red"),
+                resource_property: String::from("red"),
+                priority: Priority::Hard,
+            },
+        );
+
+        let placement = b.solve(p).expect("solves");
+        // both need a red node and they can't share one.
+        let mut used: Vec<&String> = placement.assignment.values().collect();
+        used.sort();
+        assert_eq!(used, vec!["node1", "node3"]);
+        assert_eq!(b.assignment().len(), 2);
+        assert!(b.check_all().is_empty());
+    }
+
+    #[test]
+    fn solve_failure_leaves_board_test() {
+        let mut b = board();
+        let mut p = Pending::new();
+        p.add_entity(app("big", 5));
+        let err = b.solve(p).expect_err("fails");
+        assert!(
+            matches!(&err, SolveError::NoCandidates(ex) if ex.len() == 1 && ex[0].entity_id == "big")
+        );
+        assert_eq!(
+            err.to_string(),
+            "no resource can host: big (node1: metric 'cpu' needs 5, only 4 free; \
+             node2: metric 'cpu' needs 5, only 4 free; \
+             node3: metric 'cpu' needs 5, only 4 free)"
+        );
+        assert!(b.entities.is_empty());
+
+        let mut p = Pending::new();
+        for id in ["a", "b", "c", "d"] {
+            p.add_entity(app(id, 2));
+        }
+        p.add_entity(app("e", 3));
+        p.property_relations.insert(
+            String::from("color"),
+            PropertyRelation {
+                id: String::from("color"),
+                kind: PropertyRelationKind::Affinity,
+                entity_property: String::from("red"),
+                resource_property: String::from("red"),
+                priority: Priority::Hard,
+            },
+        );
+        let err = b.solve(p).expect_err("fails");
+        assert!(matches!(err, SolveError::Unsatisfiable(ids) if ids.len() == 5));
+        assert!(b.entities.is_empty());
+        assert!(b.property_relations.is_empty());
+    }
+
+    #[test]
+    fn soft_relation_test() {
+        let mut b = board();
+        let soft = |b: &mut Board, priority| {
+            b.property_relations.insert(
+                String::from("color"),
+                PropertyRelation {
+                    id: String::from("color"),
+                    kind: PropertyRelationKind::Affinity,
+                    entity_property: String::from("red"),
+                    resource_property: String::from("red"),
+                    priority,
+                },
+            );
+        };
+        // red nodes are preferred while they have room.
+        soft(&mut b, Priority::Soft(5));
+        let mut p = Pending::new();
+        p.add_entity(app("app1", 3));
+        p.add_entity(app("app2", 3));
+        let placement = b.solve(p).expect("solves");
+        let mut used: Vec<&String> = placement.assignment.values().collect();
+        used.sort();
+        assert_eq!(used, vec!["node1", "node3"]);
+
+        // once they are full the soft relation gives way.
+        let mut p = Pending::new();
+        p.add_entity(app("app3", 3));
+        let placement = b.solve(p).expect("solves");
+        assert_eq!(placement.assignment["app3"], "node2");
+        let report = b.check_all();
+        assert_eq!(report.hard().count(), 0);
+        assert_eq!(report.soft().count(), 1);
+        assert_eq!(report.penalty(), 5);
+
+        // a hard relation does not.
+        b.remove_entity("app3").expect("removed");
+        soft(&mut b, Priority::Hard);
+        let mut p = Pending::new();
+        p.add_entity(app("app3", 3));
+        assert!(b.solve(p).is_err());
+    }
+
+    #[test]
+    fn constraint_test() {
+        let mut b = board();
+        let mut p = Pending::new();
+        let mut e = app("app1", 1);
+        e.constraint = Some(PlacementConstraint::parse("!red").unwrap());
+        p.add_entity(e);
+        let placement = b.solve(p).expect("solves");
+        assert_eq!(placement.assignment["app1"], "node2");
+
+        // moving it onto a red node breaks the constraint.
+        let (app1, node1) = (
+            b.entities.handle("app1").unwrap(),
+            b.resources.handle("node1").unwrap(),
+        );
+        b.assignment.insert(app1, node1);
+        let report = b.check_all();
+        assert_eq!(report.len(), 1);
+        assert_eq!(report.entries[0].kind, ViolationKind::Constraint);
+        assert_eq!(report.entries[0].entity_id.as_deref(), Some("app1"));
+        assert_eq!(report.entries[0].relation_id, None);
+    }
+
+    #[test]
+    fn ee_property_relation_test() {
+        let mut b = board();
+        let mut p = Pending::new();
+        for id in ["db1", "db2", "db3"] {
+            p.add_entity(app(id, 1));
+        }
+        p.property_relations.insert(
+            String::from("spread"),
+            PropertyRelation {
+                id: String::from("spread"),
+                kind: PropertyRelationKind::EEAntiAffinity,
+                entity_property: String::from("red"),
+                resource_property: String::new(),
+                priority: Priority::Hard,
+            },
+        );
+        let placement = b.solve(p).expect("solves");
+        let mut used: Vec<&String> = placement.assignment.values().collect();
+        used.sort();
+        assert_eq!(used, vec!["node1", "node2", "node3"]);
+
+        // a fourth can't go anywhere.
+        let mut p = Pending::new();
+        p.add_entity(app("db4", 1));
+        assert!(b.solve(p).is_err());
+    }
+
+    #[test]
+    fn partial_placement_test() {
+        let mut p = Pending::new();
+        for (id, cpu) in [("app1", 2), ("huge", 10), ("app2", 3)] {
+            p.add_entity(app(id, cpu));
+        }
+        p.id_relations.insert(
+            String::from("near"),
+            IDRelation {
+                id: String::from("near"),
+                kind: IDRelationKind::EEAffinity,
+                id1: String::from("app1"),
+                id2: String::from("huge"),
+                priority: Priority::Hard,
+            },
+        );
+        let mut b = board();
+        assert!(matches!(
+            b.solve(p.clone()),
+            Err(SolveError::NoCandidates(ex)) if ex[0].entity_id == "huge"
+        ));
+
+        let config = SolverConfig {
+            partial_placement: true,
+            ..Default::default()
+        };
+        let placed = b.solve_bounded(p, &config).expect("placed");
+        assert!(placed.complete);
+        let placement = placed.value;
+        assert_eq!(placement.assignment.len(), 2);
+        assert_eq!(placement.unplaced.len(), 1);
+        let (id, explanation) = &placement.unplaced[0];
+        assert_eq!(id, "huge");
+        assert!(!explanation.is_placeable());
+        assert_eq!(b.entities.len(), 2);
+        assert!(!b.id_relations.contains_key("near"));
+        assert!(b.check_all().is_empty());
+    }
+
+    #[test]
+    fn candidate_counts_test() {
+        // the counts kept while placing match the candidates computed anew.
+        let mut p = Pending::new();
+        for id in ["a", "b", "c", "d"] {
+            p.add_entity(app(id, 3));
+        }
+        p.id_relations.insert(
+            String::from("ab"),
+            IDRelation {
+                id: String::from("ab"),
+                kind: IDRelationKind::EEAntiAffinity,
+                id1: String::from("a"),
+                id2: String::from("b"),
+                priority: Priority::Hard,
+            },
+        );
+        let b = board();
+        b.with_staged(&p, |b, entity_ids| {
+            let mut search = Search::new(b);
+            let mut remaining = entity_ids.to_vec();
+            remaining.sort();
+            let mut counts = Counts::new(&search, &remaining);
+            let check = |search: &Search, counts: &Counts, remaining: &[String]| {
+                for e in remaining {
+                    assert_eq!(counts.count(e), search.candidates(e).len(), "{}", e);
+                }
+            };
+            let mut placed = Vec::new();
+            while let Some(r_id) = remaining
+                .first()
+                .and_then(|e| search.candidates(e).first().cloned())
+            {
+                let e = remaining.remove(0);
+                search.place(&e, &r_id);
+                counts.place(&search, &e, &r_id, &remaining);
+                check(&search, &counts, &remaining);
+                placed.push(e);
+            }
+            assert_eq!(placed.len(), 3);
+            while let Some(e) = placed.pop() {
+                search.unplace(&e);
+                counts.unplace();
+                remaining.insert(0, e);
+                check(&search, &counts, &remaining);
+            }
+            Ok(())
+        })
+        .expect("staged");
+    }
+
+    #[test]
+    fn large_batch_test() {
+        // the search keeps its own stack, however large the batch.
+        let solve = std::thread::Builder::new()
+            .stack_size(128 << 10)
+            .spawn(|| {
+                let mut b = board();
+                let mut p = Pending::new();
+                for i in 0..1000 {
+                    p.add_entity(Entity::new(format!("e{}", i)));
+                }
+                b.solve(p).map(|placement| placement.assignment.len())
+            })
+            .expect("spawned");
+        assert_eq!(solve.join().expect("no overflow"), Ok(1000));
+    }
+}
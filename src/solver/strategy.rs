@@ -0,0 +1,102 @@
+// placement strategies for pending batches.
+
+use super::solve::Search;
+use super::{Board, Pending, Placement, SolveError};
+
+// computes a placement for every pending entity without changing the
+// board. Used through Board::solve_with.
+pub trait Solver {
+    fn place(&self, board: &Board, pending: &Pending) -> Result<Placement, SolveError>;
+}
+
+// places the most constrained entity on its best candidate, one at a time
+// and without going back. Fast, but can fail where a placement exists.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GreedySolver;
+
+// exhaustive search that backtracks on dead ends, so it finds a placement
+// whenever one exists. This is what Board::solve uses.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BacktrackingSolver;
+
+impl Solver for GreedySolver {
+    fn place(&self, board: &Board, pending: &Pending) -> Result<Placement, SolveError> {
+        board.with_staged(pending, |b, entity_ids| {
+            let mut search = Search::new(b);
+            let mut remaining = entity_ids.to_vec();
+            if search.run_greedy(&mut remaining) {
+                Ok(search.placement(entity_ids))
+            } else {
+                Err(b.solve_failure(entity_ids))
+            }
+        })
+    }
+}
+
+impl Solver for BacktrackingSolver {
+    fn place(&self, board: &Board, pending: &Pending) -> Result<Placement, SolveError> {
+        board.with_staged(pending, |b, entity_ids| {
+            let mut search = Search::new(b);
+            let mut remaining = entity_ids.to_vec();
+            if search.run(&mut remaining) {
+                Ok(search.placement(entity_ids))
+            } else {
+                Err(b.solve_failure(entity_ids))
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::solver::{
+        BacktrackingSolver, Board, Entity, GreedySolver, Pending, Resource, SolveError, Solver,
+    };
+
+    fn setup() -> (Board, Pending) {
+        let mut b = Board::new();
+        for (id, cpu) in [("node1", 4), ("node2", 5)] {
+            let mut r = Resource::new(String::from(id));
+            r.capacities.insert(String::from("cpu"), cpu);
+            b.add_resource(r).expect("added");
+        }
+        let mut p = Pending::new();
+        for (id, cpu) in [("a", 1), ("b", 3), ("c", 2), ("d", 3)] {
+            let mut e = Entity::new(String::from(id));
+            e.metrics.insert(String::from("cpu"), cpu);
+            p.add_entity(e);
+        }
+        (b, p)
+    }
+
+    #[test]
+    fn greedy_and_backtracking_test() {
+        let (mut b, p) = setup();
+        // the batch only fits exactly, which greedy misses.
+        assert!(matches!(
+            GreedySolver.place(&b, &p),
+            Err(SolveError::Unsatisfiable(ids)) if ids.len() == 4
+        ));
+        assert!(b.entities.is_empty());
+
+        let placement = BacktrackingSolver.place(&b, &p).expect("solves");
+        assert_eq!(placement.assignment["a"], "node1");
+        assert_eq!(placement.assignment["b"], "node1");
+        assert_eq!(placement.assignment["c"], "node2");
+        assert!(b.entities.is_empty());
+
+        b.solve_with(&BacktrackingSolver, p).expect("solves");
+        assert_eq!(b.entities.len(), 4);
+        assert!(b.check_all().is_empty());
+    }
+
+    #[test]
+    fn greedy_solve_with_test() {
+        let (mut b, mut p) = setup();
+        p.entities.remove("d");
+        let placement = b.solve_with(&GreedySolver, p).expect("solves");
+        assert_eq!(placement.assignment.len(), 3);
+        assert_eq!(b.assignment().len(), 3);
+        assert!(b.check_all().is_empty());
+    }
+}
@@ -0,0 +1,208 @@
+// tracing of solver internals, with the trace feature: spans around the
+// solve and rebalance phases, events for rejected candidates and counters
+// for explored search states and generated moves. Without a subscriber,
+// or without the feature, the calls cost nothing and messages are never
+// formatted.
+
+#[cfg(feature = "trace")]
+use std::cell::RefCell;
+#[cfg(feature = "trace")]
+use std::sync::{Arc, RwLock};
+#[cfg(feature = "trace")]
+use std::time::Duration;
+
+// receives what the solver reports. Targets name the part of the solver,
+// like "solve" or "repair".
+#[cfg(feature = "trace")]
+pub trait Subscriber: Send + Sync {
+    fn span_enter(&self, _name: &'static str) {}
+    fn span_exit(&self, _name: &'static str, _elapsed: Duration) {}
+    fn event(&self, _target: &'static str, _message: &str) {}
+    // adds delta to the named counter.
+    fn count(&self, _name: &'static str, _delta: u64) {}
+}
+
+// writes every span and event to stderr, indented by span depth.
+#[cfg(feature = "trace")]
+#[derive(Debug, Default)]
+pub struct StderrSubscriber {
+    depth: std::sync::atomic::AtomicUsize,
+}
+
+#[cfg(feature = "trace")]
+impl Subscriber for StderrSubscriber {
+    fn span_enter(&self, name: &'static str) {
+        use std::sync::atomic::Ordering;
+        let depth = self.depth.fetch_add(1, Ordering::Relaxed);
+        eprintln!("{:indent$}> {}", "", name, indent = depth * 2);
+    }
+
+    fn span_exit(&self, name: &'static str, elapsed: Duration) {
+        use std::sync::atomic::Ordering;
+        let depth = self.depth.fetch_sub(1, Ordering::Relaxed).saturating_sub(1);
+        eprintln!(
+            "{:indent$}< {} {:.2?}",
+            "",
+            name,
+            elapsed,
+            indent = depth * 2
+        );
+    }
+
+    fn event(&self, target: &'static str, message: &str) {
+        use std::sync::atomic::Ordering;
+        let depth = self.depth.load(Ordering::Relaxed);
+        eprintln!("{:indent$}{}: {}", "", target, message, indent = depth * 2);
+    }
+
+    fn count(&self, name: &'static str, delta: u64) {
+        self.event("count", &format!("{} +{}", name, delta));
+    }
+}
+
+#[cfg(feature = "trace")]
+static GLOBAL: RwLock<Option<Arc<dyn Subscriber>>> = RwLock::new(None);
+
+#[cfg(feature = "trace")]
+thread_local! {
+    static SCOPED: RefCell<Option<Arc<dyn Subscriber>>> = const { RefCell::new(None) };
+}
+
+// sets the subscriber of every thread without a scoped one.
+#[cfg(feature = "trace")]
+pub fn set_global_subscriber(subscriber: Arc<dyn Subscriber>) {
+    *GLOBAL.write().expect("subscriber lock") = Some(subscriber);
+}
+
+// runs f with the subscriber on the current thread, and on the threads
+// the solver starts for it.
+#[cfg(feature = "trace")]
+pub fn with_subscriber<T>(subscriber: Arc<dyn Subscriber>, f: impl FnOnce() -> T) -> T {
+    let prev = SCOPED.with(|s| s.replace(Some(subscriber)));
+    // restores the previous one even if f panics.
+    struct Reset(Option<Arc<dyn Subscriber>>);
+    impl Drop for Reset {
+        fn drop(&mut self) {
+            SCOPED.with(|s| *s.borrow_mut() = self.0.take());
+        }
+    }
+    let _reset = Reset(prev);
+    f()
+}
+
+#[cfg(feature = "trace")]
+pub(crate) fn current() -> Option<Arc<dyn Subscriber>> {
+    SCOPED
+        .with(|s| s.borrow().clone())
+        .or_else(|| GLOBAL.read().ok().and_then(|g| g.clone()))
+}
+
+#[cfg(feature = "trace")]
+fn with_current(f: impl FnOnce(&dyn Subscriber)) {
+    if let Some(s) = current() {
+        f(s.as_ref());
+    }
+}
+
+// open span, closed on drop.
+pub(crate) struct Span {
+    #[cfg(feature = "trace")]
+    open: Option<(&'static str, std::time::Instant)>,
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        #[cfg(feature = "trace")]
+        if let Some((name, start)) = self.open.take() {
+            with_current(|s| s.span_exit(name, start.elapsed()));
+        }
+    }
+}
+
+#[cfg(feature = "trace")]
+pub(crate) fn span(name: &'static str) -> Span {
+    let mut open = None;
+    with_current(|s| {
+        s.span_enter(name);
+        open = Some((name, std::time::Instant::now()));
+    });
+    Span { open }
+}
+
+#[cfg(not(feature = "trace"))]
+pub(crate) fn span(_name: &'static str) -> Span {
+    Span {}
+}
+
+// the message is only built when a subscriber listens.
+#[cfg(feature = "trace")]
+pub(crate) fn event(target: &'static str, message: impl FnOnce() -> String) {
+    with_current(|s| s.event(target, &message()));
+}
+
+#[cfg(not(feature = "trace"))]
+pub(crate) fn event(_target: &'static str, _message: impl FnOnce() -> String) {}
+
+#[cfg(feature = "trace")]
+pub(crate) fn count(name: &'static str, delta: u64) {
+    with_current(|s| s.count(name, delta));
+}
+
+#[cfg(not(feature = "trace"))]
+pub(crate) fn count(_name: &'static str, _delta: u64) {}
+
+#[cfg(all(test, feature = "trace"))]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::sync::{Arc, Mutex};
+
+    use super::{with_subscriber, Subscriber};
+    use crate::solver::{BoardBuilder, EntityBuilder, Pending, ResourceBuilder};
+
+    #[derive(Default)]
+    struct Record {
+        lines: Mutex<Vec<String>>,
+        counts: Mutex<BTreeMap<&'static str, u64>>,
+    }
+
+    impl Subscriber for Record {
+        fn span_enter(&self, name: &'static str) {
+            self.lines.lock().unwrap().push(format!("> {}", name));
+        }
+
+        fn event(&self, target: &'static str, message: &str) {
+            self.lines
+                .lock()
+                .unwrap()
+                .push(format!("{}: {}", target, message));
+        }
+
+        fn count(&self, name: &'static str, delta: u64) {
+            *self.counts.lock().unwrap().entry(name).or_insert(0) += delta;
+        }
+    }
+
+    #[test]
+    fn trace_test() {
+        let mut b = BoardBuilder::new()
+            .resource(ResourceBuilder::new("node1").capacity("cpu", 2))
+            .resource(ResourceBuilder::new("node2").capacity("cpu", 8))
+            .build()
+            .expect("builds");
+        let mut p = Pending::new();
+        p.add_entity(EntityBuilder::new("app").metric("cpu", 4).build());
+
+        let record = Arc::new(Record::default());
+        with_subscriber(record.clone(), || {
+            b.solve(p).expect("placed");
+            b.rebalance();
+        });
+        let lines = record.lines.lock().unwrap();
+        assert_eq!(lines[0], "> solve");
+        assert!(lines.contains(&String::from("candidate: app does not fit on node1: cpu")));
+        assert!(lines.contains(&String::from("> rebalance")));
+        let counts = record.counts.lock().unwrap();
+        assert_eq!(counts.get("search.states"), Some(&1));
+        assert_eq!(counts.get("rebalance.moves"), Some(&0));
+    }
+}
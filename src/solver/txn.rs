@@ -0,0 +1,183 @@
+// transactions: board changes that can be taken back as a whole.
+
+use std::ops::{Deref, DerefMut};
+
+use super::Board;
+
+impl Board {
+    // starts a transaction. Changes are made through the returned Txn,
+    // which derefs to the board, and are kept by commit; rollback or
+    // dropping the Txn puts the board back as it was at begin. A Txn can
+    // begin another one, which rolls back on its own.
+    pub fn begin(&mut self) -> Txn<'_> {
+        let (savepoint, outermost) = self.journal.open();
+        Txn {
+            board: self,
+            savepoint,
+            outermost,
+            committed: false,
+        }
+    }
+}
+
+// an open transaction, see Board::begin. The board records what each
+// change replaces, so a rollback undoes entities, relations, the
+// assignment and reported loads, marking them for the next incremental
+// check. Listeners are told about the violations a rollback changes, not
+// about each undone step.
+#[derive(Debug)]
+pub struct Txn<'a> {
+    board: &'a mut Board,
+    // length of the undo log at begin.
+    savepoint: usize,
+    // false when nested in another transaction, which keeps the log.
+    outermost: bool,
+    committed: bool,
+}
+
+impl Txn<'_> {
+    pub fn commit(mut self) {
+        self.committed = true;
+    }
+
+    pub fn rollback(self) {
+        // undoing is done by drop.
+    }
+}
+
+impl Drop for Txn<'_> {
+    fn drop(&mut self) {
+        if !self.committed {
+            self.board.undo_to(self.savepoint);
+        }
+        if self.outermost {
+            self.board.journal.close();
+        }
+    }
+}
+
+impl Deref for Txn<'_> {
+    type Target = Board;
+
+    fn deref(&self) -> &Board {
+        self.board
+    }
+}
+
+impl DerefMut for Txn<'_> {
+    fn deref_mut(&mut self) -> &mut Board {
+        self.board
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::solver::{
+        Board, BoardBuilder, DeactivationIntent, EntityBuilder, EntityGroup, IDRelation,
+        IDRelationKind, Priority, ResourceBuilder, ResourceState, SolverError,
+    };
+
+    fn board() -> Board {
+        BoardBuilder::new()
+            .resource(ResourceBuilder::new("node1").capacity("cpu", 10))
+            .resource(ResourceBuilder::new("node2").capacity("cpu", 10))
+            .entity("node1", EntityBuilder::new("a").metric("cpu", 2))
+            .entity("node1", EntityBuilder::new("b").metric("cpu", 3))
+            .build()
+            .expect("builds")
+    }
+
+    #[test]
+    fn rollback_test() {
+        let mut b = board();
+        b.enable_change_tracking();
+        assert!(b.check_violation_incremental().is_empty());
+
+        // a plan applied step by step fails at a missing resource.
+        let mut txn = b.begin();
+        txn.move_entity("a", "node2").expect("moved");
+        txn.add_entity(
+            String::from("node2"),
+            EntityBuilder::new("c").metric("cpu", 9).build(),
+        )
+        .expect("added");
+        txn.report_load("a", "cpu", 4).expect("reported");
+        assert!(!txn.check_all().is_empty());
+        assert_eq!(
+            txn.move_entity("b", "node9"),
+            Err(SolverError::ResourceNotFound(String::from("node9")))
+        );
+        txn.rollback();
+
+        assert_eq!(b.assignment()["a"], "node1");
+        assert!(!b.entities.contains_key("c"));
+        assert_eq!(b.entities["a"].metrics["cpu"], 2);
+        assert_eq!(b.resource_load("node1")["cpu"], 5);
+        assert!(b.check_violation_incremental().is_empty());
+        assert!(b.validate().is_empty());
+
+        // committed changes stay, a dropped transaction is rolled back.
+        let mut txn = b.begin();
+        txn.move_entity("a", "node2").expect("moved");
+        {
+            let mut inner = txn.begin();
+            inner.remove_entity("a").expect("removed");
+        }
+        txn.commit();
+        assert_eq!(b.assignment()["a"], "node2");
+        {
+            let mut txn = b.begin();
+            txn.move_entity("a", "node1").expect("moved");
+        }
+        assert_eq!(b.assignment()["a"], "node2");
+        assert!(b.validate().is_empty());
+    }
+
+    #[test]
+    fn nested_commit_test() {
+        let mut b = board();
+        b.add_id_relation(IDRelation {
+            id: String::from("apart"),
+            kind: IDRelationKind::EEAntiAffinity,
+            id1: String::from("a"),
+            id2: String::from("b"),
+            priority: Priority::Hard,
+        })
+        .expect("added");
+        b.enable_change_tracking();
+        let before = b.snapshot();
+        let violations = b.check_violation_incremental();
+
+        let mut txn = b.begin();
+        {
+            // kept by the inner commit, undone with the outer transaction.
+            let mut inner = txn.begin();
+            inner.remove_relation("apart").expect("removed");
+            inner
+                .add_group(EntityGroup::new(String::from("db"), 2))
+                .expect("added");
+            inner.commit();
+        }
+        assert!(!txn.id_relations.contains_key("apart"));
+        txn.deactivate_resource("node2", DeactivationIntent::Pause)
+            .expect("paused");
+        let (_, pending) = txn.evict_resource("node1").expect("evicted");
+        assert!(pending.entities.contains_key("a"));
+        txn.rollback();
+
+        assert!(before.diff(&b).is_empty());
+        assert!(b.groups.is_empty());
+        assert_eq!(b.resources["node2"].state, ResourceState::Active);
+        assert_eq!(b.resource_load("node1")["cpu"], 5);
+        assert_eq!(b.check_violation_incremental(), violations);
+        assert_eq!(b.check_violation_incremental(), b.check_violation());
+        assert!(b.validate().is_empty());
+        // a copy made during a transaction is not rolled back with it.
+        let mut txn = b.begin();
+        txn.move_entity("a", "node2").expect("moved");
+        let copy = txn.snapshot();
+        drop(txn);
+        assert_eq!(copy.assignment()["a"], "node2");
+        assert_eq!(b.assignment()["a"], "node1");
+    }
+}
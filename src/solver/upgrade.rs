@@ -0,0 +1,249 @@
+// rolling upgrades: taking the resources down one upgrade domain at a time.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use super::{
+    Board, DomainKind, MovePlan, PlacementExplanation, ResourceState, SolveError, SolverError,
+};
+
+// why the entities of an upgrade domain cannot be moved off it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UpgradeBlocker {
+    Pinned(String),
+    // no resource outside the domain takes the entity.
+    NoCandidates(PlacementExplanation),
+    // every entity has somewhere to go, but not all of them together.
+    Unsatisfiable(Vec<String>),
+}
+
+impl fmt::Display for UpgradeBlocker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UpgradeBlocker::Pinned(id) => write!(f, "entity {} is pinned", id),
+            UpgradeBlocker::NoCandidates(ex) => {
+                write!(f, "entity {} has nowhere to go", ex.entity_id)
+            }
+            UpgradeBlocker::Unsatisfiable(ids) => {
+                write!(f, "entities {} cannot be moved together", ids.join(", "))
+            }
+        }
+    }
+}
+
+// one upgrade domain taken down.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpgradeStep {
+    pub domain: String,
+    // resources of the domain, sorted.
+    pub resources: Vec<String>,
+    // moves evacuating the domain, empty when blocked.
+    pub plan: MovePlan,
+    pub blockers: Vec<UpgradeBlocker>,
+}
+
+impl UpgradeStep {
+    pub fn is_safe(&self) -> bool {
+        self.blockers.is_empty()
+    }
+}
+
+impl fmt::Display for UpgradeStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_safe() {
+            return write!(
+                f,
+                "{} can be drained with {} moves",
+                self.domain,
+                self.plan.len()
+            );
+        }
+        let blockers: Vec<String> = self.blockers.iter().map(|b| b.to_string()).collect();
+        write!(
+            f,
+            "{} cannot be drained: {}",
+            self.domain,
+            blockers.join("; ")
+        )
+    }
+}
+
+// the steps of a rolling upgrade, in upgrade domain order.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct UpgradeReport {
+    pub steps: Vec<UpgradeStep>,
+}
+
+impl UpgradeReport {
+    pub fn is_safe(&self) -> bool {
+        self.steps.iter().all(|s| s.is_safe())
+    }
+
+    pub fn blocked(&self) -> impl Iterator<Item = &UpgradeStep> {
+        self.steps.iter().filter(|s| !s.is_safe())
+    }
+}
+
+impl fmt::Display for UpgradeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lines: Vec<String> = self.steps.iter().map(|s| s.to_string()).collect();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+impl Board {
+    // simulates a rolling upgrade on a snapshot of the board. Upgrade
+    // domains go offline one at a time, sorted by name, a resource without
+    // one being a domain of its own. Each step moves the domain's entities
+    // to resources outside it with all hard relations and capacities
+    // satisfied, and keeps the moves for the later steps once the domain is
+    // back. A blocked step moves nothing. The board is not modified.
+    pub fn simulate_upgrade(&self) -> UpgradeReport {
+        let mut domains: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for r in self.resources.values() {
+            domains
+                .entry(r.domain(DomainKind::Upgrade).to_string())
+                .or_default()
+                .push(r.id.clone());
+        }
+
+        let mut board = self.snapshot();
+        let mut report = UpgradeReport::default();
+        for (domain, mut resources) in domains {
+            resources.sort();
+            let states: Vec<ResourceState> =
+                resources.iter().map(|r| board.resources[r].state).collect();
+            for r in &resources {
+                board.resources.get_mut(r).expect("resource").state = ResourceState::Draining;
+            }
+            let (plan, blockers) = match board.evacuation_plan(&resources) {
+                Ok(plan) => (plan, Vec::new()),
+                Err(err) => (MovePlan::default(), blockers(err)),
+            };
+            board.apply_move_plan(&plan).expect("planned moves apply");
+            for (r, state) in resources.iter().zip(states) {
+                board.resources.get_mut(r).expect("resource").state = state;
+            }
+            report.steps.push(UpgradeStep {
+                domain,
+                resources,
+                plan,
+                blockers,
+            });
+        }
+        report
+    }
+}
+
+fn blockers(err: SolveError) -> Vec<UpgradeBlocker> {
+    match err {
+        SolveError::Invalid(errors) => errors
+            .into_iter()
+            .filter_map(|e| match e {
+                SolverError::Pinned(id) => Some(UpgradeBlocker::Pinned(id)),
+                _ => None,
+            })
+            .collect(),
+        SolveError::NoCandidates(explanations) => explanations
+            .into_iter()
+            .map(UpgradeBlocker::NoCandidates)
+            .collect(),
+        SolveError::Unsatisfiable(ids) | SolveError::Interrupted(ids) => {
+            vec![UpgradeBlocker::Unsatisfiable(ids)]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::solver::{
+        Board, BoardBuilder, EntityBuilder, IDRelation, IDRelationKind, Priority, ResourceBuilder,
+        UpgradeBlocker,
+    };
+
+    fn board() -> Board {
+        BoardBuilder::new()
+            .resource(
+                ResourceBuilder::new("node1")
+                    .capacity("cpu", 4)
+                    .upgrade_domain("UD1"),
+            )
+            .resource(
+                ResourceBuilder::new("node2")
+                    .capacity("cpu", 4)
+                    .upgrade_domain("UD2"),
+            )
+            .resource(
+                ResourceBuilder::new("node3")
+                    .capacity("cpu", 4)
+                    .upgrade_domain("UD3"),
+            )
+            .entity("node1", EntityBuilder::new("app1").metric("cpu", 2))
+            .entity("node2", EntityBuilder::new("app2").metric("cpu", 2))
+            .entity("node3", EntityBuilder::new("app3").metric("cpu", 1))
+            .build()
+            .expect("builds")
+    }
+
+    #[test]
+    fn rolling_upgrade_test() {
+        let b = board();
+        let report = b.simulate_upgrade();
+        assert!(report.is_safe(), "{}", report);
+        let domains: Vec<&str> = report.steps.iter().map(|s| s.domain.as_str()).collect();
+        assert_eq!(domains, ["UD1", "UD2", "UD3"]);
+        assert!(report.steps.iter().all(|s| s
+            .plan
+            .moves
+            .iter()
+            .all(|m| !s.resources.contains(&m.to))));
+        assert_eq!(
+            report.steps[0].to_string(),
+            "UD1 can be drained with 1 moves"
+        );
+        // the board itself is untouched.
+        assert_eq!(b.assignment()["app1"], "node1");
+    }
+
+    #[test]
+    fn blocked_upgrade_test() {
+        let mut b = board();
+        b.add_entity(
+            String::from("node2"),
+            EntityBuilder::new("app7").metric("cpu", 2).build(),
+        )
+        .expect("added");
+        b.entities.get_mut("app3").expect("entity").pinned = true;
+        // app7 may go neither to node1 nor next to app3.
+        for (id, kind, other) in [
+            ("off1", IDRelationKind::ERAntiAffinity, "node1"),
+            ("apart3", IDRelationKind::EEAntiAffinity, "app3"),
+        ] {
+            b.add_id_relation(IDRelation {
+                id: String::from(id),
+                kind,
+                id1: String::from("app7"),
+                id2: String::from(other),
+                priority: Priority::Hard,
+            })
+            .expect("added");
+        }
+
+        let report = b.simulate_upgrade();
+        assert!(!report.is_safe());
+        let blocked: Vec<String> = report.blocked().map(|s| s.to_string()).collect();
+        assert_eq!(
+            blocked,
+            [
+                "UD2 cannot be drained: entity app7 has nowhere to go",
+                "UD3 cannot be drained: entity app3 is pinned"
+            ]
+        );
+        let step = &report.steps[1];
+        assert!(step.plan.is_empty());
+        assert!(matches!(
+            &step.blockers[0],
+            UpgradeBlocker::NoCandidates(ex) if ex.rejections.len() == 3
+        ));
+    }
+}
@@ -0,0 +1,405 @@
+// audit of the board's internal invariants. The add_* functions keep
+// them, but the public maps can be written directly; validate catches
+// what such writes broke.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::hash::Hash;
+
+use super::{Board, IDRelationKind};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConsistencyIssue {
+    // an object is stored under a key other than its id.
+    KeyMismatch {
+        key: String,
+        id: String,
+    },
+    // the assignment names an entity the board does not hold.
+    UnknownAssignedEntity(String),
+    // the entity is assigned to a resource the board does not hold.
+    UnknownAssignedResource {
+        entity_id: String,
+        resource_id: String,
+    },
+    // the entity has no resource.
+    Unassigned(String),
+    // the relation or chain names an entity that does not exist.
+    DanglingEntity {
+        relation_id: String,
+        entity_id: String,
+    },
+    // the relation names a resource that does not exist.
+    DanglingResource {
+        relation_id: String,
+        resource_id: String,
+    },
+    // the relation's kind is not allowed for its relation type.
+    UnsupportedKind(String),
+    // the relation asks the same as the one with a smaller id, whatever
+    // their priorities.
+    DuplicateRelation {
+        relation_id: String,
+        duplicate_of: String,
+    },
+    // no resource declares a capacity for a metric of the entity, so the
+    // metric never limits its placement.
+    UncoveredMetric {
+        entity_id: String,
+        metric: String,
+    },
+    // the maintained load differs from the assignment, see
+    // Board::refresh_loads.
+    StaleLoad {
+        resource_id: String,
+        metric: String,
+        maintained: i64,
+        actual: i64,
+    },
+}
+
+impl fmt::Display for ConsistencyIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConsistencyIssue::KeyMismatch { key, id } => {
+                write!(f, "object {} is stored as {}", id, key)
+            }
+            ConsistencyIssue::UnknownAssignedEntity(id) => {
+                write!(f, "assigned entity does not exist: {}", id)
+            }
+            ConsistencyIssue::UnknownAssignedResource {
+                entity_id,
+                resource_id,
+            } => write!(
+                f,
+                "entity {} is assigned to missing resource {}",
+                entity_id, resource_id
+            ),
+            ConsistencyIssue::Unassigned(id) => write!(f, "entity has no assignment: {}", id),
+            ConsistencyIssue::DanglingEntity {
+                relation_id,
+                entity_id,
+            } => write!(
+                f,
+                "relation {} names missing entity {}",
+                relation_id, entity_id
+            ),
+            ConsistencyIssue::DanglingResource {
+                relation_id,
+                resource_id,
+            } => write!(
+                f,
+                "relation {} names missing resource {}",
+                relation_id, resource_id
+            ),
+            ConsistencyIssue::UnsupportedKind(id) => {
+                write!(f, "relation kind not supported: {}", id)
+            }
+            ConsistencyIssue::DuplicateRelation {
+                relation_id,
+                duplicate_of,
+            } => write!(f, "relation {} duplicates {}", relation_id, duplicate_of),
+            ConsistencyIssue::UncoveredMetric { entity_id, metric } => write!(
+                f,
+                "no resource has capacity for metric '{}' of {}",
+                metric, entity_id
+            ),
+            ConsistencyIssue::StaleLoad {
+                resource_id,
+                metric,
+                maintained,
+                actual,
+            } => write!(
+                f,
+                "load of '{}' on {} is {}, the assignment gives {}",
+                metric, resource_id, maintained, actual
+            ),
+        }
+    }
+}
+
+impl Board {
+    // every broken invariant of the board: keys, assignment, relations
+    // naming missing ids, duplicate relations, uncovered metrics and
+    // maintained loads, in that order and sorted by id within each part.
+    // Empty for a consistent board.
+    pub fn validate(&self) -> Vec<ConsistencyIssue> {
+        let mut issues = self.key_issues();
+        issues.extend(self.assignment_issues());
+        issues.extend(self.reference_issues());
+        issues.extend(self.duplicate_issues());
+        issues.extend(self.metric_issues());
+        issues.extend(self.load_issues());
+        issues
+    }
+
+    fn key_issues(&self) -> Vec<ConsistencyIssue> {
+        let mut pairs: Vec<(&String, &String)> = Vec::new();
+        pairs.extend(self.resources.iter().map(|(k, v)| (k, &v.id)));
+        pairs.extend(self.entities.iter().map(|(k, v)| (k, &v.id)));
+        pairs.extend(self.id_relations.iter().map(|(k, v)| (k, &v.id)));
+        pairs.extend(self.property_relations.iter().map(|(k, v)| (k, &v.id)));
+        pairs.extend(self.id_property_relations.iter().map(|(k, v)| (k, &v.id)));
+        pairs.extend(self.domain_relations.iter().map(|(k, v)| (k, &v.id)));
+        pairs.extend(self.chains.iter().map(|(k, v)| (k, &v.id)));
+        pairs.extend(self.groups.iter().map(|(k, v)| (k, &v.id)));
+        pairs.sort();
+        pairs
+            .into_iter()
+            .filter(|(k, id)| k != id)
+            .map(|(k, id)| ConsistencyIssue::KeyMismatch {
+                key: k.clone(),
+                id: id.clone(),
+            })
+            .collect()
+    }
+
+    fn assignment_issues(&self) -> Vec<ConsistencyIssue> {
+        let mut issues = Vec::new();
+        let mut assigned: Vec<(&String, &String)> = self.assignment().iter().collect();
+        assigned.sort();
+        for (entity_id, resource_id) in assigned {
+            if !self.entities.contains_key(entity_id) {
+                issues.push(ConsistencyIssue::UnknownAssignedEntity(entity_id.clone()));
+            } else if !self.resources.contains_key(resource_id) {
+                issues.push(ConsistencyIssue::UnknownAssignedResource {
+                    entity_id: entity_id.clone(),
+                    resource_id: resource_id.clone(),
+                });
+            }
+        }
+        let mut unassigned: Vec<&String> = self
+            .entities
+            .keys()
+            .filter(|id| !self.assignment().contains_key(id))
+            .collect();
+        unassigned.sort();
+        issues.extend(
+            unassigned
+                .into_iter()
+                .map(|id| ConsistencyIssue::Unassigned(id.clone())),
+        );
+        issues
+    }
+
+    fn reference_issues(&self) -> Vec<ConsistencyIssue> {
+        let mut issues = Vec::new();
+        let entity = |relation_id: &String, entity_id: &String| {
+            (!self.entities.contains_key(entity_id)).then(|| ConsistencyIssue::DanglingEntity {
+                relation_id: relation_id.clone(),
+                entity_id: entity_id.clone(),
+            })
+        };
+
+        let mut id_relations: Vec<_> = self.id_relations.values().collect();
+        id_relations.sort_by(|a, b| a.id.cmp(&b.id));
+        for rel in id_relations {
+            issues.extend(entity(&rel.id, &rel.id1));
+            match rel.kind {
+                IDRelationKind::EEAffinity | IDRelationKind::EEAntiAffinity => {
+                    issues.extend(entity(&rel.id, &rel.id2));
+                }
+                IDRelationKind::ERAffinity | IDRelationKind::ERAntiAffinity => {
+                    if !self.resources.contains_key(&rel.id2) {
+                        issues.push(ConsistencyIssue::DanglingResource {
+                            relation_id: rel.id.clone(),
+                            resource_id: rel.id2.clone(),
+                        });
+                    }
+                }
+            }
+        }
+        let mut id_property_relations: Vec<_> = self.id_property_relations.values().collect();
+        id_property_relations.sort_by(|a, b| a.id.cmp(&b.id));
+        for rel in id_property_relations {
+            issues.extend(entity(&rel.id, &rel.entity_id));
+            if rel.kind.is_ee() {
+                issues.push(ConsistencyIssue::UnsupportedKind(rel.id.clone()));
+            }
+        }
+        let mut chains: Vec<_> = self.chains.values().collect();
+        chains.sort_by(|a, b| a.id.cmp(&b.id));
+        for chain in chains {
+            if let Some(parent) = &chain.parent {
+                issues.extend(entity(&chain.id, parent));
+            }
+        }
+        issues
+    }
+
+    fn duplicate_issues(&self) -> Vec<ConsistencyIssue> {
+        let mut issues = duplicates(self.id_relations.values().map(|rel| {
+            // entity pairs mean the same in either order.
+            let (a, b) = match rel.kind {
+                IDRelationKind::EEAffinity | IDRelationKind::EEAntiAffinity
+                    if rel.id2 < rel.id1 =>
+                {
+                    (&rel.id2, &rel.id1)
+                }
+                _ => (&rel.id1, &rel.id2),
+            };
+            (&rel.id, (rel.kind, a, b))
+        }));
+        issues.extend(duplicates(self.property_relations.values().map(|rel| {
+            // ee kinds ignore the resource property.
+            let resource_property = (!rel.kind.is_ee()).then_some(&rel.resource_property);
+            (&rel.id, (rel.kind, &rel.entity_property, resource_property))
+        })));
+        issues.extend(duplicates(self.id_property_relations.values().map(|rel| {
+            (&rel.id, (rel.kind, &rel.entity_id, &rel.resource_property))
+        })));
+        issues.extend(duplicates(self.domain_relations.values().map(|rel| {
+            (&rel.id, (rel.domain, &rel.entity_property, rel.min_domains))
+        })));
+        issues.extend(duplicates(
+            self.chains
+                .values()
+                .map(|c| (&c.id, (&c.entity_property, &c.parent))),
+        ));
+        issues
+    }
+
+    fn metric_issues(&self) -> Vec<ConsistencyIssue> {
+        let covered: HashSet<&String> = self
+            .resources
+            .values()
+            .flat_map(|r| r.capacities.keys())
+            .collect();
+        let mut uncovered: Vec<(&String, &String)> = self
+            .entities
+            .iter()
+            .flat_map(|(id, e)| e.metrics.keys().map(move |m| (id, m)))
+            .filter(|(_, m)| !covered.contains(m))
+            .collect();
+        uncovered.sort();
+        uncovered
+            .into_iter()
+            .map(|(id, m)| ConsistencyIssue::UncoveredMetric {
+                entity_id: id.clone(),
+                metric: m.clone(),
+            })
+            .collect()
+    }
+
+    fn load_issues(&self) -> Vec<ConsistencyIssue> {
+        // assignments of missing entities are reported above.
+        let mut valid = self.assignment.clone();
+        for (e, _) in self.assignment.iter() {
+            if self.entities.at(e).is_none() {
+                valid.remove(e);
+            }
+        }
+        let actual = self.loads_with(&valid);
+        let mut keys: HashSet<(&String, &String)> = HashSet::new();
+        for loads in [&actual, &self.loads] {
+            for (r_id, load) in loads {
+                let r_id = self.resources.name(*r_id);
+                keys.extend(load.keys().map(|m| (r_id, m)));
+            }
+        }
+        let value = |loads: &super::capacity::Loads, r_id: &String, metric: &String| {
+            self.load_of(loads, r_id)
+                .and_then(|l| l.get(metric))
+                .copied()
+                .unwrap_or(0)
+        };
+        let mut keys: Vec<(&String, &String)> = keys.into_iter().collect();
+        keys.sort();
+        keys.into_iter()
+            .filter_map(|(r_id, metric)| {
+                let maintained = value(&self.loads, r_id, metric);
+                let actual = value(&actual, r_id, metric);
+                (maintained != actual).then(|| ConsistencyIssue::StaleLoad {
+                    resource_id: r_id.clone(),
+                    metric: metric.clone(),
+                    maintained,
+                    actual,
+                })
+            })
+            .collect()
+    }
+}
+
+// items whose key an item of smaller id already has.
+fn duplicates<'a, K: Hash + Eq>(
+    items: impl Iterator<Item = (&'a String, K)>,
+) -> Vec<ConsistencyIssue> {
+    let mut items: Vec<(&String, K)> = items.collect();
+    items.sort_by(|a, b| a.0.cmp(b.0));
+    let mut first: HashMap<K, &String> = HashMap::new();
+    let mut issues = Vec::new();
+    for (id, key) in items {
+        match first.get(&key) {
+            Some(original) => issues.push(ConsistencyIssue::DuplicateRelation {
+                relation_id: id.clone(),
+                duplicate_of: (*original).clone(),
+            }),
+            None => {
+                first.insert(key, id);
+            }
+        }
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::solver::{
+        BoardBuilder, ConsistencyIssue, Entity, EntityBuilder, IDRelation, IDRelationKind,
+        Priority, ResourceBuilder,
+    };
+
+    #[test]
+    fn validate_test() {
+        let pair = |id: &str, id1: &str, id2: &str| IDRelation {
+            id: String::from(id),
+            kind: IDRelationKind::EEAntiAffinity,
+            id1: String::from(id1),
+            id2: String::from(id2),
+            priority: Priority::Hard,
+        };
+        let mut b = BoardBuilder::new()
+            .resource(ResourceBuilder::new("node1").capacity("cpu", 4))
+            .entity("node1", EntityBuilder::new("a").metric("cpu", 1))
+            .entity("node1", EntityBuilder::new("b").metric("gpu", 1))
+            .id_relation(pair("apart", "a", "b"))
+            .id_relation(pair("apart2", "b", "a"))
+            .build()
+            .expect("builds");
+        assert_eq!(
+            b.validate(),
+            vec![
+                ConsistencyIssue::DuplicateRelation {
+                    relation_id: String::from("apart2"),
+                    duplicate_of: String::from("apart"),
+                },
+                ConsistencyIssue::UncoveredMetric {
+                    entity_id: String::from("b"),
+                    metric: String::from("gpu"),
+                },
+            ]
+        );
+
+        // direct writes around the board api.
+        b.id_relations.remove("apart2");
+        b.entities.get_mut("b").unwrap().metrics.clear();
+        b.entities.remove("a");
+        let node9 = b.resources.intern("node9");
+        b.assignment.insert(b.entities.handle("b").unwrap(), node9);
+        let mut c = Entity::new(String::from("c"));
+        c.metrics.insert(String::from("cpu"), 2);
+        b.entities.insert(String::from("c"), c);
+        let issues: Vec<String> = b.validate().iter().map(|i| i.to_string()).collect();
+        assert_eq!(
+            issues,
+            [
+                "assigned entity does not exist: a",
+                "entity b is assigned to missing resource node9",
+                "entity has no assignment: c",
+                "relation apart names missing entity a",
+                "load of 'cpu' on node1 is 1, the assignment gives 0",
+                "load of 'gpu' on node1 is 1, the assignment gives 0",
+            ]
+        );
+    }
+}
@@ -0,0 +1,692 @@
+// violation checking against the current assignment.
+
+use std::cell::OnceCell;
+use std::collections::HashSet;
+use std::fmt;
+
+use super::index::RelationIndex;
+use super::intern::Assignment;
+use super::parallel;
+use super::trace;
+use super::{Board, DomainKind, IDRelationKind, Priority, PropertyRelationKind};
+
+// what kind of constraint an entry of the report breaks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ViolationKind {
+    Property(PropertyRelationKind),
+    Id(IDRelationKind),
+    IdProperty(PropertyRelationKind),
+    Domain(DomainKind),
+    // the entity is away from the rest of its affinity chain.
+    Chain,
+    // the entity's placement constraint doesn't match the resource.
+    Constraint,
+    Capacity,
+}
+
+// names like "EEAntiAffinity", "PropertyAffinity" or "Capacity".
+impl fmt::Display for ViolationKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ViolationKind::Property(k) => write!(f, "Property{:?}", k),
+            ViolationKind::Id(k) => write!(f, "{:?}", k),
+            ViolationKind::IdProperty(k) => write!(f, "IdProperty{:?}", k),
+            ViolationKind::Domain(k) => write!(f, "{:?}Domain", k),
+            ViolationKind::Chain => f.write_str("Chain"),
+            ViolationKind::Constraint => f.write_str("Constraint"),
+            ViolationKind::Capacity => f.write_str("Capacity"),
+        }
+    }
+}
+
+// one broken constraint. Relation entries name the relation and the
+// offending entity; constraint entries only the entity; capacity entries
+// name the metric and the overage.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Violation {
+    pub kind: ViolationKind,
+    pub relation_id: Option<String>,
+    pub entity_id: Option<String>,
+    // resource the entity is assigned to, or the overloaded resource.
+    pub resource_id: String,
+    pub metric: Option<String>,
+    pub overage: Option<i64>,
+    // priority of the broken relation, capacity is always hard.
+    pub priority: Priority,
+}
+
+impl Violation {
+    fn relation(
+        kind: ViolationKind,
+        priority: Priority,
+        relation_id: &str,
+        entity_id: &str,
+        resource_id: &str,
+    ) -> Self {
+        Violation {
+            kind,
+            relation_id: Some(relation_id.to_string()),
+            entity_id: Some(entity_id.to_string()),
+            resource_id: resource_id.to_string(),
+            metric: None,
+            overage: None,
+            priority,
+        }
+    }
+
+    pub fn is_hard(&self) -> bool {
+        self.priority == Priority::Hard
+    }
+
+    // weight of a soft violation, 0 for hard ones.
+    pub fn penalty(&self) -> i64 {
+        match self.priority {
+            Priority::Hard => 0,
+            Priority::Soft(w) => w,
+        }
+    }
+}
+
+// result of a violation check.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ViolationReport {
+    pub entries: Vec<Violation>,
+}
+
+impl ViolationReport {
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Violation> {
+        self.entries.iter()
+    }
+
+    pub fn hard(&self) -> impl Iterator<Item = &Violation> {
+        self.entries.iter().filter(|v| v.is_hard())
+    }
+
+    pub fn soft(&self) -> impl Iterator<Item = &Violation> {
+        self.entries.iter().filter(|v| !v.is_hard())
+    }
+
+    // summed weight of the soft violations.
+    pub fn penalty(&self) -> i64 {
+        self.entries.iter().map(|v| v.penalty()).sum()
+    }
+
+    // ids of the broken relations.
+    pub fn relation_ids(&self) -> HashSet<String> {
+        self.entries
+            .iter()
+            .filter_map(|v| v.relation_id.clone())
+            .collect()
+    }
+
+    // entries naming the entity.
+    pub fn for_entity<'a>(&'a self, entity_id: &'a str) -> impl Iterator<Item = &'a Violation> {
+        self.entries
+            .iter()
+            .filter(move |v| v.entity_id.as_deref() == Some(entity_id))
+    }
+
+    // entries on the resource, including capacity overages.
+    pub fn for_resource<'a>(&'a self, resource_id: &'a str) -> impl Iterator<Item = &'a Violation> {
+        self.entries
+            .iter()
+            .filter(move |v| v.resource_id == resource_id)
+    }
+}
+
+impl IntoIterator for ViolationReport {
+    type Item = Violation;
+    type IntoIter = std::vec::IntoIter<Violation>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+impl Board {
+    // relation violations of the current assignment, one entry per
+    // offending entity, sorted by relation id then entity id.
+    pub fn check_violation(&self) -> ViolationReport {
+        ViolationReport {
+            entries: self.violations_with(&self.assignment),
+        }
+    }
+
+    // relation violations followed by capacity violations.
+    pub fn check_all(&self) -> ViolationReport {
+        let _span = trace::span("check");
+        ViolationReport {
+            entries: self.all_violations_with(&self.assignment),
+        }
+    }
+
+    pub(crate) fn all_violations_with(&self, assignment: &Assignment) -> Vec<Violation> {
+        self.all_violations_indexed(&RelationIndex::new(self), assignment)
+    }
+
+    pub(crate) fn all_violations_indexed(
+        &self,
+        index: &RelationIndex,
+        assignment: &Assignment,
+    ) -> Vec<Violation> {
+        let mut violations = self.violations_indexed(index, assignment);
+        for c in self.capacity_violations_with(assignment) {
+            violations.push(Violation {
+                kind: ViolationKind::Capacity,
+                relation_id: None,
+                entity_id: None,
+                resource_id: c.resource_id,
+                metric: Some(c.metric),
+                overage: Some(c.overage),
+                priority: Priority::Hard,
+            });
+        }
+        violations
+    }
+
+    // relation violations against a hypothetical assignment.
+    // entities missing from the assignment are skipped.
+    pub(crate) fn violations_with(&self, assignment: &Assignment) -> Vec<Violation> {
+        self.violations_indexed(&RelationIndex::new(self), assignment)
+    }
+
+    pub(crate) fn violations_indexed(
+        &self,
+        index: &RelationIndex,
+        assignment: &Assignment,
+    ) -> Vec<Violation> {
+        let placed = assignment.placed(index);
+        let mut violations: Vec<Violation> = parallel::map(&placed, |(entity_id, resource_id)| {
+            self.entity_violations_at(index, entity_id, resource_id, assignment)
+        })
+        .into_iter()
+        .flatten()
+        .collect();
+        violations
+            .sort_by(|a, b| (&a.relation_id, &a.entity_id).cmp(&(&b.relation_id, &b.entity_id)));
+        violations
+    }
+
+    // summed weight of the soft relations the entity would break on
+    // resource_id.
+    pub(crate) fn soft_penalty_at(
+        &self,
+        index: &RelationIndex,
+        entity_id: &str,
+        resource_id: &str,
+        assignment: &Assignment,
+    ) -> i64 {
+        self.entity_violations_at(index, entity_id, resource_id, assignment)
+            .iter()
+            .map(|v| v.penalty())
+            .sum()
+    }
+
+    // relations the entity would break if it were placed on resource_id,
+    // with the other entities placed as in assignment.
+    pub(crate) fn entity_violations_at(
+        &self,
+        index: &RelationIndex,
+        entity_id: &str,
+        resource_id: &str,
+        assignment: &Assignment,
+    ) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        let e = self.entities.get(entity_id).expect("entity not found");
+        let r = self.resources.get(resource_id).expect("resouce not found");
+        // peers are looked up by handle as well as by id, resolved once a
+        // relation with peers comes up.
+        let handles = OnceCell::new();
+        let handles = || {
+            *handles.get_or_init(|| {
+                let here = index.resource(resource_id).expect("resource not indexed");
+                (index.entity(entity_id), (resource_id, here))
+            })
+        };
+
+        if let Some(c) = &e.constraint {
+            if !c.matches(r) {
+                violations.push(Violation {
+                    kind: ViolationKind::Constraint,
+                    relation_id: None,
+                    entity_id: Some(e.id.clone()),
+                    resource_id: r.id.clone(),
+                    metric: None,
+                    overage: None,
+                    priority: Priority::Hard,
+                });
+            }
+        }
+
+        // relations whose entity property the entity has.
+        for relation in index.property_relations(entity_id) {
+            let ok = match relation.kind {
+                PropertyRelationKind::Affinity => r.has_property(&relation.resource_property),
+                PropertyRelationKind::AntiAffinity => !r.has_property(&relation.resource_property),
+                PropertyRelationKind::EEAffinity | PropertyRelationKind::EEAntiAffinity => {
+                    // resources of the other placed entities sharing the property.
+                    let (me, here) = handles();
+                    let mut peers = index
+                        .peers(&relation.entity_property)
+                        .filter(|o| Some(o.1) != me)
+                        .filter_map(|o| assignment.is_on(o.1, here.1));
+                    if relation.kind == PropertyRelationKind::EEAffinity {
+                        peers.all(|same| same)
+                    } else {
+                        !peers.any(|same| same)
+                    }
+                }
+            };
+            if !ok {
+                violations.push(Violation::relation(
+                    ViolationKind::Property(relation.kind),
+                    relation.priority,
+                    &relation.id,
+                    &e.id,
+                    &r.id,
+                ));
+            }
+        }
+
+        for relation in index.id_relations(entity_id) {
+            let ok = match relation.kind {
+                IDRelationKind::ERAffinity | IDRelationKind::ERAntiAffinity => {
+                    let same = relation.id2 == resource_id;
+                    (relation.kind == IDRelationKind::ERAffinity) == same
+                }
+                IDRelationKind::EEAffinity | IDRelationKind::EEAntiAffinity => {
+                    // both sides of a pair are reported.
+                    let other = if relation.id1 == entity_id {
+                        &relation.id2
+                    } else {
+                        &relation.id1
+                    };
+                    let Some(other_r) = assignment.resource_of(index, other) else {
+                        continue; // other entity not placed
+                    };
+                    let same = other_r == resource_id;
+                    (relation.kind == IDRelationKind::EEAffinity) == same
+                }
+            };
+            if !ok {
+                violations.push(Violation::relation(
+                    ViolationKind::Id(relation.kind),
+                    relation.priority,
+                    &relation.id,
+                    &e.id,
+                    &r.id,
+                ));
+            }
+        }
+
+        for relation in index.id_property_relations(entity_id) {
+            let has = r.has_property(&relation.resource_property);
+            let ok = match relation.kind {
+                PropertyRelationKind::Affinity => has,
+                PropertyRelationKind::AntiAffinity => !has,
+                // rejected by add_id_property_relation.
+                PropertyRelationKind::EEAffinity | PropertyRelationKind::EEAntiAffinity => continue,
+            };
+            if !ok {
+                violations.push(Violation::relation(
+                    ViolationKind::IdProperty(relation.kind),
+                    relation.priority,
+                    &relation.id,
+                    &e.id,
+                    &r.id,
+                ));
+            }
+        }
+
+        for relation in index.domain_relations(entity_id) {
+            let (me, here) = handles();
+            let domain = index.domain(here.1, relation.domain);
+            let mut domains = HashSet::from([domain]);
+            let mut members = 1;
+            let mut shared = false;
+            for o in index.peers(&relation.entity_property) {
+                if Some(o.1) == me {
+                    continue;
+                }
+                let Some(o_r) = assignment.get(o.1) else {
+                    continue;
+                };
+                let d = index.domain(o_r, relation.domain);
+                members += 1;
+                shared |= d == domain;
+                domains.insert(d);
+            }
+            if shared && domains.len() < relation.min_domains.min(members) {
+                violations.push(Violation::relation(
+                    ViolationKind::Domain(relation.domain),
+                    relation.priority,
+                    &relation.id,
+                    &e.id,
+                    &r.id,
+                ));
+            }
+        }
+
+        for chain in index.chains(entity_id) {
+            let ok = match chain.parent.as_deref() {
+                Some(p) if p == entity_id => true,
+                Some(p) => assignment
+                    .resource_of(index, p)
+                    .is_none_or(|p_r| p_r == resource_id),
+                None => {
+                    let (me, here) = handles();
+                    index
+                        .peers(&chain.entity_property)
+                        .filter(|o| Some(o.1) != me)
+                        .filter_map(|o| assignment.is_on(o.1, here.1))
+                        .all(|same| same)
+                }
+            };
+            if !ok {
+                violations.push(Violation::relation(
+                    ViolationKind::Chain,
+                    chain.priority,
+                    &chain.id,
+                    &e.id,
+                    &r.id,
+                ));
+            }
+        }
+        violations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::solver::{
+        Board, DomainKind, DomainRelation, Entity, IDPropertyRelation, IDRelation, IDRelationKind,
+        Priority, PropertyRelation, PropertyRelationKind, PropertyValue, Resource, ViolationKind,
+    };
+
+    #[test]
+    fn property_anti_affinity_test() {
+        let mut r1 = Resource::new(String::from("node1"));
+        r1.add_property(String::from("ssd"));
+        let r2 = Resource::new(String::from("node2"));
+        let mut e1 = Entity::new(String::from("app1"));
+        e1.add_property(String::from("cold"));
+        let mut e2 = Entity::new(String::from("app2"));
+        e2.add_property(String::from("cold"));
+
+        let mut b = Board::new();
+        b.add_resource(r1).expect("added");
+        b.add_resource(r2).expect("added");
+        b.add_entity(String::from("node1"), e1).expect("added");
+        b.add_entity(String::from("node2"), e2).expect("added");
+        b.add_property_relation(PropertyRelation {
+            id: String::from("no-ssd"),
+            kind: PropertyRelationKind::AntiAffinity,
+            entity_property: String::from("cold"),
+            resource_property: String::from("ssd"),
+            priority: Priority::Hard,
+        })
+        .expect("ok");
+
+        let violations = b.check_all().entries;
+        assert_eq!(violations.len(), 1);
+        assert_eq!(
+            violations[0].kind,
+            ViolationKind::Property(PropertyRelationKind::AntiAffinity)
+        );
+        assert_eq!(violations[0].relation_id.as_deref(), Some("no-ssd"));
+        assert_eq!(violations[0].entity_id.as_deref(), Some("app1"));
+        assert_eq!(violations[0].resource_id, "node1");
+    }
+
+    #[test]
+    fn id_relation_violation_test() {
+        let mut b = Board::new();
+        for id in ["node1", "node2"] {
+            b.add_resource(Resource::new(String::from(id)))
+                .expect("added");
+        }
+        for (id, r) in [("a", "node1"), ("b", "node1"), ("c", "node2")] {
+            b.add_entity(String::from(r), Entity::new(String::from(id)))
+                .expect("added");
+        }
+        let relations = [
+            // satisfied
+            ("ab-together", IDRelationKind::EEAffinity, "a", "b"),
+            ("ac-apart", IDRelationKind::EEAntiAffinity, "a", "c"),
+            ("c-on-2", IDRelationKind::ERAffinity, "c", "node2"),
+            // broken
+            ("ab-apart", IDRelationKind::EEAntiAffinity, "a", "b"),
+            ("bc-together", IDRelationKind::EEAffinity, "b", "c"),
+            ("a-on-2", IDRelationKind::ERAffinity, "a", "node2"),
+            ("b-off-1", IDRelationKind::ERAntiAffinity, "b", "node1"),
+        ];
+        for (id, kind, id1, id2) in relations {
+            b.add_id_relation(IDRelation {
+                id: String::from(id),
+                kind,
+                id1: String::from(id1),
+                id2: String::from(id2),
+                priority: Priority::Hard,
+            })
+            .expect("ok");
+        }
+
+        let mut ids: Vec<String> = b.check_violation().relation_ids().into_iter().collect();
+        ids.sort();
+        assert_eq!(ids, vec!["a-on-2", "ab-apart", "b-off-1", "bc-together"]);
+
+        // pair relations name both entities.
+        let offenders: Vec<(String, String)> = b
+            .check_all()
+            .into_iter()
+            .filter(|v| v.relation_id.as_deref() == Some("bc-together"))
+            .map(|v| (v.entity_id.unwrap(), v.resource_id))
+            .collect();
+        assert_eq!(
+            offenders,
+            vec![
+                (String::from("b"), String::from("node1")),
+                (String::from("c"), String::from("node2"))
+            ]
+        );
+    }
+
+    #[test]
+    fn id_property_relation_violation_test() {
+        let mut gpu = Resource::new(String::from("node1"));
+        gpu.add_property(String::from("gpu"));
+        let mut b = Board::new();
+        b.add_resource(gpu).expect("added");
+        b.add_resource(Resource::new(String::from("node2")))
+            .expect("added");
+        b.add_entity(String::from("node2"), Entity::new(String::from("train")))
+            .expect("added");
+        b.add_entity(String::from("node1"), Entity::new(String::from("web")))
+            .expect("added");
+
+        let relations = [
+            ("train-gpu", "train", PropertyRelationKind::Affinity),
+            ("web-no-gpu", "web", PropertyRelationKind::AntiAffinity),
+        ];
+        for (id, entity_id, kind) in relations {
+            b.add_id_property_relation(IDPropertyRelation {
+                id: String::from(id),
+                entity_id: String::from(entity_id),
+                kind,
+                resource_property: String::from("gpu"),
+                priority: Priority::Hard,
+            })
+            .expect("ok");
+        }
+        let violations = b.check_all().entries;
+        assert_eq!(violations.len(), 2);
+        assert_eq!(violations[0].relation_id.as_deref(), Some("train-gpu"));
+        assert_eq!(violations[0].resource_id, "node2");
+        assert_eq!(violations[1].relation_id.as_deref(), Some("web-no-gpu"));
+        assert_eq!(violations[1].resource_id, "node1");
+
+        assert_eq!(b.find_candidate_resources("train"), vec!["node1"]);
+        assert_eq!(b.find_candidate_resources("web"), vec!["node2"]);
+    }
+
+    #[test]
+    fn violation_report_test() {
+        let mut r1 = Resource::new(String::from("node1"));
+        r1.capacities.insert(String::from("cpu"), 2);
+        let mut b = Board::new();
+        b.add_resource(r1).expect("added");
+        b.add_resource(Resource::new(String::from("node2")))
+            .expect("added");
+        let mut e1 = Entity::new(String::from("app1"));
+        e1.metrics.insert(String::from("cpu"), 3);
+        b.add_entity(String::from("node1"), e1).expect("added");
+        b.add_id_relation(IDRelation {
+            id: String::from("pin"),
+            kind: IDRelationKind::ERAffinity,
+            id1: String::from("app1"),
+            id2: String::from("node2"),
+            priority: Priority::Hard,
+        })
+        .expect("ok");
+
+        // capacity is only part of check_all.
+        assert_eq!(b.check_violation().len(), 1);
+        let report = b.check_all();
+        assert_eq!(report.len(), 2);
+        assert_eq!(report.for_entity("app1").count(), 1);
+        let cap: Vec<_> = report
+            .iter()
+            .filter(|v| v.kind == ViolationKind::Capacity)
+            .collect();
+        assert_eq!(cap.len(), 1);
+        assert_eq!(cap[0].resource_id, "node1");
+        assert_eq!(cap[0].metric.as_deref(), Some("cpu"));
+        assert_eq!(cap[0].overage, Some(1));
+        assert_eq!(report.for_resource("node1").count(), 2);
+    }
+
+    #[test]
+    fn typed_property_relation_test() {
+        let mut b = Board::new();
+        for (id, ram) in [("small", 16), ("large", 128)] {
+            let mut r = Resource::new(String::from(id));
+            r.set_property(String::from("ram"), PropertyValue::Int(ram));
+            b.add_resource(r).expect("added");
+        }
+        let mut e = Entity::new(String::from("db"));
+        e.add_property(String::from("tier=gold"));
+        b.add_entity(String::from("small"), e).expect("added");
+        b.add_property_relation(PropertyRelation {
+            id: String::from("big-ram"),
+            kind: PropertyRelationKind::Affinity,
+            entity_property: String::from("tier==gold"),
+            resource_property: String::from("ram>=64"),
+            priority: Priority::Hard,
+        })
+        .expect("ok");
+
+        assert_eq!(b.check_all().len(), 1);
+        assert_eq!(b.find_candidate_resources("db"), vec!["large"]);
+    }
+
+    #[test]
+    fn ee_property_relation_test() {
+        let mut b = Board::new();
+        for id in ["node1", "node2"] {
+            b.add_resource(Resource::new(String::from(id)))
+                .expect("added");
+        }
+        for (id, r) in [("db1", "node1"), ("db2", "node1"), ("db3", "node2")] {
+            let mut e = Entity::new(String::from(id));
+            e.add_property(String::from("db"));
+            b.add_entity(String::from(r), e).expect("added");
+        }
+        b.add_entity(String::from("node1"), Entity::new(String::from("web")))
+            .expect("added");
+        b.add_property_relation(PropertyRelation {
+            id: String::from("spread-db"),
+            kind: PropertyRelationKind::EEAntiAffinity,
+            entity_property: String::from("db"),
+            resource_property: String::new(),
+            priority: Priority::Hard,
+        })
+        .expect("ok");
+
+        let offenders: Vec<String> = b
+            .check_all()
+            .into_iter()
+            .filter_map(|v| v.entity_id)
+            .collect();
+        assert_eq!(offenders, vec!["db1", "db2"]);
+
+        // together instead: db3 is the odd one out, and every db sees it.
+        b.property_relations.get_mut("spread-db").unwrap().kind = PropertyRelationKind::EEAffinity;
+        assert_eq!(b.check_all().len(), 3);
+
+        // id property relations have no entity pairs.
+        let err = b
+            .add_id_property_relation(IDPropertyRelation {
+                id: String::from("bad"),
+                entity_id: String::from("web"),
+                kind: PropertyRelationKind::EEAffinity,
+                resource_property: String::from("db"),
+                priority: Priority::Hard,
+            })
+            .expect_err("rejected");
+        assert_eq!(err.to_string(), "relation kind not supported: bad");
+    }
+
+    #[test]
+    fn domain_relation_test() {
+        let mut b = Board::new();
+        for (id, fd) in [("n1", "/dc1/r1"), ("n2", "/dc1/r1"), ("n3", "/dc1/r2")] {
+            let mut r = Resource::new(String::from(id));
+            r.fault_domain = Some(String::from(fd));
+            b.add_resource(r).expect("added");
+        }
+        for (id, r) in [("a", "n1"), ("b", "n2"), ("c", "n2")] {
+            let mut e = Entity::new(String::from(id));
+            e.add_property(String::from("svc"));
+            b.add_entity(String::from(r), e).expect("added");
+        }
+        b.add_domain_relation(DomainRelation {
+            id: String::from("spread"),
+            domain: DomainKind::Fault,
+            entity_property: String::from("svc"),
+            min_domains: 2,
+            priority: Priority::Hard,
+        })
+        .expect("ok");
+
+        // all in one rack.
+        let report = b.check_all();
+        assert_eq!(report.len(), 3);
+        assert_eq!(
+            report.entries[0].kind,
+            ViolationKind::Domain(DomainKind::Fault)
+        );
+        assert_eq!(report.entries[0].kind.to_string(), "FaultDomain");
+        assert_eq!(b.find_candidate_resources("c"), vec!["n3"]);
+
+        // moved directly, around the board api.
+        let (c, n3) = (
+            b.entities.handle("c").unwrap(),
+            b.resources.handle("n3").unwrap(),
+        );
+        b.assignment.insert(c, n3);
+        assert!(b.check_all().is_empty());
+
+        // upgrade domains default to one per resource.
+        b.domain_relations.get_mut("spread").unwrap().domain = DomainKind::Upgrade;
+        b.domain_relations.get_mut("spread").unwrap().min_domains = 3;
+        assert!(b.check_all().is_empty());
+    }
+}
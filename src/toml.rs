@@ -0,0 +1,593 @@
+// toml subset parser used for declarative board files. Supports tables,
+// arrays of tables, dotted keys, strings, integers, floats, booleans,
+// arrays and inline tables; dates and multi-line strings are not supported.
+// Documents are parsed into json values so decoding can be shared.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use crate::json::{Value, MAX_DEPTH};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TomlError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for TomlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for TomlError {}
+
+// parsed document with the line every table and key was defined on.
+pub struct Document {
+    pub root: Value,
+    // dotted path like "entities[0].id" -> line
+    lines: HashMap<String, usize>,
+}
+
+impl Document {
+    pub fn parse(s: &str) -> Result<Document, TomlError> {
+        let mut p = Parser {
+            src: s.as_bytes(),
+            pos: 0,
+            line: 1,
+            lines: HashMap::new(),
+            depth: 0,
+        };
+        let mut root = BTreeMap::new();
+        p.document(&mut root)?;
+        Ok(Document {
+            root: Value::Object(root),
+            lines: p.lines,
+        })
+    }
+
+    // line of the value at path, falling back to the closest parent.
+    pub fn line_of(&self, path: &str) -> Option<usize> {
+        let mut path = path;
+        loop {
+            if let Some(l) = self.lines.get(path) {
+                return Some(*l);
+            }
+            let cut = path.rfind(['.', '['])?;
+            path = &path[..cut];
+        }
+    }
+}
+
+enum Seg {
+    Key(String),
+    // the last element of an array of tables.
+    Last(String),
+}
+
+struct Parser<'a> {
+    src: &'a [u8],
+    pos: usize,
+    line: usize,
+    lines: HashMap<String, usize>,
+    // inline arrays and tables open around the position, at most
+    // MAX_DEPTH like json.
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, msg: &str) -> TomlError {
+        TomlError {
+            line: self.line,
+            message: msg.to_string(),
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.src.get(self.pos).copied()
+    }
+
+    fn bump(&mut self) {
+        if self.peek() == Some(b'\n') {
+            self.line += 1;
+        }
+        self.pos += 1;
+    }
+
+    // spaces and tabs only.
+    fn skip_blank(&mut self) {
+        while let Some(b' ' | b'\t') = self.peek() {
+            self.bump();
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        if self.peek() == Some(b'#') {
+            while self.peek().is_some_and(|c| c != b'\n') {
+                self.bump();
+            }
+        }
+    }
+
+    // whitespace, newlines and comments.
+    fn skip_all(&mut self) {
+        loop {
+            match self.peek() {
+                Some(b' ' | b'\t' | b'\r' | b'\n') => self.bump(),
+                Some(b'#') => self.skip_comment(),
+                _ => return,
+            }
+        }
+    }
+
+    fn end_of_line(&mut self) -> Result<(), TomlError> {
+        self.skip_blank();
+        self.skip_comment();
+        match self.peek() {
+            None => Ok(()),
+            Some(b'\n') => {
+                self.bump();
+                Ok(())
+            }
+            Some(b'\r') if self.src.get(self.pos + 1) == Some(&b'\n') => {
+                self.bump();
+                self.bump();
+                Ok(())
+            }
+            _ => Err(self.error("expected end of line")),
+        }
+    }
+
+    fn document(&mut self, root: &mut BTreeMap<String, Value>) -> Result<(), TomlError> {
+        let mut current: Vec<Seg> = Vec::new();
+        let mut current_path = String::new();
+        loop {
+            self.skip_all();
+            match self.peek() {
+                None => return Ok(()),
+                Some(b'[') => {
+                    let line = self.line;
+                    self.bump();
+                    let array = self.peek() == Some(b'[');
+                    if array {
+                        self.bump();
+                    }
+                    self.skip_blank();
+                    let keys = self.key()?;
+                    self.skip_blank();
+                    let close: &[u8] = if array { b"]]" } else { b"]" };
+                    if !self.src[self.pos..].starts_with(close) {
+                        return Err(self.error("unterminated table header"));
+                    }
+                    for _ in 0..close.len() {
+                        self.bump();
+                    }
+                    self.end_of_line()?;
+                    let (segs, path) = self.header(root, keys, array, line)?;
+                    current = segs;
+                    current_path = path;
+                }
+                Some(_) => {
+                    let line = self.line;
+                    let keys = self.key()?;
+                    self.skip_blank();
+                    if self.peek() != Some(b'=') {
+                        return Err(self.error("expected '='"));
+                    }
+                    self.bump();
+                    self.skip_blank();
+                    let mut path = current_path.clone();
+                    for k in &keys {
+                        if !path.is_empty() {
+                            path.push('.');
+                        }
+                        path.push_str(k);
+                    }
+                    let v = self.value(&path)?;
+                    self.end_of_line()?;
+                    let table = navigate(root, &current).map_err(|m| self.error(&m))?;
+                    insert_dotted(table, &keys, v).map_err(|m| TomlError { line, message: m })?;
+                    self.lines.insert(path, line);
+                }
+            }
+        }
+    }
+
+    // resolves a table header to the segments and path of the new table.
+    fn header(
+        &mut self,
+        root: &mut BTreeMap<String, Value>,
+        keys: Vec<String>,
+        array: bool,
+        line: usize,
+    ) -> Result<(Vec<Seg>, String), TomlError> {
+        let err = |m: &str| TomlError {
+            line,
+            message: m.to_string(),
+        };
+        let mut segs = Vec::new();
+        let mut path = String::new();
+        let (last, parents) = keys.split_last().expect("key is not empty");
+        for k in parents {
+            let table = navigate(root, &segs).map_err(|m| err(&m))?;
+            let entry = table
+                .entry(k.clone())
+                .or_insert_with(|| Value::Object(BTreeMap::new()));
+            if !path.is_empty() {
+                path.push('.');
+            }
+            path.push_str(k);
+            match entry {
+                Value::Object(_) => segs.push(Seg::Key(k.clone())),
+                Value::Array(a) => {
+                    path.push_str(&format!("[{}]", a.len().saturating_sub(1)));
+                    segs.push(Seg::Last(k.clone()));
+                }
+                _ => return Err(err(&format!("key is not a table: {}", k))),
+            }
+        }
+        let table = navigate(root, &segs).map_err(|m| err(&m))?;
+        if !path.is_empty() {
+            path.push('.');
+        }
+        path.push_str(last);
+        if array {
+            let entry = table
+                .entry(last.clone())
+                .or_insert_with(|| Value::Array(Vec::new()));
+            let Value::Array(a) = entry else {
+                return Err(err(&format!("key is not an array of tables: {}", last)));
+            };
+            path.push_str(&format!("[{}]", a.len()));
+            a.push(Value::Object(BTreeMap::new()));
+            segs.push(Seg::Last(last.clone()));
+        } else {
+            if table.contains_key(last) {
+                return Err(err(&format!("duplicate table: {}", last)));
+            }
+            table.insert(last.clone(), Value::Object(BTreeMap::new()));
+            segs.push(Seg::Key(last.clone()));
+        }
+        self.lines.insert(path.clone(), line);
+        Ok((segs, path))
+    }
+
+    // dotted key of bare or quoted parts.
+    fn key(&mut self) -> Result<Vec<String>, TomlError> {
+        let mut keys = Vec::new();
+        loop {
+            self.skip_blank();
+            let k = match self.peek() {
+                Some(b'"') => self.basic_string()?,
+                Some(b'\'') => self.literal_string()?,
+                _ => {
+                    let start = self.pos;
+                    while self
+                        .peek()
+                        .is_some_and(|c| c.is_ascii_alphanumeric() || c == b'_' || c == b'-')
+                    {
+                        self.bump();
+                    }
+                    if start == self.pos {
+                        return Err(self.error("expected key"));
+                    }
+                    String::from_utf8(self.src[start..self.pos].to_vec()).expect("ascii")
+                }
+            };
+            keys.push(k);
+            self.skip_blank();
+            if self.peek() == Some(b'.') {
+                self.bump();
+            } else {
+                return Ok(keys);
+            }
+        }
+    }
+
+    fn value(&mut self, path: &str) -> Result<Value, TomlError> {
+        match self.peek() {
+            None => Err(self.error("expected value")),
+            Some(b'"') => {
+                if self.src[self.pos..].starts_with(b"\"\"\"") {
+                    return Err(self.error("multi-line strings are not supported"));
+                }
+                Ok(Value::String(self.basic_string()?))
+            }
+            Some(b'\'') => Ok(Value::String(self.literal_string()?)),
+            Some(c @ (b'[' | b'{')) => {
+                if self.depth == MAX_DEPTH {
+                    return Err(self.error("nesting too deep"));
+                }
+                self.depth += 1;
+                let v = if c == b'[' {
+                    self.array(path)
+                } else {
+                    self.inline_table(path)
+                };
+                self.depth -= 1;
+                v
+            }
+            Some(b't') => self.literal("true", Value::Bool(true)),
+            Some(b'f') => self.literal("false", Value::Bool(false)),
+            Some(b'+' | b'-' | b'0'..=b'9') => self.number(),
+            Some(_) => Err(self.error("invalid value")),
+        }
+    }
+
+    fn literal(&mut self, word: &str, v: Value) -> Result<Value, TomlError> {
+        if self.src[self.pos..].starts_with(word.as_bytes()) {
+            self.pos += word.len();
+            Ok(v)
+        } else {
+            Err(self.error("invalid value"))
+        }
+    }
+
+    fn array(&mut self, path: &str) -> Result<Value, TomlError> {
+        self.bump();
+        let mut a = Vec::new();
+        loop {
+            self.skip_all();
+            if self.peek() == Some(b']') {
+                self.bump();
+                return Ok(Value::Array(a));
+            }
+            let item_path = format!("{}[{}]", path, a.len());
+            self.lines.insert(item_path.clone(), self.line);
+            a.push(self.value(&item_path)?);
+            self.skip_all();
+            match self.peek() {
+                Some(b',') => self.bump(),
+                Some(b']') => {}
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn inline_table(&mut self, path: &str) -> Result<Value, TomlError> {
+        self.bump();
+        let mut m = BTreeMap::new();
+        self.skip_blank();
+        if self.peek() == Some(b'}') {
+            self.bump();
+            return Ok(Value::Object(m));
+        }
+        loop {
+            let line = self.line;
+            let keys = self.key()?;
+            self.skip_blank();
+            if self.peek() != Some(b'=') {
+                return Err(self.error("expected '='"));
+            }
+            self.bump();
+            self.skip_blank();
+            let key_path = format!("{}.{}", path, keys.join("."));
+            let v = self.value(&key_path)?;
+            insert_dotted(&mut m, &keys, v).map_err(|m| TomlError { line, message: m })?;
+            self.lines.insert(key_path, line);
+            self.skip_blank();
+            match self.peek() {
+                Some(b',') => self.bump(),
+                Some(b'}') => {
+                    self.bump();
+                    return Ok(Value::Object(m));
+                }
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+    }
+
+    fn basic_string(&mut self) -> Result<String, TomlError> {
+        self.bump();
+        let mut out = String::new();
+        loop {
+            let rest = std::str::from_utf8(&self.src[self.pos..])
+                .map_err(|_| self.error("invalid utf-8"))?;
+            let Some(c) = rest.chars().next() else {
+                return Err(self.error("unterminated string"));
+            };
+            match c {
+                '"' => {
+                    self.bump();
+                    return Ok(out);
+                }
+                '\n' => return Err(self.error("unterminated string")),
+                '\\' => {
+                    self.bump();
+                    let esc = self
+                        .peek()
+                        .ok_or_else(|| self.error("unterminated string"))?;
+                    self.bump();
+                    let ch = match esc {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' | b'U' => {
+                            let n = if esc == b'u' { 4 } else { 8 };
+                            let digits = self
+                                .src
+                                .get(self.pos..self.pos + n)
+                                .and_then(|d| std::str::from_utf8(d).ok())
+                                .and_then(|d| u32::from_str_radix(d, 16).ok())
+                                .and_then(char::from_u32)
+                                .ok_or_else(|| self.error("invalid unicode escape"))?;
+                            self.pos += n;
+                            digits
+                        }
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    out.push(ch);
+                }
+                c => {
+                    out.push(c);
+                    self.pos += c.len_utf8();
+                }
+            }
+        }
+    }
+
+    fn literal_string(&mut self) -> Result<String, TomlError> {
+        self.bump();
+        let start = self.pos;
+        loop {
+            match self.peek() {
+                None | Some(b'\n') => return Err(self.error("unterminated string")),
+                Some(b'\'') => break,
+                Some(_) => self.bump(),
+            }
+        }
+        let s = std::str::from_utf8(&self.src[start..self.pos])
+            .map_err(|_| self.error("invalid utf-8"))?
+            .to_string();
+        self.bump();
+        Ok(s)
+    }
+
+    fn number(&mut self) -> Result<Value, TomlError> {
+        let start = self.pos;
+        let mut float = false;
+        while let Some(c) = self.peek() {
+            match c {
+                b'0'..=b'9' | b'-' | b'+' | b'_' => {}
+                b'.' | b'e' | b'E' => float = true,
+                _ => break,
+            }
+            self.bump();
+        }
+        let s: String = std::str::from_utf8(&self.src[start..self.pos])
+            .expect("ascii")
+            .chars()
+            .filter(|c| *c != '_')
+            .collect();
+        if !float {
+            if let Ok(i) = s.parse::<i64>() {
+                return Ok(Value::Int(i));
+            }
+        }
+        s.parse::<f64>()
+            .map(Value::Float)
+            .map_err(|_| self.error("invalid number"))
+    }
+}
+
+// table the segments point at.
+fn navigate<'a>(
+    root: &'a mut BTreeMap<String, Value>,
+    segs: &[Seg],
+) -> Result<&'a mut BTreeMap<String, Value>, String> {
+    let mut table = root;
+    for seg in segs {
+        let v = match seg {
+            Seg::Key(k) => table.get_mut(k),
+            Seg::Last(k) => match table.get_mut(k) {
+                Some(Value::Array(a)) => a.last_mut(),
+                _ => None,
+            },
+        };
+        table = match v {
+            Some(Value::Object(m)) => m,
+            _ => return Err(String::from("invalid table")),
+        };
+    }
+    Ok(table)
+}
+
+fn insert_dotted(
+    table: &mut BTreeMap<String, Value>,
+    keys: &[String],
+    v: Value,
+) -> Result<(), String> {
+    let (last, parents) = keys.split_last().expect("key is not empty");
+    let mut table = table;
+    for k in parents {
+        let entry = table
+            .entry(k.clone())
+            .or_insert_with(|| Value::Object(BTreeMap::new()));
+        table = match entry {
+            Value::Object(m) => m,
+            _ => return Err(format!("key is not a table: {}", k)),
+        };
+    }
+    if table.contains_key(last) {
+        return Err(format!("duplicate key: {}", last));
+    }
+    table.insert(last.clone(), v);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Document;
+    use crate::json::{Value, MAX_DEPTH};
+
+    #[test]
+    fn toml_parse_test() {
+        let src = r#"
+# cluster
+name = "demo"
+
+[[resources]]
+id = "node1"
+capacities = { cpu = 8, memory = 1_024 }
+
+[[resources]]
+id = 'node2'
+properties = [
+  "red", # trailing comma is fine
+]
+
+[limits]
+ratio.max = 0.5
+"#;
+        let doc = Document::parse(src).expect("parses");
+        let resources = doc.root.get("resources").unwrap().as_array().unwrap();
+        assert_eq!(resources.len(), 2);
+        assert_eq!(
+            resources[0].get("capacities").unwrap().get("memory"),
+            Some(&Value::Int(1024))
+        );
+        assert_eq!(resources[1].get("id").unwrap().as_str(), Some("node2"));
+        assert_eq!(
+            doc.root
+                .get("limits")
+                .unwrap()
+                .get("ratio")
+                .unwrap()
+                .get("max"),
+            Some(&Value::Float(0.5))
+        );
+        assert_eq!(doc.line_of("resources[1]"), Some(9));
+        assert_eq!(doc.line_of("resources[1].id"), Some(10));
+        assert_eq!(doc.line_of("resources[1].properties[0]"), Some(12));
+        assert_eq!(doc.line_of("resources[0].capacities.cpu"), Some(7));
+        assert_eq!(doc.line_of("resources[0].missing"), Some(5));
+    }
+
+    #[test]
+    fn toml_error_test() {
+        let err = Document::parse("a = 1\nb = \"x\nc = 2\n")
+            .err()
+            .expect("fails");
+        assert_eq!(err.to_string(), "line 2: unterminated string");
+        let err = Document::parse("a = 1\na = 2\n").err().expect("fails");
+        assert_eq!(err.to_string(), "line 2: duplicate key: a");
+    }
+
+    #[test]
+    fn toml_depth_test() {
+        let nested = |n: usize| format!("a = {}{}\n", "[".repeat(n), "]".repeat(n));
+        assert!(Document::parse(&nested(MAX_DEPTH)).is_ok());
+        let err = Document::parse(&nested(MAX_DEPTH + 1))
+            .err()
+            .expect("fails");
+        assert_eq!(err.to_string(), "line 1: nesting too deep");
+        let err = Document::parse(&format!("a = {}", "{b = [".repeat(100_000)))
+            .err()
+            .expect("fails");
+        assert_eq!(err.to_string(), "line 1: nesting too deep");
+    }
+}
@@ -0,0 +1,184 @@
+// webassembly exports of the solver for browser front ends, with the wasm
+// feature. Strings cross the module boundary as utf-8 in its memory, so no
+// bindings generator is needed:
+//
+//   fabric_board(board json)                      -> the board as parsed
+//   fabric_check(board json)                      -> violation report
+//   fabric_solve({"board", "pending"} json)       -> {"placement", "complete"}
+//   fabric_rebalance(board json, "budget=10&..")  -> move plan with "complete"
+//
+// Inputs go in buffers from fabric_alloc, released with fabric_free. Each
+// call returns a buffer holding a little-endian u32 length and that many
+// bytes of json, released with fabric_free_result. Failures answer
+// {"error": message} like the server does. Build with
+//
+//   cargo rustc --lib --release --features wasm \
+//       --target wasm32-unknown-unknown --crate-type cdylib
+
+use crate::api::{error_body, route};
+use crate::json::ToJson;
+use crate::solver::{Board, SolverConfig};
+
+// the json answer of an export, by name.
+pub fn call(name: &str, input: &str, query: &str) -> String {
+    // without a clock in the browser searches are not time bounded.
+    let config = SolverConfig::default();
+    let body = match name {
+        "board" => match Board::from_json(input) {
+            Ok(b) => b.to_value(),
+            Err(e) => error_body(&e.to_string()),
+        },
+        "check" | "solve" => route("POST", &format!("/{}", name), input, &config).1,
+        "rebalance" => route("POST", &format!("/rebalance?{}", query), input, &config).1,
+        _ => error_body(&format!("no such export: {}", name)),
+    };
+    body.to_string()
+}
+
+// a buffer of len bytes for an input string.
+#[no_mangle]
+pub extern "C" fn fabric_alloc(len: usize) -> *mut u8 {
+    let mut buf = Vec::<u8>::with_capacity(len);
+    let ptr = buf.as_mut_ptr();
+    std::mem::forget(buf);
+    ptr
+}
+
+/// Releases a buffer of fabric_alloc.
+///
+/// # Safety
+///
+/// ptr and len must come from one fabric_alloc call, freed once.
+#[no_mangle]
+pub unsafe extern "C" fn fabric_free(ptr: *mut u8, len: usize) {
+    drop(Vec::from_raw_parts(ptr, 0, len));
+}
+
+/// Releases a buffer returned by an export.
+///
+/// # Safety
+///
+/// ptr must come from an export, freed once.
+#[no_mangle]
+pub unsafe extern "C" fn fabric_free_result(ptr: *mut u8) {
+    let len = u32::from_le_bytes(*(ptr as *const [u8; 4])) as usize;
+    drop(Vec::from_raw_parts(ptr, 4 + len, 4 + len));
+}
+
+// the input string, invalid utf-8 replaced.
+unsafe fn input(ptr: *const u8, len: usize) -> String {
+    String::from_utf8_lossy(std::slice::from_raw_parts(ptr, len)).into_owned()
+}
+
+fn result(json: String) -> *mut u8 {
+    let mut buf = Vec::with_capacity(4 + json.len());
+    buf.extend_from_slice(&(json.len() as u32).to_le_bytes());
+    buf.extend_from_slice(json.as_bytes());
+    let mut buf = buf.into_boxed_slice();
+    let ptr = buf.as_mut_ptr();
+    std::mem::forget(buf);
+    ptr
+}
+
+/// The board parsed and written back, with every default filled in.
+///
+/// # Safety
+///
+/// ptr must point at len readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn fabric_board(ptr: *const u8, len: usize) -> *mut u8 {
+    result(call("board", &input(ptr, len), ""))
+}
+
+/// The violation report of the board.
+///
+/// # Safety
+///
+/// ptr must point at len readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn fabric_check(ptr: *const u8, len: usize) -> *mut u8 {
+    result(call("check", &input(ptr, len), ""))
+}
+
+/// The placement of {"board", "pending"}, and whether the search finished.
+///
+/// # Safety
+///
+/// ptr must point at len readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn fabric_solve(ptr: *const u8, len: usize) -> *mut u8 {
+    result(call("solve", &input(ptr, len), ""))
+}
+
+/// The move plan of the board, with the server's query options.
+///
+/// # Safety
+///
+/// ptr and query must point at len and query_len readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn fabric_rebalance(
+    ptr: *const u8,
+    len: usize,
+    query: *const u8,
+    query_len: usize,
+) -> *mut u8 {
+    result(call(
+        "rebalance",
+        &input(ptr, len),
+        &input(query, query_len),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{call, fabric_alloc, fabric_check, fabric_free, fabric_free_result};
+    use crate::json::Value;
+
+    const BOARD: &str = r#"{
+      "resources": [{"id": "node1"}, {"id": "node2"}],
+      "entities": [{"id": "a", "move_cost": 1}, {"id": "b", "move_cost": 2}],
+      "assignment": {"a": "node1", "b": "node1"},
+      "id_relations": [{"id": "apart", "kind": "EEAntiAffinity", "id1": "a", "id2": "b"}]
+    }"#;
+
+    #[test]
+    fn call_test() {
+        let board = Value::parse(&call("board", BOARD, "")).expect("json");
+        assert_eq!(
+            board
+                .get("resources")
+                .and_then(|r| r.as_array())
+                .map(Vec::len),
+            Some(2)
+        );
+        let plan = Value::parse(&call("rebalance", BOARD, "budget=5")).expect("json");
+        assert_eq!(
+            plan.get("moves").and_then(|m| m.as_array()).map(Vec::len),
+            Some(1)
+        );
+        let err = Value::parse(&call("rebalance", BOARD, "budget=x")).expect("json");
+        assert_eq!(
+            err.get("error").and_then(|e| e.as_str()),
+            Some("invalid budget: x")
+        );
+        assert!(call("nope", "", "").contains("no such export"));
+    }
+
+    #[test]
+    fn abi_test() {
+        unsafe {
+            let ptr = fabric_alloc(BOARD.len());
+            std::ptr::copy_nonoverlapping(BOARD.as_ptr(), ptr, BOARD.len());
+            let out = fabric_check(ptr, BOARD.len());
+            fabric_free(ptr, BOARD.len());
+
+            let len = u32::from_le_bytes(*(out as *const [u8; 4])) as usize;
+            let json = std::str::from_utf8(std::slice::from_raw_parts(out.add(4), len))
+                .expect("utf-8")
+                .to_string();
+            fabric_free_result(out);
+            let report = Value::parse(&json).expect("json");
+            assert_eq!(report.as_array().map(Vec::len), Some(2));
+        }
+    }
+}